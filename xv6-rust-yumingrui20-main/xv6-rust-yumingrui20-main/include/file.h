@@ -38,3 +38,7 @@ struct devsw {
 extern struct devsw devsw[];
 
 #define CONSOLE 1
+#define DEVNULL 2
+#define DEVZERO 3
+#define DEVFULL 4
+#define DEVRANDOM 5
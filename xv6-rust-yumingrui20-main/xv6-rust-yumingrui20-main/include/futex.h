@@ -0,0 +1,7 @@
+// Operations for futex(), same numbers as Linux.
+#define FUTEX_WAIT  0
+#define FUTEX_WAKE  1
+
+// Returned by FUTEX_WAIT when the timeout expires, unlike -1 for
+// errors such as the word not holding the expected value.
+#define FUTEX_TIMEDOUT (-2)
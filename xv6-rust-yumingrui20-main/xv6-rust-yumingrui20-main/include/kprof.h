@@ -0,0 +1,3 @@
+// Commands for kprof(), the kernel sampling profiler.
+#define KPROF_STOP   0  // stop sampling, returns the number of samples
+#define KPROF_START  1  // clear samples and lock statistics, start sampling
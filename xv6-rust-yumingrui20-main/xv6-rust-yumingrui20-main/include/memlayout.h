@@ -62,6 +62,9 @@
 //   fixed-size stack
 //   expandable heap
 //   ...
+//   SHMBASE (shared memory segments)
+//   ...
 //   TRAPFRAME (p->trapframe, used by the trampoline)
 //   TRAMPOLINE (the same page as in the kernel)
+#define SHMBASE (MAXVA / 2)
 #define TRAPFRAME (TRAMPOLINE - PGSIZE)
@@ -0,0 +1,12 @@
+// Events for poll(), same numbers as Linux.
+#define POLLIN    0x1
+#define POLLOUT   0x4
+#define POLLERR   0x8
+#define POLLHUP   0x10
+#define POLLNVAL  0x20
+
+struct pollfd {
+  int fd;
+  short events;
+  short revents;
+};
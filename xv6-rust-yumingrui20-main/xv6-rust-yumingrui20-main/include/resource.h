@@ -0,0 +1,7 @@
+// Resource limits for getrlimit() and setrlimit(), same numbers as Linux.
+#define RLIMIT_NOFILE  7
+
+struct rlimit {
+  uint64 rlim_cur;  // soft limit
+  uint64 rlim_max;  // hard limit, can only be lowered
+};
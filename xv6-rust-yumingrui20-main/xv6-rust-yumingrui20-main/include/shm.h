@@ -0,0 +1,5 @@
+// Shared memory keys, flags and commands, same numbers as Linux.
+#define IPC_PRIVATE  0
+#define IPC_CREAT    01000
+#define IPC_EXCL     02000
+#define IPC_RMID     0
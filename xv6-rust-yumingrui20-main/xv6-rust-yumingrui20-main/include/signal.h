@@ -0,0 +1,12 @@
+// Signal numbers, same as Linux on RISC-V.
+#define SIGINT   2
+#define SIGKILL  9
+#define SIGALRM 14
+#define SIGTERM 15
+#define SIGCONT 18
+#define SIGSTOP 19
+#define SIGTSTP 20
+
+// Dispositions for signal(); there are no user handlers yet.
+#define SIG_DFL  0
+#define SIG_IGN  1
@@ -1,22 +1,55 @@
+// generated by usys.pl - do not edit
 // System call numbers
-#define SYS_fork    1
-#define SYS_exit    2
-#define SYS_wait    3
-#define SYS_pipe    4
-#define SYS_read    5
-#define SYS_kill    6
-#define SYS_exec    7
-#define SYS_fstat   8
-#define SYS_chdir   9
-#define SYS_dup    10
-#define SYS_getpid 11
-#define SYS_sbrk   12
-#define SYS_sleep  13
-#define SYS_uptime 14
-#define SYS_open   15
-#define SYS_write  16
-#define SYS_mknod  17
-#define SYS_unlink 18
-#define SYS_link   19
-#define SYS_mkdir  20
-#define SYS_close  21
+#define SYS_fork      1
+#define SYS_exit      2
+#define SYS_wait      3
+#define SYS_pipe      4
+#define SYS_read      5
+#define SYS_kill      6
+#define SYS_exec      7
+#define SYS_fstat     8
+#define SYS_chdir     9
+#define SYS_dup      10
+#define SYS_getpid   11
+#define SYS_sbrk     12
+#define SYS_sleep    13
+#define SYS_uptime   14
+#define SYS_open     15
+#define SYS_write    16
+#define SYS_mknod    17
+#define SYS_unlink   18
+#define SYS_link     19
+#define SYS_mkdir    20
+#define SYS_close    21
+#define SYS_ioctl    22
+#define SYS_openpty  23
+#define SYS_syslog   24
+#define SYS_trace    25
+#define SYS_clone    26
+#define SYS_join     27
+#define SYS_exit_thread 28
+#define SYS_futex    29
+#define SYS_shmget   30
+#define SYS_shmat    31
+#define SYS_shmdt    32
+#define SYS_shmctl   33
+#define SYS_poll     34
+#define SYS_fcntl    35
+#define SYS_dup2     36
+#define SYS_dup3     37
+#define SYS_getrlimit 38
+#define SYS_setrlimit 39
+#define SYS_mkfifo   40
+#define SYS_setpgid  41
+#define SYS_getpgid  42
+#define SYS_setsid   43
+#define SYS_waitpid  44
+#define SYS_signal   45
+#define SYS_alarm    46
+#define SYS_setitimer 47
+#define SYS_getitimer 48
+#define SYS_clock_gettime 49
+#define SYS_nanosleep 50
+#define SYS_sched_setaffinity 51
+#define SYS_sched_getaffinity 52
+#define SYS_kprof    53
@@ -0,0 +1,12 @@
+// Actions for syslog(), same numbers as Linux.
+#define SYSLOG_ACTION_READ_ALL       3
+#define SYSLOG_ACTION_CLEAR          5
+#define SYSLOG_ACTION_CONSOLE_LEVEL  8
+#define SYSLOG_ACTION_SIZE_BUFFER   10
+
+// Kernel log levels.
+#define KLOG_ERROR  0
+#define KLOG_WARN   1
+#define KLOG_INFO   2
+#define KLOG_DEBUG  3
+#define KLOG_TRACE  4
@@ -0,0 +1,39 @@
+// Terminal control through ioctl(), a subset of Linux termios.
+
+// ioctl requests
+#define TCGETS      0x5401  // get struct termios
+#define TCSETS      0x5402  // set struct termios
+#define TIOCGPGRP   0x540f  // get foreground process group
+#define TIOCSPGRP   0x5410  // set foreground process group
+#define TIOCGWINSZ  0x5413  // get struct winsize
+#define TIOCSWINSZ  0x5414  // set struct winsize
+
+// iflag
+#define ICRNL   0x100   // translate CR to LF on input
+
+// lflag
+#define ISIG    0x1     // INTR and SUSP characters send signals
+#define ICANON  0x2     // canonical (line by line) input
+#define ECHO    0x8     // echo input characters
+
+// indices into cc[]
+#define VINTR   0
+#define VERASE  1
+#define VKILL   2
+#define VEOF    3
+#define VSUSP   4
+#define VMIN    5
+#define NCCS    8
+
+struct termios {
+  uint iflag;
+  uint lflag;
+  uchar cc[NCCS];
+};
+
+struct winsize {
+  ushort ws_row;
+  ushort ws_col;
+  ushort ws_xpixel;
+  ushort ws_ypixel;
+};
@@ -0,0 +1,22 @@
+// Clocks and interval timers for clock_gettime(), setitimer() and
+// nanosleep(), same numbers and layouts as Linux on RISC-V.
+// There is no real-time clock, so both clocks count from boot.
+#define CLOCK_REALTIME   0
+#define CLOCK_MONOTONIC  1
+
+#define ITIMER_REAL      0  // counts real time, delivers SIGALRM
+
+struct timespec {
+  long tv_sec;
+  long tv_nsec;
+};
+
+struct timeval {
+  long tv_sec;
+  long tv_usec;
+};
+
+struct itimerval {
+  struct timeval it_interval;  // period, 0 for a one-shot timer
+  struct timeval it_value;     // time to the next expiry, 0 stops the timer
+};
@@ -0,0 +1,9 @@
+// Options and status macros for waitpid(), same encoding as Linux.
+// wait() stores the plain exit status instead.
+#define WNOHANG    1  // do not wait if no child has changed state
+#define WUNTRACED  2  // also report stopped children
+
+#define WIFEXITED(s)    (((s) & 0x7f) == 0)
+#define WEXITSTATUS(s)  (((s) >> 8) & 0xff)
+#define WIFSTOPPED(s)   (((s) & 0xff) == 0x7f)
+#define WSTOPSIG(s)     (((s) >> 8) & 0xff)
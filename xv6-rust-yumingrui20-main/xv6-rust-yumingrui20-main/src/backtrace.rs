@@ -0,0 +1,146 @@
+//! 内核栈回溯
+//!
+//! 内核以帧指针编译（`Cargo.toml` 中的 `force-frame-pointers`），RISC-V 上每个栈帧的布局为：
+//! `s0`（帧指针）指向调用者的栈顶，`s0-8` 处保存返回地址，`s0-16` 处保存调用者的帧指针。
+//! 沿帧指针链向上即可得到每一层的返回地址。
+//!
+//! 返回地址按内核中嵌入的符号表换算为函数名。符号表位于 `.ksyms` 段，
+//! 编译时只预留空间，链接后由 `tools/ksyms.py` 从内核 ELF 文件提取符号写入；
+//! 没有写入符号表时只打印地址。符号表的格式为：
+//! - 8 字节魔数 [`KSYMS_MAGIC`] 与 8 字节符号数 n；
+//! - n 个按地址升序排列的符号项，每项为 8 字节地址、4 字节名称偏移与 4 字节名称长度；
+//! - 名称字符串，偏移从符号表开头算起。
+
+use core::fmt;
+use core::hint::black_box;
+use core::mem::size_of;
+use core::ptr::addr_of;
+use core::slice;
+use core::str;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::consts::KERNBASE;
+use crate::klog::{self, Level};
+use crate::mm::kvm_mapped;
+
+/// 符号表的魔数，即 "KSYMTAB\0"
+const KSYMS_MAGIC: u64 = u64::from_le_bytes(*b"KSYMTAB\0");
+
+/// 为符号表预留的空间，`tools/ksyms.py` 写入的符号表不能超过该大小
+const KSYMS_SIZE: usize = 1 << 20;
+
+/// 最多回溯的栈帧数
+const MAX_DEPTH: usize = 32;
+
+/// 嵌入的符号表，由 `tools/ksyms.py` 在链接后写入
+#[used]
+#[link_section = ".ksyms"]
+static KSYMS: [u8; KSYMS_SIZE] = [0; KSYMS_SIZE];
+
+/// 正在回溯时置位，回溯过程中再次 panic 时不再回溯
+static UNWINDING: AtomicBool = AtomicBool::new(false);
+
+#[repr(C)]
+struct Symbol {
+    addr: u64,
+    name_off: u32,
+    name_len: u32,
+}
+
+/// 已写入的符号表，没有写入时返回 `None`。
+fn symbols() -> Option<(&'static [u8], &'static [Symbol])> {
+    // 符号表在编译时全为零，链接后才被写入，不能让编译器按初始值优化读取
+    let base = black_box(addr_of!(KSYMS) as *const u8);
+    let table = unsafe { slice::from_raw_parts(base, KSYMS_SIZE) };
+    let word = |i: usize| u64::from_le_bytes(table[i*8..(i+1)*8].try_into().unwrap());
+    if word(0) != KSYMS_MAGIC {
+        return None
+    }
+    let count = word(1) as usize;
+    if 16 + count * size_of::<Symbol>() > KSYMS_SIZE {
+        return None
+    }
+    let syms = unsafe { slice::from_raw_parts(base.add(16) as *const Symbol, count) };
+    Some((table, syms))
+}
+
+/// 查找包含地址 `pc` 的函数，返回函数名与 `pc` 在函数内的偏移。
+fn lookup(pc: usize) -> Option<(&'static str, usize)> {
+    let (table, syms) = symbols()?;
+    let i = syms.partition_point(|s| s.addr as usize <= pc).checked_sub(1)?;
+    let sym = &syms[i];
+    let name = table.get(sym.name_off as usize..(sym.name_off + sym.name_len) as usize)?;
+    Some((str::from_utf8(name).ok()?, pc - sym.addr as usize))
+}
+
+/// `pc` 是否位于内核代码段。
+fn in_text(pc: usize) -> bool {
+    extern "C" {
+        fn etext();
+    }
+    pc >= usize::from(KERNBASE) && pc < etext as *const () as usize
+}
+
+/// # 功能说明
+/// 从调用者的栈帧开始沿帧指针链回溯，对每一层调用 `f`，参数为层数与返回地址。
+///
+/// # 注意
+/// 读取每个栈帧之前检查它位于内核页表已映射的内核地址，
+/// 从用户态陷入时帧指针仍是用户程序的值，回溯在陷阱处理程序处停止。
+#[inline(never)]
+pub fn walk(mut f: impl FnMut(usize, usize)) {
+    let mut fp: usize;
+    unsafe { core::arch::asm!("mv {}, s0", out(reg) fp); }
+    for depth in 0..MAX_DEPTH {
+        if fp % size_of::<usize>() != 0 || fp < usize::from(KERNBASE) + 16
+            || !kvm_mapped(fp - 16) || !kvm_mapped(fp - 1) {
+            break
+        }
+        let (ra, prev) = unsafe { (*((fp - 8) as *const usize), *((fp - 16) as *const usize)) };
+        if !in_text(ra) {
+            break
+        }
+        f(depth, ra);
+        // 栈向低地址增长，调用者的栈帧位于更高的地址
+        if prev <= fp {
+            break
+        }
+        fp = prev;
+    }
+}
+
+/// 一层栈帧，显示为 `#层数 地址 函数名+偏移`。
+struct Frame {
+    depth: usize,
+    pc: usize,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match lookup(self.pc) {
+            Some((name, off)) => write!(f, "  #{} {:#x} {}+{:#x}", self.depth, self.pc, name, off),
+            None => write!(f, "  #{} {:#x}", self.depth, self.pc),
+        }
+    }
+}
+
+/// 在控制台打印当前的调用栈，由 panic 处理函数调用。
+pub fn print() {
+    if UNWINDING.swap(true, Ordering::Relaxed) {
+        return
+    }
+    println!("backtrace:");
+    walk(|depth, pc| {
+        println!("{}", Frame { depth, pc });
+    });
+    UNWINDING.store(false, Ordering::Relaxed);
+}
+
+/// 把当前的调用栈以 `level` 级别写入内核日志，由 `kwarn_bt!` 在启用 `kernel_warning` 特性时调用。
+pub fn log(level: Level) {
+    if UNWINDING.swap(true, Ordering::Relaxed) {
+        return
+    }
+    walk(|depth, pc| klog::_log(level, format_args!("{}", Frame { depth, pc })));
+    UNWINDING.store(false, Ordering::Relaxed);
+}
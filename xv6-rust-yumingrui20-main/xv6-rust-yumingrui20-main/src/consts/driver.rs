@@ -4,18 +4,39 @@ pub const NDEV: usize = 10;
 /// buffer size for console
 pub const CONSOLE_BUF: usize = 128;
 
-/// buffer size for uart
-pub const UART_BUF: usize = 32;
+/// buffer size for the output of a pseudo-terminal, from slave to master
+pub const PTY_BUF: usize = 1024;
+
+/// transmit buffer size for uart
+pub const UART_TX_BUF: usize = 1024;
+
+/// receive buffer size for uart
+pub const UART_RX_BUF: usize = 256;
 
 /// constant device index of console
 pub const DEV_CONSOLE: usize = 1;
 
+/// constant device index of null device, discarding writes and reading EOF
+pub const DEV_NULL: usize = 2;
+
+/// constant device index of zero device, reading endless zeros
+pub const DEV_ZERO: usize = 3;
+
+/// constant device index of full device, failing every write
+pub const DEV_FULL: usize = 4;
+
+/// constant device index of random device
+pub const DEV_RANDOM: usize = 5;
+
 ////////////////////////////////////////////////
 ///////////    Control Characters   ////////////
 ////////////////////////////////////////////////
 
 // reference manual: https://man7.org/linux/man-pages/man4/console_codes.4.html
 
+/// interrupt, ^C
+pub const CTRL_ETX: u8 = 0x03;
+
 /// end of transmit/file.line
 pub const CTRL_EOT: u8 = 0x04;
 
@@ -28,6 +49,9 @@ pub const CTRL_LF: u8 = 0x0A;
 /// carriage return
 pub const CTRL_CR: u8 = 0x0D;
 
+/// suspend, ^Z
+pub const CTRL_SUB: u8 = 0x1a;
+
 /// DEL
 pub const CTRL_DEL: u8 = 0x7f;
 
@@ -41,3 +65,60 @@ pub const CTRL_PRINT_PROCESS: u8 = 0x10;
 /// backspace the whole line
 // TODO
 pub const CTRL_BS_LINE: u8 = 0x15;
+
+////////////////////////////////////////////////
+///////////    Terminal Control     ////////////
+////////////////////////////////////////////////
+
+// reference manual: https://man7.org/linux/man-pages/man3/termios.3.html
+
+/// ioctl request: get the terminal attributes into a `struct termios`
+pub const TCGETS: usize = 0x5401;
+
+/// ioctl request: set the terminal attributes from a `struct termios`
+pub const TCSETS: usize = 0x5402;
+
+/// ioctl request: get the foreground process group of the terminal
+pub const TIOCGPGRP: usize = 0x540f;
+
+/// ioctl request: set the foreground process group of the terminal
+pub const TIOCSPGRP: usize = 0x5410;
+
+/// ioctl request: get the window size into a `struct winsize`
+pub const TIOCGWINSZ: usize = 0x5413;
+
+/// ioctl request: set the window size from a `struct winsize`
+pub const TIOCSWINSZ: usize = 0x5414;
+
+/// input flag: translate carriage return to line feed
+pub const ICRNL: u32 = 0x100;
+
+/// local flag: generate signals for INTR and SUSP characters
+pub const ISIG: u32 = 0x1;
+
+/// local flag: canonical mode, input is made available line by line
+pub const ICANON: u32 = 0x2;
+
+/// local flag: echo input characters
+pub const ECHO: u32 = 0x8;
+
+/// size of the control character array in `struct termios`
+pub const NCCS: usize = 8;
+
+/// control character index: interrupt, sends SIGINT
+pub const VINTR: usize = 0;
+
+/// control character index: erase the previous character
+pub const VERASE: usize = 1;
+
+/// control character index: erase the current line
+pub const VKILL: usize = 2;
+
+/// control character index: end of file
+pub const VEOF: usize = 3;
+
+/// control character index: suspend, sends SIGTSTP
+pub const VSUSP: usize = 4;
+
+/// control character index: minimum number of bytes for a non-canonical read
+pub const VMIN: usize = 5;
@@ -20,6 +20,8 @@ pub const MAX_FILE_SIZE: usize = (NDIRECT + NINDIRECT) * BSIZE;
 
 /// root device number
 pub const ROOTDEV: u32 = 1;
+/// device number reported by the proc file system
+pub const PROCDEV: u32 = 0xff;
 /// root inode number in root device
 /// i.e., starting inode of the file tree structure
 pub const ROOTINUM: u32 = 1;
@@ -33,8 +35,13 @@ pub const NBUF: usize = MAXOPBLOCKS * 3;
 /// size of log space in disk
 pub const LOGSIZE: usize = MAXOPBLOCKS * 3;
 
-/// maxinum number of file opened by a process
+/// default limit on the number of files opened by a process
 pub const NFILE: usize = 16;
+/// upper bound of the per-process limit on opened files
+pub const NOFILE_MAX: usize = 128;
+
+/// maxinum number of mounted file systems, not counting the root fs
+pub const NMOUNT: usize = 4;
 
 /////////////////////////////////////////////////
 ///////////    File Creation Flags   ////////////
@@ -45,7 +52,50 @@ pub const O_WRONLY: i32 = 0x1;
 pub const O_RDWR: i32 = 0x2;
 pub const O_CREATE: i32 = 0x200;
 pub const O_TRUNC: i32 = 0x400;
+/// reads and writes fail instead of waiting
+pub const O_NONBLOCK: i32 = 0x800;
+/// set FD_CLOEXEC on the new descriptor
+pub const O_CLOEXEC: i32 = 0x80000;
+
+/////////////////////////////////////////////////
+///////////    fcntl Commands        ////////////
+/////////////////////////////////////////////////
+
+/// duplicate to the lowest free descriptor not below arg
+pub const F_DUPFD: i32 = 0;
+/// get the descriptor flags
+pub const F_GETFD: i32 = 1;
+/// set the descriptor flags
+pub const F_SETFD: i32 = 2;
+/// get the access mode and file status flags
+pub const F_GETFL: i32 = 3;
+/// set the file status flags, only O_NONBLOCK can be changed
+pub const F_SETFL: i32 = 4;
+/// set the capacity of a pipe, rounded up to a power-of-two number of pages
+pub const F_SETPIPE_SZ: i32 = 1031;
+/// get the capacity of a pipe
+pub const F_GETPIPE_SZ: i32 = 1032;
+/// descriptor flag: close the descriptor on exec
+pub const FD_CLOEXEC: i32 = 1;
+
+/////////////////////////////////////////////////
+///////////    Poll Event Flags      ////////////
+/////////////////////////////////////////////////
+
+/// there is data to read
+pub const POLLIN: u16 = 0x1;
+/// writing now will not block
+pub const POLLOUT: u16 = 0x4;
+/// error condition
+pub const POLLERR: u16 = 0x8;
+/// the other end hung up
+pub const POLLHUP: u16 = 0x10;
+/// the file descriptor is not open
+pub const POLLNVAL: u16 = 0x20;
 
-/// maximum data size of a pipe
-pub const PIPESIZE: usize = 454;
-pub const PIPESIZE_U32: u32 = 454;
\ No newline at end of file
+/// default capacity of a pipe in bytes
+pub const PIPESIZE: usize = super::PGSIZE;
+/// maximum capacity a pipe can be resized to with F_SETPIPE_SZ
+pub const PIPE_MAX_SIZE: usize = 16 * super::PGSIZE;
+/// writes of at most this many bytes to a pipe are atomic
+pub const PIPE_BUF: usize = super::PGSIZE;
\ No newline at end of file
@@ -22,6 +22,7 @@ use super::*;
 /// local interrupt controller, which contains the timer.
 pub const CLINT: ConstAddr = ConstAddr(0x2000000);
 pub const CLINT_MAP_SIZE: usize = 0x10000;
+pub const CLINT_MSIP: ConstAddr = CLINT.const_add(0);
 pub const CLINT_MTIMECMP: ConstAddr = CLINT.const_add(0x4000);
 pub const CLINT_MTIME: ConstAddr = CLINT.const_add(0xbff8);
 
@@ -52,7 +53,13 @@ pub const TRAMPOLINE: ConstAddr = MAXVA.const_sub(PGSIZE);
 
 /// trapframe is below the trampoline
 /// 0x3FFFFFE000
+/// the other threads sharing the address space
+/// map their trapframes in the pages below it
 pub const TRAPFRAME: ConstAddr = TRAMPOLINE.const_sub(PGSIZE);
 
 /// user text/code start address
 pub const USERTEXT: ConstAddr = ConstAddr(0);
+
+/// shared memory segments are attached between SHMBASE and
+/// the lowest thread trapframe; the heap may not grow past SHMBASE.
+pub const SHMBASE: ConstAddr = ConstAddr(MAXVA.0 / 2);
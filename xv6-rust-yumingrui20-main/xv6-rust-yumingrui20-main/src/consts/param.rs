@@ -4,6 +4,14 @@ pub const NCPU: usize = 8;
 /// Maximum number of processes
 pub const NPROC: usize = 64;
 
+/// Maximum number of threads sharing one address space
+pub const NTHREAD: usize = 16;
+
+/// Maximum number of shared memory segments
+pub const NSHM: usize = 16;
+/// Maximum size of a shared memory segment in bytes
+pub const SHMMAX: usize = 256 * 4096;
+
 /// This is actual number of harts.
 /// Same value is passed to qemu with -smp option
 pub const NSMP: usize = 3;
@@ -21,6 +29,65 @@ pub const MAXPATH: usize = 128;
 pub const MAXARG: usize = 16;
 /// maximum length of a single command line argument
 pub const MAXARGLEN: usize = 64;
+/// maximum length of the command line saved in a process, counting 0 in the end
+pub const MAXCMDLINE: usize = 128;
+
+/// frequency of the CLINT mtime counter in Hz, 10MHz on qemu's virt machine
+pub const MTIME_FREQ: u64 = 10_000_000;
+/// mtime cycles between timer interrupts, i.e. the length of a tick, 0.1s on qemu
+pub const TICK_INTERVAL: u64 = 1_000_000;
+
+/// mtime cycles between profiler samples while the profiler is running, 1ms on qemu
+pub const PROFILE_INTERVAL: u64 = 10_000;
+
+/// kprof command: stop the profiler and return the number of samples
+pub const KPROF_STOP: i32 = 0;
+/// kprof command: clear samples and lock statistics, then start the profiler
+pub const KPROF_START: i32 = 1;
+
+/// clock ids for clock_gettime, both count from boot since there is no real-time clock
+pub const CLOCK_REALTIME: i32 = 0;
+pub const CLOCK_MONOTONIC: i32 = 1;
+
+/// interval timer that counts real time and delivers SIGALRM
+pub const ITIMER_REAL: i32 = 0;
+
+/// size of the kernel log ring buffer in bytes
+pub const KLOG_BUF: usize = 16384;
+
+/// syslog action: read the whole kernel log
+pub const SYSLOG_ACTION_READ_ALL: i32 = 3;
+/// syslog action: clear the kernel log
+pub const SYSLOG_ACTION_CLEAR: i32 = 5;
+/// syslog action: set the console level
+pub const SYSLOG_ACTION_CONSOLE_LEVEL: i32 = 8;
+/// syslog action: get the size of the kernel log buffer
+pub const SYSLOG_ACTION_SIZE_BUFFER: i32 = 10;
+
+/// futex operation: sleep while the word still holds the expected value
+pub const FUTEX_WAIT: i32 = 0;
+/// futex operation: wake up processes waiting on the word
+pub const FUTEX_WAKE: i32 = 1;
+/// futex wait result: the timeout expired before the waiter was woken up,
+/// distinct from the -1 returned for errors such as a changed word
+pub const FUTEX_TIMEDOUT: isize = -2;
+
+/// shmget key that always creates a new segment
+pub const IPC_PRIVATE: usize = 0;
+/// shmget flag: create the segment if the key does not exist
+pub const IPC_CREAT: usize = 0o1000;
+/// shmget flag: fail if the key already exists
+pub const IPC_EXCL: usize = 0o2000;
+/// shmctl command: remove the segment once it is no longer attached
+pub const IPC_RMID: i32 = 0;
+
+/// resource limit on the number of open file descriptors
+pub const RLIMIT_NOFILE: i32 = 7;
+
+/// waitpid option: return 0 instead of waiting if no child has changed state
+pub const WNOHANG: i32 = 1;
+/// waitpid option: also report children that have stopped
+pub const WUNTRACED: i32 = 2;
 
 /// The smallest block size of the buddy system
 pub const LEAF_SIZE: usize = 16;
\ No newline at end of file
@@ -0,0 +1,31 @@
+//! signal numbers, same as Linux on RISC-V
+
+/// interrupt from keyboard, terminates the process by default
+pub const SIGINT: usize = 2;
+
+/// kill, terminates the process and cannot be caught
+pub const SIGKILL: usize = 9;
+
+/// timer signal from alarm or setitimer, terminates the process by default
+pub const SIGALRM: usize = 14;
+
+/// termination request, terminates the process by default
+pub const SIGTERM: usize = 15;
+
+/// continue a stopped process, cannot be ignored
+pub const SIGCONT: usize = 18;
+
+/// stop, cannot be caught or ignored
+pub const SIGSTOP: usize = 19;
+
+/// stop typed at terminal
+pub const SIGTSTP: usize = 20;
+
+/// number of signals, valid signal numbers are below it
+pub const NSIG: usize = 32;
+
+/// signal disposition: take the default action
+pub const SIG_DFL: usize = 0;
+
+/// signal disposition: ignore the signal
+pub const SIG_IGN: usize = 1;
@@ -0,0 +1,79 @@
+//! 内存类字符设备：/dev/null、/dev/zero 与 /dev/full
+
+use core::cmp::min;
+
+use crate::consts::driver::{DEV_NULL, DEV_ZERO, DEV_FULL};
+use crate::mm::Address;
+
+use super::Device;
+
+/// 登记 null、zero 与 full 三个设备。
+pub fn init() {
+    super::register(DEV_NULL, &Null).expect("mem: register null");
+    super::register(DEV_ZERO, &Zero).expect("mem: register zero");
+    super::register(DEV_FULL, &Full).expect("mem: register full");
+}
+
+/// 向 `dst` 写入 `count` 个零字节。
+fn fill_zero(mut dst: Address, count: u32) -> Result<u32, ()> {
+    let zeros = [0u8; 64];
+    let mut left = count as usize;
+    while left > 0 {
+        let n = min(left, zeros.len());
+        dst.copy_out(zeros.as_ptr(), n)?;
+        dst = dst.offset(n);
+        left -= n;
+    }
+    Ok(count)
+}
+
+/// 空设备：读取总是立即返回文件结束，写入的数据被全部丢弃。
+struct Null;
+
+impl Device for Null {
+    fn name(&self) -> &'static str {
+        "null"
+    }
+
+    fn read(&self, _dst: Address, _count: u32) -> Result<u32, ()> {
+        Ok(0)
+    }
+
+    fn write(&self, _src: Address, count: u32) -> Result<u32, ()> {
+        Ok(count)
+    }
+}
+
+/// 零设备：读取得到无穷无尽的零字节，写入的数据被全部丢弃。
+struct Zero;
+
+impl Device for Zero {
+    fn name(&self) -> &'static str {
+        "zero"
+    }
+
+    fn read(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        fill_zero(dst, count)
+    }
+
+    fn write(&self, _src: Address, count: u32) -> Result<u32, ()> {
+        Ok(count)
+    }
+}
+
+/// 满设备：读取与零设备相同，写入总是失败，用于模拟磁盘已满。
+struct Full;
+
+impl Device for Full {
+    fn name(&self) -> &'static str {
+        "full"
+    }
+
+    fn read(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        fill_zero(dst, count)
+    }
+
+    fn write(&self, _src: Address, _count: u32) -> Result<u32, ()> {
+        Err(())
+    }
+}
@@ -1,32 +1,91 @@
-//! 设备驱动模块，包含串口与磁盘的驱动
+//! 设备驱动模块，包含串口、磁盘以及各类字符设备的驱动
 
 use core::sync::atomic::AtomicBool;
 
-use crate::{consts::driver::NDEV, mm::Address};
+use crate::consts::driver::NDEV;
+use crate::consts::fs::{POLLIN, POLLOUT};
+use crate::mm::Address;
+use crate::process::PollWaiter;
+use crate::spinlock::SpinLock;
 
 pub mod virtio_disk;
 pub mod console;
 pub mod uart;
+pub mod tty;
+pub mod pty;
+pub mod mem;
+pub mod random;
 
 /// 用于表示是否有任何硬件线程触发了 panic。
 pub(crate) static PANICKED: AtomicBool = AtomicBool::new(false);
 
-pub static DEVICES: [Option<Device>; NDEV] = [
-    /* 0 */   None,
-    /* 1 */   Some(Device { read: console::read, write: console::write }),
-    /* 2 */   None,
-    /* 3 */   None,
-    /* 4 */   None,
-    /* 5 */   None,
-    /* 6 */   None,
-    /* 7 */   None,
-    /* 8 */   None,
-    /* 9 */   None,
-];
-
-pub struct Device {
-    /// 功能：从 [Address] 读取 count 个字节。
-    pub read: fn(Address, u32) -> Result<u32, ()>,
-    /// 功能：向 [Address] 写入 count 个字节。
-    pub write: fn(Address, u32) -> Result<u32, ()>,
+/// 设备注册表，以主设备号为下标。
+///
+/// 各驱动在初始化时通过 [`register`] 登记自身，
+/// 设备文件被读写时通过 [`device`] 按主设备号查找对应驱动。
+static DEVICES: SpinLock<[Option<&'static dyn Device>; NDEV]> =
+    SpinLock::new([None; NDEV], "devices");
+
+/// 字符设备驱动需要实现的接口。
+pub trait Device: Sync {
+    /// 设备名称，启动时会在 `/dev` 下创建同名的设备文件。
+    fn name(&self) -> &'static str;
+
+    /// 功能：从设备读取至多 count 个字节到 [Address]。
+    fn read(&self, dst: Address, count: u32) -> Result<u32, ()>;
+
+    /// 功能：将 [Address] 处的至多 count 个字节写入设备。
+    fn write(&self, src: Address, count: u32) -> Result<u32, ()>;
+
+    /// 功能：以非阻塞方式读取，需要等待时返回错误。默认与 `read` 相同。
+    fn read_nonblock(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        self.read(dst, count)
+    }
+
+    /// 功能：以非阻塞方式写入，需要等待时返回已写入的字节数或错误。默认与 `write` 相同。
+    fn write_nonblock(&self, src: Address, count: u32) -> Result<u32, ()> {
+        self.write(src, count)
+    }
+
+    /// 功能：执行设备相关的控制命令 `request`，`arg` 的含义由命令决定。
+    /// 默认不支持任何命令。
+    fn ioctl(&self, _request: usize, _arg: usize) -> Result<usize, ()> {
+        Err(())
+    }
+
+    /// 功能：查询设备当前的就绪状态，返回 `POLLIN`、`POLLOUT` 等标志位的组合。
+    /// 默认总是可读可写；可能阻塞的设备与 [`FileOps::poll`](crate::fs::vfs::FileOps::poll) 一样登记 `waiter`。
+    fn poll(&self, _waiter: PollWaiter) -> u16 {
+        POLLIN | POLLOUT
+    }
+}
+
+/// 将驱动 `dev` 登记到主设备号 `major` 上。
+///
+/// # 返回值
+/// - `Ok(())`：登记成功；
+/// - `Err(())`：主设备号越界或已被占用。
+pub fn register(major: usize, dev: &'static dyn Device) -> Result<(), ()> {
+    let mut devices = DEVICES.lock();
+    let slot = devices.get_mut(major).ok_or(())?;
+    if slot.is_some() {
+        return Err(())
+    }
+    *slot = Some(dev);
+    drop(devices);
+
+    #[cfg(feature = "verbose_init_info")]
+    kinfo!("device: register {} as major {}", dev.name(), major);
+
+    Ok(())
+}
+
+/// 按主设备号查找已登记的驱动。
+pub fn device(major: usize) -> Option<&'static dyn Device> {
+    *DEVICES.lock().get(major)?
+}
+
+/// 获取当前设备注册表的副本，用于在不持有锁的情况下遍历所有设备。
+pub fn devices() -> [Option<&'static dyn Device>; NDEV] {
+    *DEVICES.lock()
 }
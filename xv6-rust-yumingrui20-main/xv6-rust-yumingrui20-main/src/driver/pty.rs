@@ -0,0 +1,313 @@
+//! 伪终端（pseudo-terminal）
+//!
+//! 每个伪终端由一对文件组成：
+//! - 主端（master）写入的数据相当于从键盘输入，经过与控制台相同的行规程 [`Tty`]
+//!   处理后由从端读出，回显则进入输出缓冲区；
+//! - 从端（slave）写入的数据进入输出缓冲区，由主端读出，相当于显示到屏幕上。
+//!
+//! 终端复用器或测试程序持有主端，在从端上运行 shell 等交互程序，
+//! 不需要真实的串口硬件即可驱动一个终端会话。
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::cmp::min;
+use core::fmt;
+use core::num::Wrapping;
+use core::sync::atomic::Ordering;
+
+use crate::consts::driver::PTY_BUF;
+use crate::consts::fs::{POLLIN, POLLOUT, POLLHUP};
+use crate::fs::File;
+use crate::fs::vfs::FileOps;
+use crate::mm::Address;
+use crate::process::{CPU_MANAGER, PROC_MANAGER, PollQueue, PollWaiter};
+use crate::spinlock::SpinLock;
+
+use super::tty::{self, Tty};
+
+/// 一个伪终端，由主端与从端的文件共同持有。
+pub struct Pty {
+    /// 从端的行规程，输入来自主端的写入。
+    tty: SpinLock<Tty>,
+
+    /// 从端到主端的输出缓冲区。
+    ///
+    /// 锁的顺序：需要同时持有时，先获取 `tty` 再获取 `out`。
+    out: SpinLock<PtyOutput>,
+}
+
+struct PtyOutput {
+    buf: [u8; PTY_BUF],
+    // 读索引，由主端推进
+    ri: Wrapping<usize>,
+    // 写索引，由从端写入与回显推进
+    wi: Wrapping<usize>,
+    master_open: bool,
+    slave_open: bool,
+    // 等待输出（主端读、从端写）的 poll 等待者
+    pollq: PollQueue,
+}
+
+impl PtyOutput {
+    fn is_full(&self) -> bool {
+        (self.wi - self.ri).0 == PTY_BUF
+    }
+
+    /// 放入一个字节，缓冲区已满时丢弃。
+    fn push(&mut self, c: u8) {
+        if !self.is_full() {
+            let i = self.wi.0 % PTY_BUF;
+            self.buf[i] = c;
+            self.wi += Wrapping(1);
+        }
+    }
+
+    /// 主端读者的等待通道。
+    fn read_channel(&self) -> usize {
+        &self.ri as *const Wrapping<_> as usize
+    }
+
+    /// 从端写者的等待通道。
+    fn write_channel(&self) -> usize {
+        &self.wi as *const Wrapping<_> as usize
+    }
+}
+
+impl Pty {
+    /// 创建一个伪终端，返回 `(master, slave)` 两个可读可写的文件。
+    ///
+    /// # 返回值
+    /// - `Some((master, slave))`：创建成功；
+    /// - `None`：内存不足。
+    pub fn create() -> Option<(Arc<File>, Arc<File>)> {
+        let pty = Arc::try_new(Pty {
+            tty: SpinLock::new(Tty::new(), "pty"),
+            out: SpinLock::new(PtyOutput {
+                buf: [0; PTY_BUF],
+                ri: Wrapping(0),
+                wi: Wrapping(0),
+                master_open: true,
+                slave_open: true,
+                pollq: PollQueue::new(),
+            }, "ptyout"),
+        }).ok()?;
+
+        let master = File::from_ops(Box::new(PtyMaster(Arc::clone(&pty))), true, true)?;
+        let slave = File::from_ops(Box::new(PtySlave(pty)), true, true)?;
+        Some((master, slave))
+    }
+
+    /// 主端读取：取出从端的输出，没有输出时等待（`nonblock` 为 `true` 时返回错误）；
+    /// 从端关闭且输出读空后返回 0。
+    fn master_read(&self, dst: Address, count: u32, nonblock: bool) -> Result<u32, ()> {
+        let p = unsafe { CPU_MANAGER.my_proc() };
+
+        let mut out = self.out.lock();
+        while out.ri == out.wi && out.slave_open {
+            if nonblock || p.killed.load(Ordering::Relaxed) {
+                return Err(())
+            }
+            let channel = out.read_channel();
+            p.sleep_interruptible(channel, out, None);
+            out = self.out.lock();
+        }
+
+        let count = min(count as usize, (out.wi - out.ri).0);
+        let mut read_count = count;
+        for i in 0..count {
+            let c = out.buf[out.ri.0 % PTY_BUF];
+            if dst.offset(i).copy_out(&c as *const u8, 1).is_err() {
+                read_count = i;
+                break
+            }
+            out.ri += Wrapping(1);
+        }
+        unsafe { PROC_MANAGER.wakeup(out.write_channel()); }
+        out.pollq.notify();
+        Ok(read_count as u32)
+    }
+
+    /// 主端写入：每个字节都作为从端的键盘输入交给行规程，回显进入输出缓冲区。
+    fn master_write(&self, mut src: Address, count: u32) -> Result<u32, ()> {
+        let mut chunk = [0u8; 64];
+        let mut left = count as usize;
+        while left > 0 {
+            let n = min(left, chunk.len());
+            src.copy_in(chunk.as_mut_ptr(), n)?;
+
+            let mut tty = self.tty.lock();
+            let mut out = self.out.lock();
+            let wi = out.wi;
+            for &c in chunk[..n].iter() {
+                tty.input(c, &mut |c| out.push(c));
+            }
+            if out.wi != wi {
+                unsafe { PROC_MANAGER.wakeup(out.read_channel()); }
+                out.pollq.notify();
+            }
+            drop(out);
+            drop(tty);
+
+            src = src.offset(n);
+            left -= n;
+        }
+        Ok(count)
+    }
+
+    /// 从端写入：放入输出缓冲区，缓冲区满时等待主端读取；主端关闭后写入失败。
+    /// `nonblock` 为 `true` 时缓冲区满不等待，返回已写入的字节数。
+    fn slave_write(&self, src: Address, count: u32, nonblock: bool) -> Result<u32, ()> {
+        let p = unsafe { CPU_MANAGER.my_proc() };
+
+        let mut out = self.out.lock();
+        let mut write_count = 0;
+        while write_count < count as usize {
+            if !out.master_open || p.killed.load(Ordering::Relaxed) {
+                return Err(())
+            }
+
+            if out.is_full() {
+                if nonblock {
+                    if write_count == 0 {
+                        return Err(())
+                    }
+                    break
+                }
+                unsafe { PROC_MANAGER.wakeup(out.read_channel()); }
+                out.pollq.notify();
+                let channel = out.write_channel();
+                p.sleep_interruptible(channel, out, None);
+                out = self.out.lock();
+            } else {
+                let mut c = 0u8;
+                if src.offset(write_count).copy_in(&mut c, 1).is_err() {
+                    break
+                }
+                out.push(c);
+                write_count += 1;
+            }
+        }
+        unsafe { PROC_MANAGER.wakeup(out.read_channel()); }
+        out.pollq.notify();
+        Ok(write_count as u32)
+    }
+
+    /// 主端的就绪状态：有输出时可读，写入从不阻塞，从端关闭后报告 `POLLHUP`。
+    fn master_poll(&self, waiter: PollWaiter) -> u16 {
+        let mut out = self.out.lock();
+        out.pollq.register(waiter);
+        let mut mask = POLLOUT;
+        if out.ri != out.wi {
+            mask |= POLLIN;
+        }
+        if !out.slave_open {
+            mask |= POLLHUP;
+        }
+        mask
+    }
+
+    /// 从端的就绪状态：输入由行规程报告，输出缓冲区未满时可写。
+    /// 等待者同时登记在行规程与输出缓冲区的队列上。
+    fn slave_poll(&self, waiter: PollWaiter) -> u16 {
+        let mut tty = self.tty.lock();
+        let mut out = self.out.lock();
+        out.pollq.register(waiter);
+        let mut mask = tty.poll(waiter) & !POLLOUT;
+        if !out.is_full() {
+            mask |= POLLOUT;
+        }
+        mask
+    }
+}
+
+/// 伪终端的主端。
+pub struct PtyMaster(Arc<Pty>);
+
+/// 伪终端的从端。
+pub struct PtySlave(Arc<Pty>);
+
+impl FileOps for PtyMaster {
+    fn read(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        self.0.master_read(dst, count, false)
+    }
+
+    fn write(&self, src: Address, count: u32) -> Result<u32, ()> {
+        self.0.master_write(src, count)
+    }
+
+    fn read_nonblock(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        self.0.master_read(dst, count, true)
+    }
+
+    /// 主端与从端共用行规程的终端属性。
+    fn ioctl(&self, request: usize, arg: usize) -> Result<usize, ()> {
+        self.0.tty.lock().ioctl(request, arg)
+    }
+
+    fn poll(&self, waiter: PollWaiter) -> u16 {
+        self.0.master_poll(waiter)
+    }
+
+    /// 关闭主端：挂断从端，唤醒从端的读者与写者。
+    fn close(&mut self, _writable: bool) {
+        let mut tty = self.0.tty.lock();
+        let mut out = self.0.out.lock();
+        out.master_open = false;
+        unsafe { PROC_MANAGER.wakeup(out.write_channel()); }
+        tty.hangup();
+    }
+
+    fn kind(&self) -> &'static str {
+        "ptm"
+    }
+}
+
+impl FileOps for PtySlave {
+    fn read(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        tty::read(&self.0.tty, dst, count, false)
+    }
+
+    fn write(&self, src: Address, count: u32) -> Result<u32, ()> {
+        self.0.slave_write(src, count, false)
+    }
+
+    fn read_nonblock(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        tty::read(&self.0.tty, dst, count, true)
+    }
+
+    fn write_nonblock(&self, src: Address, count: u32) -> Result<u32, ()> {
+        self.0.slave_write(src, count, true)
+    }
+
+    fn ioctl(&self, request: usize, arg: usize) -> Result<usize, ()> {
+        self.0.tty.lock().ioctl(request, arg)
+    }
+
+    fn poll(&self, waiter: PollWaiter) -> u16 {
+        self.0.slave_poll(waiter)
+    }
+
+    /// 关闭从端：主端读空输出后将读到文件结束。
+    fn close(&mut self, _writable: bool) {
+        let mut out = self.0.out.lock();
+        out.slave_open = false;
+        unsafe { PROC_MANAGER.wakeup(out.read_channel()); }
+        out.pollq.notify();
+    }
+
+    fn kind(&self) -> &'static str {
+        "pts"
+    }
+}
+
+impl fmt::Debug for PtyMaster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PtyMaster({:p})", Arc::as_ptr(&self.0))
+    }
+}
+
+impl fmt::Debug for PtySlave {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PtySlave({:p})", Arc::as_ptr(&self.0))
+    }
+}
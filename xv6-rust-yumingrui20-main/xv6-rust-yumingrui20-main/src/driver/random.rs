@@ -0,0 +1,180 @@
+//! 随机数设备 /dev/random
+//!
+//! 以 ChaCha20 分组函数作为伪随机数生成器：
+//! - 初始密钥混入启动时刻的计时器读数、硬件线程号，以及访问内存映射寄存器时 `cycle` 计数的抖动；
+//! - 之后的熵来源于外部中断（串口、磁盘）到达时刻的 `mtime` 抖动，以及用户写入设备的数据，
+//!   它们被累积在熵池中，并在每次读取前混入密钥；
+//! - 每次读取结束后立即用新生成的输出替换密钥（快速密钥擦除），
+//!   即使之后密钥泄露，也无法推算出此前已经输出的数据。
+//!
+//! 输出**不是**密码学安全的：启动时的熵很少，且在 QEMU 中计时抖动来自宿主机，
+//! 无法估计实际收集到的熵，尤其是刚启动时的输出可能被预测，不应用于生成密钥。
+
+use core::cmp::min;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::consts::driver::DEV_RANDOM;
+use crate::mm::Address;
+use crate::process::CpuManager;
+use crate::register::{clint, counter};
+use crate::spinlock::SpinLock;
+
+use super::Device;
+
+/// ChaCha20 生成器状态。
+static RNG: SpinLock<ChaCha20> = SpinLock::new(ChaCha20::new(), "random");
+
+/// 尚未混入密钥的熵。
+static ENTROPY: AtomicU64 = AtomicU64::new(0);
+
+/// 每个密钥字采样计时抖动的次数。
+const JITTER_ROUNDS: usize = 64;
+
+/// 初始化生成器，并登记随机数设备。
+///
+/// 密钥依次混入硬件线程号与 `time` 计数器，以及每个密钥字一次的 `mtime` 读数与计时抖动。
+pub fn init() {
+    let mut rng = RNG.lock();
+    let (hart, time) = unsafe { (CpuManager::cpu_id() as u64, counter::time()) };
+    rng.reseed(hart.rotate_left(32) ^ time);
+    for i in 0..rng.key.len() {
+        let t = unsafe { clint::read_mtime() };
+        rng.key[i] ^= (t ^ (t >> 32)) as u32 ^ jitter();
+        rng.rekey();
+    }
+    drop(rng);
+    super::register(DEV_RANDOM, &Random).expect("random: register device");
+}
+
+/// 采样计时抖动：反复读取 CLINT 的 `mtime` 寄存器，累积每次读取花费的 `cycle` 计数。
+fn jitter() -> u32 {
+    let mut acc = 0u64;
+    for _ in 0..JITTER_ROUNDS {
+        let (c0, t, c1) = unsafe { (counter::cycle(), clint::read_mtime(), counter::cycle()) };
+        acc = (acc.rotate_left(7) ^ c1.wrapping_sub(c0)).wrapping_add(t);
+    }
+    (acc ^ (acc >> 32)) as u32
+}
+
+/// 向熵池中混入一个样本，同时混入当前 `mtime` 的读数。
+///
+/// 该函数开销很小，可以在中断处理程序中调用。
+pub fn add_entropy(sample: u64) {
+    let t = unsafe { clint::read_mtime() };
+    let mixed = sample.wrapping_mul(0x9e3779b97f4a7c15).rotate_left((t & 63) as u32) ^ t;
+    ENTROPY.fetch_xor(mixed, Ordering::Relaxed);
+}
+
+/// 随机数设备：读取得到 CSPRNG 的输出，写入的数据作为熵混入熵池。
+struct Random;
+
+impl Device for Random {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn read(&self, mut dst: Address, count: u32) -> Result<u32, ()> {
+        RNG.lock().reseed(ENTROPY.swap(0, Ordering::Relaxed));
+
+        let mut left = count as usize;
+        let mut ret = Ok(count);
+        while left > 0 {
+            let block = RNG.lock().next_block();
+            let n = min(left, block.len());
+            if dst.copy_out(block.as_ptr(), n).is_err() {
+                ret = Err(());
+                break
+            }
+            dst = dst.offset(n);
+            left -= n;
+        }
+
+        RNG.lock().rekey();
+        ret
+    }
+
+    fn write(&self, mut src: Address, count: u32) -> Result<u32, ()> {
+        let mut left = count as usize;
+        while left > 0 {
+            let mut sample = [0u8; 8];
+            let n = min(left, sample.len());
+            src.copy_in(sample.as_mut_ptr(), n)?;
+            add_entropy(u64::from_le_bytes(sample));
+            src = src.offset(n);
+            left -= n;
+        }
+        Ok(count)
+    }
+}
+
+/// ChaCha20 生成器：256 位密钥加 64 位分组计数器，随机数流即密钥流。
+struct ChaCha20 {
+    key: [u32; 8],
+    counter: u64,
+}
+
+impl ChaCha20 {
+    const fn new() -> Self {
+        Self {
+            key: [0; 8],
+            counter: 0,
+        }
+    }
+
+    /// 将熵混入密钥后立即换钥，使熵扩散到整个密钥中。
+    fn reseed(&mut self, entropy: u64) {
+        self.key[0] ^= entropy as u32;
+        self.key[1] ^= (entropy >> 32) as u32;
+        self.rekey();
+    }
+
+    /// 用一个新的输出分组替换密钥，并重置计数器。
+    fn rekey(&mut self) {
+        let block = self.block();
+        for i in 0..self.key.len() {
+            self.key[i] = u32::from_le_bytes([block[4*i], block[4*i+1], block[4*i+2], block[4*i+3]]);
+        }
+        self.counter = 0;
+    }
+
+    /// 生成下一个 64 字节的输出分组。
+    fn next_block(&mut self) -> [u8; 64] {
+        self.block()
+    }
+
+    /// ChaCha20 分组函数，随机数流固定使用全零的 nonce。
+    fn block(&mut self) -> [u8; 64] {
+        let mut init = [0u32; 16];
+        init[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+        init[4..12].copy_from_slice(&self.key);
+        init[12] = self.counter as u32;
+        init[13] = (self.counter >> 32) as u32;
+        self.counter = self.counter.wrapping_add(1);
+
+        let mut x = init;
+        for _ in 0..10 {
+            quarter_round(&mut x, 0, 4, 8, 12);
+            quarter_round(&mut x, 1, 5, 9, 13);
+            quarter_round(&mut x, 2, 6, 10, 14);
+            quarter_round(&mut x, 3, 7, 11, 15);
+            quarter_round(&mut x, 0, 5, 10, 15);
+            quarter_round(&mut x, 1, 6, 11, 12);
+            quarter_round(&mut x, 2, 7, 8, 13);
+            quarter_round(&mut x, 3, 4, 9, 14);
+        }
+
+        let mut out = [0u8; 64];
+        for i in 0..16 {
+            out[4*i..4*i+4].copy_from_slice(&x[i].wrapping_add(init[i]).to_le_bytes());
+        }
+        out
+    }
+}
+
+#[inline]
+fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    x[a] = x[a].wrapping_add(x[b]); x[d] = (x[d] ^ x[a]).rotate_left(16);
+    x[c] = x[c].wrapping_add(x[d]); x[b] = (x[b] ^ x[c]).rotate_left(12);
+    x[a] = x[a].wrapping_add(x[b]); x[d] = (x[d] ^ x[a]).rotate_left(8);
+    x[c] = x[c].wrapping_add(x[d]); x[b] = (x[b] ^ x[c]).rotate_left(7);
+}
@@ -0,0 +1,326 @@
+//! 终端行规程（line discipline）
+//!
+//! 控制台以及其他终端类设备共用的输入处理逻辑，行为由 [`Termios`] 控制：
+//! - 规范模式（`ICANON`）下按行编辑输入，支持退格、删除整行与文件结束符，
+//!   整行输入完成后才对读者可见；
+//! - 非规范（原始）模式下每个字节到达后立即对读者可见，读取至少等待 `VMIN` 个字节；
+//! - `ECHO` 控制是否回显输入，`ISIG` 控制中断与挂起字符是否向前台进程组发送信号。
+//!
+//! 终端设备（控制台与伪终端）以 `SpinLock<Tty>` 的形式持有行规程，
+//! 回显输出的方式由设备在调用 [`Tty::input`] 时给出。
+
+use core::cmp::min;
+use core::mem;
+use core::num::Wrapping;
+use core::sync::atomic::Ordering;
+
+use crate::consts::driver::*;
+use crate::consts::signal::{SIGINT, SIGTSTP};
+use crate::mm::Address;
+use crate::consts::fs::{POLLIN, POLLOUT, POLLHUP};
+use crate::process::{CPU_MANAGER, PROC_MANAGER, PollQueue, PollWaiter};
+use crate::spinlock::SpinLock;
+
+/// 终端属性，内存布局与用户态的 `struct termios` 一致。
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Termios {
+    pub iflag: u32,
+    pub lflag: u32,
+    pub cc: [u8; NCCS],
+}
+
+impl Termios {
+    /// 默认属性：规范模式、回显、产生信号，并将回车转换为换行。
+    const fn new() -> Self {
+        let mut cc = [0; NCCS];
+        cc[VINTR] = CTRL_ETX;
+        cc[VERASE] = CTRL_DEL;
+        cc[VKILL] = CTRL_BS_LINE;
+        cc[VEOF] = CTRL_EOT;
+        cc[VSUSP] = CTRL_SUB;
+        cc[VMIN] = 1;
+        Self {
+            iflag: ICRNL,
+            lflag: ISIG | ICANON | ECHO,
+            cc,
+        }
+    }
+}
+
+/// 终端窗口大小，内存布局与用户态的 `struct winsize` 一致。
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct WinSize {
+    pub row: u16,
+    pub col: u16,
+    pub xpixel: u16,
+    pub ypixel: u16,
+}
+
+/// 一个终端的行规程状态。
+pub struct Tty {
+    buf: [u8; CONSOLE_BUF],
+    // 读索引
+    ri: Wrapping<usize>,
+    // 写索引，[ri, wi) 中的字节对读者可见
+    wi: Wrapping<usize>,
+    // 编辑索引，[wi, ei) 中的字节是正在编辑的行
+    ei: Wrapping<usize>,
+    termios: Termios,
+    winsize: WinSize,
+    // 前台进程组，0 表示没有
+    fg: usize,
+    // 终端已挂断，读者不再等待输入
+    hungup: bool,
+    // 等待输入的 poll 等待者
+    pollq: PollQueue,
+}
+
+impl Tty {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; CONSOLE_BUF],
+            ri: Wrapping(0),
+            wi: Wrapping(0),
+            ei: Wrapping(0),
+            termios: Termios::new(),
+            winsize: WinSize { row: 24, col: 80, xpixel: 0, ypixel: 0 },
+            fg: 0,
+            hungup: false,
+            pollq: PollQueue::new(),
+        }
+    }
+
+    /// 是否开启了 `ISIG`，即控制字符是否由终端解释而不是交给读者。
+    pub fn isig(&self) -> bool {
+        self.termios.lflag & ISIG != 0
+    }
+
+    /// 读者睡眠与被唤醒时使用的等待通道。
+    fn channel(&self) -> usize {
+        &self.ri as *const Wrapping<_> as usize
+    }
+
+    /// 将正在编辑的内容提交给读者，并唤醒等待的读者。
+    fn commit(&mut self) {
+        self.wi = self.ei;
+        unsafe { PROC_MANAGER.wakeup(self.channel()); }
+        self.pollq.notify();
+    }
+
+    /// 查询终端的就绪状态并登记等待者 `waiter`：有已提交的输入时可读，总是可写，挂断后报告 `POLLHUP`。
+    pub fn poll(&mut self, waiter: PollWaiter) -> u16 {
+        self.pollq.register(waiter);
+        let mut mask = POLLOUT;
+        if self.wi != self.ri {
+            mask |= POLLIN;
+        }
+        if self.hungup {
+            mask |= POLLHUP;
+        }
+        mask
+    }
+
+    /// 挂断终端：唤醒所有读者，此后读取在缓冲区读空时返回文件结束。
+    pub fn hangup(&mut self) {
+        self.hungup = true;
+        self.commit();
+    }
+
+    /// 处理终端收到的一个输入字节
+    ///
+    /// # 功能说明
+    /// 按当前终端属性解释字节 `c`：产生信号、行编辑或放入输入缓冲区，
+    /// 需要回显的字节逐个交给 `echo` 输出。
+    ///
+    /// # 参数
+    /// - `c`: 收到的字节
+    /// - `echo`: 回显输出函数，调用时持有终端锁，不得睡眠
+    pub fn input(&mut self, c: u8, echo: &mut dyn FnMut(u8)) {
+        let Termios { iflag, lflag, cc } = self.termios;
+        let c = if c == CTRL_CR && iflag & ICRNL != 0 { CTRL_LF } else { c };
+
+        if lflag & ISIG != 0 && (c == cc[VINTR] || c == cc[VSUSP]) {
+            // 丢弃正在编辑的行
+            self.ei = self.wi;
+            if lflag & ECHO != 0 {
+                echo(b'^');
+                echo(c + b'@');
+                echo(CTRL_LF);
+            }
+            let sig = if c == cc[VINTR] { SIGINT } else { SIGTSTP };
+            if self.fg != 0 {
+                let _ = unsafe { PROC_MANAGER.signal_group(self.fg, sig) };
+            }
+            return
+        }
+
+        if lflag & ICANON != 0 {
+            if c == cc[VKILL] {
+                while self.ei != self.wi &&
+                    self.buf[(self.ei-Wrapping(1)).0 % CONSOLE_BUF] != CTRL_LF
+                {
+                    self.ei -= Wrapping(1);
+                    erase(lflag, echo);
+                }
+                return
+            }
+            if c == cc[VERASE] || c == CTRL_BS {
+                if self.ei != self.wi {
+                    self.ei -= Wrapping(1);
+                    erase(lflag, echo);
+                }
+                return
+            }
+            if c == 0 {
+                return
+            }
+        }
+
+        if (self.ei - self.ri).0 >= CONSOLE_BUF {
+            return
+        }
+        if lflag & ECHO != 0 {
+            echo(c);
+        }
+        let ei = self.ei.0 % CONSOLE_BUF;
+        self.buf[ei] = c;
+        self.ei += Wrapping(1);
+        if lflag & ICANON == 0 || c == CTRL_LF || c == cc[VEOF] ||
+            (self.ei - self.ri).0 == CONSOLE_BUF
+        {
+            self.commit();
+        }
+    }
+
+    /// 执行终端相关的 ioctl 命令，`arg` 是用户空间中参数结构的地址。
+    ///
+    /// # 返回值
+    /// - `Ok(0)`：命令执行成功；
+    /// - `Err(())`：命令不受支持、参数非法或用户地址无效。
+    pub fn ioctl(&mut self, request: usize, arg: usize) -> Result<usize, ()> {
+        let addr = Address::Virtual(arg);
+        match request {
+            TCGETS => {
+                addr.copy_out(&self.termios as *const Termios as *const u8, mem::size_of::<Termios>())?;
+            }
+            TCSETS => {
+                let mut termios = Termios::new();
+                addr.copy_in(&mut termios as *mut Termios as *mut u8, mem::size_of::<Termios>())?;
+                self.termios = termios;
+                // 切换到原始模式时，已编辑的内容立即对读者可见
+                if termios.lflag & ICANON == 0 {
+                    self.commit();
+                }
+            }
+            TIOCGWINSZ => {
+                addr.copy_out(&self.winsize as *const WinSize as *const u8, mem::size_of::<WinSize>())?;
+            }
+            TIOCSWINSZ => {
+                addr.copy_in(&mut self.winsize as *mut WinSize as *mut u8, mem::size_of::<WinSize>())?;
+            }
+            TIOCGPGRP => {
+                let fg = self.fg as i32;
+                addr.copy_out(&fg as *const i32 as *const u8, mem::size_of::<i32>())?;
+            }
+            TIOCSPGRP => {
+                let mut fg = 0i32;
+                addr.copy_in(&mut fg as *mut i32 as *mut u8, mem::size_of::<i32>())?;
+                if fg <= 0 || !unsafe { PROC_MANAGER.in_my_session(fg as usize) } {
+                    return Err(())
+                }
+                self.fg = fg as usize;
+            }
+            _ => return Err(()),
+        }
+        Ok(0)
+    }
+}
+
+/// 回显擦除一个字符：退格、空格、再退格。
+fn erase(lflag: u32, echo: &mut dyn FnMut(u8)) {
+    if lflag & ECHO != 0 {
+        echo(CTRL_BS);
+        echo(b' ');
+        echo(CTRL_BS);
+    }
+}
+
+/// 从终端读取数据
+///
+/// # 功能说明
+/// 从终端输入缓冲区读取最多`tot`字节到目标地址：
+/// - 规范模式下等待一整行，遇到换行符或文件结束符时返回；
+/// - 原始模式下等待至少 `VMIN` 个字节（不超过 `tot`），随后返回所有已到达的字节，
+///   `VMIN` 为 0 时不等待；
+/// - 终端挂断后不再等待，读空缓冲区后返回 0；
+/// - `nonblock` 为 `true` 时从不等待，返回已到达的字节。
+///
+/// # 返回值
+/// - `Ok(n)`: 实际读取的字节数
+/// - `Err(())`: 进程被终止，或非阻塞读取时没有可读的字节
+pub fn read(tty: &SpinLock<Tty>, mut dst: Address, tot: u32, nonblock: bool) -> Result<u32, ()> {
+    let mut guard = tty.lock();
+    let canonical = guard.termios.lflag & ICANON != 0;
+    let want = if canonical {
+        1
+    } else {
+        min(min(guard.termios.cc[VMIN] as usize, tot as usize), CONSOLE_BUF)
+    };
+
+    let mut left = tot;
+    while left > 0 {
+        // 等待输入
+        // 规范模式下每读取一个字节都要重新等待，原始模式下只在开始时等待
+        if canonical || left == tot {
+            while (guard.wi - guard.ri).0 < want && !guard.hungup {
+                if nonblock {
+                    if guard.ri == guard.wi && left == tot {
+                        return Err(())
+                    }
+                    break;
+                }
+                let p = unsafe { CPU_MANAGER.my_proc() };
+                if p.killed.load(Ordering::Relaxed) {
+                    return Err(())
+                }
+                let channel = guard.channel();
+                p.sleep_interruptible(channel, guard, None);
+                guard = tty.lock();
+            }
+        }
+        if guard.ri == guard.wi {
+            break;
+        }
+
+        // 读取
+        let c = guard.buf[guard.ri.0 % CONSOLE_BUF];
+        guard.ri += Wrapping(1);
+
+        // 遇到 EOF
+        // 提前返回
+        if canonical && c == guard.termios.cc[VEOF] {
+            if left < tot {
+                guard.ri -= Wrapping(1);
+            }
+            break;
+        }
+
+        // 复制到用户 / 内核空间内存
+        if dst.copy_out(&c as *const u8, 1).is_err() {
+            break;
+        }
+
+        // 更新
+        dst = dst.offset(1);
+        left -= 1;
+
+        // 遇到换行符
+        if canonical && c == CTRL_LF {
+            break;
+        }
+    }
+
+    Ok(tot - left)
+}
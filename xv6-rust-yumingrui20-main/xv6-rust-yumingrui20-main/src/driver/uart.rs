@@ -1,124 +1,139 @@
-use core::{sync::atomic::Ordering, num::Wrapping, ptr};
-use core::convert::Into;
+//! 16550 UART 驱动
+//!
+//! 收发都经过驱动内的环形缓冲区：
+//! - 发送：字符先放入发送缓冲区，在发送保持寄存器为空时一次填满硬件 FIFO，
+//!   其余字符由“发送保持寄存器空”中断继续发送；
+//! - 接收：中断处理程序先把硬件 FIFO 中的字符全部取到接收缓冲区，
+//!   再逐个交给上层的接收函数（控制台的行规程），缓冲区满时丢弃并计数。
+//!
+//! 驱动按串口实例组织，[`UART`] 是用户控制台所在的串口，内核打印也输出到这里。
+//! QEMU 的 virt 机器只有这一个 16550 串口，在有多个串口的板子上可以为其他串口创建新的实例。
 
-use crate::{consts::{UART0, driver::UART_BUF}, spinlock::SpinLock};
+use core::num::Wrapping;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::consts::{ConstAddr, UART0, driver::{UART_TX_BUF, UART_RX_BUF}};
+use crate::spinlock::SpinLock;
 use crate::process::{CPU_MANAGER, PROC_MANAGER};
-use crate::process::{push_off, pop_off};
+use crate::process::push_off;
 
 use super::PANICKED;
 use super::console;
 
-/// 寄存器访问宏
-///
-/// # 功能说明
-/// 将寄存器偏移量转换为物理地址
-///
-/// # 示例
-/// `Reg!(LSR)` 返回 LSR 寄存器的物理地址
-macro_rules! Reg {
-    ($reg: expr) => {
-        Into::<usize>::into(UART0) + $reg
-    };
-}
+/// 用户控制台所在的串口，接收到的字符交给控制台处理。
+pub static UART: Uart = Uart::new(UART0, "uart0", console::intr);
 
-/// 寄存器读取宏
-///
-/// # 功能说明
-/// 从指定寄存器读取一个字节（volatile 操作）
-///
-/// # 安全性
-/// 需要有效的寄存器偏移量
-macro_rules! ReadReg {
-    ($reg: expr) => {
-        unsafe { ptr::read_volatile(Reg!($reg) as *const u8) }
-    };
+/// 一个 16550 串口实例
+pub struct Uart {
+    /// 寄存器的基地址
+    base: ConstAddr,
+    /// 串口名称，用于统计信息展示
+    name: &'static str,
+    /// 发送缓冲区
+    tx: SpinLock<Ring<UART_TX_BUF>>,
+    /// 接收缓冲区
+    rx: SpinLock<Ring<UART_RX_BUF>>,
+    /// 接收到的字符交给该函数处理
+    receiver: fn(u8),
+    /// 收发计数与溢出计数
+    stat: UartCounters,
 }
 
-/// 寄存器写入宏
-///
-/// # 功能说明
-/// 向指定寄存器写入一个字节（volatile 操作）
-///
-/// # 安全性
-/// 需要有效的寄存器偏移量
-macro_rules! WriteReg {
-    ($reg: expr, $value: expr) => {
-        unsafe {
-            ptr::write_volatile(Reg!($reg) as *mut u8, $value);
-        }
-    };
+/// 串口的收发统计
+#[derive(Clone, Copy, Debug)]
+pub struct UartStat {
+    pub name: &'static str,
+    /// 已发送的字节数
+    pub tx: usize,
+    /// 已接收的字节数
+    pub rx: usize,
+    /// 接收缓冲区满而被丢弃的字节数
+    pub rx_dropped: usize,
+    /// 硬件报告的接收溢出（LSR.OE）次数，即来不及读取硬件 FIFO 而丢失数据的次数
+    pub overruns: usize,
 }
 
-/// 初始化 UART 设备
-///
-/// # 功能说明
-/// 配置 UART 设备参数：
-/// 1. 禁用中断
-/// 2. 设置波特率 (38.4K)
-/// 3. 配置数据格式 (8N1)
-/// 4. 启用 FIFO 缓冲区
-/// 5. 启用接收中断
-pub(super) fn init() {
-    // 禁用中断
-    WriteReg!(IER, 0x00);
-
-    // 用于设置波特率的特殊模式
-    WriteReg!(LCR, 0x80);
-
-    // 38.4K 波特率的最低有效位
-    WriteReg!(0, 0x03);
-
-    // 38.4K 波特率的最高有效位
-    WriteReg!(1, 0x00);
-
-    // 退出设置波特率模式，
-    // 并将字长设置为 8 位，无校验。
-    WriteReg!(LCR, 0x03);
-
-    //  重置并启用 FIFO
-    WriteReg!(FCR, 0x07);
-
-    // 启用接收中断
-    WriteReg!(IER, 0x03);
+struct UartCounters {
+    tx: AtomicUsize,
+    rx: AtomicUsize,
+    rx_dropped: AtomicUsize,
+    overruns: AtomicUsize,
 }
 
-/// 同步阻塞方式输出字符
-///
-/// # 功能说明
-/// 1. 禁用中断 (push_off)
-/// 2. 检查恐慌状态
-/// 3. 等待 UART 空闲
-/// 4. 发送字符
-/// 5. 恢复中断状态 (pop_off)
-///
-/// # 参数
-/// - `c`: 要发送的字符
-///
-/// # 注意
-/// 在系统恐慌状态下会进入死循环
-pub(super) fn putc_sync(c: u8) {
-    push_off();
-    if PANICKED.load(Ordering::Relaxed) {
-        loop {}
-    }
-    while !is_idle() {}
-    WriteReg!(THR, c);
-    pop_off();
-}
+impl Uart {
+    const fn new(base: ConstAddr, name: &'static str, receiver: fn(u8)) -> Self {
+        Self {
+            base,
+            name,
+            tx: SpinLock::new(Ring::new(), "uart_tx"),
+            rx: SpinLock::new(Ring::new(), "uart_rx"),
+            receiver,
+            stat: UartCounters {
+                tx: AtomicUsize::new(0),
+                rx: AtomicUsize::new(0),
+                rx_dropped: AtomicUsize::new(0),
+                overruns: AtomicUsize::new(0),
+            },
+        }
+    }
+
+    /// 从指定寄存器读取一个字节（volatile 操作）
+    #[inline]
+    fn read_reg(&self, reg: usize) -> u8 {
+        unsafe { ptr::read_volatile((usize::from(self.base) + reg) as *const u8) }
+    }
+
+    /// 向指定寄存器写入一个字节（volatile 操作）
+    #[inline]
+    fn write_reg(&self, reg: usize, value: u8) {
+        unsafe { ptr::write_volatile((usize::from(self.base) + reg) as *mut u8, value); }
+    }
+
+    /// 检查发送保持寄存器（启用 FIFO 时即整个发送 FIFO）是否为空
+    ///
+    /// # 说明
+    /// LSR[5] 位表示传输保持寄存器是否为空
+    /// 当该位为 1 时，可以向发送 FIFO 写入至多 [`FIFO_SIZE`] 个字节
+    #[inline]
+    fn is_idle(&self) -> bool {
+        self.read_reg(LSR) & LSR_TX_IDLE > 0
+    }
+
+    /// 初始化 UART 设备
+    ///
+    /// # 功能说明
+    /// 配置 UART 设备参数：
+    /// 1. 禁用中断
+    /// 2. 设置波特率 (38.4K)
+    /// 3. 配置数据格式 (8N1)
+    /// 4. 启用 FIFO 缓冲区
+    /// 5. 启用接收中断与发送保持寄存器空中断
+    pub fn init(&self) {
+        // 禁用中断
+        self.write_reg(IER, 0x00);
+
+        // 用于设置波特率的特殊模式
+        self.write_reg(LCR, 0x80);
+
+        // 38.4K 波特率的最低有效位
+        self.write_reg(0, 0x03);
+
+        // 38.4K 波特率的最高有效位
+        self.write_reg(1, 0x00);
+
+        // 退出设置波特率模式，
+        // 并将字长设置为 8 位，无校验。
+        self.write_reg(LCR, 0x03);
+
+        //  重置并启用 FIFO
+        self.write_reg(FCR, 0x07);
 
-/// 全局 UART 实例（自旋锁保护）
-pub static UART: SpinLock<Uart> = SpinLock::new(
-    Uart {
-        buf: [0; UART_BUF],
-        ri: Wrapping(0),
-        wi: Wrapping(0),
-    },
-    "uart",
-);
-
-/// 为自旋锁保护的 UART 实例添加扩展方法
-impl SpinLock<Uart> {
-    /// 异步输出字符到 UART
+        // 启用接收中断与发送中断
+        self.write_reg(IER, IER_RX_ENABLE | IER_TX_ENABLE);
+    }
+
+    /// 输出字符到 UART，发送缓冲区满时睡眠等待
     ///
     /// # 功能说明
     /// 1. 检查恐慌状态
@@ -126,77 +141,198 @@ impl SpinLock<Uart> {
     /// 3. 将字符放入缓冲区
     /// 4. 尝试传输数据
     ///
-    /// # 参数
-    /// - `c`: 要发送的字符
+    /// 只能在进程上下文中调用，例如控制台的 `write`。
     pub fn putc(&self, c: u8) {
-        let mut uart = self.lock();
+        let mut tx = self.tx.lock();
 
         if PANICKED.load(Ordering::Relaxed) {
             loop {}
         }
 
-        loop {
-            if uart.wi == uart.ri + Wrapping(UART_BUF) {
-                let p = unsafe { CPU_MANAGER.my_proc() };
-                p.sleep(&uart.ri as *const Wrapping<_> as usize, uart);
-                uart = self.lock();
-            } else {
-                let wi = uart.wi.0 % UART_BUF;
-                uart.buf[wi] = c;
-                uart.wi += Wrapping(1);
-                uart.transmit();
-                break
-            }
+        while tx.is_full() {
+            let p = unsafe { CPU_MANAGER.my_proc() };
+            p.sleep(&tx.ri as *const Wrapping<_> as usize, tx);
+            tx = self.tx.lock();
+        }
+        tx.push(c);
+        self.transmit(&mut tx);
+    }
+
+    /// 尝试输出字符到 UART，不会睡眠也不会忙等
+    ///
+    /// # 返回值
+    /// - `Ok(())`：字符已放入发送缓冲区；
+    /// - `Err(())`：发送缓冲区已满，字符未被输出。
+    ///
+    /// 供控制台的非阻塞写使用。
+    pub fn try_putc(&self, c: u8) -> Result<(), ()> {
+        let mut tx = self.tx.lock();
+
+        if PANICKED.load(Ordering::Relaxed) {
+            loop {}
+        }
+
+        if tx.is_full() {
+            return Err(())
+        }
+        tx.push(c);
+        self.transmit(&mut tx);
+        Ok(())
+    }
+
+    /// 输出字符到 UART，不会睡眠
+    ///
+    /// # 功能说明
+    /// 供内核打印与控制台回显使用，可以在中断处理程序或持有自旋锁时调用：
+    /// - 通常只是将字符放入发送缓冲区；
+    /// - 发送缓冲区满时，忙等硬件发送掉一部分字符后再放入；
+    /// - 系统恐慌后绕过缓冲区直接忙等发送，其他硬件线程的输出被挂起。
+    ///
+    /// # 注意
+    /// 恐慌的硬件线程应在设置恐慌状态前调用 [`flush`](Self::flush)，
+    /// 确保缓冲区中已有的内容被发送出去。
+    pub fn putc_nosleep(&self, c: u8) {
+        if PANICKED.load(Ordering::Relaxed) {
+            push_off();
+            loop {}
+        }
+
+        let mut tx = self.tx.lock();
+        while tx.is_full() {
+            while !self.is_idle() {}
+            self.transmit(&mut tx);
+        }
+        tx.push(c);
+        self.transmit(&mut tx);
+    }
+
+    /// 忙等直到发送缓冲区中的所有字符都已写入硬件。
+    pub fn flush(&self) {
+        let mut tx = self.tx.lock();
+        while !tx.is_empty() {
+            while !self.is_idle() {}
+            self.transmit(&mut tx);
         }
     }
 
     /// UART 中断处理函数
     ///
     /// # 功能说明
-    /// 1. 接收数据：读取所有可用字符并传递给控制台
-    /// 2. 传输数据：尝试发送缓冲区中的字符
+    /// 1. 接收数据：将硬件 FIFO 中的字符全部取到接收缓冲区，并记录溢出
+    /// 2. 将接收缓冲区中的字符逐个交给接收函数，此时不持有接收缓冲区的锁
+    /// 3. 传输数据：尝试发送缓冲区中的字符，并唤醒等待缓冲区空间的进程
     pub fn intr(&self) {
+        // 读取中断状态寄存器以确认中断
+        self.read_reg(ISR);
+
         // receive
+        let mut rx = self.rx.lock();
         loop {
-            let c: u8;
-            if ReadReg!(LSR) & 1 > 0 {
-                c = ReadReg!(RHR);
-            } else {
+            let lsr = self.read_reg(LSR);
+            if lsr & LSR_OVERRUN > 0 {
+                self.stat.overruns.fetch_add(1, Ordering::Relaxed);
+            }
+            if lsr & LSR_RX_READY == 0 {
                 break
             }
-            console::intr(c);
+            let c = self.read_reg(RHR);
+            self.stat.rx.fetch_add(1, Ordering::Relaxed);
+            if rx.is_full() {
+                self.stat.rx_dropped.fetch_add(1, Ordering::Relaxed);
+            } else {
+                rx.push(c);
+            }
+        }
+        drop(rx);
+
+        loop {
+            let c = self.rx.lock().pop();
+            match c {
+                Some(c) => (self.receiver)(c),
+                None => break,
+            }
         }
 
         // transmit
-        self.lock().transmit();
+        let mut tx = self.tx.lock();
+        self.transmit(&mut tx);
+        unsafe { PROC_MANAGER.wakeup(&tx.ri as *const Wrapping<_> as usize); }
     }
-}
 
-/// UART 内部状态结构
-pub struct Uart {
-    buf: [u8; UART_BUF],
-    ri: Wrapping<usize>,
-    wi: Wrapping<usize>,
-}
+    /// 获取串口的收发统计
+    pub fn stat(&self) -> UartStat {
+        UartStat {
+            name: self.name,
+            tx: self.stat.tx.load(Ordering::Relaxed),
+            rx: self.stat.rx.load(Ordering::Relaxed),
+            rx_dropped: self.stat.rx_dropped.load(Ordering::Relaxed),
+            overruns: self.stat.overruns.load(Ordering::Relaxed),
+        }
+    }
 
-impl Uart {
     /// 传输缓冲区内容
     ///
     /// # 功能说明
     /// 1. 当 UART 空闲且缓冲区有数据时
-    /// 2. 从缓冲区读取字符
-    /// 3. 发送到 THR 寄存器
-    /// 4. 更新读索引
-    /// 5. 唤醒可能等待的进程
-    fn transmit(&mut self) {
-        while self.wi != self.ri && is_idle() {
-            let ri = self.ri.0 % UART_BUF;
-            let c = self.buf[ri];
-            self.ri += Wrapping(1);
-            unsafe { PROC_MANAGER.wakeup(&self.ri as *const Wrapping<_> as usize); }
-            WriteReg!(THR, c);
+    /// 2. 从缓冲区读取至多 [`FIFO_SIZE`] 个字符写入硬件 FIFO
+    ///
+    /// 硬件 FIFO 发送完毕后会产生中断，由 [`intr`](Self::intr) 继续发送剩余字符，
+    /// 并唤醒等待缓冲区空间的进程。这里不唤醒进程，因为内核打印可能发生在持有进程锁时。
+    fn transmit(&self, tx: &mut Ring<UART_TX_BUF>) {
+        if tx.is_empty() || !self.is_idle() {
+            return
+        }
+        let mut n = 0;
+        while n < FIFO_SIZE {
+            match tx.pop() {
+                Some(c) => self.write_reg(THR, c),
+                None => break,
+            }
+            n += 1;
+        }
+        self.stat.tx.fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+/// 固定大小的字节环形缓冲区
+struct Ring<const N: usize> {
+    buf: [u8; N],
+    ri: Wrapping<usize>,
+    wi: Wrapping<usize>,
+}
+
+impl<const N: usize> Ring<N> {
+    const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            ri: Wrapping(0),
+            wi: Wrapping(0),
         }
     }
+
+    fn is_empty(&self) -> bool {
+        self.ri == self.wi
+    }
+
+    fn is_full(&self) -> bool {
+        (self.wi - self.ri).0 == N
+    }
+
+    /// 放入一个字节，调用者需保证缓冲区未满
+    fn push(&mut self, c: u8) {
+        debug_assert!(!self.is_full());
+        self.buf[self.wi.0 % N] = c;
+        self.wi += Wrapping(1);
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None
+        }
+        let c = self.buf[self.ri.0 % N];
+        self.ri += Wrapping(1);
+        Some(c)
+    }
 }
 
 // 16550 UART 寄存器偏移量定义
@@ -209,16 +345,11 @@ const ISR: usize = 2;       // 中断状态寄存器 (只读)
 const LCR: usize = 3;       // 线路控制寄存器
 const LSR: usize = 5;       // 线路状态寄存器
 
-/// 检查 UART 是否空闲（可发送数据）
-///
-/// # 返回值
-/// - `true`: 传输保持寄存器空闲 (LSR[5] = 1)
-/// - `false`: 传输保持寄存器忙
-///
-/// # 说明
-/// LSR[5] 位表示传输保持寄存器是否为空
-/// 当该位为 1 时，可以发送新数据
-#[inline]
-fn is_idle() -> bool {
-    ReadReg!(LSR) & (1 << 5) > 0
-}
+const IER_RX_ENABLE: u8 = 1 << 0;   // 接收数据就绪中断
+const IER_TX_ENABLE: u8 = 1 << 1;   // 发送保持寄存器空中断
+const LSR_RX_READY: u8 = 1 << 0;    // 接收保持寄存器中有数据
+const LSR_OVERRUN: u8 = 1 << 1;     // 接收溢出
+const LSR_TX_IDLE: u8 = 1 << 5;     // 发送保持寄存器空
+
+/// 16550 的硬件发送 FIFO 大小
+const FIFO_SIZE: usize = 16;
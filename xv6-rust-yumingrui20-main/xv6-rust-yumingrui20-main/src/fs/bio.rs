@@ -4,7 +4,7 @@ use array_macro::array;
 
 use core::ptr;
 use core::ops::{Deref, DerefMut};
-use core::sync::atomic::{Ordering, AtomicBool};
+use core::sync::atomic::{Ordering, AtomicBool, AtomicUsize};
 
 use crate::sleeplock::{SleepLock, SleepLockGuard};
 use crate::spinlock::SpinLock;
@@ -34,6 +34,12 @@ pub struct Bcache {
     /// 每个缓冲块包含块数据和一个睡眠锁（`SleepLock`），
     /// 以支持对缓冲区数据的细粒度同步访问。缓存内容由 `ctrl` 控制字段协调管理。
     bufs: [BufInner; NBUF],
+
+    /// `bread` 命中缓存（无需读盘）的次数。
+    hits: AtomicUsize,
+
+    /// `bread` 未命中缓存、需要从磁盘读取的次数。
+    misses: AtomicUsize,
 }
 
 impl Bcache {
@@ -41,6 +47,23 @@ impl Bcache {
         Self {
             ctrl: SpinLock::new(BufLru::new(), "BufLru"),
             bufs: array![_ => BufInner::new(); NBUF],
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// 获取缓冲区缓存的统计信息快照。
+    pub fn stat(&self) -> BcacheStat {
+        let ctrl = self.ctrl.lock();
+        let in_use = ctrl.inner.iter().filter(|b| b.refcnt > 0).count();
+        drop(ctrl);
+        let valid = self.bufs.iter().filter(|b| b.valid.load(Ordering::Relaxed)).count();
+        BcacheStat {
+            nbuf: NBUF,
+            in_use,
+            valid,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
         }
     }
 
@@ -192,8 +215,11 @@ impl Bcache {
     pub fn bread<'a>(&'a self, dev: u32, blockno: u32) -> Buf<'a> {
         let mut b = self.bget(dev, blockno);
         if !self.bufs[b.index].valid.load(Ordering::Relaxed) {
+            self.misses.fetch_add(1, Ordering::Relaxed);
             DISK.rw(&mut b, false);
             self.bufs[b.index].valid.store(true, Ordering::Relaxed);
+        } else {
+            self.hits.fetch_add(1, Ordering::Relaxed);
         }
         b
     }
@@ -209,6 +235,20 @@ impl Bcache {
     }
 }
 
+/// 缓冲区缓存的统计信息，由 [`Bcache::stat`] 产生。
+pub struct BcacheStat {
+    /// 缓冲块总数。
+    pub nbuf: usize,
+    /// 正在被引用的缓冲块数。
+    pub in_use: usize,
+    /// 持有有效磁盘数据的缓冲块数。
+    pub valid: usize,
+    /// 缓存命中次数。
+    pub hits: usize,
+    /// 缓存未命中次数。
+    pub misses: usize,
+}
+
 /// 缓冲块数据的包装结构，表示一个已分配的磁盘块缓存实体。
 ///
 /// `Buf` 结构代表一个特定 `(dev, blockno)` 的缓冲区块，
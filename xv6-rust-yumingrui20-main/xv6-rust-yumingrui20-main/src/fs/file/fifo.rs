@@ -0,0 +1,219 @@
+//! 命名管道（FIFO）
+//!
+//! FIFO 是文件系统中类型为 [`InodeType::Fifo`](crate::fs::InodeType::Fifo) 的节点，本身不存储数据。
+//! 第一次打开时以（设备号，inode 编号）为键创建一个内存中的 [`Pipe`]，之后的打开共享同一个管道，
+//! 最后一个打开者关闭后管道连同其中未读的数据一起被丢弃。
+//!
+//! 打开遵循 POSIX 的阻塞语义：
+//! - 只读打开等待直到有写者打开，只写打开等待直到有读者打开；
+//! - 指定 `O_NONBLOCK` 时只读打开立即成功，没有读者时只写打开失败；
+//! - 以 `O_RDWR` 打开从不等待。
+//!
+//! 锁的顺序：[`FIFOS`] 先于管道自身的锁。
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+use crate::consts::fs::{O_NONBLOCK, O_RDWR, O_WRONLY};
+use crate::fs::{FileStat, Inode, LOG};
+use crate::mm::Address;
+use crate::process::{PROC_MANAGER, CPU_MANAGER, PollWaiter};
+use crate::spinlock::SpinLock;
+
+use super::super::vfs::FileOps;
+use super::Pipe;
+
+/// 当前被打开的所有 FIFO。
+static FIFOS: SpinLock<Vec<FifoNode>> = SpinLock::new(Vec::new(), "fifos");
+
+/// 一个被打开的 FIFO 及其打开者的计数。
+struct FifoNode {
+    dev: u32,
+    inum: u32,
+    pipe: Arc<Pipe>,
+    /// 以可读方式打开的文件数
+    readers: usize,
+    /// 以可写方式打开的文件数
+    writers: usize,
+    /// 可读打开的累计次数，等待写者的只写打开据此发现读者曾经到来
+    read_opens: usize,
+    /// 可写打开的累计次数
+    write_opens: usize,
+}
+
+impl FifoNode {
+    /// 等待该 FIFO 另一端打开的进程所使用的等待通道。
+    fn channel(&self) -> usize {
+        Arc::as_ptr(&self.pipe) as usize
+    }
+
+    /// 登记一个打开者，唤醒等待另一端的打开者。
+    fn attach(&mut self, readable: bool, writable: bool) {
+        if readable {
+            self.readers += 1;
+            self.read_opens = self.read_opens.wrapping_add(1);
+        }
+        if writable {
+            self.writers += 1;
+            self.write_opens = self.write_opens.wrapping_add(1);
+        }
+        self.pipe.set_open(self.readers > 0, self.writers > 0);
+        unsafe { PROC_MANAGER.wakeup(self.channel()); }
+    }
+
+    /// 注销一个打开者，某一端的打开者全部关闭时另一端看到文件结束或写入失败。
+    fn detach(&mut self, readable: bool, writable: bool) {
+        if readable {
+            self.readers -= 1;
+        }
+        if writable {
+            self.writers -= 1;
+        }
+        self.pipe.set_open(self.readers > 0, self.writers > 0);
+    }
+}
+
+fn find(fifos: &mut [FifoNode], dev: u32, inum: u32) -> Option<&mut FifoNode> {
+    fifos.iter_mut().find(|n| n.dev == dev && n.inum == inum)
+}
+
+/// 命名管道的一个打开实例，是 FIFO 节点的 [`FileOps`] 实现。
+#[derive(Debug)]
+pub struct FifoFile {
+    dev: u32,
+    inum: u32,
+    pipe: Arc<Pipe>,
+    readable: bool,
+    writable: bool,
+    nonblock: bool,
+    /// FIFO 节点的 inode，用于 `fstat`，关闭时在日志事务中释放。
+    inode: Option<Inode>,
+}
+
+impl FifoFile {
+    /// 以 `flags` 打开设备 `dev` 上编号为 `inum` 的 FIFO 节点 `inode`，
+    /// 在打开者计数中登记，但不等待另一端，等待由 [`FileOps::wait_open`] 完成。
+    ///
+    /// # 返回值
+    /// - `Some(ops)`：打开成功；
+    /// - `None`：以 `O_WRONLY | O_NONBLOCK` 打开而没有读者，或内存不足。
+    pub fn open(inode: Inode, dev: u32, inum: u32, flags: i32) -> Option<Self> {
+        let readable = flags & O_WRONLY == 0;
+        let writable = flags & (O_WRONLY | O_RDWR) != 0;
+        let nonblock = flags & O_NONBLOCK != 0;
+
+        let mut fifos = FIFOS.lock();
+        let node = match fifos.iter().position(|n| n.dev == dev && n.inum == inum) {
+            Some(i) => &mut fifos[i],
+            None => {
+                if !readable && nonblock {
+                    return None
+                }
+                let pipe = Pipe::new()?;
+                fifos.try_reserve(1).ok()?;
+                fifos.push(FifoNode {
+                    dev,
+                    inum,
+                    pipe,
+                    readers: 0,
+                    writers: 0,
+                    read_opens: 0,
+                    write_opens: 0,
+                });
+                fifos.last_mut().unwrap()
+            },
+        };
+        if !readable && nonblock && node.readers == 0 {
+            return None
+        }
+        node.attach(readable, writable);
+        let pipe = Arc::clone(&node.pipe);
+        drop(fifos);
+
+        Some(Self { dev, inum, pipe, readable, writable, nonblock, inode: Some(inode) })
+    }
+}
+
+impl FileOps for FifoFile {
+    fn read(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        Pipe::read(&self.pipe, dst, count, false)
+    }
+
+    fn write(&self, src: Address, count: u32) -> Result<u32, ()> {
+        Pipe::write(&self.pipe, src, count, false)
+    }
+
+    fn read_nonblock(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        Pipe::read(&self.pipe, dst, count, true)
+    }
+
+    fn write_nonblock(&self, src: Address, count: u32) -> Result<u32, ()> {
+        Pipe::write(&self.pipe, src, count, true)
+    }
+
+    fn stat(&self, stat: &mut FileStat) -> Result<(), ()> {
+        let idata = self.inode.as_ref().unwrap().lock();
+        idata.istat(stat);
+        Ok(())
+    }
+
+    fn poll(&self, waiter: PollWaiter) -> u16 {
+        Pipe::poll(&self.pipe, waiter)
+    }
+
+    fn fcntl(&self, cmd: i32, arg: usize) -> Result<usize, ()> {
+        Pipe::fcntl(&self.pipe, cmd, arg)
+    }
+
+    /// 只读打开等待写者，只写打开等待读者；非阻塞打开与 `O_RDWR` 打开不等待。
+    /// 等待期间另一端打开后又关闭同样视为等待结束。进程被杀死时返回 `Err(())`。
+    fn wait_open(&self) -> Result<(), ()> {
+        if self.nonblock || self.readable == self.writable {
+            return Ok(())
+        }
+        let p = unsafe { CPU_MANAGER.my_proc() };
+        let mut fifos = FIFOS.lock();
+        let node = find(&mut fifos, self.dev, self.inum).unwrap();
+        let start = if self.readable { node.write_opens } else { node.read_opens };
+        loop {
+            let node = find(&mut fifos, self.dev, self.inum).unwrap();
+            let (peers, opens) = if self.readable {
+                (node.writers, node.write_opens)
+            } else {
+                (node.readers, node.read_opens)
+            };
+            if peers > 0 || opens != start {
+                return Ok(())
+            }
+            if p.killed.load(Ordering::Relaxed) {
+                return Err(())
+            }
+            let channel = node.channel();
+            p.sleep_interruptible(channel, fifos, None);
+            fifos = FIFOS.lock();
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        "fifo"
+    }
+
+    /// 注销打开者，最后一个打开者关闭时丢弃管道，然后在日志事务中释放 inode 引用。
+    fn close(&mut self, _writable: bool) {
+        let mut fifos = FIFOS.lock();
+        let i = fifos.iter().position(|n| n.dev == self.dev && n.inum == self.inum).unwrap();
+        fifos[i].detach(self.readable, self.writable);
+        let node = if fifos[i].readers == 0 && fifos[i].writers == 0 {
+            Some(fifos.swap_remove(i))
+        } else {
+            None
+        };
+        drop(fifos);
+        drop(node);
+
+        LOG.begin_op();
+        drop(self.inode.take());
+        LOG.end_op();
+    }
+}
@@ -1,67 +1,66 @@
 //! 文件以及管道相关的操作
 
+use alloc::boxed::Box;
 use alloc::sync::Arc;
-use core::cell::UnsafeCell;
-use core::cmp::min;
-use core::convert::TryInto;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-use crate::consts::driver::NDEV;
-use crate::consts::fs::{MAXOPBLOCKS, BSIZE};
-use crate::consts::fs::{O_RDONLY, O_WRONLY, O_RDWR, O_CREATE, O_TRUNC};
-use crate::driver::DEVICES;
+use crate::consts::fs::{O_CREATE, O_NONBLOCK};
+use crate::consts::fs::{O_RDONLY, O_WRONLY, O_RDWR, POLLIN, POLLOUT};
 use crate::mm::Address;
+use crate::process::PollWaiter;
 
-use super::{ICACHE, LOG, inode::FileStat};
-use super::{Inode, InodeType};
+use super::{inode::FileStat, InodeType};
+use super::vfs::{self, FileOps};
 
 mod pipe;
+mod fifo;
 
 pub use pipe::Pipe;
+pub use fifo::FifoFile;
 
 /// 表示内核中的文件抽象结构，构建在 inode 之上。
 ///
-/// `File` 类型用于统一表示三类文件实体：常规文件（regular file）、设备文件（device）、以及管道（pipe）。
-/// 它封装了底层 inode 结构，并通过 `FileInner` 枚举区分实际文件类型。`File` 是用户进程打开文件后在内核态持有的资源，
-/// 支持对文件的读写与状态获取等操作，同时在文件关闭时自动释放 inode 或关闭管道端口。
+/// `File` 类型用于统一表示各类文件实体：常规文件（regular file）、设备文件（device）、管道（pipe），
+/// 以及其他挂载到 VFS 上的文件系统中的文件。它持有一个实现了 [`FileOps`] 的对象，
+/// 所有读写、状态查询与关闭都通过该接口分派到具体实现。`File` 是用户进程打开文件后在内核态持有的资源，
+/// 在文件关闭时自动调用 [`FileOps::close`] 释放 inode 或关闭管道端口。
 ///
 /// ### 使用注意：
 /// - `File` 使用 `Arc<File>` 管理引用计数，便于在多个线程之间共享；
-/// - 文件偏移量通过内部结构中的 `UnsafeCell` 表示，由 inode 锁进行同步；
+/// - 文件偏移量等打开实例的状态由 [`FileOps`] 的实现者自行维护与同步；
 /// - 打开文件后需调用 `drop` 或将 `Arc` 释放，以触发 inode 或资源的正确回收。
 #[derive(Debug)]
 pub struct File {
-    /// 封装文件内部数据，区分是常规文件、管道还是设备。
-    inner: FileInner,
+    /// 具体文件实现的操作对象，区分是常规文件、管道、设备或其他文件系统中的文件。
+    inner: Box<dyn FileOps>,
 
     /// 标志该文件是否支持读取操作。
     readable: bool,
 
     /// 标志该文件是否支持写入操作。
     writable: bool,
-}
 
+    /// 读写是否为非阻塞方式（`O_NONBLOCK`），由共享该文件的所有文件描述符共用。
+    nonblock: AtomicBool,
+}
 
-unsafe impl Send for File {}
-unsafe impl Sync for File {}
 
 impl File {
     /// 打开指定路径的文件，并根据传入的标志位决定是否创建新文件。
     ///
     /// # 功能说明
-    /// 该函数提供文件打开功能，支持对常规文件、目录、设备文件进行统一处理。
+    /// 该函数提供文件打开功能，支持对常规文件、目录、设备文件以及其他挂载文件系统中的文件进行统一处理。
     /// 若传入 `O_CREATE` 标志，则尝试在路径不存在时创建新文件；否则尝试查找并打开已有文件。
-    /// 对于不同类型的 inode，会构造对应的 `FileInner` 实例并初始化可读/可写标志。
+    /// 路径先经 VFS 挂载表分派到负责的文件系统，再由其节点构造对应的 [`FileOps`] 对象。
     ///
     /// # 流程解释
-    /// 1. 启动日志操作（`LOG.begin_op()`），以保障文件系统操作的一致性；
-    /// 2. 若指定 `O_CREATE`，尝试使用 `ICACHE.create()` 创建普通文件；
-    ///    否则通过 `ICACHE.namei()` 查找现有文件；
-    /// 3. 根据 inode 类型判断处理逻辑：
-    ///    - 若为 `Directory`，只允许 `O_RDONLY` 打开；
-    ///    - 若为 `File`，根据 `O_TRUNC` 标志判断是否截断文件；
-    ///    - 若为 `Device`，检查 major 编号合法性并封装为设备文件；
-    /// 4. 构造 `File` 结构体并返回其 `Arc` 包装；
-    /// 5. 所有路径在出错时需释放 inode 并结束日志操作。
+    /// 1. 若指定 `O_CREATE`，通过 `vfs::create()` 创建普通文件（已存在则复用）；
+    ///    否则通过 `vfs::lookup()` 查找现有文件；
+    /// 2. 调用节点的 [`InodeOps::open`](vfs::InodeOps::open)，由具体文件系统根据节点类型检查 `flags`
+    ///    并构造文件操作对象（例如 xv6 文件系统中目录只允许 `O_RDONLY` 打开、`O_TRUNC` 截断普通文件）；
+    /// 3. 释放节点引用，构造 `File` 结构体；
+    /// 4. 调用 [`FileOps::wait_open`] 等待打开完成（例如命名管道等待另一端），成功后返回其 `Arc` 包装。
     ///
     /// # 参数
     /// - `path`: 文件路径，使用字节数组形式表示（如 C 字符串）；
@@ -73,94 +72,55 @@ impl File {
     ///
     /// # 可能的错误
     /// - 路径不存在且未指定 `O_CREATE`；
-    /// - 创建文件失败（如目录不存在或权限问题）；
-    /// - 尝试以非只读方式打开目录；
-    /// - 打开设备文件但 major 编号非法；
-    /// - 日志事务未正确结束（通过提前 return 路径确保处理）。
+    /// - 创建文件失败（如目录不存在，或目标文件系统不支持创建）；
+    /// - 具体文件系统拒绝以 `flags` 打开该节点（如以写方式打开目录、设备号非法）；
+    /// - 等待打开完成时进程被杀死。
     ///
     /// # 安全性
     /// - 使用 `Arc<File>` 保证跨线程安全共享；
-    /// - `offset` 字段通过 `UnsafeCell` 表示内部可变性，由 inode 锁保护并发访问；
-    /// - inode 在函数内生命周期受控，出错路径确保正确释放资源与日志。
+    /// - 日志事务由 xv6 文件系统实现在各自的操作内部开启与结束。
     pub fn open(path: &[u8], flags: i32) -> Option<Arc<Self>> {
-        LOG.begin_op();
-
-        let inode: Inode;
-        if flags & O_CREATE > 0 {
-            match ICACHE.create(&path, InodeType::File, 0, 0, true) {
-                Some(i) => inode = i,
-                None => {
-                    LOG.end_op();
-                    return None
-                }
-            }
+        let inode = if flags & O_CREATE > 0 {
+            vfs::create(path, InodeType::File, 0, 0, true)?
         } else {
-            match ICACHE.namei(&path) {
-                Some(i) => inode = i,
-                None => {
-                    LOG.end_op();
-                    return None
-                }
-            }
-        }
+            vfs::lookup(path)?
+        };
+
+        let inner = inode.open(flags)?;
+        drop(inode);
 
-        let mut idata = inode.lock();
-        let inner;
         let readable = (flags & O_WRONLY) == 0;
         let writable = ((flags & O_WRONLY) | (flags & O_RDWR)) > 0;
-        match idata.get_itype() {
-            InodeType::Empty => panic!("empty inode"),
-            InodeType::Directory => {
-                if flags != O_RDONLY {
-                    drop(idata); drop(inode); LOG.end_op();
-                    return None
-                }
-                drop(idata);
-                inner = FileInner::Regular(FileRegular { offset: UnsafeCell::new(0), inode: Some(inode) });
-            },
-            InodeType::File => {
-                if flags & O_TRUNC > 0 {
-                    idata.truncate();
-                }
-                drop(idata);
-                inner = FileInner::Regular(FileRegular { offset: UnsafeCell::new(0), inode: Some(inode) });
-            },
-            InodeType::Device => {
-                let (major, _) = idata.get_devnum();
-                if major as usize >= NDEV {
-                    drop(idata); drop(inode); LOG.end_op();
-                    return None
-                }
-                drop(idata);
-                inner = FileInner::Device(FileDevice { major, inode: Some(inode) });
-            }
-        }
+        let file = Arc::try_new(File {
+            inner,
+            readable,
+            writable,
+            nonblock: AtomicBool::new(flags & O_NONBLOCK != 0),
+        }).ok()?;
+        file.inner.wait_open().ok()?;
+        Some(file)
+    }
 
-        LOG.end_op();
-        Some(Arc::new(File {
+    /// 以给定的文件操作对象构造一个 `File`，供管道、设备注册表等不经过路径打开的文件使用。
+    pub fn from_ops(inner: Box<dyn FileOps>, readable: bool, writable: bool) -> Option<Arc<Self>> {
+        Arc::try_new(File {
             inner,
             readable,
-            writable
-        }))
+            writable,
+            nonblock: AtomicBool::new(false),
+        }).ok()
     }
 
     /// 从文件中读取数据到用户空间缓冲区。
     ///
     /// # 功能说明
     /// 该函数从文件中读取至多 `count` 字节的数据，并将其写入用户态地址 `addr` 指向的缓冲区中。
-    /// 根据文件类型（普通文件、设备文件或管道）执行不同的读取逻辑，并在读取成功后更新偏移量。
+    /// 具体的读取逻辑（普通文件、设备文件或管道）由 [`FileOps::read`] 的实现决定。
     ///
     /// # 流程解释
     /// 1. 首先检查文件是否具有可读权限（`readable` 标志）；
-    /// 2. 根据文件内部类型（`FileInner`）分派读取行为：
-    ///    - 若为 `Pipe`，直接调用管道的 `read()` 方法；
-    ///    - 若为 `Regular` 文件：
-    ///       - 锁住对应 inode；
-    ///       - 读取当前偏移量（通过 `UnsafeCell` 提供内部可变性）；
-    ///       - 调用 `idata.try_iread()` 读取数据；
-    ///       - 更新偏移量并解锁；
-    ///    - 若为 `Device` 文件：
-    ///       - 查找对应设备驱动的 `read` 函数并调用。
+    /// 2. 将用户地址包装为 `Address::Virtual`，调用内部文件操作对象的 `read()`，
+    ///    设置了 `O_NONBLOCK` 时调用 `read_nonblock()`。
     ///
     /// # 参数
     /// - `addr`: 目标用户缓冲区的起始虚拟地址，读取内容将写入该地址；
@@ -172,57 +132,34 @@ impl File {
     ///
     /// # 可能的错误
     /// - 文件被标记为不可读（`readable == false`）；
-    /// - 对管道/文件进行读取时出现内部错误；
-    /// - 对设备文件进行读取时未找到有效驱动；
-    /// - `try_iread` 失败（可能因偏移越界或页表映射失败）。
+    /// - 设置了 `O_NONBLOCK` 而读取需要等待；
+    /// - 具体实现返回的错误（如进程被杀死、设备未注册、页表映射失败等）。
     ///
     /// # 安全性
-    /// - 函数本身为不可变借用（`&self`），内部通过 `UnsafeCell` 修改偏移量，仅在持有 inode 锁时进行，确保并发安全；
-    /// - 用户空间地址由调用者提供，`try_iread` 负责进行边界检查和页表验证；
-    /// - 所有资源使用完毕后立即释放锁，避免死锁或资源泄露。
+    /// - 函数本身为不可变借用（`&self`），打开实例的状态由具体实现负责同步；
+    /// - 用户空间地址由调用者提供，实现通过 `Address::copy_out` 完成边界检查和页表验证。
     pub fn fread(&self, addr: usize, count: u32) -> Result<u32, ()> {
         if !self.readable {
             return Err(())
         }
 
-        match self.inner {
-            FileInner::Pipe(ref pipe) => pipe.read(addr, count),
-            FileInner::Regular(ref file) => {
-                let mut idata = file.inode.as_ref().unwrap().lock();
-                let offset = unsafe { &mut *file.offset.get() };
-                match idata.try_iread(Address::Virtual(addr), *offset, count.try_into().unwrap()) {
-                    Ok(read_count) => {
-                        *offset += read_count;
-                        drop(idata);
-                        Ok(read_count)
-                    },
-                    Err(()) => Err(())
-                }
-            },
-            FileInner::Device(ref dev) => {
-                let dev_read = DEVICES[dev.major as usize].as_ref().ok_or(())?.read;
-                dev_read(Address::Virtual(addr), count)
-            },
+        if self.nonblock.load(Ordering::Relaxed) {
+            self.inner.read_nonblock(Address::Virtual(addr), count)
+        } else {
+            self.inner.read(Address::Virtual(addr), count)
         }
     }
 
     /// 将用户空间的数据从给定地址写入文件，总共写入不超过 `count` 字节。
     ///
     /// # 功能说明
-    /// 该函数负责将用户提供的缓冲区内容写入文件。根据文件类型（普通文件、管道或设备），
-    /// 使用不同的方式写入，并在常规文件场景中自动处理偏移更新与日志事务保护。
+    /// 该函数负责将用户提供的缓冲区内容写入文件。具体的写入逻辑（普通文件的分批日志事务、
+    /// 管道的阻塞写入或设备驱动写入）由 [`FileOps::write`] 的实现决定。
     ///
     /// # 流程解释
     /// 1. 检查文件是否具有可写权限（`writable`）；
-    /// 2. 根据 `FileInner` 类型选择写入路径：
-    ///    - `Pipe`：调用管道的 `write()` 实现；
-    ///    - `Regular` 文件：
-    ///       - 将写入按批次进行分段处理，每批大小为 `((MAXOPBLOCKS-4)/2)*BSIZE` 字节，避免单次事务过大；
-    ///       - 每批调用 `LOG.begin_op()` / `end_op()` 包裹文件系统事务；
-    ///       - 锁住 inode，调用 `try_iwrite()` 写入当前段；
-    ///       - 成功后更新偏移量并移动用户缓冲地址；
-    ///    - `Device` 文件：
-    ///       - 查找注册的设备驱动中的写入函数并调用。
+    /// 2. 将用户地址包装为 `Address::Virtual`，调用内部文件操作对象的 `write()`，
+    ///    设置了 `O_NONBLOCK` 时调用 `write_nonblock()`。
     ///
     /// # 参数
     /// - `addr`: 用户空间起始地址，写入数据从该地址读取；
@@ -234,96 +171,126 @@ impl File {
     ///
     /// # 可能的错误
     /// - 文件未设置为可写（`writable == false`）；
-    /// - 管道或设备写入操作失败；
-    /// - 对常规文件调用 `try_iwrite()` 失败（如磁盘空间不足、页表错误等）；
-    /// - 设备未注册写入函数；
-    /// - 写入中途失败（如部分批次失败），返回已成功写入的部分字节。
+    /// - 设置了 `O_NONBLOCK` 而写入需要等待且一个字节都未写入；
+    /// - 具体实现返回的错误（如管道读端关闭、磁盘空间不足等）；
+    /// - 写入中途失败时，具体实现可能返回已成功写入的部分字节。
     ///
     /// # 安全性
-    /// - 偏移更新通过 `UnsafeCell` 实现内部可变性，写入前持有 inode 锁确保并发安全；
-    /// - 每批写入都封装在日志事务内，保证文件系统的一致性和崩溃恢复能力；
-    /// - 用户地址由上层调用者提供，`try_iwrite()` 承担页表检查与物理地址映射验证；
-    /// - 写入失败时尽早退出，避免逻辑错误或未定义行为。
+    /// - 用户地址由上层调用者提供，具体实现承担页表检查与物理地址映射验证。
     pub fn fwrite(&self, addr: usize, count: u32) -> Result<u32, ()> {
         if !self.writable {
             return Err(())
         }
 
-        match self.inner {
-            FileInner::Pipe(ref pipe) => pipe.write(addr, count),
-            FileInner::Regular(ref file) => {
-                let batch = ((MAXOPBLOCKS-4)/2*BSIZE) as u32;
-                let mut addr = Address::Virtual(addr);
-                for i in (0..count).step_by(batch as usize) {
-                    let write_count = min(batch, count - i);
-                    LOG.begin_op();
-                    let mut idata = file.inode.as_ref().unwrap().lock();
-                    let offset = unsafe { &mut *file.offset.get() };
-                    let ret = idata.try_iwrite(addr, *offset, write_count);
-                    if let Ok(actual_count) = ret {
-                        *offset += actual_count;
-                    }
-                    drop(idata);
-                    LOG.end_op();
+        if self.nonblock.load(Ordering::Relaxed) {
+            self.inner.write_nonblock(Address::Virtual(addr), count)
+        } else {
+            self.inner.write(Address::Virtual(addr), count)
+        }
+    }
 
-                    match ret {
-                        Ok(actual_count) => {
-                            if actual_count != write_count {
-                                return Ok(i+actual_count)
-                            }
-                        },
-                        Err(()) => return Err(()),
-                    }
-                    addr = addr.offset(write_count as usize);
-                }
-                Ok(count)
-            },
-            FileInner::Device(ref dev) => {
-                let dev_write = DEVICES[dev.major as usize].as_ref().ok_or(())?.write;
-                dev_write(Address::Virtual(addr), count)
-            },
+    /// 将内核缓冲区中的数据写入文件，用于内核代替进程输出（例如系统调用跟踪）。
+    ///
+    /// # 返回值
+    /// - `Ok(n)`：实际成功写入的字节数 `n`；
+    /// - `Err(())`：文件不可写或写入失败。
+    pub fn fwrite_kernel(&self, src: &[u8]) -> Result<u32, ()> {
+        if !self.writable {
+            return Err(())
+        }
+
+        if self.nonblock.load(Ordering::Relaxed) {
+            self.inner.write_nonblock(Address::Kernel(src.as_ptr()), src.len() as u32)
+        } else {
+            self.inner.write(Address::Kernel(src.as_ptr()), src.len() as u32)
+        }
+    }
+
+    /// 文件是否以可写方式打开。
+    pub fn writable(&self) -> bool {
+        self.writable
+    }
+
+    /// 文件的访问方式与状态标志，即 `fcntl(F_GETFL)` 的结果。
+    pub fn status_flags(&self) -> i32 {
+        let mode = match (self.readable, self.writable) {
+            (true, true) => O_RDWR,
+            (false, true) => O_WRONLY,
+            _ => O_RDONLY,
+        };
+        if self.nonblock.load(Ordering::Relaxed) {
+            mode | O_NONBLOCK
+        } else {
+            mode
         }
     }
 
+    /// 设置文件的状态标志，目前只有 `O_NONBLOCK` 可以改变，其余位被忽略。
+    pub fn set_status_flags(&self, flags: i32) {
+        self.nonblock.store(flags & O_NONBLOCK != 0, Ordering::Relaxed);
+    }
+
     /// 将文件状态信息复制到用户提供的缓冲区中。
     ///
     /// # 功能说明
-    /// 该函数用于查询当前文件对应的 inode 元信息，并将其填充到用户提供的 `FileStat` 结构中。
+    /// 该函数用于查询当前文件的元信息，并将其填充到用户提供的 `FileStat` 结构中。
     /// 该信息包括文件类型、大小、设备号等，用于向用户空间暴露文件的基础属性。
     ///
-    /// # 流程解释
-    /// 1. 判断文件类型（`FileInner`）：
-    ///    - 若为 `Pipe` 类型，不支持 fstat 操作，直接返回错误；
-    ///    - 否则获取对应的 `inode`；
-    /// 2. 获取 `inode` 锁，确保元数据访问的并发安全；
-    /// 3. 调用 `istat()` 方法将 inode 内部状态写入 `stat`；
-    /// 4. 解锁并返回成功。
-    ///
     /// # 参数
     /// - `stat`: 指向 `FileStat` 结构体的可变引用，用于接收查询到的文件状态信息。
     ///
     /// # 返回值
-    /// - `Ok(())`：成功将 inode 信息写入到 `stat`；
-    /// - `Err(())`：当前文件为管道类型，不支持状态查询。
-    ///
-    /// # 可能的错误
-    /// - 管道文件不支持状态查询，调用该函数时会立即返回错误；
-    /// - 若 `inode` 字段为 `None`，`.unwrap()` 会引发 panic（当前代码逻辑中认为这种情况不会发生，但在未来修改中应警惕）。
+    /// - `Ok(())`：成功将文件信息写入到 `stat`；
+    /// - `Err(())`：具体实现不支持状态查询（例如管道）。
     ///
     /// # 安全性
-    /// - `inode` 的锁机制确保了 `istat` 操作过程中的并发安全；
-    /// - 使用 `.unwrap()` 解包 `Option<Inode>`，假设 `Regular` 和 `Device` 类型的 `File` 必定包含有效的 `inode`；
-    ///   若此前已被 `drop` 释放则可能触发未定义行为，因此要求在 `drop` 调用前完成所有状态访问；
-    /// - `stat` 指针必须来源于内核或受控用户空间，确保写入不会越界或违反内存访问规则。
+    /// - 并发安全由具体实现保证（如 xv6 文件在持有 inode 锁时调用 `istat`）。
     pub fn fstat(&self, stat: &mut FileStat) -> Result<(), ()> {
-        let inode: &Inode;
-        match self.inner {
-            FileInner::Pipe(_) => return Err(()),
-            FileInner::Regular(ref file) => inode = file.inode.as_ref().unwrap(),
-            FileInner::Device(ref dev) => inode = dev.inode.as_ref().unwrap(),
+        self.inner.stat(stat)
+    }
+
+    /// 查询文件当前的就绪状态并登记等待者 `waiter`，不可读或不可写的文件不报告 `POLLIN` 或 `POLLOUT`。
+    pub fn poll(&self, waiter: PollWaiter) -> u16 {
+        let mut mask = self.inner.poll(waiter);
+        if !self.readable {
+            mask &= !POLLIN;
+        }
+        if !self.writable {
+            mask &= !POLLOUT;
+        }
+        mask
+    }
+
+    /// 对文件执行控制命令 `request`，`arg` 的含义由命令决定。
+    ///
+    /// # 返回值
+    /// - `Ok(n)`：命令执行成功，`n` 为命令的返回值；
+    /// - `Err(())`：文件不支持该命令或参数非法。
+    pub fn ioctl(&self, request: usize, arg: usize) -> Result<usize, ()> {
+        self.inner.ioctl(request, arg)
+    }
+
+    /// 对文件执行文件种类相关的 fcntl 命令 `cmd`。
+    ///
+    /// # 返回值
+    /// - `Ok(n)`：命令执行成功，`n` 为命令的返回值；
+    /// - `Err(())`：文件不支持该命令或参数非法。
+    pub fn fcntl(&self, cmd: i32, arg: usize) -> Result<usize, ()> {
+        self.inner.fcntl(cmd, arg)
+    }
+}
+
+impl fmt::Display for File {
+    /// 以 `rw kind [stat]` 的形式输出文件的简要信息，用于 /proc 展示。
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{} {}",
+            if self.readable { 'r' } else { '-' },
+            if self.writable { 'w' } else { '-' },
+            self.inner.kind())?;
+        let mut stat = FileStat::uninit();
+        if self.inner.stat(&mut stat).is_ok() {
+            write!(f, " {}", stat)?;
         }
-        let idata = inode.lock();
-        idata.istat(stat);
         Ok(())
     }
 }
@@ -333,15 +300,8 @@ impl Drop for File {
     ///
     /// # 功能说明
     /// 此函数为 `File` 结构体的析构实现（Drop trait），用于在文件引用计数归零时自动释放资源。
-    /// 根据文件类型（管道、常规文件、设备）执行相应的关闭逻辑，确保 inode 正确释放或管道端正确关闭。
-    ///
-    /// # 流程解释
-    /// 1. 根据 `FileInner` 的具体变体进行匹配：
-    ///    - 若为 `Pipe` 类型，调用其 `close()` 方法，并传入当前 `File` 是否为写端；
-    ///    - 若为 `Regular` 或 `Device` 类型，执行以下步骤：
-    ///      - 开启日志事务（`LOG.begin_op()`）；
-    ///      - 将其中的 inode 设置为 `None`，释放其引用；
-    ///      - 结束日志事务（`LOG.end_op()`）。
+    /// 具体的关闭逻辑由 [`FileOps::close`] 实现：管道关闭对应的端口，
+    /// xv6 文件在日志事务中释放 inode 引用。
     ///
     /// # 参数
     /// 无参数。该函数为析构器，由 Rust 在 `File` 被销毁时自动调用。
@@ -349,87 +309,9 @@ impl Drop for File {
     /// # 返回值
     /// 无返回值。
     ///
-    /// # 可能的错误
-    /// - 本函数本身不返回错误；但如若 `inode.take()` 前已经非法释放或未按约定初始化，可能引发逻辑错误或 panic；
-    /// - 管道关闭逻辑依赖于 `Pipe::close()` 的内部实现，若其处理不当可能出现资源未完全释放。
-    ///
     /// # 安全性
-    /// - 对 `inode` 的释放使用 `Option::take()`，确保只释放一次，防止双重释放；
-    /// - `LOG.begin_op()` / `end_op()` 包裹对 inode 的释放，保障文件系统状态一致性；
-    /// - 管道关闭操作可能涉及跨线程通信，需确保 `close()` 内部实现具备并发安全保障；
     /// - 函数不应在未完成文件操作前手动调用，应由 Rust 生命周期自动触发。
     fn drop(&mut self) {
-        match self.inner {
-            FileInner::Pipe(ref pipe) => pipe.close(self.writable),
-            FileInner::Regular(ref mut file) => {
-                LOG.begin_op();
-                drop(file.inode.take());
-                LOG.end_op();
-            },
-            FileInner::Device(ref mut dev) => {
-                LOG.begin_op();
-                drop(dev.inode.take());
-                LOG.end_op();
-            },
-        }
+        self.inner.close(self.writable);
     }
 }
-
-/// 表示文件内部的具体实现类型，用于区分文件的实际存储或通信方式。
-///
-/// 该枚举结构用于 `File` 抽象内部，根据不同文件类型（管道、普通文件、设备）
-/// 封装对应的数据结构，从而实现统一的文件接口（如读写、关闭、状态查询）
-/// 而无需暴露具体实现细节。每个变体都携带相应的资源句柄或元数据。
-#[derive(Debug)]
-enum FileInner {
-    /// 管道文件，表示进程间通信通道，封装为引用计数的 `Pipe` 实例。
-    /// 使用 `Arc` 保证跨线程共享与安全释放。
-    Pipe(Arc<Pipe>),
-
-    /// 常规文件，包含偏移量与 inode，用于磁盘文件的读写。
-    Regular(FileRegular),
-
-    /// 设备文件，包含主设备号与 inode，用于通过驱动进行 I/O。
-    Device(FileDevice),
-}
-
-
-/// 表示普通文件的内部状态结构，封装在 `FileInner::Regular` 变体中。
-///
-/// 用于管理磁盘上的常规文件（regular file），包含当前文件的偏移位置和 inode 引用。
-/// 文件偏移用于顺序读写操作，inode 提供底层元数据与数据访问接口。
-/// 该结构承载对常规文件的状态管理职责。
-#[derive(Debug)]
-struct FileRegular {
-    /// 当前文件偏移量，表示下一次读写操作的起始位置。
-    ///
-    /// 该字段通过 `UnsafeCell` 提供内部可变性，
-    /// 实际使用中由 inode 上的锁（`idata`）保护，确保并发访问时的一致性与内存安全。
-    offset: UnsafeCell<u32>,
-
-    /// 指向该文件对应的 inode 对象，用于文件的元数据与数据访问。
-    ///
-    /// 使用 `Option<Inode>` 表示可释放性，在文件关闭（drop）时会被设置为 `None`。
-    inode: Option<Inode>,
-}
-
-
-/// 表示设备文件的内部状态结构，封装在 `FileInner::Device` 变体中。
-///
-/// 该结构用于管理字符设备文件，允许通过设备号与驱动接口进行读写操作。
-/// 设备文件并不实际存储数据，而是通过设备号与内核中注册的设备驱动进行通信。
-/// 该结构用于抽象和封装对设备节点的访问。
-#[derive(Debug)]
-struct FileDevice {
-    /// 主设备号（major device number），用于在设备表中索引对应的设备驱动。
-    ///
-    /// 在 xv6 中，每种设备（如控制台、磁盘等）都有唯一的主设备号，
-    /// 通过该编号调用统一的设备操作接口（如 `read` / `write`）。
-    major: u16,
-
-    /// 指向设备对应的 inode 对象。
-    ///
-    /// 尽管设备文件不依赖 inode 进行实际数据存储，但依然使用 inode 记录其元信息，
-    /// 并在文件关闭时通过 `Option::take()` 释放。
-    inode: Option<Inode>,
-}
@@ -1,17 +1,28 @@
 //! 管道操作
+//!
+//! 管道的数据保存在由若干物理页组成的环形缓冲区 [`PipeBuf`] 中，容量为 2 的幂个页，
+//! 默认为 [`PIPESIZE`]，可以通过 `fcntl(F_SETPIPE_SZ)` 在 [`PIPE_MAX_SIZE`] 以内调整。
+//! 读写按页内连续的区域整段复制，不超过 [`PIPE_BUF`] 字节的写入是原子的，
+//! 不会与其他写者的数据交错。
 
+use alloc::boxed::Box;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::mem;
 use core::num::Wrapping;
-use core::sync::atomic::{Ordering, AtomicUsize};
-use core::cmp::min;
-use core::ptr::addr_of_mut;
+use core::sync::atomic::Ordering;
+use core::cmp::{min, max};
+use core::ptr;
 
-use crate::consts::fs::{PIPESIZE, PIPESIZE_U32};
-use crate::process::{CPU_MANAGER, PROC_MANAGER};
+use crate::consts::PGSIZE;
+use crate::consts::fs::{PIPESIZE, PIPE_MAX_SIZE, PIPE_BUF, F_GETPIPE_SZ, F_SETPIPE_SZ};
+use crate::consts::fs::{POLLIN, POLLOUT, POLLERR, POLLHUP};
+use crate::mm::{Address, RawPage, RawSinglePage};
+use crate::process::{CPU_MANAGER, PROC_MANAGER, PollQueue, PollWaiter};
 use crate::spinlock::SpinLock;
 
-use super::{File, FileInner};
+use super::File;
+use super::super::vfs::FileOps;
 
 /// 表示一个内核态的管道（pipe）通信结构，封装了对 `PipeInner` 的同步访问。
 ///
@@ -19,10 +30,10 @@ use super::{File, FileInner};
 /// 并被另一个进程读取。内部通过 [`SpinLock`] 实现对 [`PipeInner`] 的互斥访问，
 /// 以保障多核环境下并发读写的正确性与一致性。
 ///
-/// 管道采用固定大小的环形缓冲区进行数据传输，并结合进程的休眠与唤醒机制，
+/// 管道采用页式的环形缓冲区进行数据传输，并结合进程的休眠与唤醒机制，
 /// 实现对读写双方的阻塞控制和同步通信能力。
 ///
-/// 本结构体通常由 [`FileInner::Pipe`] 所引用，通过 [`File`] 文件抽象与用户进程交互。
+/// 本结构体以 `Arc<Pipe>` 的形式作为 [`FileOps`] 实现被 [`File`] 持有，通过文件抽象与用户进程交互。
 #[derive(Debug)]
 pub struct Pipe(SpinLock<PipeInner>);
 
@@ -36,11 +47,10 @@ impl Pipe {
     /// 并将内核态的管道结构抽象为用户可读写的文件接口。
     ///
     /// # 流程解释
-    /// 1. 使用 `Arc::try_new_zeroed` 申请未初始化的 [`Pipe`] 空间；
-    /// 2. 使用 `SpinLock::init_name` 初始化管道内的自旋锁（用于调试）；
-    /// 3. 初始化 `PipeInner` 中的读写状态；
+    /// 1. 通过 [`Pipe::new`] 分配默认容量的缓冲区与管道结构；
+    /// 2. 将读写两端都标记为打开；
     /// 4. 构造两个 [`File`] 对象，一个可读、一个可写，并都引用该管道；
-    /// 5. 若任意资源分配失败，返回 `None`。
+    /// 4. 若任意资源分配失败，返回 `None`。
     ///
     /// # 参数
     /// 无输入参数。
@@ -52,44 +62,50 @@ impl Pipe {
     /// - 如果分配内存或构造文件对象失败，返回 `None`。
     ///
     /// # 可能的错误
-    /// - 内存不足或 `Arc::try_new_zeroed` / `Arc::try_new` 分配失败；
-    /// - 内部未能正确初始化管道锁或构造文件结构体。
-    ///
-    /// # 安全性
-    /// - 使用 `unsafe` 语句块对未初始化的 `Pipe` 执行原地初始化，
-    ///   要求调用者确保 `Arc::get_mut_unchecked` 的唯一性前提成立；
-    /// - 使用 `assume_init` 对 `MaybeUninit<Arc<Pipe>>` 解包，必须确保已完成所有字段初始化；
-    /// - 此操作在受控环境下是安全的，因本函数是 `create` 的唯一初始化入口。
+    /// - 内存不足，缓冲区页、管道结构或文件结构体分配失败。
     pub fn create() -> Option<(Arc<File>, Arc<File>)> {
-        debug_assert!(mem::size_of::<Pipe>() <= 512-2*mem::size_of::<AtomicUsize>());
-
         //  创建一个管道
-        let mut pipe = Arc::<Self>::try_new_zeroed().ok()?;
-        let pipe = unsafe {
-            let ptr = Arc::get_mut_unchecked(&mut pipe).as_mut_ptr();
-            SpinLock::init_name(addr_of_mut!((*ptr).0), "pipe");
-            pipe.assume_init()
-        };
+        let pipe = Self::new()?;
         let mut guard = pipe.0.lock();
         guard.read_open = true;
         guard.write_open = true;
         drop(guard);
 
         // 创建两个文件
-        let read_file = Arc::try_new(File {
-            inner: FileInner::Pipe(Arc::clone(&pipe)),
-            readable: true,
-            writable: false,
-        }).ok()?;
-        let write_file = Arc::try_new(File {
-            inner: FileInner::Pipe(Arc::clone(&pipe)),
-            readable: false,
-            writable: true,
-        }).ok()?;
+        let read_file = File::from_ops(Box::new(Arc::clone(&pipe)), true, false)?;
+        let write_file = File::from_ops(Box::new(pipe), false, true)?;
 
         Some((read_file, write_file))
     }
 
+    /// 创建一个读写两端都未打开、容量为 [`PIPESIZE`] 的空管道，内存不足时返回 `None`。
+    pub(super) fn new() -> Option<Arc<Self>> {
+        let buf = PipeBuf::new(PIPESIZE)?;
+        Arc::try_new(Pipe(SpinLock::new(PipeInner {
+            read_open: false,
+            write_open: false,
+            read_cnt: Wrapping(0),
+            write_cnt: Wrapping(0),
+            buf,
+            pollq: PollQueue::new(),
+        }, "pipe"))).ok()
+    }
+
+    /// 设置读端与写端是否打开，供命名管道在打开者增减时使用。
+    /// 一端由打开变为关闭时，与 [`close`](Self::close) 一样唤醒另一端等待的进程。
+    pub(super) fn set_open(&self, read_open: bool, write_open: bool) {
+        let mut pipe = self.0.lock();
+        if pipe.write_open && !write_open {
+            unsafe { PROC_MANAGER.wakeup(&pipe.read_cnt as *const Wrapping<_> as usize); }
+        }
+        if pipe.read_open && !read_open {
+            unsafe { PROC_MANAGER.wakeup(&pipe.write_cnt as *const Wrapping<_> as usize); }
+        }
+        pipe.read_open = read_open;
+        pipe.write_open = write_open;
+        pipe.pollq.notify();
+    }
+
     /// 从管道中读取数据，将字节复制到用户空间缓冲区中。
     ///
     /// # 功能说明
@@ -101,17 +117,18 @@ impl Pipe {
     /// - 获取当前进程指针 `p`；
     /// - 通过 `SpinLock` 加锁管道内部状态；
     /// - 若管道为空且写端未关闭，则调用 `p.sleep()` 阻塞当前进程，直到有数据可读或写端关闭；
-    /// - 重新加锁后计算可读字节数（读写指针差值），逐字节复制到用户空间；
-    /// - 若中途发生复制错误，则提前结束读取；
+    /// - 重新加锁后计算可读字节数（读写指针差值），按缓冲区中页内连续的区域整段复制到用户空间；
+    /// - 若中途发生复制错误，则提前结束读取，未复制的区域仍留在管道中；
     /// - 更新读指针 `read_cnt`，并唤醒可能因缓冲区满而阻塞的写进程。
     ///
     /// # 参数
-    /// - `addr`: 用户空间中的目标地址，数据将复制到该地址开始的缓冲区；
-    /// - `count`: 请求读取的最大字节数。
+    /// - `dst`: 目标地址，数据将复制到该地址开始的缓冲区；
+    /// - `count`: 请求读取的最大字节数；
+    /// - `nonblock`: 为 `true` 时管道为空不等待，直接返回错误。
     ///
     /// # 返回值
     /// - `Ok(n)`：实际成功读取并复制的字节数 `n`；
-    /// - `Err(())`：如果当前进程被标记为已终止（`killed == true`），则返回错误。
+    /// - `Err(())`：如果当前进程被标记为已终止（`killed == true`），或非阻塞读取时管道为空，则返回错误。
     ///
     /// # 可能的错误
     /// - 进程在等待数据期间被外部标记为终止，读取中断，返回 `Err(())`；
@@ -119,35 +136,35 @@ impl Pipe {
     ///
     /// # 安全性
     /// - 使用 `unsafe` 获取当前进程指针 `p`，需确保调用者在内核上下文中且该指针有效；
-    /// - 用户空间地址 `addr` 的有效性由 `copy_out()` 检查与处理；
+    /// - 目标地址 `dst` 的有效性由 `copy_out()` 检查与处理；
     /// - 锁的获取、释放、睡眠与唤醒操作在受控环境中调用，确保不会造成死锁或竞态。
-    pub(super) fn read(&self, addr: usize, count: u32) -> Result<u32, ()> {
+    pub(super) fn read(&self, dst: Address, count: u32, nonblock: bool) -> Result<u32, ()> {
         let p = unsafe { CPU_MANAGER.my_proc() };
 
         let mut pipe = self.0.lock();
 
         // 等待数据被写入
         while pipe.read_cnt == pipe.write_cnt && pipe.write_open {
-            if p.killed.load(Ordering::Relaxed) {
+            if nonblock || p.killed.load(Ordering::Relaxed) {
                 return Err(())
             }
-            p.sleep(&pipe.read_cnt as *const Wrapping<_> as usize, pipe);
+            p.sleep_interruptible(&pipe.read_cnt as *const Wrapping<_> as usize, pipe, None);
             pipe = self.0.lock();
         }
 
         // 从管道读取到用户内存
         let count = min(count, (pipe.write_cnt - pipe.read_cnt).0);
-        let mut read_count = count;
-        for i in 0..count {
-            let index = (pipe.read_cnt.0 % PIPESIZE_U32) as usize;
-            let byte = pipe.data[index];
-            pipe.read_cnt += Wrapping(1);
-            if p.data.get_mut().copy_out(&byte as *const u8, addr+(i as usize), 1).is_err() {
-                read_count = i;
+        let mut read_count = 0;
+        while read_count < count {
+            let (run, len) = pipe.buf.run(pipe.read_cnt.0, count - read_count);
+            if dst.offset(read_count as usize).copy_out(run, len as usize).is_err() {
                 break
             }
+            pipe.read_cnt += Wrapping(len);
+            read_count += len;
         }
         unsafe { PROC_MANAGER.wakeup(&pipe.write_cnt as *const Wrapping<_> as usize); }
+        pipe.pollq.notify();
         drop(pipe);
         Ok(read_count)
     }
@@ -155,7 +172,7 @@ impl Pipe {
     /// 向管道写入数据，从用户空间缓冲区读取字节写入环形缓冲区。
     ///
     /// # 功能说明
-    /// 尝试从用户空间地址 `addr` 读取最多 `count` 个字节，并写入当前管道的缓冲区中。
+    /// 尝试从源地址 `src` 读取最多 `count` 个字节，并写入当前管道的缓冲区中。
     /// 如果管道写满，则当前进程会被阻塞直到缓冲区中有可用空间或读端被关闭。
     /// 支持读写端同步机制和用户态数据拷贝，并自动处理写阻塞与唤醒逻辑。
     ///
@@ -163,19 +180,23 @@ impl Pipe {
     /// - 获取当前进程指针 `p`；
     /// - 加锁管道以访问内部状态；
     /// - 持续尝试写入数据：
-    ///   - 若写端尚未完成，且缓冲区未满，则从用户空间复制一个字节至缓冲区；
-    ///   - 若缓冲区已满，则唤醒读进程，并将当前进程阻塞在写端等待点；
+    ///   - 若缓冲区有空闲空间，则从用户空间复制一段页内连续的数据至缓冲区；
+    ///   - 不超过 [`PIPE_BUF`] 字节的写入须等到空闲空间足以容纳全部数据，再一次写入，
+    ///     因此不会与其他写者的数据交错；
+    ///   - 若空闲空间不足，则唤醒读进程，并将当前进程阻塞在写端等待点；
     ///   - 若读端已关闭或当前进程被终止，则立即中断写入并返回错误。
     /// - 每次写入后推进写指针 `write_cnt`，最终返回成功写入的字节数。
     /// - 写入完成后唤醒读进程以通知数据可读。
     ///
     /// # 参数
-    /// - `addr`：用户空间中源缓冲区的起始地址；
-    /// - `count`：尝试写入的最大字节数。
+    /// - `src`：源缓冲区的起始地址；
+    /// - `count`：尝试写入的最大字节数；
+    /// - `nonblock`：为 `true` 时空闲空间不足不等待，返回已写入的字节数；
+    ///   原子写入要么全部写入，要么返回错误。
     ///
     /// # 返回值
     /// - `Ok(n)`：成功写入的字节数 `n`；
-    /// - `Err(())`：当读端已关闭或进程已被标记为终止时，返回错误。
+    /// - `Err(())`：当读端已关闭或进程已被标记为终止时，或非阻塞写入时一个字节都未写入，返回错误。
     ///
     /// # 可能的错误
     /// - 若读端被关闭，`read_open == false`，则立即返回 `Err(())`；
@@ -186,35 +207,44 @@ impl Pipe {
     /// - 使用 `unsafe` 获取当前进程指针 `p`，调用上下文需保证在有效的内核态；
     /// - 用户空间地址的读取通过 `copy_in()` 进行边界检查与错误控制；
     /// - 锁操作、进程休眠与唤醒在管道内部状态一致性前提下安全使用；
-    /// - 写入操作严格限制在环形缓冲区有效索引范围内，避免越界访问。
-    pub(super) fn write(&self, addr: usize, count: u32) -> Result<u32, ()> {
+    /// - 写入操作严格限制在缓冲区页内的有效范围内，避免越界访问。
+    pub(super) fn write(&self, src: Address, count: u32, nonblock: bool) -> Result<u32, ()> {
         let p = unsafe { CPU_MANAGER.my_proc() };
 
         let mut pipe = self.0.lock();
 
+        let atomic = count as usize <= PIPE_BUF;
         let mut write_count = 0;
         while write_count < count {
             if !pipe.read_open || p.killed.load(Ordering::Relaxed) {
                 return Err(())
             }
 
-            if pipe.write_cnt == pipe.read_cnt + Wrapping(PIPESIZE_U32) {
+            let free = pipe.buf.size() - (pipe.write_cnt - pipe.read_cnt).0;
+            let need = if atomic { count - write_count } else { 1 };
+            if free < need {
+                if nonblock {
+                    if write_count == 0 {
+                        return Err(())
+                    }
+                    break
+                }
                 // 等待数据被读取
                 unsafe { PROC_MANAGER.wakeup(&pipe.read_cnt as *const Wrapping<_> as usize); }
-                p.sleep(&pipe.write_cnt as *const Wrapping<_> as usize, pipe);
+                pipe.pollq.notify();
+                p.sleep_interruptible(&pipe.write_cnt as *const Wrapping<_> as usize, pipe, None);
                 pipe = self.0.lock();
             } else {
-                let mut byte: u8 = 0;
-                if p.data.get_mut().copy_in(addr+(write_count as usize), &mut byte, 1).is_err() {
-                    break;                    
+                let (run, len) = pipe.buf.run(pipe.write_cnt.0, min(free, count - write_count));
+                if src.offset(write_count as usize).copy_in(run, len as usize).is_err() {
+                    break
                 }
-                let i = (pipe.write_cnt.0 % PIPESIZE_U32) as usize;
-                pipe.data[i] = byte;
-                pipe.write_cnt += Wrapping(1);
-                write_count += 1;
+                pipe.write_cnt += Wrapping(len);
+                write_count += len;
             }
         }
         unsafe { PROC_MANAGER.wakeup(&pipe.read_cnt as *const Wrapping<_> as usize); }
+        pipe.pollq.notify();
         drop(pipe);
         Ok(write_count)
     }
@@ -250,7 +280,7 @@ impl Pipe {
     /// # 安全性
     /// - 唤醒操作通过 `PROC_MANAGER.wakeup` 实现，需保证唤醒地址来源合法（即 `read_cnt`/`write_cnt` 字段的地址）；
     /// - 通过 `SpinLock` 保证对管道状态的互斥访问，防止并发修改带来不一致。
-    pub(super) fn close(&self, is_write: bool) {
+    fn close(&self, is_write: bool) {
         let mut pipe = self.0.lock();
         if is_write {
             pipe.write_open = false;
@@ -259,6 +289,102 @@ impl Pipe {
             pipe.read_open = false;
             unsafe { PROC_MANAGER.wakeup(&pipe.write_cnt as *const Wrapping<_> as usize); }
         }
+        pipe.pollq.notify();
+    }
+
+    /// 查询管道的就绪状态并登记等待者 `waiter`：有数据时可读，缓冲区未满时可写，
+    /// 写端关闭时报告 `POLLHUP`，读端关闭时报告 `POLLERR`。
+    pub(super) fn poll(&self, waiter: PollWaiter) -> u16 {
+        let mut pipe = self.0.lock();
+        pipe.pollq.register(waiter);
+        let mut mask = 0;
+        if pipe.read_cnt != pipe.write_cnt {
+            mask |= POLLIN;
+        }
+        if (pipe.write_cnt - pipe.read_cnt).0 != pipe.buf.size() {
+            mask |= POLLOUT;
+        }
+        if !pipe.write_open {
+            mask |= POLLHUP;
+        }
+        if !pipe.read_open {
+            mask |= POLLERR;
+        }
+        mask
+    }
+
+    /// 执行管道相关的 fcntl 命令：
+    /// - `F_GETPIPE_SZ`：返回管道的容量；
+    /// - `F_SETPIPE_SZ`：将容量调整为不小于 `arg` 的 2 的幂个页，返回新的容量。
+    ///   新容量超过 [`PIPE_MAX_SIZE`]、小于管道中已有的数据量或内存不足时失败。
+    pub(super) fn fcntl(&self, cmd: i32, arg: usize) -> Result<usize, ()> {
+        match cmd {
+            F_GETPIPE_SZ => Ok(self.0.lock().buf.size() as usize),
+            F_SETPIPE_SZ => {
+                let size = max(arg, PGSIZE).checked_next_power_of_two().ok_or(())?;
+                if size > PIPE_MAX_SIZE {
+                    return Err(())
+                }
+                let mut buf = PipeBuf::new(size).ok_or(())?;
+                let mut pipe = self.0.lock();
+                let len = (pipe.write_cnt - pipe.read_cnt).0;
+                if len as usize > size {
+                    drop(pipe);
+                    return Err(())
+                }
+                // 把已有数据搬到新缓冲区的开头
+                let mut moved = 0;
+                while moved < len {
+                    let (from, n) = pipe.buf.run(pipe.read_cnt.0.wrapping_add(moved), len - moved);
+                    let (to, n) = buf.run(moved, n);
+                    unsafe { ptr::copy_nonoverlapping(from, to, n as usize); }
+                    moved += n;
+                }
+                pipe.read_cnt = Wrapping(0);
+                pipe.write_cnt = Wrapping(len);
+                buf = mem::replace(&mut pipe.buf, buf);
+                unsafe { PROC_MANAGER.wakeup(&pipe.write_cnt as *const Wrapping<_> as usize); }
+                pipe.pollq.notify();
+                drop(pipe);
+                drop(buf);
+                Ok(size)
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl FileOps for Arc<Pipe> {
+    fn read(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        Pipe::read(self, dst, count, false)
+    }
+
+    fn write(&self, src: Address, count: u32) -> Result<u32, ()> {
+        Pipe::write(self, src, count, false)
+    }
+
+    fn read_nonblock(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        Pipe::read(self, dst, count, true)
+    }
+
+    fn write_nonblock(&self, src: Address, count: u32) -> Result<u32, ()> {
+        Pipe::write(self, src, count, true)
+    }
+
+    fn poll(&self, waiter: PollWaiter) -> u16 {
+        Pipe::poll(self, waiter)
+    }
+
+    fn fcntl(&self, cmd: i32, arg: usize) -> Result<usize, ()> {
+        Pipe::fcntl(self, cmd, arg)
+    }
+
+    fn close(&mut self, writable: bool) {
+        Pipe::close(self, writable)
+    }
+
+    fn kind(&self) -> &'static str {
+        "pipe"
     }
 }
 
@@ -299,7 +425,7 @@ impl Drop for Pipe {
 /// 表示内核管道的内部状态，用于管理数据缓冲区与读写端口状态。
 ///
 /// `PipeInner` 是 `Pipe` 的核心数据结构，负责维护管道的状态与数据传输逻辑，
-/// 包括读写端是否开启、当前的读写偏移量，以及一个定长的环形缓冲区。
+/// 包括读写端是否开启、当前的读写偏移量，以及一个页式的环形缓冲区。
 /// 该结构由外层的 [`SpinLock`] 保护，确保在并发读写或关闭时的数据一致性。
 ///
 /// 管道通过 `read_cnt` 和 `write_cnt` 实现环形缓冲区读写索引的推进，
@@ -322,7 +448,53 @@ struct PipeInner {
     /// 同样使用 [`Wrapping<u32>`]，确保写指针在缓冲区内环绕。
     write_cnt: Wrapping<u32>,
 
-    /// 固定大小的字节缓冲区，用于存储写入的数据。
-    /// 该缓冲区采用环形策略访问，大小为 `PIPESIZE`。
-    data: [u8; PIPESIZE],
+    /// 存储写入数据的环形缓冲区，容量为 2 的幂个页，
+    /// 因此读写计数器回绕时在缓冲区中的位置仍然连续。
+    buf: PipeBuf,
+
+    /// 在两端等待的 poll 等待者。
+    pollq: PollQueue,
+}
+
+/// 由若干物理页组成的管道环形缓冲区，页数为 2 的幂。
+#[derive(Debug)]
+struct PipeBuf {
+    /// 各页的起始地址
+    pages: Vec<usize>,
+}
+
+impl PipeBuf {
+    /// 分配 `size` 字节的缓冲区，`size` 须为 2 的幂且不小于一页，内存不足时返回 `None`。
+    fn new(size: usize) -> Option<Self> {
+        debug_assert!(size.is_power_of_two() && size >= PGSIZE);
+        let npages = size / PGSIZE;
+        let mut buf = Self { pages: Vec::new() };
+        buf.pages.try_reserve_exact(npages).ok()?;
+        for _ in 0..npages {
+            let page = unsafe { RawSinglePage::try_new_uninit().ok()? };
+            buf.pages.push(page as usize);
+        }
+        Some(buf)
+    }
+
+    /// 缓冲区的容量（字节数）
+    fn size(&self) -> u32 {
+        (self.pages.len() * PGSIZE) as u32
+    }
+
+    /// 计数器 `cnt` 对应位置开始、不跨页的连续区域，长度不超过 `limit`。
+    fn run(&self, cnt: u32, limit: u32) -> (*mut u8, u32) {
+        let pos = (cnt & (self.size() - 1)) as usize;
+        let off = pos % PGSIZE;
+        let len = min(limit, (PGSIZE - off) as u32);
+        ((self.pages[pos / PGSIZE] + off) as *mut u8, len)
+    }
+}
+
+impl Drop for PipeBuf {
+    fn drop(&mut self) {
+        for &pa in self.pages.iter() {
+            unsafe { RawSinglePage::from_raw_and_drop(pa as *mut u8); }
+        }
+    }
 }
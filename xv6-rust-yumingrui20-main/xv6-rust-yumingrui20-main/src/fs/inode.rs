@@ -270,7 +270,7 @@ impl InodeCache {
             inode = self.get(ROOTDEV, ROOTINUM);
         } else {
             let p = unsafe { CPU_MANAGER.my_proc() };
-            inode = self.dup(p.data.get_mut().cwd.as_ref().unwrap());
+            inode = p.data.get_mut().cwd();
         }
 
         let mut cur: usize = 0;
@@ -1146,7 +1146,7 @@ impl InodeData {
     /// - 本函数不涉及 `unsafe` 操作；
     /// - 所有数据读取通过封装好的 `iread` 完成，避免直接操作指针；
     /// - 调用者必须确保在持有 `InodeData` 锁的前提下调用本函数，防止并发访问目录内容；
-    fn dir_lookup(&mut self, name: &[u8; MAX_DIR_SIZE], need_offset: bool) -> Option<(Inode, Option<u32>)> {
+    pub(super) fn dir_lookup(&mut self, name: &[u8; MAX_DIR_SIZE], need_offset: bool) -> Option<(Inode, Option<u32>)> {
         let (dev, _) = *self.valid.as_ref().unwrap();
         debug_assert!(dev != 0);
         if self.dinode.itype != InodeType::Directory {
@@ -1432,6 +1432,24 @@ impl FileStat {
             size: 0,
         }
     }
+
+    /// 由非磁盘文件系统（如 VFS 中的合成文件系统）直接构造文件状态。
+    pub const fn new(dev: u32, inum: u32, itype: InodeType, nlink: u16, size: u64) -> Self {
+        Self {
+            dev,
+            inum,
+            itype,
+            nlink,
+            size,
+        }
+    }
+}
+
+impl core::fmt::Display for FileStat {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "dev={} inum={} type={:?} nlink={} size={}",
+            self.dev, self.inum, self.itype, self.nlink, self.size)
+    }
 }
 
 /// 磁盘上的 inode 结构体，用于描述文件的元信息与数据块映射信息。
@@ -1502,6 +1520,7 @@ pub enum InodeType {
     Directory = 1,
     File = 2,
     Device = 3,
+    Fifo = 4,
 }
 
 /// 磁盘上的目录项结构体，用于表示目录中的单个文件或子目录的名称与 inode 映射关系。
@@ -1511,18 +1530,18 @@ pub enum InodeType {
 /// 它用于维护文件名与 inode 编号之间的映射关系，是路径解析、文件创建与删除等操作的基础。
 /// 当读取目录内容或插入/删除目录项时，系统会以 `DirEntry` 为基本单位进行处理。
 #[repr(C)]
-struct DirEntry {
+pub(super) struct DirEntry {
     /// 对应目标文件或子目录的 inode 编号。
     /// 为 0 表示该目录项为空（可复用）。
-    inum: u16,
+    pub(super) inum: u16,
 
     /// 目录项对应的文件名，长度不超过 `MAX_DIR_SIZE`，以 0 结尾（C 字符串风格）。
-    name: [u8; MAX_DIR_SIZE],
+    pub(super) name: [u8; MAX_DIR_SIZE],
 }
 
 
 impl DirEntry {
-    const fn empty() -> Self {
+    pub(super) const fn empty() -> Self {
         Self {
             inum: 0,
             name: [0; MAX_DIR_SIZE],
@@ -2,12 +2,16 @@
 
 use core::ops::DerefMut;
 
+use crate::consts::MAXPATH;
+use crate::driver;
+
 mod file;
 mod inode;
 mod log;
 mod bio;
 mod block;
 mod superblock;
+pub mod vfs;
 
 // TODO - Buf 也可以?
 pub use bio::Buf;
@@ -15,22 +19,44 @@ pub use bio::Buf;
 pub use bio::BCACHE;
 pub use inode::{ICACHE, Inode, InodeData, InodeType, FileStat};
 pub use log::LOG;
-pub use file::{File, Pipe};
+pub use file::{File, Pipe, FifoFile};
 
 use superblock::SUPER_BLOCK;
 use log::Log;
 use bio::BufData;
 use inode::icheck;
 
-/// 初始化文件系统，读取磁盘超级块信息，并根据需要进行日志恢复
+/// 初始化文件系统，读取磁盘超级块信息，并根据需要进行日志恢复，随后挂载 /proc 并创建 /dev 下的设备文件
 /// 安全性：必须在系统启动时被调用一次
 pub unsafe fn init(dev: u32) {
     SUPER_BLOCK.init(dev);
     let log_ptr = LOG.lock().deref_mut() as *mut Log;
     log_ptr.as_mut().unwrap().init(dev);
     icheck();
+    vfs::mount(b"/proc", &vfs::PROCFS).expect("cannot mount procfs");
+    make_dev_nodes();
     println!("file system: setup done");
 
     #[cfg(feature = "verbose_init_info")]
-    println!("file system: {} inode per block with size {}", inode::IPB, crate::consts::fs::BSIZE);
+    kinfo!("file system: {} inode per block with size {}", inode::IPB, crate::consts::fs::BSIZE);
+}
+
+/// 为设备注册表中的每个驱动在 `/dev` 下创建同名的设备文件（主设备号即注册表下标，次设备号为 0），
+/// 已存在的文件会被直接复用。
+fn make_dev_nodes() {
+    vfs::create(b"/dev\0", InodeType::Directory, 0, 0, true).expect("cannot create /dev");
+
+    for (major, dev) in driver::devices().iter().enumerate() {
+        let dev = match dev {
+            Some(dev) => dev,
+            None => continue,
+        };
+        let mut path = [0u8; MAXPATH];
+        let name = dev.name().as_bytes();
+        path[..5].copy_from_slice(b"/dev/");
+        path[5..5+name.len()].copy_from_slice(name);
+        if vfs::create(&path, InodeType::Device, major as u16, 0, true).is_none() {
+            panic!("cannot create /dev/{}", dev.name());
+        }
+    }
 }
@@ -82,7 +82,7 @@ impl SuperBlock {
         drop(buf);
 
         #[cfg(feature = "verbose_init_info")]
-        println!("super block data: {:?}", self.data.as_ptr().as_ref().unwrap());
+        kinfo!("super block data: {:?}", self.data.as_ptr().as_ref().unwrap());
     }
 
     /// 获取已初始化的超级块只读引用
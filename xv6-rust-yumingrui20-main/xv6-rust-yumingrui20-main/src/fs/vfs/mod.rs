@@ -0,0 +1,345 @@
+//! 虚拟文件系统（VFS）层
+//!
+//! 将“文件系统实例”“文件系统中的节点”“已打开的文件”三类对象抽象为
+//! [`SuperBlockOps`]、[`InodeOps`] 与 [`FileOps`] 三个 trait，
+//! 系统调用与 [`File`](super::File) 只通过这些接口访问具体实现。
+//! 基于日志的 xv6 磁盘文件系统是第一个实现，挂载在根目录 `/` 上；
+//! 其他文件系统可以通过 [`mount`] 挂载到某个绝对路径上。
+
+use array_macro::array;
+
+use alloc::boxed::Box;
+use core::fmt::Debug;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::consts::fs::{MAX_DIR_SIZE, NMOUNT, POLLIN, POLLOUT};
+use crate::mm::Address;
+use crate::process::PollWaiter;
+use crate::spinlock::SpinLock;
+
+use super::{FileStat, InodeType};
+
+mod xv6;
+mod procfs;
+
+pub use xv6::XV6FS;
+pub use procfs::PROCFS;
+
+/// 已打开文件的操作接口。
+///
+/// 每个 [`File`](super::File) 内部持有一个实现了该 trait 的对象，
+/// 常规文件、设备文件与管道都通过它完成读写、状态查询与关闭。
+/// 读写偏移等与“打开实例”相关的状态由实现者自行维护。
+pub trait FileOps: Debug + Send + Sync {
+    /// 从文件读取至多 `count` 字节到 `dst`，返回实际读取的字节数。
+    fn read(&self, dst: Address, count: u32) -> Result<u32, ()>;
+
+    /// 将 `src` 处的至多 `count` 字节写入文件，返回实际写入的字节数。
+    fn write(&self, src: Address, count: u32) -> Result<u32, ()>;
+
+    /// 以非阻塞方式读取：需要等待时不睡眠而是返回错误（相当于 `EAGAIN`）。
+    /// 默认与 [`read`](Self::read) 相同，适用于读取不会阻塞的实现（例如常规文件）。
+    fn read_nonblock(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        self.read(dst, count)
+    }
+
+    /// 以非阻塞方式写入：需要等待时返回已写入的字节数，一个字节都未写入时返回错误。
+    /// 默认与 [`write`](Self::write) 相同。
+    fn write_nonblock(&self, src: Address, count: u32) -> Result<u32, ()> {
+        self.write(src, count)
+    }
+
+    /// 查询文件状态，默认不支持（例如管道）。
+    fn stat(&self, _stat: &mut FileStat) -> Result<(), ()> {
+        Err(())
+    }
+
+    /// 执行设备相关的控制命令，`arg` 通常是用户空间中参数结构的地址，
+    /// 默认不支持（例如常规文件与管道）。
+    fn ioctl(&self, _request: usize, _arg: usize) -> Result<usize, ()> {
+        Err(())
+    }
+
+    /// 执行文件种类相关的 fcntl 命令（例如管道的 `F_SETPIPE_SZ`），
+    /// 通用的命令由系统调用层处理，默认不支持。
+    fn fcntl(&self, _cmd: i32, _arg: usize) -> Result<usize, ()> {
+        Err(())
+    }
+
+    /// 查询文件当前的就绪状态，返回 `POLLIN`、`POLLOUT` 等标志位的组合。
+    /// 默认总是可读可写（例如常规文件）；可能阻塞的实现须在检查状态的同时把 `waiter`
+    /// 登记到自身的 [`PollQueue`](crate::process::PollQueue) 上，并在状态改变时通知该队列。
+    fn poll(&self, _waiter: PollWaiter) -> u16 {
+        POLLIN | POLLOUT
+    }
+
+    /// 打开后、交给进程之前调用，等待打开完成所需的条件（例如命名管道等待另一端打开）。
+    /// 返回 `Err(())` 时打开失败，文件随即被关闭。默认不等待。
+    fn wait_open(&self) -> Result<(), ()> {
+        Ok(())
+    }
+
+    /// 文件的最后一个引用被释放时调用，`writable` 指示被关闭的是否为可写端。
+    fn close(&mut self, _writable: bool) {}
+
+    /// 文件种类的简短名称，用于 /proc 等信息展示。
+    fn kind(&self) -> &'static str {
+        "anon"
+    }
+}
+
+/// 目录迭代时返回的一个目录项。
+pub struct VDirEntry {
+    /// 目录项对应节点的编号，在所属文件系统内唯一。
+    pub inum: u32,
+
+    /// 以 0 结尾的目录项名称。
+    pub name: [u8; MAX_DIR_SIZE],
+}
+
+/// 文件系统中单个节点（文件、目录或设备）的操作接口。
+///
+/// 实现者持有对底层节点的一个引用，释放该对象即释放引用。
+pub trait InodeOps: Send + Sync {
+    /// 节点的类型。
+    fn itype(&self) -> InodeType;
+
+    /// 将节点的元信息填入 `stat`。
+    fn stat(&self, stat: &mut FileStat);
+
+    /// 以 `flags` 指定的方式打开该节点，返回对应的文件操作对象。
+    fn open(&self, flags: i32) -> Option<Box<dyn FileOps>>;
+
+    /// 在目录节点中查找名为 `name` 的目录项。
+    fn lookup(&self, name: &[u8; MAX_DIR_SIZE]) -> Option<Box<dyn InodeOps>>;
+
+    /// 目录迭代：从游标 `pos` 开始查找下一个有效目录项，
+    /// 返回该目录项以及下一次迭代应使用的游标；迭代结束时返回 `None`。
+    fn read_dir(&self, pos: usize) -> Option<(VDirEntry, usize)>;
+}
+
+/// 已挂载文件系统实例（超级块）的操作接口。
+///
+/// 传入的路径均是相对于挂载点的路径：以 `/` 开头时从该文件系统的根开始解析，
+/// 并且与内核其他路径一样以 0 结尾。
+pub trait SuperBlockOps: Sync {
+    /// 文件系统类型的名称，用于调试与 /proc 等信息展示。
+    fn name(&self) -> &'static str;
+
+    /// 获取文件系统的根目录节点。
+    fn root(&self) -> Option<Box<dyn InodeOps>>;
+
+    /// 解析路径并返回对应节点。
+    ///
+    /// 默认实现从根目录开始逐级调用 [`InodeOps::lookup`]，
+    /// 只适用于不支持相对路径的文件系统。
+    fn lookup(&self, path: &[u8]) -> Option<Box<dyn InodeOps>> {
+        let mut inode = self.root()?;
+        let mut name: [u8; MAX_DIR_SIZE] = [0; MAX_DIR_SIZE];
+        let mut cur = 0;
+        loop {
+            cur = next_component(path, cur, &mut name);
+            if cur == 0 {
+                return Some(inode)
+            }
+            if inode.itype() != InodeType::Directory {
+                return None
+            }
+            inode = inode.lookup(&name)?;
+        }
+    }
+
+    /// 在路径处创建类型为 `itype` 的节点；`reuse` 为真时若节点已存在则直接返回它。
+    fn create(&self, _path: &[u8], _itype: InodeType, _major: u16, _minor: u16, _reuse: bool)
+        -> Option<Box<dyn InodeOps>>
+    {
+        None
+    }
+
+    /// 删除路径对应的目录项。
+    fn unlink(&self, _path: &[u8]) -> Result<(), ()> {
+        Err(())
+    }
+
+    /// 为 `old_path` 指向的节点创建新的硬链接 `new_path`。
+    fn link(&self, _old_path: &[u8], _new_path: &[u8]) -> Result<(), ()> {
+        Err(())
+    }
+}
+
+/// 挂载表中的一项：挂载点路径（不含结尾 0）与文件系统实例。
+struct Mount {
+    path: &'static [u8],
+    sb: &'static dyn SuperBlockOps,
+}
+
+/// 挂载表，未命中任何挂载点的路径都交给根文件系统 [`XV6FS`] 处理。
+static MOUNTS: SpinLock<[Option<Mount>; NMOUNT]> =
+    SpinLock::new(array![_ => None; NMOUNT], "mounts");
+
+/// 将文件系统 `sb` 挂载到绝对路径 `path`（不含结尾 0）上。
+///
+/// # 返回值
+/// - `Ok(())`：挂载成功；
+/// - `Err(())`：路径不是绝对路径、已被挂载或挂载表已满。
+pub fn mount(path: &'static [u8], sb: &'static dyn SuperBlockOps) -> Result<(), ()> {
+    if path.len() < 2 || path[0] != b'/' {
+        return Err(())
+    }
+
+    let mut mounts = MOUNTS.lock();
+    if mounts.iter().flatten().any(|m| m.path == path) {
+        return Err(())
+    }
+    let slot = mounts.iter_mut().find(|m| m.is_none()).ok_or(())?;
+    *slot = Some(Mount { path, sb });
+    drop(mounts);
+
+    #[cfg(feature = "verbose_init_info")]
+    kinfo!("vfs: mount {} on {}", sb.name(), core::str::from_utf8(path).unwrap());
+
+    Ok(())
+}
+
+/// 根据挂载表找到负责 `path` 的文件系统，并返回挂载点之后的剩余路径。
+///
+/// 相对路径总是从进程当前工作目录出发，而当前工作目录只能位于根文件系统中，
+/// 因此相对路径总是交给根文件系统处理。
+fn resolve(path: &[u8]) -> (&'static dyn SuperBlockOps, &[u8]) {
+    if path[0] == b'/' {
+        let mounts = MOUNTS.lock();
+        for m in mounts.iter().flatten() {
+            let len = m.path.len();
+            if path.len() > len && &path[..len] == m.path && (path[len] == b'/' || path[len] == 0) {
+                return (m.sb, &path[len..])
+            }
+        }
+    }
+    (&XV6FS, path)
+}
+
+/// 判断路径是否位于根文件系统中。
+pub fn on_root_fs(path: &[u8]) -> bool {
+    same_fs(resolve(path).0, &XV6FS)
+}
+
+/// 解析路径并返回对应节点。
+pub fn lookup(path: &[u8]) -> Option<Box<dyn InodeOps>> {
+    let (sb, rest) = resolve(path);
+    sb.lookup(rest)
+}
+
+/// 在路径处创建节点，见 [`SuperBlockOps::create`]。
+pub fn create(path: &[u8], itype: InodeType, major: u16, minor: u16, reuse: bool)
+    -> Option<Box<dyn InodeOps>>
+{
+    let (sb, rest) = resolve(path);
+    sb.create(rest, itype, major, minor, reuse)
+}
+
+/// 删除路径对应的目录项。
+pub fn unlink(path: &[u8]) -> Result<(), ()> {
+    let (sb, rest) = resolve(path);
+    sb.unlink(rest)
+}
+
+/// 创建硬链接，两个路径必须位于同一个文件系统中。
+pub fn link(old_path: &[u8], new_path: &[u8]) -> Result<(), ()> {
+    let (old_sb, old_rest) = resolve(old_path);
+    let (new_sb, new_rest) = resolve(new_path);
+    if !same_fs(old_sb, new_sb) {
+        return Err(())
+    }
+    old_sb.link(old_rest, new_rest)
+}
+
+/// 比较两个文件系统实例是否为同一个（只比较数据指针）。
+#[inline]
+fn same_fs(a: &dyn SuperBlockOps, b: &dyn SuperBlockOps) -> bool {
+    ptr::eq(a as *const dyn SuperBlockOps as *const u8, b as *const dyn SuperBlockOps as *const u8)
+}
+
+/// 从 `path[cur..]` 中取出下一个路径分量写入 `name`，返回其后的位置；
+/// 若已没有更多分量则返回 0。
+fn next_component(path: &[u8], mut cur: usize, name: &mut [u8; MAX_DIR_SIZE]) -> usize {
+    while cur < path.len() && path[cur] == b'/' {
+        cur += 1;
+    }
+    if cur >= path.len() || path[cur] == 0 {
+        return 0
+    }
+
+    let start = cur;
+    while cur < path.len() && path[cur] != b'/' && path[cur] != 0 {
+        cur += 1;
+    }
+    let count = core::cmp::min(cur - start, MAX_DIR_SIZE - 1);
+    name[..count].copy_from_slice(&path[start..start+count]);
+    name[count..].fill(0);
+    cur
+}
+
+/// 通用的目录文件：读取时把 [`InodeOps::read_dir`] 的结果
+/// 按照磁盘目录项（`DirEntry`）的格式依次输出，
+/// 使得 `ls` 等按 xv6 目录格式读取目录的程序可以遍历非磁盘文件系统。
+#[derive(Debug)]
+pub struct DirFile {
+    inode: DirFileInode,
+    /// 目录迭代游标；`read_dir` 可能睡眠，因此不能用自旋锁保护。
+    pos: AtomicUsize,
+}
+
+/// 为了给 [`DirFile`] 派生 `Debug` 而对节点对象做的一层包装。
+struct DirFileInode(Box<dyn InodeOps>);
+
+impl Debug for DirFileInode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("DirFileInode")
+    }
+}
+
+impl DirFile {
+    pub fn new(inode: Box<dyn InodeOps>) -> Self {
+        Self {
+            inode: DirFileInode(inode),
+            pos: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl FileOps for DirFile {
+    fn read(&self, mut dst: Address, count: u32) -> Result<u32, ()> {
+        let de_size = core::mem::size_of::<super::inode::DirEntry>();
+        let mut pos = self.pos.load(Ordering::Relaxed);
+        let mut read_count = 0;
+        while (count as usize) - read_count >= de_size {
+            let (entry, next) = match self.inode.0.read_dir(pos) {
+                Some(e) => e,
+                None => break,
+            };
+            let mut de = super::inode::DirEntry::empty();
+            de.inum = entry.inum as u16;
+            de.name = entry.name;
+            dst.copy_out(&de as *const _ as *const u8, de_size)?;
+            dst = dst.offset(de_size);
+            read_count += de_size;
+            pos = next;
+        }
+        self.pos.store(pos, Ordering::Relaxed);
+        Ok(read_count as u32)
+    }
+
+    fn write(&self, _src: Address, _count: u32) -> Result<u32, ()> {
+        Err(())
+    }
+
+    fn stat(&self, stat: &mut FileStat) -> Result<(), ()> {
+        self.inode.0.stat(stat);
+        Ok(())
+    }
+
+    fn kind(&self) -> &'static str {
+        "dir"
+    }
+}
@@ -0,0 +1,434 @@
+//! /proc 内核信息文件系统
+//!
+//! 该文件系统不对应任何磁盘数据，其中的文件在打开时即时生成内容：
+//! - `/proc/meminfo`：内核堆（伙伴系统）与共享内存的统计信息；
+//! - `/proc/bcache`：缓冲区缓存统计信息；
+//! - `/proc/uptime`：系统启动以来的时钟滴答数；
+//! - `/proc/cpuinfo`：各硬件线程上正在运行的进程与切换次数；
+//! - `/proc/uart`：各串口的收发字节数与溢出计数；
+//! - `/proc/lockstat`：各类自旋锁的获取次数、竞争次数与忙等待循环次数；
+//! - `/proc/profile`：内核采样分析器的采样，按地址汇总；
+//! - `/proc/<pid>/{status, cmdline, maps, fd}`：单个进程的状态、命令行、内存布局与打开的文件。
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::min;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::consts::{NSMP, NTHREAD, PGSIZE, TRAMPOLINE, TRAPFRAME};
+use crate::consts::fs::{MAX_DIR_SIZE, PROCDEV, O_WRONLY, O_RDWR};
+use crate::driver::uart::{self, UartStat};
+use crate::fs::{BCACHE, File};
+use crate::mm::{shm, Address, PteFlag, KERNEL_HEAP};
+use crate::process::{CPU_MANAGER, PROC_MANAGER};
+use crate::profile;
+use crate::spinlock;
+use crate::trap;
+
+use super::super::{FileStat, InodeType};
+use super::{DirFile, FileOps, InodeOps, SuperBlockOps, VDirEntry};
+
+/// /proc 文件系统实例，在文件系统初始化时挂载到 `/proc`。
+pub static PROCFS: ProcFs = ProcFs;
+
+/// /proc 文件系统的超级块操作，只支持查找，不支持创建、删除与链接。
+pub struct ProcFs;
+
+impl SuperBlockOps for ProcFs {
+    fn name(&self) -> &'static str {
+        "procfs"
+    }
+
+    fn root(&self) -> Option<Box<dyn InodeOps>> {
+        Some(Box::new(ProcNode::Root))
+    }
+}
+
+/// 全局信息文件。
+#[derive(Clone, Copy, Debug)]
+enum GlobalFile {
+    Meminfo,
+    Bcache,
+    Uptime,
+    Cpuinfo,
+    Uart,
+    Lockstat,
+    Profile,
+}
+
+/// 进程目录下的信息文件。
+#[derive(Clone, Copy, Debug)]
+enum PidFile {
+    Status,
+    Cmdline,
+    Maps,
+    Fd,
+}
+
+const GLOBAL_FILES: [(&[u8], GlobalFile); 7] = [
+    (b"meminfo", GlobalFile::Meminfo),
+    (b"bcache", GlobalFile::Bcache),
+    (b"uptime", GlobalFile::Uptime),
+    (b"cpuinfo", GlobalFile::Cpuinfo),
+    (b"uart", GlobalFile::Uart),
+    (b"lockstat", GlobalFile::Lockstat),
+    (b"profile", GlobalFile::Profile),
+];
+
+const PID_FILES: [(&[u8], PidFile); 4] = [
+    (b"status", PidFile::Status),
+    (b"cmdline", PidFile::Cmdline),
+    (b"maps", PidFile::Maps),
+    (b"fd", PidFile::Fd),
+];
+
+/// /proc 中的一个节点。
+///
+/// 节点编号的分配方式：根目录为 1，全局文件从 2 开始依次编号，
+/// 进程目录为 `(slot+1) << 4`，进程目录下的文件在此基础上加上文件序号加一，
+/// 其中 `slot` 是进程在进程表中的下标。编号不随 PID 增长，总能放入目录项的 16 位 inode 编号。
+#[derive(Clone, Copy, Debug)]
+enum ProcNode {
+    Root,
+    Global(usize),
+    /// PID 与进程表下标
+    PidDir(usize, usize),
+    /// PID、进程表下标与文件序号
+    PidFile(usize, usize, usize),
+}
+
+impl ProcNode {
+    fn inum(&self) -> u32 {
+        match *self {
+            Self::Root => 1,
+            Self::Global(i) => 2 + i as u32,
+            Self::PidDir(_, slot) => (slot as u32 + 1) << 4,
+            Self::PidFile(_, slot, i) => ((slot as u32 + 1) << 4) | (i as u32 + 1),
+        }
+    }
+
+    /// 生成文件内容，进程已不存在时返回 `None`。
+    fn generate(&self) -> Option<String> {
+        let mut buf = String::new();
+        let ret = match *self {
+            Self::Global(i) => match GLOBAL_FILES[i].1 {
+                GlobalFile::Meminfo => gen_meminfo(&mut buf),
+                GlobalFile::Bcache => gen_bcache(&mut buf),
+                GlobalFile::Uptime => writeln!(buf, "{} ticks", trap::clock_read()),
+                GlobalFile::Cpuinfo => gen_cpuinfo(&mut buf),
+                GlobalFile::Uart => gen_uart(&mut buf),
+                GlobalFile::Lockstat => gen_lockstat(&mut buf),
+                GlobalFile::Profile => profile::dump(&mut buf),
+            },
+            Self::PidFile(pid, _, i) => match PID_FILES[i].1 {
+                PidFile::Status => gen_status(pid, &mut buf)?,
+                PidFile::Cmdline => gen_cmdline(pid, &mut buf)?,
+                PidFile::Maps => gen_maps(pid, &mut buf)?,
+                PidFile::Fd => gen_fd(pid, &mut buf)?,
+            },
+            Self::Root | Self::PidDir(..) => return None,
+        };
+        ret.ok()?;
+        Some(buf)
+    }
+}
+
+impl InodeOps for ProcNode {
+    fn itype(&self) -> InodeType {
+        match self {
+            Self::Root | Self::PidDir(..) => InodeType::Directory,
+            Self::Global(_) | Self::PidFile(..) => InodeType::File,
+        }
+    }
+
+    fn stat(&self, stat: &mut FileStat) {
+        *stat = FileStat::new(PROCDEV, self.inum(), self.itype(), 1, 0);
+    }
+
+    /// 只允许以只读方式打开，不检查访问方式以外的标志；普通文件的内容在打开时一次性生成。
+    fn open(&self, flags: i32) -> Option<Box<dyn FileOps>> {
+        if flags & (O_WRONLY | O_RDWR) != 0 {
+            return None
+        }
+        match self {
+            Self::Root | Self::PidDir(..) => Some(Box::new(DirFile::new(Box::new(*self)))),
+            Self::Global(_) | Self::PidFile(..) => {
+                let data = self.generate()?.into_bytes();
+                Some(Box::new(ProcFile {
+                    inum: self.inum(),
+                    data,
+                    offset: AtomicUsize::new(0),
+                }))
+            },
+        }
+    }
+
+    fn lookup(&self, name: &[u8; MAX_DIR_SIZE]) -> Option<Box<dyn InodeOps>> {
+        let name = cstr_bytes(name);
+        let node = match *self {
+            Self::Root => {
+                if let Some(i) = GLOBAL_FILES.iter().position(|(n, _)| *n == name) {
+                    Self::Global(i)
+                } else {
+                    let pid = parse_pid(name)?;
+                    let slot = unsafe { PROC_MANAGER.inspect(pid, |p| p.slot)? };
+                    Self::PidDir(pid, slot)
+                }
+            },
+            Self::PidDir(pid, slot) => {
+                let i = PID_FILES.iter().position(|(n, _)| *n == name)?;
+                Self::PidFile(pid, slot, i)
+            },
+            Self::Global(_) | Self::PidFile(..) => return None,
+        };
+        Some(Box::new(node))
+    }
+
+    /// 根目录的游标先遍历全局文件，之后为进程表下标加上全局文件数量；
+    /// 进程目录的游标即文件序号。
+    fn read_dir(&self, pos: usize) -> Option<(VDirEntry, usize)> {
+        match *self {
+            Self::Root => {
+                if pos < GLOBAL_FILES.len() {
+                    let node = Self::Global(pos);
+                    return Some((dir_entry(node.inum(), GLOBAL_FILES[pos].0), pos + 1))
+                }
+                let (pid, next) = unsafe { PROC_MANAGER.next_pid(pos - GLOBAL_FILES.len())? };
+                let mut digits = [0u8; MAX_DIR_SIZE];
+                let len = format_pid(pid, &mut digits);
+                let node = Self::PidDir(pid, next - 1);
+                Some((dir_entry(node.inum(), &digits[..len]), next + GLOBAL_FILES.len()))
+            },
+            Self::PidDir(pid, slot) => {
+                if pos >= PID_FILES.len() {
+                    return None
+                }
+                Some((dir_entry(Self::PidFile(pid, slot, pos).inum(), PID_FILES[pos].0), pos + 1))
+            },
+            Self::Global(_) | Self::PidFile(..) => None,
+        }
+    }
+}
+
+/// 打开的 /proc 文件，持有打开时生成的内容快照。
+#[derive(Debug)]
+struct ProcFile {
+    inum: u32,
+    data: Vec<u8>,
+    offset: AtomicUsize,
+}
+
+impl FileOps for ProcFile {
+    fn read(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        let offset = self.offset.load(Ordering::Relaxed);
+        let count = min(count as usize, self.data.len() - offset);
+        dst.copy_out(self.data[offset..].as_ptr(), count)?;
+        self.offset.store(offset + count, Ordering::Relaxed);
+        Ok(count as u32)
+    }
+
+    fn write(&self, _src: Address, _count: u32) -> Result<u32, ()> {
+        Err(())
+    }
+
+    fn stat(&self, stat: &mut FileStat) -> Result<(), ()> {
+        *stat = FileStat::new(PROCDEV, self.inum, InodeType::File, 1, self.data.len() as u64);
+        Ok(())
+    }
+
+    fn kind(&self) -> &'static str {
+        "proc"
+    }
+}
+
+fn gen_meminfo(buf: &mut String) -> fmt::Result {
+    let stat = KERNEL_HEAP.stat();
+    let total = stat.end - stat.base;
+    let free = stat.free_bytes();
+    writeln!(buf, "heap: [{:#x}, {:#x})", stat.base, stat.end)?;
+    writeln!(buf, "total: {} bytes", total)?;
+    writeln!(buf, "used: {} bytes", total - free)?;
+    writeln!(buf, "free: {} bytes", free)?;
+    let (segments, shm_bytes) = shm::stat();
+    writeln!(buf, "shm: {} bytes in {} segments", shm_bytes, segments)?;
+    writeln!(buf, "order block free")?;
+    for k in 0..stat.nsizes {
+        writeln!(buf, "{:5} {:5} {:4}", k, stat.blk_size(k), stat.free_blocks[k])?;
+    }
+    Ok(())
+}
+
+fn gen_bcache(buf: &mut String) -> fmt::Result {
+    let stat = BCACHE.stat();
+    writeln!(buf, "buffers: {}", stat.nbuf)?;
+    writeln!(buf, "in use: {}", stat.in_use)?;
+    writeln!(buf, "valid: {}", stat.valid)?;
+    writeln!(buf, "hits: {}", stat.hits)?;
+    writeln!(buf, "misses: {}", stat.misses)
+}
+
+fn gen_uart(buf: &mut String) -> fmt::Result {
+    writeln!(buf, "name        tx       rx  dropped  overrun")?;
+    let mut write_stat = |stat: UartStat| writeln!(buf, "{:5} {:8} {:8} {:8} {:8}",
+        stat.name, stat.tx, stat.rx, stat.rx_dropped, stat.overruns);
+    write_stat(uart::UART.stat())
+}
+
+fn gen_lockstat(buf: &mut String) -> fmt::Result {
+    writeln!(buf, "name           acquired  contended        spins")?;
+    for stat in spinlock::lock_stats() {
+        writeln!(buf, "{:12} {:10} {:10} {:12}",
+            stat.name, stat.acquired, stat.contended, stat.spins)?;
+    }
+    Ok(())
+}
+
+fn gen_cpuinfo(buf: &mut String) -> fmt::Result {
+    for id in 0..NSMP {
+        let stat = unsafe { CPU_MANAGER.hart_stat(id) };
+        write!(buf, "hart {}: ", id)?;
+        match stat.pid {
+            Some(pid) => {
+                let mut name = [0u8; 16];
+                unsafe { PROC_MANAGER.inspect(pid, |v| name = v.name); }
+                write!(buf, "pid {} ({})", pid, cstr(&name))?;
+            },
+            None => write!(buf, "idle")?,
+        }
+        writeln!(buf, ", {} switches, {} steals, {} queued, idle {} ms",
+            stat.switches, stat.steals, stat.queued, stat.idle_ms)?;
+    }
+    Ok(())
+}
+
+fn gen_status(pid: usize, buf: &mut String) -> Option<fmt::Result> {
+    unsafe { PROC_MANAGER.inspect(pid, |v| {
+        writeln!(buf, "name: {}", cstr(&v.name))?;
+        writeln!(buf, "pid: {}", v.pid)?;
+        match v.ppid {
+            Some(ppid) => writeln!(buf, "ppid: {}", ppid)?,
+            None => writeln!(buf, "ppid: -")?,
+        }
+        writeln!(buf, "pgid: {}", v.pgid)?;
+        writeln!(buf, "sid: {}", v.sid)?;
+        writeln!(buf, "state: {:?}", v.state)?;
+        writeln!(buf, "killed: {}", v.killed)?;
+        if let Some(vm) = v.vm.as_ref() {
+            let size = vm.lock().sz;
+            writeln!(buf, "size: {}", size)?;
+        }
+        Ok(())
+    }) }
+}
+
+fn gen_cmdline(pid: usize, buf: &mut String) -> Option<fmt::Result> {
+    unsafe { PROC_MANAGER.inspect(pid, |v| writeln!(buf, "{}", cstr(&v.cmdline))) }
+}
+
+fn gen_maps(pid: usize, buf: &mut String) -> Option<fmt::Result> {
+    unsafe { PROC_MANAGER.inspect(pid, |v| {
+        let vm = match v.vm.as_ref() {
+            Some(vm) => vm.lock(),
+            None => return Ok(()),
+        };
+        let mut ret = Ok(());
+        let mut shm = vm.shm_mappings().iter().peekable();
+        vm.pagetable.for_each_region(vm.sz, |start, end, perm| {
+            // 共享内存映射位于堆与陷阱帧之间，按地址顺序插入
+            while let Some(m) = shm.next_if(|m| m.va < start) {
+                ret = ret.and_then(|()| writeln!(buf, "{:#011x}-{:#011x} rw-u [shm {}]",
+                    m.va, m.end(), m.seg.id()));
+            }
+            let label = if start == usize::from(TRAMPOLINE) {
+                " [trampoline]"
+            } else if start >= usize::from(TRAPFRAME) - (NTHREAD-1)*PGSIZE {
+                " [trapframe]"
+            } else {
+                ""
+            };
+            ret = ret.and_then(|()| writeln!(buf, "{:#011x}-{:#011x} {}{}{}{}{}",
+                start, end,
+                if perm.contains(PteFlag::R) { 'r' } else { '-' },
+                if perm.contains(PteFlag::W) { 'w' } else { '-' },
+                if perm.contains(PteFlag::X) { 'x' } else { '-' },
+                if perm.contains(PteFlag::U) { 'u' } else { '-' },
+                label));
+        });
+        ret
+    }) }
+}
+
+/// 在持有文件表的锁时只克隆文件引用，格式化（可能需要获取 inode 的睡眠锁）放到释放锁之后进行。
+fn gen_fd(pid: usize, buf: &mut String) -> Option<fmt::Result> {
+    let mut files: Vec<(usize, Arc<File>)> = Vec::new();
+    unsafe { PROC_MANAGER.inspect(pid, |v| {
+        if let Some(fdt) = v.fdt.as_ref() {
+            let fdt = fdt.lock();
+            // 内存不足时不列出文件
+            let n = fdt.files().count();
+            if files.try_reserve_exact(n).is_ok() {
+                files.extend(fdt.files().map(|(fd, f)| (fd, Arc::clone(f))));
+            }
+        }
+    })? };
+    for (fd, file) in files.iter() {
+        if let Err(e) = writeln!(buf, "{}: {}", fd, file) {
+            return Some(Err(e))
+        }
+    }
+    Some(Ok(()))
+}
+
+/// 构造目录项，名称超长时截断。
+fn dir_entry(inum: u32, name: &[u8]) -> VDirEntry {
+    let mut entry = VDirEntry { inum, name: [0; MAX_DIR_SIZE] };
+    let len = min(name.len(), MAX_DIR_SIZE-1);
+    entry.name[..len].copy_from_slice(&name[..len]);
+    entry
+}
+
+/// 将 pid 以十进制写入 `buf`，返回写入的字节数。
+fn format_pid(mut pid: usize, buf: &mut [u8; MAX_DIR_SIZE]) -> usize {
+    let mut digits = [0u8; MAX_DIR_SIZE];
+    let mut len = 0;
+    loop {
+        digits[len] = b'0' + (pid % 10) as u8;
+        len += 1;
+        pid /= 10;
+        if pid == 0 || len == MAX_DIR_SIZE-1 {
+            break
+        }
+    }
+    for i in 0..len {
+        buf[i] = digits[len-1-i];
+    }
+    len
+}
+
+/// 将十进制名称解析为 pid。
+fn parse_pid(name: &[u8]) -> Option<usize> {
+    if name.is_empty() {
+        return None
+    }
+    name.iter().try_fold(0usize, |acc, &c| {
+        if c.is_ascii_digit() {
+            acc.checked_mul(10)?.checked_add((c - b'0') as usize)
+        } else {
+            None
+        }
+    })
+}
+
+/// 截取以 0 结尾的字节串中 0 之前的部分。
+fn cstr_bytes(bytes: &[u8]) -> &[u8] {
+    let len = bytes.iter().position(|&c| c == 0).unwrap_or(bytes.len());
+    &bytes[..len]
+}
+
+/// 将以 0 结尾的字节串转换为字符串，用于格式化输出。
+fn cstr(bytes: &[u8]) -> &str {
+    core::str::from_utf8(cstr_bytes(bytes)).unwrap_or("?")
+}
@@ -0,0 +1,384 @@
+//! xv6 磁盘文件系统在 VFS 层上的实现
+
+use alloc::boxed::Box;
+use core::cell::UnsafeCell;
+use core::cmp::min;
+use core::convert::TryInto;
+use core::mem;
+
+use crate::consts::fs::{MAXOPBLOCKS, BSIZE, MAX_DIR_SIZE, ROOTIPATH};
+use crate::consts::fs::{O_WRONLY, O_RDWR, O_TRUNC, POLLERR};
+use crate::driver;
+use crate::mm::Address;
+use crate::process::PollWaiter;
+
+use super::super::{ICACHE, LOG, Inode, InodeType, FileStat, FifoFile};
+use super::super::inode::DirEntry;
+use super::{FileOps, InodeOps, SuperBlockOps, VDirEntry};
+
+/// 根文件系统实例，即基于日志的 xv6 磁盘文件系统。
+pub static XV6FS: Xv6Fs = Xv6Fs;
+
+/// xv6 文件系统的超级块操作。
+///
+/// 所有操作都直接委托给全局的 [`ICACHE`]，并各自包裹在独立的日志事务中。
+pub struct Xv6Fs;
+
+impl SuperBlockOps for Xv6Fs {
+    fn name(&self) -> &'static str {
+        "xv6fs"
+    }
+
+    fn root(&self) -> Option<Box<dyn InodeOps>> {
+        self.lookup(&ROOTIPATH)
+    }
+
+    /// 使用 `namei` 解析路径，支持相对于当前工作目录的路径。
+    fn lookup(&self, path: &[u8]) -> Option<Box<dyn InodeOps>> {
+        LOG.begin_op();
+        let inode = ICACHE.namei(path);
+        LOG.end_op();
+        inode.map(|i| Box::new(Xv6Inode(Some(i))) as Box<dyn InodeOps>)
+    }
+
+    fn create(&self, path: &[u8], itype: InodeType, major: u16, minor: u16, reuse: bool)
+        -> Option<Box<dyn InodeOps>>
+    {
+        LOG.begin_op();
+        let inode = ICACHE.create(path, itype, major, minor, reuse);
+        LOG.end_op();
+        inode.map(|i| Box::new(Xv6Inode(Some(i))) as Box<dyn InodeOps>)
+    }
+
+    /// 在父目录中删除对应目录项，目标 inode 的链接数随之减少。
+    fn unlink(&self, path: &[u8]) -> Result<(), ()> {
+        LOG.begin_op();
+        let mut name: [u8; MAX_DIR_SIZE] = [0; MAX_DIR_SIZE];
+        let dir_inode: Inode;
+        if let Some(inode) = ICACHE.namei_parent(path, &mut name) {
+            dir_inode = inode;
+        } else {
+            LOG.end_op();
+            return Err(())
+        }
+
+        let mut dir_idata = dir_inode.lock();
+        let ret = dir_idata.dir_unlink(&name);
+        drop(dir_idata);
+        drop(dir_inode);
+        LOG.end_op();
+        ret
+    }
+
+    /// 先增加原 inode 的链接数，再在新路径的父目录中创建目录项；
+    /// 若后一步失败则撤销链接数的修改。
+    fn link(&self, old_path: &[u8], new_path: &[u8]) -> Result<(), ()> {
+        LOG.begin_op();
+
+        // 查找原文件
+        let old_inode = ICACHE.namei(old_path).ok_or_else(|| {LOG.end_op(); ()})?;
+        let mut old_idata = old_inode.lock();
+        let (old_dev, old_inum) = old_idata.get_dev_inum();
+        if old_idata.get_itype() == InodeType::Directory {
+            #[cfg(feature = "kernel_warning")]
+            kwarn!("kernel warning: trying to create new link to a directory");
+            drop(old_idata);
+            drop(old_inode);
+            LOG.end_op();
+            return Err(())
+        }
+        old_idata.link();
+        old_idata.update();
+        drop(old_idata);
+
+        // 如果无法创建新路径
+        let revert_link = move |inode: Inode| {
+            let mut idata = inode.lock();
+            idata.unlink();
+            idata.update();
+            drop(idata);
+            drop(inode);
+            LOG.end_op();
+        };
+
+        // 创建新路径
+        let mut name: [u8; MAX_DIR_SIZE] = [0; MAX_DIR_SIZE];
+        let new_inode: Inode;
+        match ICACHE.namei_parent(new_path, &mut name) {
+            Some(inode) => new_inode = inode,
+            None => {
+                revert_link(old_inode);
+                return Err(())
+            }
+        }
+        let mut new_idata = new_inode.lock();
+        if new_idata.get_dev_inum().0 != old_dev || new_idata.dir_link(&name, old_inum).is_err() {
+            drop(new_idata);
+            drop(new_inode);
+            revert_link(old_inode);
+            return Err(())
+        }
+        drop(new_idata);
+        drop(new_inode);
+        drop(old_inode);
+
+        LOG.end_op();
+        Ok(())
+    }
+}
+
+/// xv6 文件系统中的一个节点，持有 inode 缓存中的一个引用。
+///
+/// 释放 inode 引用可能触发磁盘写入（链接数为 0 时截断文件），
+/// 因此在 `Drop` 中用日志事务包裹。
+struct Xv6Inode(Option<Inode>);
+
+impl Xv6Inode {
+    #[inline]
+    fn inode(&self) -> &Inode {
+        self.0.as_ref().unwrap()
+    }
+}
+
+impl Drop for Xv6Inode {
+    fn drop(&mut self) {
+        LOG.begin_op();
+        drop(self.0.take());
+        LOG.end_op();
+    }
+}
+
+impl InodeOps for Xv6Inode {
+    fn itype(&self) -> InodeType {
+        self.inode().lock().get_itype()
+    }
+
+    fn stat(&self, stat: &mut FileStat) {
+        self.inode().lock().istat(stat);
+    }
+
+    /// 根据 inode 类型构造文件操作对象：
+    /// - 目录只允许以只读方式打开，`O_NONBLOCK` 等其他标志不受影响；
+    /// - 普通文件在指定 `O_TRUNC` 时先截断；
+    /// - 设备文件需要主设备号上已登记驱动；
+    /// - 命名管道共享以节点为键的内存管道，等待另一端由 [`FileOps::wait_open`] 完成。
+    fn open(&self, flags: i32) -> Option<Box<dyn FileOps>> {
+        LOG.begin_op();
+        let mut idata = self.inode().lock();
+        let ret: Option<Box<dyn FileOps>> = match idata.get_itype() {
+            InodeType::Empty => panic!("empty inode"),
+            InodeType::Directory => {
+                if flags & (O_WRONLY | O_RDWR) != 0 {
+                    None
+                } else {
+                    Some(Box::new(FileRegular { offset: UnsafeCell::new(0), inode: Some(self.inode().clone()) }))
+                }
+            },
+            InodeType::File => {
+                if flags & O_TRUNC > 0 {
+                    idata.truncate();
+                }
+                Some(Box::new(FileRegular { offset: UnsafeCell::new(0), inode: Some(self.inode().clone()) }))
+            },
+            InodeType::Device => {
+                let (major, _) = idata.get_devnum();
+                if driver::device(major as usize).is_none() {
+                    None
+                } else {
+                    Some(Box::new(FileDevice { major, inode: Some(self.inode().clone()) }))
+                }
+            },
+            InodeType::Fifo => {
+                let (dev, inum) = idata.get_dev_inum();
+                FifoFile::open(self.inode().clone(), dev, inum, flags)
+                    .map(|f| Box::new(f) as Box<dyn FileOps>)
+            },
+        };
+        drop(idata);
+        LOG.end_op();
+        ret
+    }
+
+    fn lookup(&self, name: &[u8; MAX_DIR_SIZE]) -> Option<Box<dyn InodeOps>> {
+        let mut idata = self.inode().lock();
+        if idata.get_itype() != InodeType::Directory {
+            return None
+        }
+        let found = idata.dir_lookup(name, false);
+        drop(idata);
+        found.map(|(i, _)| Box::new(Xv6Inode(Some(i))) as Box<dyn InodeOps>)
+    }
+
+    /// 游标即目录文件内的字节偏移，每次前进一个磁盘目录项的大小。
+    fn read_dir(&self, mut pos: usize) -> Option<(VDirEntry, usize)> {
+        let de_size = mem::size_of::<DirEntry>();
+        let mut idata = self.inode().lock();
+        if idata.get_itype() != InodeType::Directory {
+            return None
+        }
+        let mut de = DirEntry::empty();
+        let de_ptr = Address::KernelMut(&mut de as *mut DirEntry as *mut u8);
+        loop {
+            match idata.try_iread(de_ptr, pos as u32, de_size as u32) {
+                Ok(n) if n as usize == de_size => {},
+                _ => return None,
+            }
+            pos += de_size;
+            if de.inum != 0 {
+                return Some((VDirEntry { inum: de.inum as u32, name: de.name }, pos))
+            }
+        }
+    }
+}
+
+/// 表示普通文件的内部状态结构，是 xv6 文件系统中普通文件与目录的 [`FileOps`] 实现。
+///
+/// 用于管理磁盘上的常规文件（regular file），包含当前文件的偏移位置和 inode 引用。
+/// 文件偏移用于顺序读写操作，inode 提供底层元数据与数据访问接口。
+/// 该结构承载对常规文件的状态管理职责。
+#[derive(Debug)]
+struct FileRegular {
+    /// 当前文件偏移量，表示下一次读写操作的起始位置。
+    ///
+    /// 该字段通过 `UnsafeCell` 提供内部可变性，
+    /// 实际使用中由 inode 上的锁（`idata`）保护，确保并发访问时的一致性与内存安全。
+    offset: UnsafeCell<u32>,
+
+    /// 指向该文件对应的 inode 对象，用于文件的元数据与数据访问。
+    ///
+    /// 使用 `Option<Inode>` 表示可释放性，在文件关闭（drop）时会被设置为 `None`。
+    inode: Option<Inode>,
+}
+
+unsafe impl Send for FileRegular {}
+unsafe impl Sync for FileRegular {}
+
+impl FileOps for FileRegular {
+    /// 锁住 inode，从当前偏移处调用 `try_iread` 读取并推进偏移量。
+    fn read(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        let mut idata = self.inode.as_ref().unwrap().lock();
+        let offset = unsafe { &mut *self.offset.get() };
+        match idata.try_iread(dst, *offset, count.try_into().unwrap()) {
+            Ok(read_count) => {
+                *offset += read_count;
+                drop(idata);
+                Ok(read_count)
+            },
+            Err(()) => Err(())
+        }
+    }
+
+    /// 将写入按批次进行分段处理，每批大小为 `((MAXOPBLOCKS-4)/2)*BSIZE` 字节，
+    /// 避免单次日志事务过大；每批都包裹在 `LOG.begin_op()` / `end_op()` 中。
+    fn write(&self, src: Address, count: u32) -> Result<u32, ()> {
+        let batch = ((MAXOPBLOCKS-4)/2*BSIZE) as u32;
+        let mut addr = src;
+        for i in (0..count).step_by(batch as usize) {
+            let write_count = min(batch, count - i);
+            LOG.begin_op();
+            let mut idata = self.inode.as_ref().unwrap().lock();
+            let offset = unsafe { &mut *self.offset.get() };
+            let ret = idata.try_iwrite(addr, *offset, write_count);
+            if let Ok(actual_count) = ret {
+                *offset += actual_count;
+            }
+            drop(idata);
+            LOG.end_op();
+
+            match ret {
+                Ok(actual_count) => {
+                    if actual_count != write_count {
+                        return Ok(i+actual_count)
+                    }
+                },
+                Err(()) => return Err(()),
+            }
+            addr = addr.offset(write_count as usize);
+        }
+        Ok(count)
+    }
+
+    fn stat(&self, stat: &mut FileStat) -> Result<(), ()> {
+        let idata = self.inode.as_ref().unwrap().lock();
+        idata.istat(stat);
+        Ok(())
+    }
+
+    fn kind(&self) -> &'static str {
+        "file"
+    }
+
+    /// 在日志事务中释放 inode 引用。
+    fn close(&mut self, _writable: bool) {
+        LOG.begin_op();
+        drop(self.inode.take());
+        LOG.end_op();
+    }
+}
+
+/// 表示设备文件的内部状态结构，是 xv6 文件系统中设备节点的 [`FileOps`] 实现。
+///
+/// 该结构用于管理字符设备文件，允许通过设备号与驱动接口进行读写操作。
+/// 设备文件并不实际存储数据，而是通过设备号与内核中注册的设备驱动进行通信。
+/// 该结构用于抽象和封装对设备节点的访问。
+#[derive(Debug)]
+struct FileDevice {
+    /// 主设备号（major device number），用于在设备表中索引对应的设备驱动。
+    ///
+    /// 在 xv6 中，每种设备（如控制台、磁盘等）都有唯一的主设备号，
+    /// 通过该编号调用统一的设备操作接口（如 `read` / `write`）。
+    major: u16,
+
+    /// 指向设备对应的 inode 对象。
+    ///
+    /// 尽管设备文件不依赖 inode 进行实际数据存储，但依然使用 inode 记录其元信息，
+    /// 并在文件关闭时通过 `Option::take()` 释放。
+    inode: Option<Inode>,
+}
+
+impl FileOps for FileDevice {
+    /// 在设备注册表中查找对应驱动并调用其 `read`。
+    fn read(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        driver::device(self.major as usize).ok_or(())?.read(dst, count)
+    }
+
+    /// 在设备注册表中查找对应驱动并调用其 `write`。
+    fn write(&self, src: Address, count: u32) -> Result<u32, ()> {
+        driver::device(self.major as usize).ok_or(())?.write(src, count)
+    }
+
+    fn read_nonblock(&self, dst: Address, count: u32) -> Result<u32, ()> {
+        driver::device(self.major as usize).ok_or(())?.read_nonblock(dst, count)
+    }
+
+    fn write_nonblock(&self, src: Address, count: u32) -> Result<u32, ()> {
+        driver::device(self.major as usize).ok_or(())?.write_nonblock(src, count)
+    }
+
+    fn stat(&self, stat: &mut FileStat) -> Result<(), ()> {
+        let idata = self.inode.as_ref().unwrap().lock();
+        idata.istat(stat);
+        Ok(())
+    }
+
+    /// 在设备注册表中查找对应驱动并调用其 `ioctl`。
+    fn ioctl(&self, request: usize, arg: usize) -> Result<usize, ()> {
+        driver::device(self.major as usize).ok_or(())?.ioctl(request, arg)
+    }
+
+    /// 在设备注册表中查找对应驱动并调用其 `poll`，设备未注册时报告错误。
+    fn poll(&self, waiter: PollWaiter) -> u16 {
+        driver::device(self.major as usize).map_or(POLLERR, |dev| dev.poll(waiter))
+    }
+
+    fn kind(&self) -> &'static str {
+        "device"
+    }
+
+    /// 在日志事务中释放 inode 引用。
+    fn close(&mut self, _writable: bool) {
+        LOG.begin_op();
+        drop(self.inode.take());
+        LOG.end_op();
+    }
+}
@@ -0,0 +1,97 @@
+//! 处理器间中断（IPI）与跨核调用
+//!
+//! 向某个 CPU 发送处理器间中断时写它在 CLINT 中的 `msip` 寄存器，
+//! 目标 CPU 的 `timervec` 清除 `msip` 并转为监督者模式软件中断，与时钟中断共用同一个入口，
+//! 陷阱处理程序调用 [`handle`] 执行发给它的跨核调用。
+//!
+//! 跨核调用 [`call`] 是同步的：调用者把调用放进每个目标 CPU 的信箱 [`MAILBOX`] 后发送中断，
+//! 然后等待所有目标执行完毕。信箱中每个发送者占一个槽位，调用者同时只有一个未完成的调用，
+//! 因此不需要锁。调用者等待时、以及自旋锁忙等待时都会处理发给自己的调用，
+//! 两个 CPU 互相调用或调用者持有目标正在等待的锁时不会死锁。
+
+use array_macro::array;
+
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::consts::NCPU;
+use crate::process::{CpuManager, push_off, pop_off};
+use crate::register::clint;
+
+/// `MAILBOX[target][sender]` 是 `sender` 发给 `target` 的调用 [`Call`] 的地址，0 表示没有。
+static MAILBOX: [[AtomicUsize; NCPU]; NCPU] = array![_ => array![_ => AtomicUsize::new(0); NCPU]; NCPU];
+
+/// 已经可以接收处理器间中断的 CPU 的位图。
+static ONLINE: AtomicUsize = AtomicUsize::new(0);
+
+/// 一次跨核调用，位于调用者的栈上，所有目标执行完毕之前调用者不会返回。
+struct Call<'a> {
+    func: &'a (dyn Fn() + Sync),
+    /// 尚未执行完毕的目标 CPU 数
+    pending: AtomicUsize,
+}
+
+/// 当前 CPU 已经安装了陷阱向量，可以接收处理器间中断。
+/// 尚未上线的 CPU 不会成为跨核调用的目标，它们上线时会自行刷新 TLB 等状态。
+pub fn set_online() {
+    push_off();
+    let id = unsafe { CpuManager::cpu_id() };
+    ONLINE.fetch_or(1 << id, Ordering::SeqCst);
+    pop_off();
+}
+
+/// 向 CPU `id` 发送处理器间中断，唤醒在 `wfi` 中空闲的 CPU 也使用它。
+pub fn send(id: usize) {
+    unsafe { clint::set_msip(id); }
+}
+
+/// # 功能说明
+/// 在位图 `harts` 中的每个 CPU 上执行 `func`，等待全部执行完毕后返回。
+/// 当前 CPU 在位图中时直接执行，尚未上线的 CPU 被忽略。
+///
+/// # 参数
+/// - `harts`：目标 CPU 的位图，第 n 位对应 n 号 CPU；
+/// - `func`：要执行的函数，在目标 CPU 的中断上下文中执行。
+///
+/// # 注意
+/// `func` 中不得获取锁、睡眠或再发起跨核调用。
+pub fn call(harts: usize, func: &(dyn Fn() + Sync)) {
+    push_off();
+    let me = unsafe { CpuManager::cpu_id() };
+    if harts & (1 << me) != 0 {
+        func();
+    }
+
+    let targets = harts & ONLINE.load(Ordering::SeqCst) & !(1 << me);
+    if targets != 0 {
+        let call = Call {
+            func,
+            pending: AtomicUsize::new(targets.count_ones() as usize),
+        };
+        for id in (0..NCPU).filter(|&id| targets & (1 << id) != 0) {
+            MAILBOX[id][me].store(&call as *const Call<'_> as usize, Ordering::Release);
+            send(id);
+        }
+        while call.pending.load(Ordering::Acquire) != 0 {
+            handle();
+            spin_loop();
+        }
+    }
+    pop_off();
+}
+
+/// 执行其他 CPU 发给当前 CPU 的跨核调用。
+/// 由监督者模式软件中断的处理程序以及忙等待的代码调用，调用时中断必须关闭。
+pub fn handle() {
+    let me = unsafe { CpuManager::cpu_id() };
+    for slot in MAILBOX[me].iter() {
+        let addr = slot.swap(0, Ordering::Acquire);
+        if addr == 0 {
+            continue
+        }
+        // 调用者在 pending 归零前不会返回，之后不再访问它
+        let call = unsafe { &*(addr as *const Call<'_>) };
+        (call.func)();
+        call.pending.fetch_sub(1, Ordering::Release);
+    }
+}
@@ -0,0 +1,254 @@
+//! 内核日志
+//!
+//! 带级别的内核日志写入内存中的环形缓冲区 [`KLOG`]，不会因为滚出终端而丢失，
+//! 用户程序通过 `syslog` 系统调用（`dmesg` 命令）读回。
+//! 级别不高于控制台级别的日志同时输出到控制台，控制台级别可以在运行时调整。
+//!
+//! 每条日志在缓冲区中占一行，格式为 `<级别>[秒.微秒] 内容`，
+//! 时间取自 CLINT 的 `mtime`，因此在中断处理程序中记录日志也不会获取任何锁。
+
+use core::cmp::min;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::consts::{KLOG_BUF, MTIME_FREQ};
+use crate::mm::Address;
+use crate::register::clint;
+use crate::spinlock::SpinLock;
+
+/// 日志级别，数值越小越重要。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+    Trace = 4,
+}
+
+/// 日志环形缓冲区。
+static KLOG: SpinLock<LogRing> = SpinLock::new(LogRing::new(), "klog");
+
+/// 控制台级别：级别数值不大于它的日志会同时输出到控制台。
+static CONSOLE_LEVEL: AtomicUsize = AtomicUsize::new(Level::Info as usize);
+
+/// 单条日志的最大长度，超出部分被截断。
+const LINE_MAX: usize = 256;
+
+/// 记录一条日志（被宏调用）
+///
+/// # 功能说明
+/// 先在栈上格式化整条日志，再在持有缓冲区锁时复制进去：
+/// 格式化参数时可能获取其他锁，不能在持有日志锁时进行。
+pub fn _log(level: Level, args: fmt::Arguments<'_>) {
+    let mut line = LineBuf::new();
+    let t = unsafe { clint::read_mtime() };
+    let _ = write!(line, "<{}>[{:5}.{:06}] ", level as usize,
+        t / MTIME_FREQ, t % MTIME_FREQ * 1_000_000 / MTIME_FREQ);
+    let prefix = line.len;
+    let _ = line.write_fmt(args);
+    line.finish();
+
+    KLOG.lock().write(line.as_bytes());
+
+    if level as usize <= CONSOLE_LEVEL.load(Ordering::Relaxed) {
+        crate::printf::_print(format_args!("{}", line.as_str(prefix)));
+    }
+}
+
+/// 设置控制台级别，`level` 超出范围时返回错误。
+pub fn set_console_level(level: usize) -> Result<(), ()> {
+    if level > Level::Trace as usize {
+        return Err(())
+    }
+    CONSOLE_LEVEL.store(level, Ordering::Relaxed);
+    Ok(())
+}
+
+/// 清空日志缓冲区。
+pub fn clear() {
+    let mut ring = KLOG.lock();
+    ring.start = ring.end;
+}
+
+/// 将缓冲区中的日志复制到 `dst`
+///
+/// # 功能说明
+/// 缓冲区中的内容多于 `count` 字节时只复制最新的部分（从某一行的开头开始）。
+/// 复制分块进行，复制到用户空间时不持有日志锁，期间新写入的日志不会被复制；
+/// 尚未复制的部分被新日志覆盖时跳过被覆盖的部分，全部被覆盖时只返回已经复制的字节。
+///
+/// # 返回值
+/// - `Ok(n)`：实际复制的字节数；
+/// - `Err(())`：目标地址无效。
+pub fn read(mut dst: Address, count: usize) -> Result<usize, ()> {
+    let mut chunk = [0u8; 128];
+    let (mut pos, end) = {
+        let ring = KLOG.lock();
+        (ring.line_start(ring.end - min(count, ring.end - ring.start)), ring.end)
+    };
+
+    let mut copied = 0;
+    while pos < end {
+        let n = {
+            let ring = KLOG.lock();
+            // 复制期间写入的日志超过缓冲区大小时，`end` 之前尚未复制的部分已全部被覆盖
+            if ring.start >= end {
+                break
+            }
+            // 尚未复制的部分已被部分覆盖，从最旧的完整行继续
+            if pos < ring.start {
+                pos = ring.start;
+            }
+            let n = min(chunk.len(), end.saturating_sub(pos));
+            for i in 0..n {
+                chunk[i] = ring.buf[(pos + i) % KLOG_BUF];
+            }
+            n
+        };
+        if n == 0 {
+            break
+        }
+        dst.copy_out(chunk.as_ptr(), n)?;
+        dst = dst.offset(n);
+        pos += n;
+        copied += n;
+    }
+    Ok(copied)
+}
+
+/// 日志环形缓冲区，`[start, end)` 是有效内容的逻辑位置，总是从某一行的开头开始。
+struct LogRing {
+    buf: [u8; KLOG_BUF],
+    start: usize,
+    end: usize,
+}
+
+impl LogRing {
+    const fn new() -> Self {
+        Self {
+            buf: [0; KLOG_BUF],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// 写入一条完整的日志，空间不足时丢弃最旧的若干行。
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.buf[self.end % KLOG_BUF] = b;
+            self.end += 1;
+        }
+        if self.end - self.start > KLOG_BUF {
+            self.start = self.line_start(self.end - KLOG_BUF);
+        }
+    }
+
+    /// 返回位置 `pos` 处或之后的第一个行首。
+    fn line_start(&self, mut pos: usize) -> usize {
+        if pos <= self.start {
+            return self.start
+        }
+        while pos < self.end && self.buf[(pos - 1) % KLOG_BUF] != b'\n' {
+            pos += 1;
+        }
+        pos
+    }
+}
+
+/// 栈上的单行格式化缓冲区，超长的内容按字符边界截断。
+struct LineBuf {
+    buf: [u8; LINE_MAX],
+    len: usize,
+}
+
+impl LineBuf {
+    fn new() -> Self {
+        Self {
+            buf: [0; LINE_MAX],
+            len: 0,
+        }
+    }
+
+    /// 以换行符结束这一行。
+    fn finish(&mut self) {
+        if self.len == LINE_MAX {
+            self.len -= 1;
+            while !self.as_bytes().is_empty() && core::str::from_utf8(self.as_bytes()).is_err() {
+                self.len -= 1;
+            }
+        }
+        self.buf[self.len] = b'\n';
+        self.len += 1;
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// 从第 `from` 个字节开始的内容。
+    fn as_str(&self, from: usize) -> &str {
+        core::str::from_utf8(&self.buf[from..self.len]).unwrap_or("")
+    }
+}
+
+impl Write for LineBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut n = min(s.len(), LINE_MAX - self.len);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len..self.len+n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// 以指定级别记录一条内核日志
+#[macro_export]
+macro_rules! klog {
+    ($level:expr, $($arg:tt)*) => {
+        $crate::klog::_log($level, format_args!($($arg)*))
+    };
+}
+
+/// 记录一条错误级别的内核日志
+#[macro_export]
+macro_rules! kerror {
+    ($($arg:tt)*) => { $crate::klog!($crate::klog::Level::Error, $($arg)*) };
+}
+
+/// 记录一条警告级别的内核日志
+#[macro_export]
+macro_rules! kwarn {
+    ($($arg:tt)*) => { $crate::klog!($crate::klog::Level::Warn, $($arg)*) };
+}
+
+/// 与 `kwarn!` 相同，启用 `kernel_warning` 特性时随后记录当前的调用栈。
+/// 只用于内核自身出错的地方，用户程序能够触发的警告（例如系统调用参数无效）使用 `kwarn!`。
+#[macro_export]
+macro_rules! kwarn_bt {
+    ($($arg:tt)*) => {{
+        $crate::kwarn!($($arg)*);
+        #[cfg(feature = "kernel_warning")]
+        $crate::backtrace::log($crate::klog::Level::Warn);
+    }};
+}
+
+/// 记录一条信息级别的内核日志
+#[macro_export]
+macro_rules! kinfo {
+    ($($arg:tt)*) => { $crate::klog!($crate::klog::Level::Info, $($arg)*) };
+}
+
+/// 记录一条调试级别的内核日志
+#[macro_export]
+macro_rules! kdebug {
+    ($($arg:tt)*) => { $crate::klog!($crate::klog::Level::Debug, $($arg)*) };
+}
+
+/// 记录一条跟踪级别的内核日志
+#[macro_export]
+macro_rules! ktrace {
+    ($($arg:tt)*) => { $crate::klog!($crate::klog::Level::Trace, $($arg)*) };
+}
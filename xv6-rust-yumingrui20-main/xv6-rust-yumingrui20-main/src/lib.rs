@@ -2,7 +2,6 @@
 
 #![no_std]
 #![feature(slice_ptr_get)]
-#![feature(get_mut_unchecked)]
 #![feature(allocator_api)]
 #![feature(alloc_error_handler)]
 #![allow(dead_code)]
@@ -23,6 +22,8 @@ global_asm!(include_str!("asm/trampoline.S"));
 
 #[macro_use]
 mod printf;
+#[macro_use]
+mod klog;
 
 mod consts;
 mod fs;
@@ -34,6 +35,12 @@ mod spinlock;
 mod sleeplock;
 mod start;
 mod trap;
+mod timer;
+mod ipi;
+mod profile;
+mod backtrace;
+#[cfg(feature = "lockdep")]
+mod lockdep;
 mod driver;
 mod plic;
 
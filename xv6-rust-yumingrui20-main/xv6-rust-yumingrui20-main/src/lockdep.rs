@@ -0,0 +1,296 @@
+//! 锁依赖检查（lockdep）
+//!
+//! 启用 `lockdep` 特性时编译，检查自旋锁与睡眠锁的获取顺序。名称相同的锁属于同一个锁类，
+//! 获取一个锁时，为当前已经持有的每个锁类记录一条指向新锁类的依赖边。
+//! 新加入的边使依赖图出现环时，说明另有执行路径以相反的顺序获取了这些锁，可能发生死锁，此时打印环上的锁链。
+//! 另外检查持有自旋锁时睡眠（获取睡眠锁或调用 `sleep`）。
+//!
+//! 自旋锁持有期间中断关闭、不会换到其他 CPU，按 CPU 记录；睡眠锁可以跨越睡眠持有，按进程记录。
+//! 同一锁类内部的嵌套（例如同时持有两个进程的锁）不检查。
+//!
+//! 本模块不能使用自旋锁，依赖图由一个单独的原子标志保护。
+//! 第一次报告问题后停止检查，报告时打印输出所需的锁不会再次触发检查。
+
+use core::hint::spin_loop;
+use core::ptr::addr_of;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::consts::{NCPU, NPROC};
+use crate::process::{CpuManager, CPU_MANAGER, push_off, pop_off};
+
+/// 锁类数量上限，依赖边用 `u64` 位图表示
+const NCLASS: usize = 64;
+
+/// 每个 CPU（或进程）同时持有的锁数量上限
+const NHELD: usize = 16;
+
+/// 锁的种类
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    Spin,
+    Sleep,
+}
+
+/// 锁类与依赖边
+struct Graph {
+    names: [&'static str; NCLASS],
+    nclass: usize,
+    /// `edges[a]` 的第 b 位表示曾在持有锁类 a 时获取锁类 b
+    edges: [u64; NCLASS],
+}
+
+#[derive(Clone, Copy)]
+struct Held {
+    class: usize,
+    addr: usize,
+}
+
+/// 一个 CPU 持有的自旋锁或一个进程持有的睡眠锁
+struct HeldStack {
+    locks: [Held; NHELD],
+    len: usize,
+}
+
+/// 需要报告的问题
+enum Report {
+    /// 获取 `chain[0]` 时持有 `chain[len-1]`，而依赖图中已有沿 `chain` 的路径
+    Cycle { chain: [u8; NCLASS], len: usize },
+    /// 持有 `held` 中的自旋锁时睡眠
+    Sleep { held: [u8; NHELD], len: usize },
+    /// 锁类或持有的锁超过上限
+    Full,
+}
+
+const EMPTY: HeldStack = HeldStack {
+    locks: [Held { class: 0, addr: 0 }; NHELD],
+    len: 0,
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static GRAPH_LOCK: AtomicBool = AtomicBool::new(false);
+static mut GRAPH: Graph = Graph {
+    names: [""; NCLASS],
+    nclass: 0,
+    edges: [0; NCLASS],
+};
+static mut CPU_HELD: [HeldStack; NCPU] = [EMPTY; NCPU];
+static mut PROC_HELD: [HeldStack; NPROC] = [EMPTY; NPROC];
+
+/// # 功能说明
+/// 在获取锁之前调用（忙等待或睡眠等待之前，真的发生死锁时也能先看到报告），
+/// 记录已持有的锁到该锁的依赖边并检查是否成环。获取睡眠锁时还检查是否持有自旋锁。
+///
+/// # 参数
+/// - `name`：锁的名称，即锁类；
+/// - `addr`：锁的地址，释放时用于查找；
+/// - `kind`：锁的种类。
+pub fn acquire(name: &'static str, addr: usize, kind: LockKind) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return
+    }
+    push_off();
+    let cpu = unsafe { CpuManager::cpu_id() };
+    let ret = unsafe { record(cpu, name, addr, kind) };
+    pop_off();
+    if let Err(report) = ret {
+        report.print(cpu);
+    }
+}
+
+/// 释放地址为 `addr` 的锁之前调用。
+pub fn release(addr: usize, kind: LockKind) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return
+    }
+    push_off();
+    unsafe {
+        match kind {
+            LockKind::Spin => CPU_HELD[CpuManager::cpu_id()].remove(addr),
+            LockKind::Sleep => if let Some(held) = proc_held() {
+                held.remove(addr);
+            },
+        }
+    }
+    pop_off();
+}
+
+/// 当前进程即将睡眠，此时当前 CPU 除了 `allowed` 个自旋锁（如进程自身的锁）之外不应持有其他自旋锁。
+pub fn might_sleep(allowed: usize) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return
+    }
+    push_off();
+    let cpu = unsafe { CpuManager::cpu_id() };
+    let ret = unsafe { CPU_HELD[cpu].check_sleep(allowed) };
+    pop_off();
+    if let Err(report) = ret {
+        report.print(cpu);
+    }
+}
+
+/// 当前进程持有的睡眠锁，没有进程运行时返回 `None`。
+unsafe fn proc_held() -> Option<&'static mut HeldStack> {
+    let p = CPU_MANAGER.my_proc_ptr();
+    if p.is_null() {
+        None
+    } else {
+        Some(&mut PROC_HELD[(*p).index()])
+    }
+}
+
+/// 记录 CPU `cpu` 的一次获取，调用时中断必须关闭。
+unsafe fn record(cpu: usize, name: &'static str, addr: usize, kind: LockKind) -> Result<(), Report> {
+    let cpu_held = &mut CPU_HELD[cpu];
+    let mut proc_held = proc_held();
+    if kind == LockKind::Sleep {
+        cpu_held.check_sleep(0)?;
+    }
+
+    while GRAPH_LOCK.compare_exchange(false, true,
+        Ordering::Acquire, Ordering::Relaxed).is_err() {
+        spin_loop();
+    }
+    let graph = &mut GRAPH;
+    let ret = graph.class(name).and_then(|class| {
+        graph.add_edges(class, cpu_held)?;
+        if let Some(held) = proc_held.as_deref() {
+            graph.add_edges(class, held)?;
+        }
+        Ok(class)
+    });
+    GRAPH_LOCK.store(false, Ordering::Release);
+
+    let class = ret?;
+    match (kind, proc_held.as_deref_mut()) {
+        (LockKind::Spin, _) => cpu_held.push(class, addr),
+        (LockKind::Sleep, Some(held)) => held.push(class, addr),
+        (LockKind::Sleep, None) => Ok(()),
+    }
+}
+
+impl Graph {
+    /// 返回名称为 `name` 的锁类，第一次出现时新建。
+    fn class(&mut self, name: &'static str) -> Result<usize, Report> {
+        if let Some(class) = self.names[..self.nclass].iter().position(|&n| n == name) {
+            return Ok(class)
+        }
+        if self.nclass == NCLASS {
+            return Err(Report::Full)
+        }
+        self.names[self.nclass] = name;
+        self.nclass += 1;
+        Ok(self.nclass - 1)
+    }
+
+    /// 记录 `held` 中每个锁类到锁类 `to` 的依赖边，新边使依赖图成环时返回环上的锁链。
+    fn add_edges(&mut self, to: usize, held: &HeldStack) -> Result<(), Report> {
+        for from in held.locks[..held.len].iter().map(|h| h.class) {
+            if from == to || self.edges[from] & (1 << to) != 0 {
+                continue
+            }
+            let mut chain = [0u8; NCLASS];
+            if let Some(len) = self.path(to, from, &mut chain) {
+                return Err(Report::Cycle { chain, len })
+            }
+            self.edges[from] |= 1 << to;
+        }
+        Ok(())
+    }
+
+    /// 广度优先查找从锁类 `from` 到 `to` 的路径，找到时把路径写入 `chain` 并返回其长度。
+    fn path(&self, from: usize, to: usize, chain: &mut [u8; NCLASS]) -> Option<usize> {
+        let mut prev = [u8::MAX; NCLASS];
+        let mut queue = [0u8; NCLASS];
+        let (mut head, mut tail) = (0, 1);
+        queue[0] = from as u8;
+        prev[from] = from as u8;
+        while head < tail {
+            let a = queue[head] as usize;
+            head += 1;
+            if a == to {
+                let (mut len, mut c) = (0, to);
+                loop {
+                    chain[len] = c as u8;
+                    len += 1;
+                    if c == from {
+                        break
+                    }
+                    c = prev[c] as usize;
+                }
+                chain[..len].reverse();
+                return Some(len)
+            }
+            for b in (0..self.nclass).filter(|&b| self.edges[a] & (1 << b) != 0) {
+                if prev[b] == u8::MAX {
+                    prev[b] = a as u8;
+                    queue[tail] = b as u8;
+                    tail += 1;
+                }
+            }
+        }
+        None
+    }
+}
+
+impl HeldStack {
+    fn push(&mut self, class: usize, addr: usize) -> Result<(), Report> {
+        if self.len == NHELD {
+            return Err(Report::Full)
+        }
+        self.locks[self.len] = Held { class, addr };
+        self.len += 1;
+        Ok(())
+    }
+
+    /// 锁不一定按获取的相反顺序释放，从栈顶开始查找。
+    fn remove(&mut self, addr: usize) {
+        if let Some(i) = (0..self.len).rev().find(|&i| self.locks[i].addr == addr) {
+            self.locks.copy_within(i+1..self.len, i);
+            self.len -= 1;
+        }
+    }
+
+    fn check_sleep(&self, allowed: usize) -> Result<(), Report> {
+        if self.len <= allowed {
+            return Ok(())
+        }
+        let mut held = [0u8; NHELD];
+        for (i, h) in self.locks[..self.len].iter().enumerate() {
+            held[i] = h.class as u8;
+        }
+        Err(Report::Sleep { held, len: self.len })
+    }
+}
+
+impl Report {
+    /// 打印 CPU `cpu` 上发现的问题并停止检查，只有第一次报告会被打印。
+    fn print(&self, cpu: usize) {
+        if !ENABLED.swap(false, Ordering::Relaxed) {
+            return
+        }
+        // 停止检查后锁类不再增加，名称可以直接读取
+        let names = unsafe { &*addr_of!(GRAPH.names) };
+        match self {
+            Report::Cycle { chain, len } => {
+                println!("lockdep: cpu {} acquiring \"{}\" while holding \"{}\" may deadlock",
+                    cpu, names[chain[0] as usize], names[chain[len-1] as usize]);
+                print!("lockdep: existing dependency chain:");
+                for (i, &class) in chain[..*len].iter().enumerate() {
+                    print!("{} \"{}\"", if i == 0 { "" } else { " ->" }, names[class as usize]);
+                }
+                println!();
+            }
+            Report::Sleep { held, len } => {
+                print!("lockdep: cpu {} sleeping while holding spinlock", cpu);
+                for &class in held[..*len].iter() {
+                    print!(" \"{}\"", names[class as usize]);
+                }
+                println!();
+            }
+            Report::Full => {
+                println!("lockdep: too many lock classes or held locks");
+            }
+        }
+        println!("lockdep: checking disabled");
+    }
+}
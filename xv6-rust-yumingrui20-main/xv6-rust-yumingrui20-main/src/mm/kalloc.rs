@@ -108,6 +108,52 @@ impl KernelHeap {
     unsafe fn init(&self, start: usize, end: usize) {
         self.0.lock().init(start, end);
     }
+
+    /// 获取内核堆的统计信息快照。
+    ///
+    /// 统计期间持有堆锁，因此本函数自身不会进行任何堆分配，
+    /// 结果以定长数组的形式返回。
+    pub fn stat(&self) -> HeapStat {
+        let mut buddy = self.0.lock();
+        let mut stat = HeapStat {
+            base: buddy.base,
+            end: buddy.actual_end,
+            nsizes: cmp::min(buddy.nsizes, HEAP_STAT_SIZES),
+            free_blocks: [0; HEAP_STAT_SIZES],
+        };
+        for k in 0..stat.nsizes {
+            stat.free_blocks[k] = unsafe { buddy.get_info_mut(k).free.len() };
+        }
+        stat
+    }
+}
+
+/// [`HeapStat`] 最多记录的块大小等级数量。
+pub const HEAP_STAT_SIZES: usize = 32;
+
+/// 内核堆的统计信息，由 [`KernelHeap::stat`] 产生。
+pub struct HeapStat {
+    /// 伙伴系统管理的起始物理地址。
+    pub base: usize,
+    /// 伙伴系统管理的结束物理地址（不含）。
+    pub end: usize,
+    /// 块大小等级数量，等级 `k` 的块大小为 `2^k * LEAF_SIZE`。
+    pub nsizes: usize,
+    /// 各等级空闲链表中的块数量。
+    pub free_blocks: [usize; HEAP_STAT_SIZES],
+}
+
+impl HeapStat {
+    /// 等级 `k` 的块大小（字节）。
+    #[inline]
+    pub fn blk_size(&self, k: usize) -> usize {
+        blk_size(k)
+    }
+
+    /// 空闲内存的总字节数。
+    pub fn free_bytes(&self) -> usize {
+        (0..self.nsizes).map(|k| self.free_blocks[k] * blk_size(k)).sum()
+    }
 }
 
 /// 实现 `GlobalAlloc` 接口以支持全局堆分配。
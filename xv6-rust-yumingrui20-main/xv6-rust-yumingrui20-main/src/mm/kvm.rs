@@ -140,7 +140,7 @@ pub unsafe fn kvm_init() {
 /// 该函数负责调用底层页表映射方法，添加连续页的映射关系。
 pub unsafe fn kvm_map(va: VirtAddr, pa: PhysAddr, size: usize, perm: PteFlag) {
     #[cfg(feature = "verbose_init_info")]
-    println!(
+    kinfo!(
         "kvm_map: va={:#x}, pa={:#x}, size={:#x}",
         va.as_usize(),
         pa.as_usize(),
@@ -185,3 +185,15 @@ pub unsafe fn kvm_pa(va: VirtAddr) -> u64 {
         }
     }
 }
+
+/// 虚拟地址 `va` 在内核页表中是否已经映射，用于读取可能无效的地址（如栈回溯中的帧指针）之前检查。
+/// 分页尚未开启时，内核所用物理内存范围内的地址都可以访问。
+pub fn kvm_mapped(va: usize) -> bool {
+    if satp::read() == 0 {
+        return va >= usize::from(KERNBASE) && va < usize::from(PHYSTOP)
+    }
+    match VirtAddr::try_from(va) {
+        Ok(va) => unsafe { KERNEL_PAGE_TABLE.walk(va) }.map_or(false, |pte| pte.is_valid()),
+        Err(_) => false,
+    }
+}
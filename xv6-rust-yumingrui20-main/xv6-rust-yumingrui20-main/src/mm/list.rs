@@ -99,4 +99,15 @@ impl List {
     pub fn is_empty(&self) -> bool {
         ptr::eq(self.next, self)
     }
+
+    /// 统计链表中有效节点的数量（不含头节点），需要遍历整个链表。
+    pub fn len(&self) -> usize {
+        let mut count = 0;
+        let mut cur = self.next as *const List;
+        while !ptr::eq(cur, self) {
+            count += 1;
+            cur = unsafe { (*cur).next };
+        }
+        count
+    }
 }
@@ -7,14 +7,17 @@ use crate::consts::PGSIZE;
 use crate::process::CPU_MANAGER;
 
 pub use addr::{Addr, PhysAddr, VirtAddr};
-pub use kvm::{kvm_init, kvm_init_hart, kvm_map, kvm_pa};
+pub use kvm::{kvm_init, kvm_init_hart, kvm_map, kvm_pa, kvm_mapped};
 pub use pagetable::{PageTable, PteFlag};
 pub use kalloc::{KernelHeap, KERNEL_HEAP};
+pub use shm::{ShmSegment, SHM};
 
 mod addr;
 pub mod kalloc;
 mod kvm;
 mod pagetable;
+pub mod shm;
+pub mod tlb;
 mod list;
 
 /// 定义物理页帧分配接口，用于分配页大小对齐的内存块。
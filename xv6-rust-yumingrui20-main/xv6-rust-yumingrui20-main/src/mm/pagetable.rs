@@ -6,8 +6,11 @@ use alloc::boxed::Box;
 use core::{cmp::min, convert::TryFrom};
 use core::ptr;
 
-use crate::consts::{PGSHIFT, PGSIZE, SATP_SV39, SV39FLAGLEN, USERTEXT, TRAMPOLINE, TRAPFRAME};
-use super::{Addr, PhysAddr, RawPage, RawSinglePage, VirtAddr, pg_round_up};
+use crate::consts::{PGSHIFT, PGSIZE, SATP_SV39, SV39FLAGLEN, USERTEXT, TRAMPOLINE, TRAPFRAME, NTHREAD};
+use super::{Addr, PhysAddr, RawPage, RawSinglePage, VirtAddr, pg_round_up, tlb};
+
+/// [`PageTable::uvm_unmap`] 每批解除映射的页数
+const UNMAP_BATCH: usize = 32;
 
 bitflags! {
     /// 内存页表项权限标志（Page Table Entry Flags）
@@ -563,7 +566,7 @@ impl PageTable {
                     ) {
                         Err(s) => {
                             #[cfg(feature = "kernel_warning")]
-                            println!("kernel warning: uvm_alloc occurs {}", s);
+                            kwarn_bt!("kernel warning: uvm_alloc occurs {}", s);
                             unsafe { RawSinglePage::from_raw_and_drop(mem); }
                             self.uvm_dealloc(cur_size, old_size);
                             return Err(())
@@ -644,20 +647,33 @@ impl PageTable {
             panic!("va not page aligned");
         }
 
-        for ca in (va..(va+PGSIZE*count)).step_by(PGSIZE) {
-            let pte = self.walk_mut(unsafe {VirtAddr::from_raw(ca)})
-                                        .expect("unable to find va available");
-            if !pte.is_valid() {
-                panic!("this pte is not valid");
-            }
-            if !pte.is_leaf() {
-                panic!("this pte is not a leaf");
+        // 分批解除映射，其他CPU的TLB刷新之后才释放这一批物理页
+        let satp = self.as_satp();
+        let mut start = va;
+        while start < va + PGSIZE * count {
+            let end = min(start + PGSIZE * UNMAP_BATCH, va + PGSIZE * count);
+            let mut freed = [0usize; UNMAP_BATCH];
+            for (i, ca) in (start..end).step_by(PGSIZE).enumerate() {
+                let pte = self.walk_mut(unsafe {VirtAddr::from_raw(ca)})
+                                            .expect("unable to find va available");
+                if !pte.is_valid() {
+                    panic!("this pte is not valid");
+                }
+                if !pte.is_leaf() {
+                    panic!("this pte is not a leaf");
+                }
+                freed[i] = pte.as_phys_addr().into_raw();
+                pte.write_zero();
             }
+
+            let pages = (end - start) / PGSIZE;
+            tlb::shootdown(satp, start, pages);
             if freeing {
-                let pa = pte.as_phys_addr();
-                unsafe { RawSinglePage::from_raw_and_drop(pa.into_raw() as *mut u8); }
+                for &pa in freed[..pages].iter() {
+                    unsafe { RawSinglePage::from_raw_and_drop(pa as *mut u8); }
+                }
             }
-            pte.write_zero();
+            start = end;
         }
     }
 
@@ -683,6 +699,7 @@ impl PageTable {
         let pte = self.walk_mut(VirtAddr::try_from(va).unwrap())
                                                 .expect("cannot find available pte");
         pte.clear_user();
+        tlb::shootdown(self.as_satp(), va, 1);
     }
 
     /// # 功能说明
@@ -829,7 +846,7 @@ impl PageTable {
                 Ok(phys_addr) => pa = phys_addr,
                 Err(s) => {
                     #[cfg(feature = "kernel_warning")]
-                    println!("kernel warning: {} when pagetable copy_out", s);
+                    kwarn!("kernel warning: {} when pagetable copy_out", s);
                     return Err(())
                 }
             }
@@ -885,7 +902,7 @@ impl PageTable {
                 Ok(_) => return Ok(()),
                 Err(s) => {
                     #[cfg(feature = "kernel_warning")]
-                    println!("kernel warning: {} when pagetable copy_in", s);
+                    kwarn!("kernel warning: {} when pagetable copy_in", s);
                     return Err(())
                 }
             }
@@ -897,7 +914,7 @@ impl PageTable {
                 Ok(phys_addr) => pa = phys_addr,
                 Err(s) => {
                     #[cfg(feature = "kernel_warning")]
-                    println!("kernel warning: {} when pagetable copy_in", s);
+                    kwarn!("kernel warning: {} when pagetable copy_in", s);
                     return Err(())
                 }
             }
@@ -917,6 +934,42 @@ impl PageTable {
             debug_assert_eq!(src, va.as_usize());
         }
     }
+
+    /// # 功能说明
+    /// 遍历用户地址空间 `[0, size)` 以及位于地址空间顶端的各线程 trapframe 页与 trampoline 页，
+    /// 把虚拟地址连续、权限相同的已映射页合并为一个区间，依次以 `(起始地址, 结束地址, 权限)` 调用 `f`。
+    /// 主要用于 /proc 中进程内存布局的展示。
+    ///
+    /// # 参数
+    /// - `size`：用户地址空间大小（字节）；
+    /// - `f`：区间回调，结束地址不包含在区间内。
+    ///
+    /// # 安全性
+    /// - 只读遍历页表，调用者需保证遍历期间页表不会被释放或修改。
+    pub fn for_each_region(&self, size: usize, mut f: impl FnMut(usize, usize, PteFlag)) {
+        let mut region: Option<(usize, usize, PteFlag)> = None;
+        let pages = (0..pg_round_up(size)).step_by(PGSIZE)
+            .chain((usize::from(TRAPFRAME) - (NTHREAD-1)*PGSIZE..=usize::from(TRAMPOLINE)).step_by(PGSIZE));
+        for va in pages {
+            let perm = VirtAddr::try_from(va).ok()
+                .and_then(|v| self.walk(v))
+                .filter(|pte| pte.is_valid())
+                .map(|pte| pte.read_perm() & !(PteFlag::V | PteFlag::A | PteFlag::D));
+            region = match (region, perm) {
+                (Some((start, end, flag)), Some(perm)) if end == va && flag == perm =>
+                    Some((start, va + PGSIZE, flag)),
+                (old, perm) => {
+                    if let Some((start, end, flag)) = old {
+                        f(start, end, flag);
+                    }
+                    perm.map(|perm| (va, va + PGSIZE, perm))
+                }
+            };
+        }
+        if let Some((start, end, flag)) = region {
+            f(start, end, flag);
+        }
+    }
 }
 
 impl Drop for PageTable {
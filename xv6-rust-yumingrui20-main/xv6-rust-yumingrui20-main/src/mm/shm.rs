@@ -0,0 +1,167 @@
+//! 共享内存段
+//!
+//! 共享内存段是内核持有的一组物理页，以引用计数管理：
+//! 段表与每个映射了该段的地址空间各持有一个引用，
+//! 段被删除（`IPC_RMID`）且最后一个映射被解除后，物理页才被释放。
+//!
+//! 用户通过 `shmget` 按键值查找或创建段，`shmat` 把段映射到 `SHMBASE` 之上的共享内存区域，
+//! `shmdt` 解除映射。fork 时子进程继承所有映射，exec 与 exit 释放地址空间时解除映射。
+
+use array_macro::array;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::consts::{NSHM, PGSIZE, SHMMAX, IPC_PRIVATE, IPC_CREAT, IPC_EXCL};
+use crate::spinlock::SpinLock;
+
+use super::{pg_round_up, PageTable, PhysAddr, PteFlag, RawPage, RawSinglePage, VirtAddr};
+
+/// 全局共享内存段表。
+///
+/// 段号为 `seq * NSHM + slot`，`slot` 是段在表中的下标，`seq` 是创建时的序号。
+/// 段被删除后下标会被新段复用，序号使新段的段号与旧段号不同，持有旧段号的进程不会映射到无关的新段。
+pub static SHM: SpinLock<ShmTable> = SpinLock::new(ShmTable::new(), "shm");
+
+/// 存在的段数，包括已删除但仍被映射的段
+static SEGMENTS: AtomicUsize = AtomicUsize::new(0);
+/// 所有段占用的物理页数
+static PAGES: AtomicUsize = AtomicUsize::new(0);
+
+/// 一个共享内存段，最后一个引用被丢弃时释放其物理页。
+pub struct ShmSegment {
+    id: usize,
+    pages: Vec<usize>,
+}
+
+impl ShmSegment {
+    /// 分配 `size` 字节（向上取整到页）的已清零物理页，内存不足时返回 `None`。
+    fn new(id: usize, size: usize) -> Option<Self> {
+        let npages = pg_round_up(size) / PGSIZE;
+        let mut seg = Self { id, pages: Vec::new() };
+        SEGMENTS.fetch_add(1, Ordering::Relaxed);
+        seg.pages.try_reserve_exact(npages).ok()?;
+        for _ in 0..npages {
+            let page = unsafe { RawSinglePage::try_new_zeroed().ok()? };
+            seg.pages.push(page as usize);
+            PAGES.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(seg)
+    }
+
+    /// 段号
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// 段的大小（字节数）
+    pub fn size(&self) -> usize {
+        self.pages.len() * PGSIZE
+    }
+
+    /// 将段的所有页以用户可读写的权限映射到页表 `pgt` 中从 `va` 开始的地址，
+    /// 失败时撤销已建立的映射。
+    pub fn map(&self, pgt: &mut PageTable, va: usize) -> Result<(), ()> {
+        for (i, &pa) in self.pages.iter().enumerate() {
+            let mapped = pgt.map_pages(
+                VirtAddr::try_from(va + i*PGSIZE).unwrap(),
+                PGSIZE,
+                PhysAddr::try_from(pa).unwrap(),
+                PteFlag::R | PteFlag::W | PteFlag::U,
+            );
+            if mapped.is_err() {
+                pgt.uvm_unmap(va, i, false);
+                return Err(())
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ShmSegment {
+    fn drop(&mut self) {
+        for &pa in self.pages.iter() {
+            unsafe { RawSinglePage::from_raw_and_drop(pa as *mut u8); }
+        }
+        PAGES.fetch_sub(self.pages.len(), Ordering::Relaxed);
+        SEGMENTS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 共享内存段表
+pub struct ShmTable {
+    segs: [Option<(usize, Arc<ShmSegment>)>; NSHM],
+    /// 下一个新段的序号
+    seq: usize,
+}
+
+/// 序号的上限，保证段号能以非负的 `int` 返回给用户
+const SEQ_MAX: usize = i32::MAX as usize / NSHM;
+
+impl ShmTable {
+    const fn new() -> Self {
+        Self {
+            segs: array![_ => None; NSHM],
+            seq: 0,
+        }
+    }
+
+    /// # 功能说明
+    /// 查找键值为 `key` 的段，不存在且 `flags` 含 `IPC_CREAT` 时创建一个大小为 `size` 的新段。
+    /// 键值为 `IPC_PRIVATE` 时总是创建新段。
+    ///
+    /// # 返回值
+    /// - `Ok(id)`：段号；
+    /// - `Err(())`：段不存在且未指定 `IPC_CREAT`，或指定了 `IPC_EXCL` 而段已存在，
+    ///   或已有的段小于 `size`，或大小非法，或段表已满、内存不足。
+    pub fn get(&mut self, key: usize, size: usize, flags: usize) -> Result<usize, ()> {
+        if key != IPC_PRIVATE {
+            let found = self.segs.iter().flatten().find(|(k, _)| *k == key);
+            if let Some((_, seg)) = found {
+                if flags & IPC_EXCL != 0 || size > seg.size() {
+                    return Err(())
+                }
+                return Ok(seg.id())
+            }
+            if flags & IPC_CREAT == 0 {
+                return Err(())
+            }
+        }
+        if size == 0 || size > SHMMAX {
+            return Err(())
+        }
+        let slot = self.segs.iter().position(|s| s.is_none()).ok_or(())?;
+        let id = self.seq * NSHM + slot;
+        let seg = ShmSegment::new(id, size).ok_or(())?;
+        self.segs[slot] = Some((key, Arc::try_new(seg).map_err(|_| ())?));
+        self.seq = (self.seq + 1) % SEQ_MAX;
+        Ok(id)
+    }
+
+    /// 段号 `id` 对应的段在表中的位置，段已被删除时返回 `None`。
+    fn slot(&mut self, id: usize) -> Option<&mut Option<(usize, Arc<ShmSegment>)>> {
+        let slot = self.segs.get_mut(id % NSHM)?;
+        match slot {
+            Some((_, seg)) if seg.id() == id => Some(slot),
+            _ => None,
+        }
+    }
+
+    /// 段号 `id` 对应的段。
+    pub fn lookup(&mut self, id: usize) -> Option<Arc<ShmSegment>> {
+        self.slot(id)?.as_ref().map(|(_, seg)| Arc::clone(seg))
+    }
+
+    /// 从表中删除段号为 `id` 的段，段在最后一个映射被解除后释放。
+    pub fn remove(&mut self, id: usize) -> Result<(), ()> {
+        self.slot(id).ok_or(())?.take().ok_or(())?;
+        Ok(())
+    }
+}
+
+/// 返回现存的共享内存段数与其占用的字节数，包括已删除但仍被映射的段。
+pub fn stat() -> (usize, usize) {
+    (SEGMENTS.load(Ordering::Relaxed), PAGES.load(Ordering::Relaxed) * PGSIZE)
+}
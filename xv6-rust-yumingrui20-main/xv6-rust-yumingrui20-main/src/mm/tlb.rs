@@ -0,0 +1,64 @@
+//! TLB 一致性
+//!
+//! 陷入内核与返回用户空间时跳板代码都会切换 `satp` 并刷新整个 TLB，
+//! 因此只有正在运行用户代码的 CPU 可能缓存着用户页表的旧映射。
+//! 每个 CPU 返回用户空间前记录所用的 `satp`（见 [`enter_user`]），
+//! 修改用户页表后只需向使用同一页表的 CPU 发起跨核调用刷新 TLB（见 [`shootdown`]）。
+
+use array_macro::array;
+
+use core::arch::asm;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+
+use crate::consts::{NCPU, PGSIZE};
+use crate::ipi;
+use crate::process::CpuManager;
+
+/// 超过该页数时刷新整个 TLB，而不是逐页刷新。
+const FLUSH_ALL_PAGES: usize = 32;
+
+/// 每个 CPU 正在运行的用户代码所用的 `satp`，在内核中时为 0。
+static USER_SATP: [AtomicUsize; NCPU] = array![_ => AtomicUsize::new(0); NCPU];
+
+/// 当前 CPU 即将以页表 `satp` 返回用户空间。
+/// 必须在关中断之后、不再获取任何锁时调用：记录之后其他 CPU 修改该页表时会等待本 CPU 响应。
+pub fn enter_user(satp: usize) {
+    let id = unsafe { CpuManager::cpu_id() };
+    USER_SATP[id].store(satp, Ordering::SeqCst);
+}
+
+/// 当前 CPU 从用户空间陷入内核，跳板代码已经刷新了 TLB。
+pub fn leave_user() {
+    let id = unsafe { CpuManager::cpu_id() };
+    USER_SATP[id].store(0, Ordering::SeqCst);
+}
+
+/// # 功能说明
+/// 修改页表 `satp` 中从 `va` 开始的 `count` 页的映射之后调用，
+/// 刷新正在使用该页表运行用户代码的其他 CPU 的 TLB，返回时它们不会再使用旧的映射。
+///
+/// # 注意
+/// 被解除映射的物理页必须在本函数返回之后才能释放。
+pub fn shootdown(satp: usize, va: usize, count: usize) {
+    // 先写页表项再检查：之后才返回用户空间的 CPU 会在跳板代码中刷新 TLB
+    fence(Ordering::SeqCst);
+    let harts = (0..NCPU)
+        .filter(|&id| USER_SATP[id].load(Ordering::SeqCst) == satp)
+        .fold(0, |harts, id| harts | (1 << id));
+    if harts != 0 {
+        ipi::call(harts, &|| flush(va, count));
+    }
+}
+
+/// 刷新当前 CPU 的 TLB 中从 `va` 开始的 `count` 页。
+fn flush(va: usize, count: usize) {
+    unsafe {
+        if count > FLUSH_ALL_PAGES {
+            asm!("sfence.vma zero, zero");
+            return
+        }
+        for page in (0..count).map(|i| va + i * PGSIZE) {
+            asm!("sfence.vma {}, zero", in(reg) page);
+        }
+    }
+}
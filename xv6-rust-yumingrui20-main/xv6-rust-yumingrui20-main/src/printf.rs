@@ -4,7 +4,7 @@ use core::fmt;
 use core::panic;
 use core::sync::atomic::Ordering;
 
-use crate::driver::{console, PANICKED};
+use crate::driver::{console, uart, PANICKED};
 use crate::spinlock::SpinLock;
 
 /// 零大小类型（ZST）的打印结构体，用于在多个 CPU 之间对打印操作进行排序。
@@ -83,15 +83,18 @@ macro_rules! println {
 /// 全局panic处理函数
 ///
 /// # 功能说明
-/// 1. 打印panic信息
-/// 2. 设置全局panic状态标志
-/// 3. 挂起系统（无限循环）
+/// 1. 打印panic信息与调用栈
+/// 2. 忙等串口发送缓冲区中的内容全部输出
+/// 3. 设置全局panic状态标志
+/// 4. 挂起系统（无限循环）
 ///
 /// # 注意
 /// 此函数由core库在panic时自动调用
 #[panic_handler]
 fn panic(info: &panic::PanicInfo<'_>) -> ! {
     crate::println!("{}", info);
+    crate::backtrace::print();
+    uart::UART.flush();
     PANICKED.store(true, Ordering::Relaxed);
     loop {}
 }
@@ -2,13 +2,21 @@
 
 use array_macro::array;
 
+use core::arch::asm;
 use core::ptr;
+use core::sync::atomic::{fence, AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
 use crate::register::{tp, sstatus};
-use crate::spinlock::SpinLockGuard;
-use crate::consts::NCPU;
+use crate::spinlock::{SpinLock, SpinLockGuard};
+use crate::consts::{MTIME_FREQ, NCPU, NPROC, NSMP};
+use crate::ipi;
+use crate::timer;
+use crate::trap;
 use super::{Context, PROC_MANAGER, Proc, ProcState, proc::ProcExcl};
 
+/// 所有 CPU 的位图，是进程默认的 CPU 亲和性。
+pub const ALL_HARTS: usize = (1 << NSMP) - 1;
+
 /// 全局 CPU 管理器实例
 ///
 /// `CPU_MANAGER` 是一个静态可变变量，代表整个系统中所有 CPU（hart）的管理结构。
@@ -101,6 +109,113 @@ impl CpuManager {
         p
     }
 
+    /// 返回当前 CPU 上正在运行的进程的裸指针，没有进程运行时返回空指针。
+    /// 与 [`my_proc`](Self::my_proc) 不同，该方法不会在没有进程时 panic。
+    pub fn my_proc_ptr(&self) -> *const Proc {
+        push_off();
+        let p = unsafe { self.my_cpu().proc as *const Proc };
+        pop_off();
+        p
+    }
+
+    /// 返回编号为 `id` 的 CPU 的统计信息，参见 [`HartStat`]。
+    ///
+    /// 其他 CPU 上的状态随时可能改变，结果只是一个近似的快照，仅用于 /proc 等信息展示。
+    pub fn hart_stat(&self, id: usize) -> HartStat {
+        let c = &self.table[id];
+        let p = unsafe { ptr::read_volatile(&c.proc) };
+        let pid = match p.is_null() {
+            true => None,
+            false => Some(unsafe { p.as_ref().unwrap().excl.lock().pid }),
+        };
+        HartStat {
+            pid,
+            switches: c.switches.load(Ordering::Relaxed),
+            steals: c.steals.load(Ordering::Relaxed),
+            queued: c.runq.lock().len,
+            idle_ms: c.idle_time.load(Ordering::Relaxed) * 1000 / MTIME_FREQ,
+        }
+    }
+
+    /// # 功能说明
+    /// 将进程 `p` 置为 `RUNNABLE` 并加入某个 CPU 的运行队列，进程已在队列中时只修改状态。
+    ///
+    /// # 流程解释
+    /// 在进程亲和性允许的 CPU 中按以下顺序选择：
+    /// 1. 进程上次运行的 CPU 空闲，或进程正在让出该 CPU 而它的队列为空时，选择该 CPU；
+    /// 2. 否则选择一个空闲的 CPU；
+    /// 3. 都不空闲时仍选择上次运行的 CPU，若亲和性不允许则选择队列最短的 CPU。
+    /// 选中的 CPU 若在 `wfi` 中空闲则将其唤醒。
+    ///
+    /// # 参数
+    /// - `p`：要加入运行队列的进程；
+    /// - `excl`：`p` 的排它锁保护的状态，调用者必须持有该锁。
+    ///
+    /// # 安全性
+    /// - 进程的锁在运行队列的锁之前获取，调度器取出下标后再获取进程的锁，两者不会同时持有。
+    pub fn enqueue(&self, p: &Proc, excl: &mut ProcExcl) {
+        let yielding = excl.state == ProcState::RUNNING;
+        excl.state = ProcState::RUNNABLE;
+        if excl.queued {
+            return
+        }
+        excl.queued = true;
+
+        let mask = p.affinity.load(Ordering::Relaxed);
+        let allowed = |id: usize| mask & (1 << id) != 0;
+        let idle = |id: usize| self.table[id].idle.load(Ordering::SeqCst);
+        let last = excl.last_cpu;
+        let id = if allowed(last) && (idle(last) || (yielding && self.table[last].runq.lock().len == 0)) {
+            last
+        } else if let Some(id) = (0..NSMP).find(|&id| allowed(id) && idle(id)) {
+            id
+        } else if allowed(last) {
+            last
+        } else {
+            (0..NSMP).filter(|&id| allowed(id))
+                .min_by_key(|&id| self.table[id].runq.lock().len)
+                .expect("process with an empty affinity mask")
+        };
+        self.table[id].runq.lock().push_back(p.index());
+
+        // 与 idle 配对：先入队再检查是否空闲，空闲的一方先标记空闲再检查队列，
+        // 两者之中至少有一方能看到对方的修改
+        fence(Ordering::SeqCst);
+        if idle(id) && id != unsafe { Self::cpu_id() } {
+            ipi::send(id);
+        }
+    }
+
+    /// 从其他 CPU 的运行队列窃取一个允许在 CPU `id` 上运行的进程，返回其在进程表中的下标。
+    fn steal(&self, id: usize) -> Option<usize> {
+        for victim in (1..NSMP).map(|k| (id + k) % NSMP) {
+            let i = self.table[victim].runq.lock().steal(|i| {
+                unsafe { PROC_MANAGER.table[i].affinity.load(Ordering::Relaxed) & (1 << id) != 0 }
+            });
+            if i.is_some() {
+                self.table[id].steals.fetch_add(1, Ordering::Relaxed);
+                return i
+            }
+        }
+        None
+    }
+
+    /// CPU `id` 没有可运行的进程：设置下一次时钟中断后执行 `wfi`，直到有中断挂起。
+    /// 调用时中断必须关闭，等待的时间计入空闲时间。
+    unsafe fn idle(&self, id: usize) {
+        let c = &self.table[id];
+        c.idle.store(true, Ordering::SeqCst);
+        trap::clock_arm(true);
+        // 标记空闲之后入队的进程会唤醒本 CPU，之前入队的在这里就能看到
+        fence(Ordering::SeqCst);
+        if c.runq.lock().len == 0 {
+            let start = timer::mtime();
+            asm!("wfi");
+            c.idle_time.fetch_add(timer::mtime() - start, Ordering::Relaxed);
+        }
+        c.idle.store(false, Ordering::SeqCst);
+    }
+
     /// # 功能说明
     /// CPU 调度器主循环，实现多核环境下对进程的抢占式调度。
     /// 该函数从进程管理器中选择一个可运行进程，进行上下文切换，
@@ -110,14 +225,15 @@ impl CpuManager {
     /// # 流程解释
     /// 1. 调用 `my_cpu_mut()` 获取当前 CPU 的可变引用。
     /// 2. 进入无限循环，确保设备中断打开以允许硬件中断响应。
-    /// 3. 通过 `PROC_MANAGER.alloc_runnable()` 尝试获取一个可运行的进程。
-    ///    - 若成功，设置当前 CPU 的 `proc` 指针指向该进程。
-    ///    - 获取该进程的排他锁，修改进程状态为 `RUNNING`。
+    /// 3. 从当前 CPU 的运行队列取出一个进程，队列为空时从其他 CPU 窃取。
+    ///    - 获取该进程的排他锁，确认进程仍可运行且亲和性允许在当前 CPU 上运行，
+    ///      亲和性已改变时将其重新入队。
+    ///    - 修改进程状态为 `RUNNING`，设置当前 CPU 的 `proc` 指针指向该进程。
     ///    - 调用外部汇编函数 `swtch`，完成从调度器上下文切换到进程上下文。
     ///    - 上下文切换返回后，检查 `proc` 是否为空，
     ///      若为空则触发 panic，说明调度异常。
     ///    - 清空 `proc` 指针，释放进程锁。
-    /// 4. 若无可运行进程，继续循环等待。
+    /// 4. 若无可运行进程，设置下一次时钟中断为最早的定时器，执行 `wfi` 等待中断。
     ///
     /// # 参数
     /// - `&mut self`：`CpuManager` 的可变引用，允许修改 CPU 相关状态。
@@ -134,7 +250,7 @@ impl CpuManager {
     /// # 安全性
     /// - 该函数为 `unsafe`，调用时必须保证当前 CPU 和进程状态的正确初始化，
     ///   并且调用环境无竞态。
-    /// - 调用期间中断被开启以响应外部事件，需确保中断处理正确。
+    /// - 每轮循环先开中断处理挂起的中断，查找进程与 `wfi` 时中断关闭，需确保中断处理正确。
     /// - 上下文切换依赖外部汇编函数 `swtch`，要求其正确保存和恢复寄存器状态。
     /// - 进程的排他锁在调度期间被持有，防止进程状态并发修改。
     ///
@@ -143,30 +259,54 @@ impl CpuManager {
             fn swtch(old: *mut Context, new: *mut Context);
         }
 
-        let c = self.my_cpu_mut();
+        let id = Self::cpu_id();
 
         loop {
-            //  确保设备能够中断
+            // 先处理挂起的中断，再关中断查找可运行的进程：
+            // 查找之后到来的中断保持挂起，使 wfi 立即返回，唤醒不会丢失
             sstatus::intr_on();
+            sstatus::intr_off();
 
-            // 使用 ProcManager 查找一个可运行的进程
-            match PROC_MANAGER.alloc_runnable() {
-                Some(p) => {
-                    c.proc = p as *mut _;
-                    let mut guard = p.excl.lock();
-                    guard.state = ProcState::RUNNING;
-
-                    swtch(&mut c.scheduler as *mut Context,
-                        p.data.get_mut().get_context());
-                    
-                    if c.proc.is_null() {
-                        panic!("context switch back with no process reference");
-                    }
-                    c.proc = ptr::null_mut();
-                    drop(guard);
+            // 先取本 CPU 的运行队列，为空时从其他 CPU 窃取，都没有时空闲等待
+            let next = self.table[id].runq.lock().pop_front();
+            let i = match next.or_else(|| self.steal(id)) {
+                Some(i) => i,
+                None => {
+                    self.idle(id);
+                    continue
                 },
-                None => {},
+            };
+
+            let p = &mut PROC_MANAGER.table[i];
+            let p_ptr = p as *mut Proc;
+            let mut guard = p.excl.lock();
+            guard.queued = false;
+            if guard.state != ProcState::RUNNABLE {
+                drop(guard);
+                continue
+            }
+            if p.affinity.load(Ordering::Relaxed) & (1 << id) == 0 {
+                // 入队之后亲和性被修改
+                self.enqueue(p, &mut guard);
+                drop(guard);
+                continue
+            }
+            guard.state = ProcState::RUNNING;
+            guard.last_cpu = id;
+            // 时间片到下一个时钟周期开始时结束
+            trap::clock_arm(false);
+
+            let c = self.my_cpu_mut();
+            c.proc = p_ptr;
+            c.switches.fetch_add(1, Ordering::Relaxed);
+            swtch(&mut c.scheduler as *mut Context,
+                p.data.get_mut().get_context());
+
+            if c.proc.is_null() {
+                panic!("context switch back with no process reference");
             }
+            c.proc = ptr::null_mut();
+            drop(guard);
         }
     }
 }
@@ -195,6 +335,21 @@ pub struct Cpu {
     /// 中断使能标志，记录关闭中断之前的中断使能状态，
     /// 用于恢复中断使能。
     intena: bool,
+
+    /// 调度器切换到用户进程的累计次数，供 /proc 统计展示。
+    switches: AtomicUsize,
+
+    /// 运行队列，保存等待在该 CPU 上运行的进程的下标。
+    runq: SpinLock<RunQueue>,
+
+    /// 是否正在 `wfi` 中空闲，向空闲的 CPU 加入进程时需要唤醒它。
+    idle: AtomicBool,
+
+    /// 在 `wfi` 中空闲的累计时间（`mtime` 周期数）。
+    idle_time: AtomicU64,
+
+    /// 从其他 CPU 的运行队列窃取进程的累计次数。
+    steals: AtomicUsize,
 }
 
 impl Cpu {
@@ -204,6 +359,11 @@ impl Cpu {
             scheduler: Context::new(),
             noff: 0,
             intena: false,
+            switches: AtomicUsize::new(0),
+            runq: SpinLock::new(RunQueue::new(), "runq"),
+            idle: AtomicBool::new(false),
+            idle_time: AtomicU64::new(0),
+            steals: AtomicUsize::new(0),
         }
     }
 
@@ -312,6 +472,67 @@ impl Cpu {
     }
 }
 
+/// CPU 的运行队列：可运行进程在进程表中的下标组成的环形队列。
+///
+/// 每个进程同时至多在一个队列中（见 `ProcExcl::queued`），因此容量 `NPROC` 足够，入队不会失败。
+struct RunQueue {
+    buf: [usize; NPROC],
+    head: usize,
+    len: usize,
+}
+
+impl RunQueue {
+    const fn new() -> Self {
+        Self {
+            buf: [0; NPROC],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push_back(&mut self, i: usize) {
+        assert!(self.len < NPROC, "run queue overflow");
+        self.buf[(self.head + self.len) % NPROC] = i;
+        self.len += 1;
+    }
+
+    fn pop_front(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            return None
+        }
+        let i = self.buf[self.head];
+        self.head = (self.head + 1) % NPROC;
+        self.len -= 1;
+        Some(i)
+    }
+
+    /// 从队尾开始查找第一个满足 `allowed` 的下标并将其移出队列。
+    /// 队尾的进程入队最晚，被窃取对原 CPU 的影响最小。
+    fn steal(&mut self, allowed: impl Fn(usize) -> bool) -> Option<usize> {
+        let k = (0..self.len).rev().find(|&k| allowed(self.buf[(self.head + k) % NPROC]))?;
+        let i = self.buf[(self.head + k) % NPROC];
+        for k in k..self.len-1 {
+            self.buf[(self.head + k) % NPROC] = self.buf[(self.head + k + 1) % NPROC];
+        }
+        self.len -= 1;
+        Some(i)
+    }
+}
+
+/// 某个 CPU 的统计信息，由 [`CpuManager::hart_stat`] 给出。
+pub struct HartStat {
+    /// 正在运行的进程的 PID，空闲时为 `None`
+    pub pid: Option<usize>,
+    /// 切换到进程的累计次数
+    pub switches: usize,
+    /// 从其他 CPU 窃取进程的累计次数
+    pub steals: usize,
+    /// 运行队列中等待的进程数
+    pub queued: usize,
+    /// 在 `wfi` 中空闲的累计毫秒数
+    pub idle_ms: u64,
+}
+
 /// # 功能说明
 /// 关闭当前 CPU 的中断，并记录中断关闭的嵌套次数。
 /// 与 `intr_off()` 类似，但支持成对使用，
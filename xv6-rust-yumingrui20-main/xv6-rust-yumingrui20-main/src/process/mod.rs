@@ -2,20 +2,24 @@
 
 use array_macro::array;
 
+use alloc::sync::Arc;
 use core::convert::TryFrom;
 use core::ptr;
 use core::mem;
 use core::sync::atomic::Ordering;
 
-use crate::consts::{NPROC, PGSIZE, TRAMPOLINE, fs::ROOTDEV};
-use crate::mm::{kvm_map, PhysAddr, PteFlag, VirtAddr, RawPage, RawSinglePage, PageTable, RawQuadPage};
-use crate::spinlock::SpinLock;
+use crate::consts::{MAXCMDLINE, NPROC, PGSIZE, TRAMPOLINE, TRAPFRAME, WNOHANG, WUNTRACED, fs::ROOTDEV};
+use crate::consts::signal::{NSIG, SIGALRM, SIGCONT, SIGINT, SIGKILL, SIGSTOP, SIGTERM, SIGTSTP};
+use crate::mm::{kvm_map, PhysAddr, PteFlag, VirtAddr, RawPage, RawSinglePage, RawQuadPage};
+use crate::spinlock::{SpinLock, SpinLockGuard};
 use crate::trap::user_trap_ret;
+use crate::timer::{self, TimerKey};
 use crate::fs;
 
 pub use cpu::{CPU_MANAGER, CpuManager};
-pub use cpu::{push_off, pop_off};
-pub use proc::Proc;
+pub use cpu::{push_off, pop_off, ALL_HARTS};
+pub use proc::{Proc, ProcState, AddrSpace, PollQueue, PollWaiter};
+use proc::{FdTable, ProcExcl};
 
 mod context;
 mod proc;
@@ -23,7 +27,6 @@ mod cpu;
 mod trapframe;
 
 use context::Context;
-use proc::ProcState;
 use trapframe::TrapFrame;
 
 /// 全局进程管理器（Process Manager）
@@ -83,7 +86,7 @@ impl ProcManager {
             table: array![i => Proc::new(i); NPROC],
             parents: SpinLock::new(array![_ => None; NPROC], "proc parents"),
             init_proc: 0,
-            pid: SpinLock::new(0, "pid"),
+            pid: SpinLock::new(1, "pid"),
         }
     }
 
@@ -171,18 +174,20 @@ impl ProcManager {
     /// 
     /// 在进程表中查找一个状态为 `UNUSED` 的空闲进程条目，
     /// 如果找到则为该进程分配新的 PID，初始化运行内核所需的状态，
-    /// 包括分配陷阱帧（trapframe）和页表，
+    /// 包括分配陷阱帧（trapframe）和地址空间，
     /// 并将进程状态设置为 `ALLOCATED`。
     /// 返回该已分配但尚未运行的进程的可变引用。
     /// 
     /// # 参数
     /// 
     /// - `&mut self`：对进程管理器的可变引用，允许修改进程表。
+    /// - `shared`：为 `Some` 时新进程是与其共享地址空间的线程，陷阱帧映射到共享地址空间的空闲槽位；
+    ///   为 `None` 时创建新的地址空间。
     /// 
     /// # 返回值
     /// 
-    /// - 返回 `Some(&mut Proc)`，表示成功分配并初始化了一个进程。
-    /// - 返回 `None`，表示没有找到可用的空闲进程，或资源分配失败（如内存不足）。
+    /// - 返回 `Some(&mut Proc)`，表示成功分配并初始化了一个进程，其线程组主线程为自身。
+    /// - 返回 `None`，表示没有找到可用的空闲进程，或资源分配失败（如内存不足、线程数已达上限）。
     /// 
     /// # 可能的错误
     /// 
@@ -195,12 +200,12 @@ impl ProcManager {
     /// 
     /// - 分配陷阱帧时使用了 `unsafe`，调用 `RawSinglePage::try_new_zeroed()`，
     ///   需要保证底层内存分配正确且有效。
-    fn alloc_proc(&mut self) ->
+    fn alloc_proc(&mut self, shared: Option<&Arc<SpinLock<AddrSpace>>>) ->
         Option<&mut Proc>
     {
         let new_pid = self.alloc_pid();
 
-        for p in self.table.iter_mut() {
+        for (i, p) in self.table.iter_mut().enumerate() {
             let mut guard = p.excl.lock();
             match guard.state {
                 ProcState::UNUSED => {
@@ -210,16 +215,26 @@ impl ProcManager {
                     // 分配陷阱帧
                     pd.tf = unsafe { RawSinglePage::try_new_zeroed().ok()? as *mut TrapFrame };
 
-                    debug_assert!(pd.pagetable.is_none());
-                    match PageTable::alloc_proc_pagetable(pd.tf as usize) {
-                        Some(pgt) => pd.pagetable = Some(pgt),
+                    // 映射陷阱帧：线程映射到共享地址空间，进程则创建新的地址空间
+                    let vm = match shared {
+                        Some(vm) => vm.lock().map_trapframe(pd.tf as usize)
+                            .map(|va| (Arc::clone(vm), va)),
+                        None => AddrSpace::new(pd.tf as usize)
+                            .and_then(|vm| Arc::try_new(SpinLock::new(vm, "vm")).ok())
+                            .map(|vm| (vm, usize::from(TRAPFRAME))),
+                    };
+                    match vm {
+                        Some((vm, va)) => pd.set_vm(vm, va),
                         None => {
                             unsafe { RawSinglePage::from_raw_and_drop(pd.tf as *mut u8); }
+                            pd.tf = ptr::null_mut();
                             return None
                         },
                     }
                     pd.init_context();
+                    p.affinity.store(ALL_HARTS, Ordering::Relaxed);
                     guard.pid = new_pid;
+                    guard.leader = i;
                     guard.state = ProcState::ALLOCATED;
 
                     drop(guard);
@@ -232,57 +247,12 @@ impl ProcManager {
         None
     }
 
-    /// # 功能说明
-    ///
-    /// 在进程表中查找第一个状态为 `RUNNABLE` 的进程，
-    /// 将其状态修改为 `ALLOCATED`，
-    /// 并返回该进程的可变引用，
-    /// 返回时不持有该进程的锁，
-    /// 该函数通常由各 CPU 的调度器调用以选取下一个运行的进程。
-    ///
-    /// # 参数
-    ///
-    /// - `&mut self`：进程管理器的可变引用，允许修改进程状态。
-    ///
-    /// # 返回值
-    ///
-    /// - 返回 `Some(&mut Proc)` 表示找到一个可运行的进程并分配成功。
-    /// - 返回 `None` 表示当前没有可运行的进程。
-    ///
-    /// # 可能的错误
-    ///
-    /// - 无明显错误返回，但若存在并发访问冲突，可能导致调度延迟或饥饿。
-    ///
-    /// # 安全性
-    ///
-    /// - 通过持有进程的 `excl` 自旋锁保证状态修改的原子性，避免竞态条件。
-    /// - 返回时释放了锁，调用者需确保使用该进程指针时的并发安全。
-    fn alloc_runnable(&mut self) ->
-        Option<&mut Proc>
-    {
-        for p in self.table.iter_mut() {
-            let mut guard = p.excl.lock();
-            match guard.state {
-                ProcState::RUNNABLE => {
-                    guard.state = ProcState::ALLOCATED;
-                    drop(guard);
-                    return Some(p)
-                },
-                _ => {
-                    drop(guard);
-                },
-            }
-        }
-
-        None
-    }
-
     /// # 功能说明
     ///
     /// 初始化系统的第一个用户进程。
     /// 该函数调用 `alloc_proc` 分配一个新的进程结构，
     /// 执行该进程的用户态初始化逻辑，
-    /// 并将其状态设置为 `RUNNABLE`，
+    /// 并将其加入运行队列，
     /// 准备被调度执行。
     ///
     /// # 参数
@@ -306,11 +276,14 @@ impl ProcManager {
     ///     并且该索引对应的进程尚未被占用。
     /// - 在调用期间不允许并发访问 `ProcManager`，避免数据竞争。
     pub unsafe fn user_init(&mut self) {
-        let p = self.alloc_proc()
+        let p = self.alloc_proc(None)
             .expect("all process should be unused");
         p.user_init();
         let mut guard = p.excl.lock();
-        guard.state = ProcState::RUNNABLE;
+        // init 是第一个会话与进程组的首进程
+        guard.pgid = guard.pid;
+        guard.sid = guard.pid;
+        CPU_MANAGER.enqueue(p, &mut guard);
     }
 
     /// 检查给定的进程是否是init
@@ -350,10 +323,56 @@ impl ProcManager {
         for p in self.table.iter() {
             let mut guard = p.excl.lock();
             if guard.state == ProcState::SLEEPING && guard.channel == channel {
-                guard.state = ProcState::RUNNABLE;
+                unsafe { CPU_MANAGER.enqueue(p, &mut guard); }
+            }
+            drop(guard);
+        }
+    }
+
+    /// 与 [`wakeup`](Self::wakeup) 相同，但最多唤醒 `n` 个进程，返回实际唤醒的数量。
+    pub fn wakeup_n(&self, channel: usize, n: usize) -> usize {
+        let mut woken = 0;
+        for p in self.table.iter() {
+            if woken == n {
+                break;
+            }
+            let mut guard = p.excl.lock();
+            if guard.state == ProcState::SLEEPING && guard.channel == channel {
+                unsafe { CPU_MANAGER.enqueue(p, &mut guard); }
+                woken += 1;
             }
             drop(guard);
         }
+        woken
+    }
+
+    /// 时钟中断时调用：唤醒睡眠截止时间不晚于当前时钟周期数 `now` 的进程。
+    pub fn wakeup_clock(&self, now: usize) {
+        for p in self.table.iter() {
+            let mut guard = p.excl.lock();
+            if guard.state == ProcState::SLEEPING {
+                let expired = guard.deadline
+                    .map_or(false, |deadline| now.wrapping_sub(deadline) as isize >= 0);
+                if expired {
+                    unsafe { CPU_MANAGER.enqueue(p, &mut guard); }
+                }
+            }
+            drop(guard);
+        }
+    }
+
+    /// 睡眠进程中最早的截止时钟周期，没有时返回 `None`。
+    /// 截止时间以时钟周期 `base` 为起点比较先后。
+    pub fn next_deadline(&self, base: usize) -> Option<usize> {
+        self.table.iter()
+            .filter_map(|p| {
+                let guard = p.excl.lock();
+                match guard.state {
+                    ProcState::SLEEPING => guard.deadline,
+                    _ => None,
+                }
+            })
+            .min_by_key(|deadline| deadline.wrapping_sub(base) as isize)
     }
 
     /// # 功能说明
@@ -391,7 +410,10 @@ impl ProcManager {
     /// # 功能说明
     ///
     /// 使指定进程进入退出状态，执行退出清理流程。
-    /// 具体包括关闭进程打开的文件，
+    /// 任一线程调用 exit 或被杀死都会使整个线程组退出：
+    /// 非主线程记录线程组的退出状态、杀死组内其他线程后自行退出；
+    /// 主线程则等待并回收组内其他线程，
+    /// 然后关闭进程打开的文件，
     /// 将其子进程的父进程重新指向初始进程，
     /// 唤醒相关父进程，
     /// 设置退出状态并将进程状态改为 `ZOMBIE`，
@@ -402,6 +424,7 @@ impl ProcManager {
     /// - `&self`：进程管理器的不可变引用，允许访问和修改进程相关数据。
     /// - `exit_pi: usize`：要退出的进程在进程表中的索引。
     /// - `exit_status: i32`：进程退出状态码，用于父进程查询。
+    ///   若线程组已由其他线程开始退出，则以先记录的状态为准。
     ///
     /// # 返回值
     ///
@@ -429,17 +452,49 @@ impl ProcManager {
             panic!("init process exiting");
         }
 
-        unsafe { self.table[exit_pi].data.get().as_mut().unwrap().close_files(); }
+        // 记录线程组的退出状态，已有线程开始退出时沿用其状态
+        let leader = self.table[exit_pi].excl.lock().leader;
+        let (exit_status, execing) = {
+            let mut leader_excl = self.table[leader].excl.lock();
+            if !leader_excl.group_exit && !leader_excl.execing {
+                leader_excl.group_exit = true;
+                leader_excl.exit_status = exit_status;
+            }
+            (leader_excl.exit_status, leader_excl.execing)
+        };
+
+        if exit_pi != leader {
+            // 由主线程负责整个线程组的退出，有线程正在 exec 时只结束自身
+            if !execing {
+                let leader_pid = self.table[leader].excl.lock().pid;
+                let _ = self.kill(leader_pid);
+            }
+            self.thread_exiting(exit_pi, exit_status);
+        }
+        self.table[leader].killed.store(true, Ordering::Relaxed);
+        self.wait_threads(leader, false);
+
+        // 停止进程的间隔定时器
+        let pid = self.table[exit_pi].excl.lock().pid;
+        timer::cancel(TimerKey::Real(pid));
+
+        self.table[exit_pi].close_files();
 
         let mut parent_map = self.parents.lock();
 
         // 将子进程的父进程设置为 init 进程。
         let mut have_child = false;
-        for child in parent_map.iter_mut() {
+        for (i, child) in parent_map.iter_mut().enumerate() {
             match child {
                 Some(parent) if *parent == exit_pi => {
                     *parent = self.init_proc;
                     have_child = true;
+                    // init 不做作业控制，被停止的子进程再也不会被继续，因此杀死它
+                    let mut excl = self.table[i].excl.lock();
+                    if excl.state == ProcState::STOPPED {
+                        self.table[i].killed.store(true, Ordering::Relaxed);
+                        unsafe { CPU_MANAGER.enqueue(&self.table[i], &mut excl); }
+                    }
                 },
                 _ => {},
             }
@@ -464,25 +519,250 @@ impl ProcManager {
 
     /// # 功能说明
     ///
-    /// 父进程等待其任一子进程退出（进入 ZOMBIE 状态）。
+    /// 结束调用 `exit_thread` 的线程，不影响线程组中的其他线程。
+    /// 主线程要等待组内其他线程全部结束后，以 `exit_status` 退出整个进程；
+    /// 其他线程直接退出，退出状态供 `join` 查询。
+    fn exit_thread(&self, pi: usize, exit_status: i32) {
+        let leader = self.table[pi].excl.lock().leader;
+        if pi == leader {
+            self.wait_threads(leader, false);
+            self.exiting(pi, exit_status);
+        } else {
+            self.thread_exiting(pi, exit_status);
+        }
+        unreachable!("thread exiting {}", pi);
+    }
+
+    /// # 功能说明
+    ///
+    /// 非主线程退出：释放对文件表与地址空间的引用，唤醒在主线程上等待的线程，
+    /// 设置退出状态并将状态改为 `ZOMBIE`，之后由 `join` 或主线程回收。不返回。
+    fn thread_exiting(&self, pi: usize, exit_status: i32) {
+        let p = &self.table[pi];
+        let leader = p.excl.lock().leader;
+        let pdata = unsafe { p.data.get().as_mut().unwrap() };
+        p.close_files();
+        p.release_vm();
+
+        let parent_map = self.parents.lock();
+        self.wakeup(&self.table[leader] as *const Proc as usize);
+
+        let mut excl = p.excl.lock();
+        excl.exit_status = exit_status;
+        excl.state = ProcState::ZOMBIE;
+        drop(parent_map);
+        unsafe {
+            let ctx = pdata.get_context();
+            CPU_MANAGER.my_cpu_mut().sched(excl, ctx);
+        }
+
+        unreachable!("thread exiting {}", pi);
+    }
+
+    /// # 功能说明
+    ///
+    /// 由主线程调用，等待并回收线程组中的其他线程，直到只剩主线程。
+    /// `kill` 为 `true` 或主线程被杀死（线程组正在退出）时，每轮都会杀死尚未结束的线程。
+    /// 主线程在等待期间被执行 exec 的线程接替（不再是主线程）时直接返回。
+    fn wait_threads(&self, leader: usize, kill: bool) {
+        let lp = &self.table[leader];
+        let mut parent_map = self.parents.lock();
+
+        loop {
+            if lp.excl.lock().leader != leader {
+                return
+            }
+            let kill = kill || lp.killed.load(Ordering::Relaxed);
+            let mut have_thread = false;
+            for i in 0..NPROC {
+                if i == leader {
+                    continue;
+                }
+                let mut excl = self.table[i].excl.lock();
+                if excl.state == ProcState::UNUSED || excl.leader != leader {
+                    continue;
+                }
+                if excl.state == ProcState::ZOMBIE {
+                    self.reap(i, excl);
+                    continue;
+                }
+                have_thread = true;
+                if kill {
+                    self.table[i].killed.store(true, Ordering::Relaxed);
+                    if excl.state == ProcState::SLEEPING || excl.state == ProcState::STOPPED {
+                        unsafe { CPU_MANAGER.enqueue(&self.table[i], &mut excl); }
+                    }
+                }
+            }
+
+            if !have_thread {
+                return
+            }
+
+            lp.sleep(lp as *const Proc as usize, parent_map);
+            parent_map = self.parents.lock();
+        }
+    }
+
+    /// # 功能说明
+    ///
+    /// 由 exec 调用：杀死线程组中除调用者外的所有线程，并等待回收它们。
+    /// 调用者不是主线程时先接替主线程：取得进程的 PID、进程组、会话、被忽略的信号与父子关系，
+    /// 原来的主线程改用调用者的线程号，和其他线程一起被结束。
+    ///
+    /// # 参数
+    ///
+    /// - `pi`：调用者在进程表中的索引。
+    ///
+    /// # 返回值
+    ///
+    /// - `Ok(())`：调用者已是进程中唯一的线程。
+    /// - `Err(())`：线程组正在退出、已有其他线程在执行 exec，或调用者已被杀死。
+    fn exec_threads(&self, pi: usize) -> Result<(), ()> {
+        let p = &self.table[pi];
+        let mut parent_map = self.parents.lock();
+        let (leader, tid) = {
+            let excl = p.excl.lock();
+            (excl.leader, excl.pid)
+        };
+
+        let mut leader_excl = self.table[leader].excl.lock();
+        if leader_excl.group_exit || leader_excl.execing || p.killed.load(Ordering::Relaxed) {
+            return Err(())
+        }
+        if pi == leader {
+            leader_excl.execing = true;
+            drop(leader_excl);
+        } else {
+            let pid = leader_excl.pid;
+            let pgid = leader_excl.pgid;
+            let sid = leader_excl.sid;
+            let sigignore = leader_excl.sigignore;
+            let stop_report = leader_excl.stop_report.take();
+            leader_excl.pid = tid;
+            drop(leader_excl);
+
+            let mut excl = p.excl.lock();
+            excl.pid = pid;
+            excl.pgid = pgid;
+            excl.sid = sid;
+            excl.sigignore = sigignore;
+            excl.stop_report = stop_report;
+            excl.execing = true;
+            drop(excl);
+
+            // 线程组（包括原来的主线程）改以调用者为主线程
+            for i in 0..NPROC {
+                let mut excl = self.table[i].excl.lock();
+                if excl.state != ProcState::UNUSED && excl.leader == leader {
+                    excl.leader = pi;
+                }
+            }
+            parent_map[pi] = parent_map[leader].take();
+            for parent in parent_map.iter_mut() {
+                if *parent == Some(leader) {
+                    *parent = Some(pi);
+                }
+            }
+        }
+        drop(parent_map);
+
+        self.wait_threads(pi, true);
+        p.excl.lock().execing = false;
+        Ok(())
+    }
+
+    /// # 功能说明
+    ///
+    /// 等待同一线程组中线程号为 `tid` 的线程退出并回收它。
+    ///
+    /// # 参数
+    ///
+    /// - `pi`：调用者在进程表中的索引。
+    /// - `tid`：要等待的线程号，不能是主线程或调用者自身。
+    /// - `addr`：用户空间地址，若非零，线程的退出状态将复制到该地址。
+    ///
+    /// # 返回值
+    ///
+    /// - `Ok(usize)`：被回收线程的线程号。
+    /// - `Err(())`：组内没有该线程，或调用者被杀死，或 `addr` 无效。
+    fn joining(&self, pi: usize, tid: usize, addr: usize) -> Result<usize, ()> {
+        let p = &self.table[pi];
+        let leader = p.excl.lock().leader;
+        let pdata = unsafe { p.data.get().as_mut().unwrap() };
+        let mut parent_map = self.parents.lock();
+
+        loop {
+            let mut found = false;
+            for i in 0..NPROC {
+                if i == pi || i == leader {
+                    continue;
+                }
+                let excl = self.table[i].excl.lock();
+                if excl.state == ProcState::UNUSED || excl.leader != leader || excl.pid != tid {
+                    continue;
+                }
+                if excl.state == ProcState::ZOMBIE {
+                    if addr != 0 && pdata.copy_out(&excl.exit_status as *const _ as *const u8,
+                        addr, mem::size_of_val(&excl.exit_status)).is_err()
+                    {
+                        return Err(())
+                    }
+                    self.reap(i, excl);
+                    return Ok(tid)
+                }
+                found = true;
+                break;
+            }
+
+            if !found || p.killed.load(Ordering::Relaxed) {
+                return Err(())
+            }
+
+            let channel = &self.table[leader] as *const Proc as usize;
+            p.sleep_interruptible(channel, parent_map, None);
+            parent_map = self.parents.lock();
+        }
+    }
+
+    /// 回收处于 `ZOMBIE` 状态的进程或线程，释放其剩余资源，调用者须持有父子关系表的锁。
+    fn reap(&self, i: usize, mut excl: SpinLockGuard<'_, ProcExcl>) {
+        self.table[i].killed.store(false, Ordering::Relaxed);
+        self.table[i].stop.store(0, Ordering::Relaxed);
+        let data = unsafe { self.table[i].data.get().as_mut().unwrap() };
+        data.cleanup();
+        excl.cleanup();
+    }
+
+    /// # 功能说明
+    ///
+    /// 父进程等待 `pid` 选中的任一子进程退出（进入 ZOMBIE 状态），
+    /// 指定 `WUNTRACED` 时也等待子进程被停止。
+    /// 子进程属于整个线程组，组内任一线程都可以等待。
     /// 如果找到已退出的子进程，将子进程的退出状态复制到用户空间，
     /// 清理子进程资源，解除父子关系映射，
-    /// 并返回该子进程的 PID。
-    /// 如果没有子进程或调用进程被杀死，则返回错误。
-    /// 若存在子进程但均未退出，调用进程将进入睡眠状态，
+    /// 并返回该子进程的 PID；被停止的子进程只报告一次，不被回收。
+    /// 若存在选中的子进程但均未改变状态，调用进程将进入睡眠状态，
     /// 直到被唤醒重新检测。
     ///
     /// # 参数
     ///
     /// - `&self`：进程管理器的不可变引用，用于访问进程表和父子关系。
     /// - `pi: usize`：调用该函数的父进程在进程表中的索引。
+    /// - `pid: isize`：大于 0 时只等待该子进程；为 -1 时等待任一子进程；
+    ///   为 0 时等待与调用者同一进程组的子进程；小于 -1 时等待进程组 `-pid` 中的子进程。
     /// - `addr: usize`：用户空间地址，
-    ///   若非零，退出状态将复制到该地址。
+    ///   若非零，状态将复制到该地址。
+    /// - `options: i32`：`WNOHANG` 与 `WUNTRACED` 的组合。
+    /// - `encode: bool`：为 `true` 时按 `waitpid` 的约定编码状态：
+    ///   退出为 `(status & 0xff) << 8`，停止为 `(sig << 8) | 0x7f`；
+    ///   为 `false` 时直接复制退出状态（`wait` 的约定）。
     ///
     /// # 返回值
     ///
-    /// - `Ok(usize)`：返回已退出子进程的 PID。
-    /// - `Err(())`：表示没有子进程可等待，或者调用进程被杀死。
+    /// - `Ok(usize)`：返回已退出或被停止的子进程的 PID；
+    ///   指定 `WNOHANG` 而没有子进程改变状态时返回 0。
+    /// - `Err(())`：表示没有选中的子进程可等待，或者调用进程被杀死。
     ///
     /// # 可能的错误
     ///
@@ -494,9 +774,11 @@ impl ProcManager {
     /// - 使用了多处 `unsafe`，访问裸指针和非线程安全的结构，
     ///   需保证对应内存有效且数据未被并发破坏。
     /// - 通过持有自旋锁保护父子映射表的访问，防止竞态。
-    /// - 调用 `sleep` 使调用进程阻塞，等待唤醒重新检测，
+    /// - 调用 `sleep_interruptible` 使调用进程阻塞，等待唤醒重新检测，
     ///   需保证唤醒机制和锁释放顺序正确避免死锁。
-    fn waiting(&self, pi: usize, addr: usize) -> Result<usize, ()> {
+    fn waiting(&self, pi: usize, pid: isize, addr: usize, options: i32, encode: bool) -> Result<usize, ()> {
+        let leader = self.table[pi].excl.lock().leader;
+        let pgid = self.table[leader].excl.lock().pgid;
         let mut parent_map = self.parents.lock();
         let p = unsafe { CPU_MANAGER.my_proc() };
         let pdata = unsafe { p.data.get().as_mut().unwrap() };
@@ -504,36 +786,52 @@ impl ProcManager {
         loop {
             let mut have_child = false;
             for i in 0..NPROC {
-                if parent_map[i].is_none() || *parent_map[i].as_ref().unwrap() != pi {
+                if parent_map[i].is_none() || *parent_map[i].as_ref().unwrap() != leader {
                     continue;
                 }
 
                 let mut child_excl = self.table[i].excl.lock();
-                have_child = true;
-                if child_excl.state != ProcState::ZOMBIE {
+                let selected = match pid {
+                    -1 => true,
+                    0 => child_excl.pgid == pgid,
+                    pid if pid < 0 => child_excl.pgid == pid.unsigned_abs(),
+                    pid => child_excl.pid == pid as usize,
+                };
+                if !selected {
                     continue;
                 }
+                have_child = true;
                 let child_pid = child_excl.pid;
-                if addr != 0 && pdata.copy_out(&child_excl.exit_status as *const _ as *const u8,
-                    addr, mem::size_of_val(&child_excl.exit_status)).is_err()
+                let status = match (child_excl.state, child_excl.stop_report) {
+                    (ProcState::ZOMBIE, _) if encode => (child_excl.exit_status & 0xff) << 8,
+                    (ProcState::ZOMBIE, _) => child_excl.exit_status,
+                    (_, Some(sig)) if options & WUNTRACED != 0 => ((sig as i32) << 8) | 0x7f,
+                    _ => continue,
+                };
+                if addr != 0 && pdata.copy_out(&status as *const i32 as *const u8,
+                    addr, mem::size_of::<i32>()).is_err()
                 {
                     return Err(())
                 }
-                parent_map[i].take();
-                self.table[i].killed.store(false, Ordering::Relaxed);
-                let child_data = unsafe { self.table[i].data.get().as_mut().unwrap() };
-                child_data.cleanup();
-                child_excl.cleanup();           
+                if child_excl.state == ProcState::ZOMBIE {
+                    parent_map[i].take();
+                    self.reap(i, child_excl);
+                } else {
+                    child_excl.stop_report = None;
+                }
                 return Ok(child_pid)
             }
 
             if !have_child || p.killed.load(Ordering::Relaxed) {
                 return Err(())
             }
+            if options & WNOHANG != 0 {
+                return Ok(0)
+            }
 
-            // have children, but none of them exit
-            let channel = p as *const Proc as usize;
-            p.sleep(channel, parent_map);
+            // 有子进程，但都没有改变状态
+            let channel = &self.table[leader] as *const Proc as usize;
+            p.sleep_interruptible(channel, parent_map, None);
             parent_map = self.parents.lock();
         }
     }
@@ -542,7 +840,7 @@ impl ProcManager {
     ///
     /// 根据给定的进程标识符（PID）杀死对应的进程。
     /// 查找进程表中匹配的进程，将其 `killed` 标记置为 `true`，
-    /// 如果该进程处于 `SLEEPING` 状态，则将其状态改为 `RUNNABLE`，
+    /// 如果该进程处于 `SLEEPING` 或 `STOPPED` 状态，则将其状态改为 `RUNNABLE`，
     /// 以便尽快响应终止请求。
     ///
     /// # 参数
@@ -567,10 +865,10 @@ impl ProcManager {
     pub fn kill(&self, pid: usize) -> Result<(), ()> {
         for i in 0..NPROC {
             let mut guard = self.table[i].excl.lock();
-            if guard.pid == pid {
+            if guard.pid == pid && guard.state != ProcState::UNUSED {
                 self.table[i].killed.store(true, Ordering::Relaxed);
-                if guard.state == ProcState::SLEEPING {
-                    guard.state = ProcState::RUNNABLE;
+                if guard.state == ProcState::SLEEPING || guard.state == ProcState::STOPPED {
+                    unsafe { CPU_MANAGER.enqueue(&self.table[i], &mut guard); }
                 }
                 return Ok(())
             }
@@ -578,6 +876,403 @@ impl ProcManager {
 
         Err(())
     }
+
+    /// PID 为 `pid` 的进程或线程所在线程组的主线程在进程表中的下标。
+    fn leader_of(&self, pid: usize) -> Option<usize> {
+        (0..NPROC).find_map(|i| {
+            let guard = self.table[i].excl.lock();
+            match guard.state {
+                ProcState::UNUSED => None,
+                _ if guard.pid == pid => Some(guard.leader),
+                _ => None,
+            }
+        })
+    }
+
+    /// # 功能说明
+    ///
+    /// 向 PID 为 `pid` 的进程所在的线程组发送信号 `sig`，按信号的默认动作处理。
+    /// 内核尚不支持用户自定义信号处理函数，但进程可以忽略部分信号：
+    /// - `SIGINT`、`SIGALRM`、`SIGTERM` 与 `SIGKILL` 终止目标进程，等同于 [`kill`](Self::kill)；
+    /// - `SIGSTOP` 与 `SIGTSTP` 停止整个线程组，直到收到 `SIGCONT`；
+    /// - `SIGCONT` 继续被停止的线程组；
+    /// - `SIGKILL`、`SIGSTOP` 与 `SIGCONT` 不能被忽略；
+    /// - 信号 0 不产生任何动作，只检查进程是否存在。
+    ///
+    /// # 返回值
+    ///
+    /// - `Ok(())`：信号已送达或被忽略；
+    /// - `Err(())`：进程不存在或信号编号不受支持。
+    pub fn signal(&self, pid: usize, sig: usize) -> Result<(), ()> {
+        if !signal_supported(sig) {
+            return Err(())
+        }
+        let leader = self.leader_of(pid).ok_or(())?;
+        self.deliver(leader, sig);
+        Ok(())
+    }
+
+    /// 向进程组 `pgid` 中的所有进程发送信号 `sig`，规则同 [`signal`](Self::signal)。
+    ///
+    /// # 返回值
+    ///
+    /// - `Ok(())`：至少有一个进程属于该进程组；
+    /// - `Err(())`：进程组不存在或信号编号不受支持。
+    pub fn signal_group(&self, pgid: usize, sig: usize) -> Result<(), ()> {
+        if !signal_supported(sig) {
+            return Err(())
+        }
+        let mut found = false;
+        for i in 0..NPROC {
+            let guard = self.table[i].excl.lock();
+            let member = guard.state != ProcState::UNUSED && guard.state != ProcState::ZOMBIE &&
+                guard.leader == i && guard.pgid == pgid;
+            drop(guard);
+            if member {
+                found = true;
+                self.deliver(i, sig);
+            }
+        }
+        if found { Ok(()) } else { Err(()) }
+    }
+
+    /// 按默认动作向主线程为 `leader` 的线程组投递信号 `sig`，被忽略的信号直接丢弃。
+    fn deliver(&self, leader: usize, sig: usize) {
+        let excl = self.table[leader].excl.lock();
+        let (pid, ignored) = (excl.pid, excl.sigignore & (1 << sig) != 0);
+        drop(excl);
+        match sig {
+            SIGKILL => { let _ = self.kill(pid); },
+            SIGINT | SIGTERM | SIGALRM if !ignored => { let _ = self.kill(pid); },
+            SIGSTOP => self.stop_group(leader, sig),
+            SIGTSTP if !ignored => self.stop_group(leader, sig),
+            SIGCONT => self.continue_group(leader),
+            _ => {},
+        }
+    }
+
+    /// # 功能说明
+    ///
+    /// 以信号 `sig` 停止主线程为 `leader` 的线程组。
+    /// 处于可中断睡眠的线程立即进入 `STOPPED` 状态；
+    /// 其他线程记下待处理的停止信号，在返回用户空间之前由 [`stopping`](Self::stopping) 停止。
+    /// 主线程停止时记录停止信号供父进程的 `waitpid` 报告，并唤醒父进程。
+    fn stop_group(&self, leader: usize, sig: usize) {
+        let parent_map = self.parents.lock();
+        let mut stopped = false;
+        for i in 0..NPROC {
+            let mut excl = self.table[i].excl.lock();
+            if excl.state == ProcState::UNUSED || excl.leader != leader {
+                continue;
+            }
+            match excl.state {
+                ProcState::ZOMBIE | ProcState::STOPPED => {},
+                ProcState::SLEEPING if excl.interruptible => {
+                    excl.state = ProcState::STOPPED;
+                    if i == leader {
+                        excl.stop_report = Some(sig);
+                        stopped = true;
+                    }
+                },
+                _ => self.table[i].stop.store(sig, Ordering::Relaxed),
+            }
+        }
+        if stopped {
+            if let Some(parent) = parent_map[leader] {
+                self.wakeup(&self.table[parent] as *const Proc as usize);
+            }
+        }
+        drop(parent_map);
+    }
+
+    /// 继续主线程为 `leader` 的线程组：清除待处理的停止信号，被停止的线程重新可运行。
+    fn continue_group(&self, leader: usize) {
+        for i in 0..NPROC {
+            let mut excl = self.table[i].excl.lock();
+            if excl.state == ProcState::UNUSED || excl.leader != leader {
+                continue;
+            }
+            self.table[i].stop.store(0, Ordering::Relaxed);
+            if excl.state == ProcState::STOPPED {
+                unsafe { CPU_MANAGER.enqueue(&self.table[i], &mut excl); }
+            }
+            if i == leader {
+                excl.stop_report = None;
+            }
+        }
+    }
+
+    /// # 功能说明
+    ///
+    /// 处理进程表下标为 `pi` 的线程待处理的停止信号：进入 `STOPPED` 状态并让出 CPU，
+    /// 直到收到 `SIGCONT` 或被杀死后返回。主线程停止时唤醒父进程，使其 `waitpid` 能报告停止。
+    fn stopping(&self, pi: usize) {
+        let p = &self.table[pi];
+        let parent_map = self.parents.lock();
+        let leader = p.excl.lock().leader;
+        if pi == leader {
+            if let Some(parent) = parent_map[pi] {
+                self.wakeup(&self.table[parent] as *const Proc as usize);
+            }
+        }
+
+        let mut excl = p.excl.lock();
+        let sig = p.stop.swap(0, Ordering::Relaxed);
+        if sig == 0 || p.killed.load(Ordering::Relaxed) {
+            return
+        }
+        excl.state = ProcState::STOPPED;
+        if pi == leader {
+            excl.stop_report = Some(sig);
+        }
+        drop(parent_map);
+        unsafe {
+            let ctx = p.data.get().as_mut().unwrap().get_context();
+            excl = CPU_MANAGER.my_cpu_mut().sched(excl, ctx);
+        }
+        drop(excl);
+    }
+
+    /// # 功能说明
+    ///
+    /// 设置 PID 为 `pid` 的进程的进程组为 `pgid`，`pid` 为 0 表示调用者，`pgid` 为 0 表示与 `pid` 相同。
+    ///
+    /// # 返回值
+    ///
+    /// - `Ok(())`：设置成功；
+    /// - `Err(())`：目标不是调用者或其子进程，或不在调用者的会话中，或是会话首进程；
+    ///   或 `pgid` 既不等于目标的 PID，也不是调用者会话中已有的进程组。
+    fn set_pgid(&self, pi: usize, pid: usize, pgid: usize) -> Result<(), ()> {
+        let parent_map = self.parents.lock();
+        let leader = self.table[pi].excl.lock().leader;
+        let (caller_pid, sid) = {
+            let excl = self.table[leader].excl.lock();
+            (excl.pid, excl.sid)
+        };
+        let pid = if pid == 0 { caller_pid } else { pid };
+        let pgid = if pgid == 0 { pid } else { pgid };
+
+        let target = (0..NPROC).find(|&i| {
+            let excl = self.table[i].excl.lock();
+            excl.state != ProcState::UNUSED && excl.state != ProcState::ZOMBIE &&
+                excl.leader == i && excl.pid == pid
+        }).ok_or(())?;
+        if target != leader && parent_map[target] != Some(leader) {
+            return Err(())
+        }
+        if pgid != pid && !self.group_in_session(pgid, sid) {
+            return Err(())
+        }
+
+        let mut excl = self.table[target].excl.lock();
+        if excl.pid != pid || excl.sid != sid || excl.sid == pid {
+            return Err(())
+        }
+        excl.pgid = pgid;
+        drop(excl);
+        drop(parent_map);
+        Ok(())
+    }
+
+    /// 进程组 `pgid` 是否存在且与当前进程属于同一会话，用于设置终端的前台进程组。
+    pub fn in_my_session(&self, pgid: usize) -> bool {
+        let leader = unsafe { CPU_MANAGER.my_proc().excl.lock().leader };
+        let sid = self.table[leader].excl.lock().sid;
+        self.group_in_session(pgid, sid)
+    }
+
+    /// 进程组 `pgid` 是否存在且属于会话 `sid`。
+    fn group_in_session(&self, pgid: usize, sid: usize) -> bool {
+        (0..NPROC).any(|i| {
+            let excl = self.table[i].excl.lock();
+            excl.state != ProcState::UNUSED && excl.leader == i &&
+                excl.pgid == pgid && excl.sid == sid
+        })
+    }
+
+    /// 下标为 `pi` 的线程所在线程组的主线程的 PID，即进程号。
+    fn leader_pid(&self, pi: usize) -> usize {
+        let leader = self.table[pi].excl.lock().leader;
+        self.table[leader].excl.lock().pid
+    }
+
+    /// 主线程为 `leader` 的线程组的进程组号、会话号与被忽略的信号，fork 时由子进程继承。
+    fn inherited(&self, leader: usize) -> (usize, usize, u32) {
+        let excl = self.table[leader].excl.lock();
+        (excl.pgid, excl.sid, excl.sigignore)
+    }
+
+    /// PID 为 `pid` 的进程的进程组号与会话号，`pid` 为 0 表示调用者。
+    fn group_ids(&self, pi: usize, pid: usize) -> Option<(usize, usize)> {
+        let leader = match pid {
+            0 => self.table[pi].excl.lock().leader,
+            pid => self.leader_of(pid)?,
+        };
+        let excl = self.table[leader].excl.lock();
+        Some((excl.pgid, excl.sid))
+    }
+
+    /// 创建新的会话，调用者成为新会话与新进程组的首进程。
+    /// 调用者已是进程组首进程时返回 `Err(())`，成功时返回新的会话号。
+    fn set_sid(&self, pi: usize) -> Result<usize, ()> {
+        let _parent_map = self.parents.lock();
+        let leader = self.table[pi].excl.lock().leader;
+        let pid = self.table[leader].excl.lock().pid;
+        let is_group_leader = (0..NPROC).any(|i| {
+            let excl = self.table[i].excl.lock();
+            excl.state != ProcState::UNUSED && excl.leader == i && excl.pgid == pid
+        });
+        if is_group_leader {
+            return Err(())
+        }
+        let mut excl = self.table[leader].excl.lock();
+        excl.pgid = pid;
+        excl.sid = pid;
+        Ok(pid)
+    }
+
+    /// 设置调用者对信号 `sig` 的处理方式为 `disp`（`SIG_DFL` 或 `SIG_IGN`），返回原来的处理方式。
+    /// `SIGKILL` 与 `SIGSTOP` 的处理方式不能改变。
+    fn set_disposition(&self, pi: usize, sig: usize, ignore: bool) -> Result<bool, ()> {
+        if !signal_supported(sig) || sig == 0 || sig == SIGKILL || sig == SIGSTOP {
+            return Err(())
+        }
+        let leader = self.table[pi].excl.lock().leader;
+        let mut excl = self.table[leader].excl.lock();
+        let old = excl.sigignore & (1 << sig) != 0;
+        if ignore {
+            excl.sigignore |= 1 << sig;
+        } else {
+            excl.sigignore &= !(1 << sig);
+        }
+        Ok(old)
+    }
+
+    /// 查询并设置线程 `pid` 的 CPU 亲和性，`pid` 为 0 表示下标为 `pi` 的调用者自身。
+    /// `mask` 为 `Some` 时设置新的亲和性，返回原有的亲和性；线程不存在或已退出时返回 `Err(())`。
+    /// 正在其他 CPU 上运行的线程在下一次让出 CPU 时迁移。
+    fn affinity(&self, pi: usize, pid: usize, mask: Option<usize>) -> Result<usize, ()> {
+        let i = match pid {
+            0 => pi,
+            pid => (0..NPROC).find(|&i| {
+                let excl = self.table[i].excl.lock();
+                excl.pid == pid && excl.state != ProcState::UNUSED && excl.state != ProcState::ZOMBIE
+            }).ok_or(())?,
+        };
+        let p = &self.table[i];
+        let excl = p.excl.lock();
+        if (pid != 0 && excl.pid != pid) || excl.state == ProcState::ZOMBIE {
+            return Err(())
+        }
+        let old = p.affinity.load(Ordering::Relaxed);
+        if let Some(mask) = mask {
+            p.affinity.store(mask, Ordering::Relaxed);
+        }
+        drop(excl);
+        Ok(old)
+    }
+
+    /// 从进程表下标 `from` 开始查找下一个正在使用的进程槽位，
+    /// 返回其 PID 以及下一次查找应使用的下标；没有更多进程时返回 `None`。
+    ///
+    /// 用于 /proc 根目录的遍历，遍历期间进程可能创建或退出，结果只是一个近似的快照。
+    pub fn next_pid(&self, from: usize) -> Option<(usize, usize)> {
+        for i in from..NPROC {
+            let guard = self.table[i].excl.lock();
+            if guard.state != ProcState::UNUSED {
+                return Some((guard.pid, i+1))
+            }
+        }
+        None
+    }
+
+    /// # 功能说明
+    ///
+    /// 在持有目标进程排它锁的情况下为 PID 为 `pid` 的进程生成状态快照 [`ProcView`]，
+    /// 释放锁之后交给回调 `f` 处理，供 /proc 等内核信息导出使用。
+    ///
+    /// # 参数
+    ///
+    /// - `pid`：目标进程的 PID；
+    /// - `f`：检查回调，在释放目标进程的排它锁之后被调用，可以睡眠。
+    ///
+    /// # 返回值
+    ///
+    /// - `Some(R)`：找到目标进程，返回回调的结果；
+    /// - `None`：不存在该 PID 的进程。
+    ///
+    /// # 安全性
+    ///
+    /// - 目标进程可能正在其他硬件线程上运行，快照只复制在持有排它锁时才会被修改的私有数据：
+    ///   名称、命令行（exec 时修改），以及地址空间与文件表的引用（exec 与退出时替换或放弃）；
+    /// - 地址空间与文件表的内容由各自的锁保护，回调需要时自行加锁读取。
+    pub fn inspect<R>(&self, pid: usize, f: impl FnOnce(&ProcView) -> R) -> Option<R> {
+        if pid == 0 {
+            return None
+        }
+
+        let i = (0..NPROC).find(|&i| self.table[i].excl.lock().pid == pid)?;
+        let ppid = self.parents.lock()[i].map(|pi| self.table[pi].excl.lock().pid);
+
+        let leader = self.table[i].excl.lock().leader;
+        let (pgid, sid) = {
+            let lguard = self.table[leader].excl.lock();
+            (lguard.pgid, lguard.sid)
+        };
+
+        let p = &self.table[i];
+        let guard = p.excl.lock();
+        if guard.pid != pid || guard.state == ProcState::UNUSED {
+            return None
+        }
+        let data = unsafe { p.data.get().as_ref().unwrap() };
+        let view = ProcView {
+            pid,
+            slot: i,
+            ppid,
+            pgid,
+            sid,
+            state: guard.state,
+            killed: p.killed.load(Ordering::Relaxed),
+            name: *data.name(),
+            cmdline: *data.cmdline(),
+            vm: data.shared_vm(),
+            fdt: data.shared_fdtable(),
+        };
+        drop(guard);
+        Some(f(&view))
+    }
+}
+
+/// 内核能够处理的信号，0 只用于检查进程是否存在。
+fn signal_supported(sig: usize) -> bool {
+    sig < NSIG && matches!(sig, 0 | SIGINT | SIGKILL | SIGALRM | SIGTERM | SIGCONT | SIGSTOP | SIGTSTP)
+}
+
+/// 某个进程在某一时刻的只读视图，由 [`ProcManager::inspect`] 构造。
+pub struct ProcView {
+    /// 进程标识符。
+    pub pid: usize,
+    /// 进程在进程表中的下标，小于 `NPROC`。
+    pub slot: usize,
+    /// 父进程标识符，init 进程没有父进程。
+    pub ppid: Option<usize>,
+    /// 进程组号。
+    pub pgid: usize,
+    /// 会话号。
+    pub sid: usize,
+    /// 进程状态。
+    pub state: ProcState,
+    /// 进程是否已被标记为杀死。
+    pub killed: bool,
+    /// 进程名称（以 0 结尾）。
+    pub name: [u8; 16],
+    /// 进程的命令行（以 0 结尾）。
+    pub cmdline: [u8; MAXCMDLINE],
+    /// 进程的地址空间，进程已退出时为 `None`。
+    pub vm: Option<Arc<SpinLock<AddrSpace>>>,
+    /// 进程的文件表，进程已退出时为 `None`。
+    pub fdt: Option<Arc<SpinLock<FdTable>>>,
 }
 
 /// fork 创建的子进程首次被调度器调度时，
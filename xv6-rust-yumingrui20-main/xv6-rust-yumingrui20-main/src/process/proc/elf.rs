@@ -1,14 +1,16 @@
 //! 从文件系统加载ELF文件开始执行
 
 use alloc::boxed::Box;
+use alloc::sync::Arc;
 use alloc::str;
 use core::{cmp::min, convert::TryFrom, mem::{self, MaybeUninit}};
 
-use crate::{consts::{MAXARGLEN, PGSIZE, MAXARG}, sleeplock::SleepLockGuard};
+use crate::{consts::{MAXARGLEN, PGSIZE, MAXARG, TRAPFRAME}, sleeplock::SleepLockGuard};
+use crate::spinlock::SpinLock;
 use crate::mm::{Address, PageTable, Addr, VirtAddr, pg_round_up};
 use crate::fs::{ICACHE, Inode, LOG, InodeData};
 
-use super::Proc;
+use super::{AddrSpace, Proc, PROC_MANAGER};
 
 /// 功能说明
 /// 该函数用于将指定路径（path）对应的 ELF 可执行文件加载到进程（Proc）的用户空间中，
@@ -24,8 +26,9 @@ use super::Proc;
 ///    - 加载程序段数据到相应虚拟地址
 /// 5. 在程序段末尾分配两页用户栈空间（一页作为栈，另一页作为栈保护页）。
 /// 6. 将传入的命令行参数逐个拷贝进用户栈，构造用户栈上的 argv 数组。
-/// 7. 更新进程数据结构中的页表、地址空间大小、程序入口点（epc）和栈指针（sp）。
-/// 8. 释放旧的页表对应资源，返回命令行参数数量。
+/// 7. 结束并回收同一线程组中的其他线程（见 `ProcManager::exec_threads`），
+///    更新进程数据结构中的页表、地址空间大小、程序入口点（epc）和栈指针（sp）。
+/// 8. 释放旧的页表对应资源，关闭设置了 `FD_CLOEXEC` 的文件描述符，返回命令行参数数量。
 ///
 /// 参数
 /// - `p: &mut Proc`
@@ -51,6 +54,7 @@ use super::Proc;
 /// - 程序段加载失败（"load program section error"）
 /// - 用户虚拟内存不足，无法分配用户栈（"not enough uvm for user stack"）
 /// - 命令行参数拷贝失败或超出栈空间限制（"cmd args too much for stack" / "copy cmd args to pagetable go wrong"）
+/// - 线程组正在退出或已有其他线程在执行 exec（"process exiting or execing"）
 ///
 /// 安全性
 /// - 该函数通过严格校验 ELF 头与程序段元数据保证加载的合法性，避免内存越界和地址不对齐的问题。
@@ -193,7 +197,21 @@ pub fn load(p: &mut Proc, path: &[u8], argv: &[Option<Box<[u8; MAXARGLEN]>>]) ->
         return Err("copy cmd args to pagetable go wrong")
     }
 
+    // 结束其他线程，之后旧的地址空间只被当前线程使用
+    if unsafe { PROC_MANAGER.exec_threads(p.index) }.is_err() {
+        pgt.dealloc_proc_pagetable(proc_size);
+        return Err("process exiting or execing")
+    }
+    debug_assert!(pdata.single_threaded());
+
+    let vm = match Arc::try_new(SpinLock::new(AddrSpace::from_pagetable(pgt, proc_size), "vm")) {
+        Ok(vm) => vm,
+        Err(_) => return Err("mem not enough"),
+    };
+
     // update the process's info
+    // 名称、命令行与地址空间在持有排它锁时修改，/proc 在排它锁下读取它们
+    let excl = p.excl.lock();
     let tf = unsafe { pdata.tf.as_mut().unwrap() };
     tf.a1 = stack_pointer;
     let off = path.iter().position(|x| *x!=b'/').unwrap();
@@ -201,13 +219,28 @@ pub fn load(p: &mut Proc, path: &[u8], argv: &[Option<Box<[u8; MAXARGLEN]>>]) ->
     for i in 0..count {
         pdata.name[i] = path[i+off];
     }
-    let mut old_pgt = pdata.pagetable.replace(pgt).unwrap();
-    let old_size = pdata.sz;
-    pdata.sz = proc_size;
+    let mut args: [&[u8]; MAXARG] = [&[]; MAXARG];
+    for i in 0..argc {
+        let arg_slice = argv[i].as_deref().unwrap();
+        let len = arg_slice.iter().position(|x| *x==0).unwrap();
+        args[i] = &arg_slice[..len];
+    }
+    pdata.set_cmdline(&args[..argc]);
+    // 旧的地址空间在这里被释放
+    pdata.set_vm(vm, usize::from(TRAPFRAME));
+    drop(excl);
     tf.epc = elf.entry as usize;
     tf.sp = stack_pointer;
-    old_pgt.dealloc_proc_pagetable(old_size);
-    
+
+    // 关闭设置了 FD_CLOEXEC 的文件描述符，文件在释放文件表的锁之后关闭
+    loop {
+        let file = pdata.fdtable().take_cloexec();
+        match file {
+            Some(file) => drop(file),
+            None => break,
+        }
+    }
+
     Ok(argc)
 }
 
@@ -0,0 +1,167 @@
+//! 进程的打开文件表
+//!
+//! [`FdTable`] 保存文件描述符到打开文件的映射、每个文件描述符的标志（如 `FD_CLOEXEC`）以及当前工作目录，
+//! fork 时复制一份，clone 创建的线程则与创建者共享同一份。
+//!
+//! 表按需增长，文件描述符须小于进程的软限制 [`FdTable::limit`]，默认为 [`NFILE`]，
+//! 可以通过 `setrlimit(RLIMIT_NOFILE)` 在硬限制以内调整；硬限制只能降低，最大为 [`NOFILE_MAX`]。
+//!
+//! 表由自旋锁保护，而关闭文件可能需要睡眠（释放 inode 时会写磁盘），
+//! 因此从表中取出的文件必须在释放锁之后再丢弃。
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::consts::fs::{NFILE, NOFILE_MAX, FD_CLOEXEC};
+use crate::fs::{File, Inode, LOG};
+
+/// 一个打开的文件描述符
+#[derive(Clone)]
+struct Fd {
+    file: Arc<File>,
+    /// 文件描述符标志
+    flags: i32,
+}
+
+pub struct FdTable {
+    /// 下标即文件描述符，长度不超过硬限制
+    fds: Vec<Option<Fd>>,
+    /// 软限制，新的文件描述符须小于该值
+    cur: usize,
+    /// 硬限制，软限制不能超过该值
+    max: usize,
+    /// 当前工作目录
+    pub cwd: Option<Inode>,
+}
+
+impl FdTable {
+    pub const fn new() -> Self {
+        Self {
+            fds: Vec::new(),
+            cur: NFILE,
+            max: NOFILE_MAX,
+            cwd: None,
+        }
+    }
+
+    /// 文件描述符 `fd` 对应的打开文件。
+    pub fn get(&self, fd: usize) -> Option<&Arc<File>> {
+        self.fds.get(fd)?.as_ref().map(|f| &f.file)
+    }
+
+    /// 以最小的空闲文件描述符放入 `file`，没有空闲的文件描述符时返回 `None`。
+    pub fn install(&mut self, file: Arc<File>) -> Option<usize> {
+        self.install_from(file, 0)
+    }
+
+    /// 以不小于 `min` 的最小空闲文件描述符放入 `file`，新文件描述符的标志被清零。
+    /// 达到软限制或内存不足时返回 `None`。
+    pub fn install_from(&mut self, file: Arc<File>, min: usize) -> Option<usize> {
+        let fd = (min..self.cur).find(|&fd| self.fds.get(fd).map_or(true, |f| f.is_none()))?;
+        self.slot(fd)?.replace(Fd { file, flags: 0 });
+        Some(fd)
+    }
+
+    /// 在文件描述符 `fd` 处放入 `file`，标志设为 `flags`。
+    ///
+    /// # 返回值
+    /// - `Ok(old)`：`fd` 原先打开的文件；
+    /// - `Err(file)`：`fd` 超出软限制或内存不足，交还 `file`。
+    ///
+    /// 两种情况下返回的文件都须在释放表的锁之后再丢弃。
+    pub fn install_at(&mut self, file: Arc<File>, fd: usize, flags: i32) -> Result<Option<Arc<File>>, Arc<File>> {
+        if fd >= self.cur {
+            return Err(file)
+        }
+        match self.slot(fd) {
+            Some(slot) => Ok(slot.replace(Fd { file, flags }).map(|f| f.file)),
+            None => Err(file),
+        }
+    }
+
+    /// 文件描述符 `fd` 的槽位，必要时扩展表，内存不足时返回 `None`。
+    fn slot(&mut self, fd: usize) -> Option<&mut Option<Fd>> {
+        if fd >= self.fds.len() {
+            self.fds.try_reserve(fd + 1 - self.fds.len()).ok()?;
+            self.fds.resize(fd + 1, None);
+        }
+        Some(&mut self.fds[fd])
+    }
+
+    /// 取出文件描述符 `fd` 对应的打开文件，调用者须在释放表的锁之后再丢弃它。
+    pub fn take(&mut self, fd: usize) -> Option<Arc<File>> {
+        self.fds.get_mut(fd)?.take().map(|f| f.file)
+    }
+
+    /// 取出一个设置了 `FD_CLOEXEC` 的打开文件，exec 时反复调用以关闭这些文件描述符，
+    /// 调用者须在释放表的锁之后再丢弃它。
+    pub fn take_cloexec(&mut self) -> Option<Arc<File>> {
+        let slot = self.fds.iter_mut()
+            .find(|f| f.as_ref().map_or(false, |f| f.flags & FD_CLOEXEC != 0))?;
+        slot.take().map(|f| f.file)
+    }
+
+    /// 文件描述符 `fd` 的标志，`fd` 未打开时返回 `None`。
+    pub fn flags(&self, fd: usize) -> Option<i32> {
+        self.fds.get(fd)?.as_ref().map(|f| f.flags)
+    }
+
+    /// 设置文件描述符 `fd` 的标志，`fd` 未打开时返回 `Err(())`。
+    pub fn set_flags(&mut self, fd: usize, flags: i32) -> Result<(), ()> {
+        self.fds.get_mut(fd).ok_or(())?.as_mut().ok_or(())?.flags = flags;
+        Ok(())
+    }
+
+    /// 所有打开的文件描述符及其文件，按文件描述符递增。
+    pub fn files(&self) -> impl Iterator<Item = (usize, &Arc<File>)> {
+        self.fds.iter().enumerate().filter_map(|(fd, f)| f.as_ref().map(|f| (fd, &f.file)))
+    }
+
+    /// 打开文件数的软限制与硬限制。
+    pub fn limit(&self) -> (usize, usize) {
+        (self.cur, self.max)
+    }
+
+    /// 设置打开文件数的软限制 `cur` 与硬限制 `max`。
+    /// 已打开的超出新限制的文件描述符不受影响。
+    ///
+    /// # 返回值
+    /// - `Ok(())`：设置成功；
+    /// - `Err(())`：`cur` 大于 `max`，或 `max` 超过当前的硬限制。
+    pub fn set_limit(&mut self, cur: usize, max: usize) -> Result<(), ()> {
+        if cur > max || max > self.max {
+            return Err(())
+        }
+        self.cur = cur;
+        self.max = max;
+        Ok(())
+    }
+
+    /// fork 时复制文件表：子进程与父进程共享打开文件，但各自拥有文件描述符及其标志，
+    /// 限制也一并继承。内存不足时返回 `None`。
+    pub fn try_clone(&self) -> Option<Self> {
+        let mut fds = Vec::new();
+        fds.try_reserve_exact(self.fds.len()).ok()?;
+        fds.extend(self.fds.iter().cloned());
+        Some(Self {
+            fds,
+            cur: self.cur,
+            max: self.max,
+            cwd: self.cwd.clone(),
+        })
+    }
+}
+
+impl Drop for FdTable {
+    /// 最后一个使用者退出时关闭所有文件，并在日志事务中释放当前工作目录。
+    fn drop(&mut self) {
+        for f in self.fds.iter_mut() {
+            drop(f.take())
+        }
+        if self.cwd.is_some() {
+            LOG.begin_op();
+            drop(self.cwd.take());
+            LOG.end_op();
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! 快速用户空间互斥（futex）
+//!
+//! 用户程序以内存中的 32 位字实现锁，只在需要阻塞时进入内核：
+//! - `FUTEX_WAIT`：若字的值仍等于 `val`，睡眠直到被唤醒或超时，超时返回 [`FUTEX_TIMEDOUT`]；
+//! - `FUTEX_WAKE`：唤醒最多 `val` 个在该字上等待的进程。
+//!
+//! 等待队列以字的物理地址为睡眠通道，因此共享同一物理页的线程与进程可以互相唤醒，
+//! 字可以位于堆中，也可以位于映射在 `SHMBASE` 之上的共享内存段中。
+//! 内核内存是恒等映射的，用户页的物理地址不会与内核对象的地址（其他睡眠通道）重合。
+//! 检查字的值与睡眠都在 [`FUTEX`] 锁内完成，唤醒方同样先获取该锁，因此不会丢失唤醒。
+
+use core::convert::TryFrom;
+use core::mem;
+use core::sync::atomic::{AtomicI32, Ordering};
+
+use crate::consts::{PGSIZE, FUTEX_WAIT, FUTEX_WAKE, FUTEX_TIMEDOUT};
+use crate::mm::VirtAddr;
+use crate::process::PROC_MANAGER;
+use crate::spinlock::SpinLock;
+use crate::trap;
+
+use super::Proc;
+
+/// 序列化所有 futex 的检查与唤醒。
+static FUTEX: SpinLock<()> = SpinLock::new((), "futex");
+
+impl Proc {
+    /// # 功能说明
+    /// 对用户地址 `addr` 处的字执行 futex 操作 `op`。
+    ///
+    /// # 参数
+    /// - `addr`：用户空间中 4 字节对齐的字的地址，位于堆或共享内存映射中。
+    /// - `op`：`FUTEX_WAIT` 或 `FUTEX_WAKE`。
+    /// - `val`：等待时为期望的值，唤醒时为最多唤醒的进程数。
+    /// - `timeout`：等待的最长时钟周期数，为 0 时不限时，唤醒时忽略。
+    ///
+    /// # 返回值
+    /// - `Ok(0)`：等待后被唤醒；
+    /// - `Ok(FUTEX_TIMEDOUT)`：等待超时，以负数返回给用户，与出错时的 -1 区分；
+    /// - `Ok(n)`：唤醒了 `n` 个进程；
+    /// - `Err(())`：地址无效，或操作未知，或字的值不等于 `val`，或进程被杀死。
+    pub(super) fn futex(&mut self, addr: usize, op: i32, val: i32, timeout: usize) -> Result<usize, ()> {
+        if addr % mem::size_of::<i32>() != 0 {
+            return Err(())
+        }
+        let guard = FUTEX.lock();
+        let pa = {
+            let pdata = self.data.get_mut();
+            let vm = pdata.vm();
+            if !vm.in_user_range(addr, mem::size_of::<i32>()) {
+                return Err(())
+            }
+            let va = VirtAddr::try_from(addr).map_err(|_| ())?;
+            let page = vm.pagetable.walk_addr(va).map_err(|_| ())?;
+            page.into_raw() + addr % PGSIZE
+        };
+
+        match op {
+            FUTEX_WAIT => {
+                // 持有 FUTEX 锁时读取，唤醒方修改字之后才会获取该锁
+                let word = unsafe { &*(pa as *const AtomicI32) };
+                if word.load(Ordering::SeqCst) != val {
+                    return Err(())
+                }
+                if self.killed.load(Ordering::Relaxed) {
+                    return Err(())
+                }
+                let deadline = match timeout {
+                    0 => None,
+                    t => Some(trap::clock_read().wrapping_add(t)),
+                };
+                self.sleep_interruptible(pa, guard, deadline);
+                if self.killed.load(Ordering::Relaxed) {
+                    return Err(())
+                }
+                match deadline {
+                    Some(d) if trap::clock_read().wrapping_sub(d) as isize >= 0 => Ok(FUTEX_TIMEDOUT as usize),
+                    _ => Ok(0),
+                }
+            }
+            FUTEX_WAKE => {
+                let n = if val < 0 { 0 } else { val as usize };
+                let woken = unsafe { PROC_MANAGER.wakeup_n(pa, n) };
+                drop(guard);
+                Ok(woken)
+            }
+            _ => Err(()),
+        }
+    }
+}
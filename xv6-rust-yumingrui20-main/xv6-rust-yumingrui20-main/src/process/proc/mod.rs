@@ -1,31 +1,40 @@
 //! 进程状态管理，包含fork，sleep等多种进程状态操作
 
-use array_macro::array;
-
-use alloc::boxed::Box;
 use alloc::sync::Arc;
+use core::cmp::min;
 use core::mem;
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use core::option::Option;
 use core::ptr;
 use core::cell::UnsafeCell;
 
-use crate::consts::{PGSIZE, fs::{NFILE, ROOTIPATH}};
-use crate::mm::{PageTable, RawPage, RawSinglePage};
+use crate::consts::{PGSIZE, MAXCMDLINE, fs::ROOTIPATH};
+use crate::mm::{RawPage, RawSinglePage};
 use crate::register::{satp, sepc, sstatus};
 use crate::spinlock::{SpinLock, SpinLockGuard};
 use crate::trap::user_trap;
-use crate::fs::{Inode, ICACHE, LOG, File};
+use crate::fs::{Inode, ICACHE, File};
 
 use super::CpuManager;
 use super::PROC_MANAGER;
-use super::cpu::CPU_MANAGER;
+use super::cpu::{CPU_MANAGER, ALL_HARTS};
 use super::{fork_ret, Context, TrapFrame};
 
-use self::syscall::Syscall;
+use self::syscall::syscall_warning;
+
+pub use self::files::FdTable;
+pub use self::vm::AddrSpace;
+pub use self::poll::{PollQueue, PollWaiter};
+
 
 mod syscall;
 mod elf;
+mod files;
+mod futex;
+mod poll;
+mod vm;
+mod systable;
+mod trace;
 
 /// 进程状态枚举类型，表示操作系统内核中进程的不同生命周期状态。
 ///
@@ -45,6 +54,8 @@ pub enum ProcState {
     ALLOCATED,
     /// 进程已退出，处于僵尸状态，等待父进程回收。
     ZOMBIE,
+    /// 进程被停止信号停止，直到收到 `SIGCONT` 才重新可运行。
+    STOPPED,
 }
 
 
@@ -61,6 +72,29 @@ pub struct ProcExcl {
     pub channel: usize,
     /// 进程的唯一标识符（进程ID）。
     pub pid: usize,
+    /// 线程组主线程在进程表中的下标，主线程为自身的下标。
+    pub leader: usize,
+    /// 线程组已开始退出，`exit_status` 为整个线程组的退出状态，仅对主线程有效。
+    pub group_exit: bool,
+    /// 线程组中有线程正在执行 exec，其他线程被杀死时只结束自身，仅对主线程有效。
+    pub execing: bool,
+    /// 睡眠的截止时钟周期，到期时即使通道未被唤醒也会被时钟中断唤醒。
+    pub deadline: Option<usize>,
+    /// 当前的睡眠是否可中断，即能否在睡眠中直接被停止信号停止。
+    pub interruptible: bool,
+    /// 进程组号，仅对主线程有效。
+    pub pgid: usize,
+    /// 会话号，仅对主线程有效。
+    pub sid: usize,
+    /// 被忽略的信号的位图，第 n 位为 1 表示忽略 n 号信号，仅对主线程有效。
+    pub sigignore: u32,
+    /// 使线程组停止的信号，尚未被父进程的 `waitpid` 报告时为 `Some`，仅对主线程有效。
+    pub stop_report: Option<usize>,
+    /// 进程上次运行的 CPU，进程可运行时优先加入该 CPU 的运行队列。
+    pub last_cpu: usize,
+    /// 进程的下标是否在某个 CPU 的运行队列中。
+    /// 调度器取出下标后才清除，因此槽位回收时不清除，避免同一下标重复入队。
+    pub queued: bool,
 }
 
 
@@ -71,6 +105,17 @@ impl ProcExcl {
             exit_status: 0,
             channel: 0,
             pid: 0,
+            leader: 0,
+            group_exit: false,
+            execing: false,
+            deadline: None,
+            interruptible: false,
+            pgid: 0,
+            sid: 0,
+            sigignore: 0,
+            stop_report: None,
+            last_cpu: 0,
+            queued: false,
         }
     }
 
@@ -79,6 +124,15 @@ impl ProcExcl {
         self.pid = 0;
         self.channel = 0;
         self.exit_status = 0;
+        self.leader = 0;
+        self.group_exit = false;
+        self.execing = false;
+        self.deadline = None;
+        self.interruptible = false;
+        self.pgid = 0;
+        self.sid = 0;
+        self.sigignore = 0;
+        self.stop_report = None;
         self.state = ProcState::UNUSED;
     }
 }
@@ -90,20 +144,24 @@ impl ProcExcl {
 pub struct ProcData {
     /// 进程内核栈的起始虚拟地址。
     kstack: usize,
-    /// 进程使用的内存大小（字节数）。
-    sz: usize,
     /// 进程上下文（寄存器状态等），用于上下文切换。
     context: Context,
     /// 进程名称，最长16字节，通常用于调试和显示。
     name: [u8; 16],
-    /// 进程打开的文件数组，元素为可选的引用计数智能指针。
-    open_files: [Option<Arc<File>>; NFILE],
+    /// 最近一次 exec 时的命令行参数，以空格分隔并以 0 结尾，用于 /proc 展示。
+    cmdline: [u8; MAXCMDLINE],
+    /// 打开文件表与当前工作目录，同一线程组的线程共享。
+    fdt: Option<Arc<SpinLock<FdTable>>>,
     /// 指向 TrapFrame 的裸指针，保存用户态寄存器临时值等信息。
     pub tf: *mut TrapFrame,
-    /// 进程的用户页表，管理用户地址空间映射。
-    pub pagetable: Option<Box<PageTable>>,
-    /// 进程当前工作目录的 inode。
-    pub cwd: Option<Inode>,
+    /// 陷阱帧在用户页表中的虚拟地址。
+    tf_va: usize,
+    /// 用户地址空间（页表与大小），同一线程组的线程共享。
+    vm: Option<Arc<SpinLock<AddrSpace>>>,
+    /// 系统调用跟踪掩码，第 n 位为 1 表示跟踪 n 号系统调用。
+    trace_mask: u64,
+    /// 跟踪输出写入的文件，为 `None` 时写入内核日志。
+    trace_file: Option<Arc<File>>,
 }
 
 
@@ -111,13 +169,15 @@ impl ProcData {
     const fn new() -> Self {
         Self {
             kstack: 0,
-            sz: 0,
             context: Context::new(),
             name: [0; 16],
-            open_files: array![_ => None; NFILE],
+            cmdline: [0; MAXCMDLINE],
+            fdt: None,
             tf: ptr::null_mut(),
-            pagetable: None,
-            cwd: None,
+            tf_va: 0,
+            vm: None,
+            trace_mask: 0,
+            trace_file: None,
         }
     }
 
@@ -154,6 +214,85 @@ impl ProcData {
         &mut self.context as *mut _
     }
 
+    /// 进程名称（以 0 结尾）。
+    #[inline]
+    pub fn name(&self) -> &[u8; 16] {
+        &self.name
+    }
+
+    /// 进程的命令行（以 0 结尾）。
+    #[inline]
+    pub fn cmdline(&self) -> &[u8; MAXCMDLINE] {
+        &self.cmdline
+    }
+
+    /// 记录 exec 时的命令行参数，各参数之间以空格分隔，超长部分被截断。
+    pub fn set_cmdline(&mut self, argv: &[&[u8]]) {
+        let mut pos = 0;
+        for (i, arg) in argv.iter().enumerate() {
+            if i > 0 && pos < MAXCMDLINE-1 {
+                self.cmdline[pos] = b' ';
+                pos += 1;
+            }
+            let count = min(arg.len(), MAXCMDLINE-1-pos);
+            self.cmdline[pos..pos+count].copy_from_slice(&arg[..count]);
+            pos += count;
+        }
+        self.cmdline[pos] = 0;
+    }
+
+    /// 进程用户地址空间的大小（字节数）。
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.vm.as_ref().map_or(0, |vm| vm.lock().sz)
+    }
+
+    /// 锁定并返回进程的用户地址空间。
+    #[inline]
+    pub fn vm(&self) -> SpinLockGuard<'_, AddrSpace> {
+        self.vm.as_ref().unwrap().lock()
+    }
+
+    /// 锁定并返回进程的打开文件表，下标即文件描述符。
+    #[inline]
+    pub fn fdtable(&self) -> SpinLockGuard<'_, FdTable> {
+        self.fdt.as_ref().unwrap().lock()
+    }
+
+    /// 地址空间的一个引用，进程没有地址空间（已退出或槽位未使用）时返回 `None`。
+    /// 其他进程需在持有本进程排它锁时调用，替换与放弃地址空间都在持有排它锁时进行。
+    pub fn shared_vm(&self) -> Option<Arc<SpinLock<AddrSpace>>> {
+        self.vm.clone()
+    }
+
+    /// 文件表的一个引用，进程已关闭文件表（退出后）时返回 `None`。
+    /// 与 [`shared_vm`](Self::shared_vm) 一样，其他进程需在持有本进程排它锁时调用。
+    pub fn shared_fdtable(&self) -> Option<Arc<SpinLock<FdTable>>> {
+        self.fdt.clone()
+    }
+
+    /// 进程当前工作目录的 inode。
+    pub fn cwd(&self) -> Inode {
+        self.fdtable().cwd.clone().unwrap()
+    }
+
+    /// 地址空间是否只被当前线程使用（没有其他线程共享，包括尚未回收的线程）。
+    pub fn single_threaded(&self) -> bool {
+        self.vm.as_ref().map_or(true, |vm| Arc::strong_count(vm) == 1)
+    }
+
+    /// 替换进程的地址空间，陷阱帧在新地址空间中映射于 `tf_va`，旧的地址空间被释放。
+    pub fn set_vm(&mut self, vm: Arc<SpinLock<AddrSpace>>, tf_va: usize) {
+        drop(self.vm.replace(vm));
+        self.tf_va = tf_va;
+    }
+
+    /// 陷阱帧在用户页表中的虚拟地址，返回用户空间时交给 `userret`。
+    #[inline]
+    pub fn trapframe_va(&self) -> usize {
+        self.tf_va
+    }
+
     /// # 功能说明
     /// 准备进程从内核态返回到用户态所需的 TrapFrame 和寄存器状态，
     /// 并返回用户页表的 satp 寄存器值以切换地址空间。
@@ -196,15 +335,16 @@ impl ProcData {
         // 恢复之前存储在 sepc 中的用户程序计数器
         sepc::write(tf.epc);
 
-        self.pagetable.as_ref().unwrap().as_satp()
+        self.vm().pagetable.as_satp()
     }
 
-    /// 简单检查用户传入的虚拟地址是否在合法范围内。
+    /// 简单检查用户传入的虚拟地址是否在合法范围内：位于堆中（可以等于堆的大小），或位于某一个共享内存映射中。
     fn check_user_addr(&self, user_addr: usize) -> Result<(), ()> {
-        if user_addr > self.sz {
-            Err(())
-        } else {
+        let vm = self.vm();
+        if user_addr <= vm.sz || vm.in_user_range(user_addr, 1) {
             Ok(())
+        } else {
+            Err(())
         }
     }
 
@@ -213,7 +353,7 @@ impl ProcData {
     /// 实际操作会转发调用到页表的对应方法。
     #[inline]
     pub fn copy_out(&mut self, src: *const u8, dst: usize, count: usize) -> Result<(), ()> {
-        self.pagetable.as_mut().unwrap().copy_out(src, dst, count)
+        self.vm().pagetable.copy_out(src, dst, count)
     }
 
     /// 将内容从用户的源虚拟地址 src 复制到内核空间的目标地址 dst。
@@ -221,29 +361,13 @@ impl ProcData {
     /// 实际操作会转发调用到页表的对应方法。
     #[inline]
     pub fn copy_in(&self, src: usize, dst: *mut u8, count: usize) -> Result<(), ()> {
-        self.pagetable.as_ref().unwrap().copy_in(src, dst, count)
+        self.vm().pagetable.copy_in(src, dst, count)
     }
 
-    /// 分配一个新的文件描述符。
-    /// 返回的文件描述符可直接作为索引使用，因为它仅属于当前进程私有。
-    fn alloc_fd(&mut self) -> Option<usize> {
-        self.open_files.iter()
-            .enumerate()
-            .find(|(_, f)| f.is_none())
-            .map(|(i, _)| i)
-    }
-
-    /// 分配一对文件描述符。
-    /// 通常用于管道（pipe）的创建。
-    fn alloc_fd2(&mut self) -> Option<(usize, usize)> {
-        let mut iter = self.open_files.iter()
-            .enumerate()
-            .filter(|(_, f)| f.is_none())
-            .take(2)
-            .map(|(i, _)| i);
-        let fd1 = iter.next()?;
-        let fd2 = iter.next()?;
-        Some((fd1, fd2))
+    /// 将用户虚拟地址 src 处以 0 结尾的字符串复制到 dst。
+    #[inline]
+    pub fn copy_in_str(&self, src: usize, dst: &mut [u8]) -> Result<(), &'static str> {
+        self.vm().pagetable.copy_in_str(src, dst)
     }
 
     /// # 功能说明
@@ -275,51 +399,23 @@ impl ProcData {
     /// - 释放页表时必须保证当前进程内存映射处于可安全释放状态，避免悬挂指针。
     pub fn cleanup(&mut self) {
         self.name[0] = 0;
+        self.cmdline[0] = 0;
+        self.trace_mask = 0;
         let tf = self.tf;
         self.tf = ptr::null_mut();
         if !tf.is_null() {
             unsafe { RawSinglePage::from_raw_and_drop(tf as *mut u8); }
         }
-        let pgt = self.pagetable.take();
-        if let Some(mut pgt) = pgt {
-            pgt.dealloc_proc_pagetable(self.sz);
-        }
-        self.sz = 0;
+        self.release_vm();
     }
 
-    /// # 功能说明
-    /// 关闭进程打开的所有文件，并释放当前工作目录的引用。
-    /// 该函数通常在进程退出时调用，用于清理进程的文件资源和目录引用。
-    ///
-    /// # 流程解释
-    /// 1. 遍历进程打开的文件句柄数组 `open_files`，逐个取出并释放文件引用。
-    /// 2. 调用日志系统 `LOG` 的 `begin_op()`，开始一次文件系统操作。
-    /// 3. 使用断言确保当前工作目录 `cwd` 不为空。
-    /// 4. 释放当前工作目录的引用（调用 `take()` 后立即 drop）。
-    /// 5. 调用 `LOG.end_op()` 结束日志操作。
-    ///
-    /// # 参数
-    /// - `&mut self`：当前进程私有数据的可变引用，用于操作其文件和目录成员。
-    ///
-    /// # 返回值
-    /// - 无返回值。
-    ///
-    /// # 可能的错误
-    /// - 若 `cwd` 为 `None`，`debug_assert!` 会在调试模式下触发断言失败。
-    /// - 释放文件句柄和目录引用过程中，若底层文件系统操作失败，可能影响资源释放完整性（依赖日志系统机制）。
-    ///
-    /// # 安全性
-    /// - 函数依赖外部日志系统 `LOG` 正确管理文件系统操作的事务一致性。
-    /// - 关闭文件和释放目录引用必须确保调用时无其他线程或代码持有相关资源，避免竞态条件。
-    /// - 本函数无不安全代码调用，符合 Rust 安全规范。
-    pub fn close_files(&mut self) {
-        for f in self.open_files.iter_mut() {
-            drop(f.take())
+    /// 撤销陷阱帧在地址空间中的映射并放弃对地址空间的引用。
+    /// 线程退出时即调用，这样线程组中剩下的线程无需等待它被回收即可 exec。
+    pub fn release_vm(&mut self) {
+        if let Some(vm) = self.vm.take() {
+            vm.lock().unmap_trapframe(self.tf_va);
         }
-        LOG.begin_op();
-        debug_assert!(self.cwd.is_some());
-        drop(self.cwd.take());
-        LOG.end_op();
+        self.tf_va = 0;
     }
 
     /// # 功能说明
@@ -352,15 +448,19 @@ impl ProcData {
     /// - 调用者需保证调整操作在进程内存空间允许的范围内，避免非法访问。
     /// - 函数内部无使用不安全代码，符合 Rust 内存安全原则。
     fn sbrk(&mut self, increment: i32) -> Result<usize, ()> {
-        let old_size = self.sz;
+        let mut vm = self.vm();
+        let old_size = vm.sz;
         if increment > 0 {
             let new_size = old_size + (increment as usize);
-            self.pagetable.as_mut().unwrap().uvm_alloc(old_size, new_size)?;
-            self.sz = new_size;
+            if new_size > AddrSpace::max_size() {
+                return Err(())
+            }
+            vm.pagetable.uvm_alloc(old_size, new_size)?;
+            vm.sz = new_size;
         } else if increment < 0 {
             let new_size = old_size - ((-increment) as usize);
-            self.pagetable.as_mut().unwrap().uvm_dealloc(old_size, new_size);
-            self.sz = new_size;
+            vm.pagetable.uvm_dealloc(old_size, new_size);
+            vm.sz = new_size;
         }
         Ok(old_size)
     }
@@ -371,7 +471,7 @@ impl ProcData {
 /// 该结构体封装了进程在进程表中的索引，
 /// 进程状态的排它锁保护数据（`ProcExcl`），
 /// 进程私有数据（`ProcData`），
-/// 以及进程是否被杀死的原子标志。
+/// 以及进程是否被杀死、是否被要求停止的原子标志。
 ///
 /// 通过该结构体，操作系统能够管理进程调度、状态更新和资源访问的并发安全。
 pub struct Proc {
@@ -383,6 +483,11 @@ pub struct Proc {
     pub data: UnsafeCell<ProcData>,
     /// 标识进程是否被杀死的原子布尔变量，用于调度和信号处理。
     pub killed: AtomicBool,
+    /// 待处理的停止信号，0 表示没有；进程在返回用户空间前检查并停止。
+    pub stop: AtomicUsize,
+    /// 允许运行进程的 CPU 的位图，第 n 位为 1 表示可以在 n 号 CPU 上运行。
+    /// 窃取任务的调度器不持有进程的锁读取它，因此使用原子变量。
+    pub affinity: AtomicUsize,
 }
 
 impl Proc {
@@ -392,9 +497,17 @@ impl Proc {
             excl: SpinLock::new(ProcExcl::new(), "ProcExcl"),
             data: UnsafeCell::new(ProcData::new()),
             killed: AtomicBool::new(false),
+            stop: AtomicUsize::new(0),
+            affinity: AtomicUsize::new(ALL_HARTS),
         }
     }
 
+    /// 进程在进程表中的下标。
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
     /// # 功能说明
     /// 初始化第一个用户进程的相关数据，包括加载初始化代码到用户页表、
     /// 设置用户程序计数器（PC）和栈指针（SP），
@@ -406,7 +519,7 @@ impl Proc {
     /// 3. 设置进程内存大小 `sz` 为一页大小（`PGSIZE`）。
     /// 4. 获取进程的 TrapFrame 指针 `tf`，设置用户态程序计数器 `epc` 为 0，
     ///    栈指针 `sp` 为一页大小，准备用户态执行环境。
-    /// 5. 将进程名称设置为 `"initcode"`，通过不安全的内存复制完成。
+    /// 5. 将进程名称与命令行设置为 `"initcode"`，名称通过不安全的内存复制完成。
     /// 6. 断言当前工作目录 `cwd` 为空，确保进程尚未设置目录。
     /// 7. 通过根目录路径 `ROOTIPATH` 从 inode 缓存中获取根目录 inode，
     ///    并设置为当前工作目录。
@@ -430,8 +543,11 @@ impl Proc {
         let pd = self.data.get_mut();
 
         // 在用户页表中映射初始化代码
-        pd.pagetable.as_mut().unwrap().uvm_init(&INITCODE);
-        pd.sz = PGSIZE;
+        {
+            let mut vm = pd.vm();
+            vm.pagetable.uvm_init(&INITCODE);
+            vm.sz = PGSIZE;
+        }
 
         // 准备返回程序计数器和栈指针
         let tf = unsafe { pd.tf.as_mut().unwrap() };
@@ -446,9 +562,54 @@ impl Proc {
                 init_name.len()
             );
         }
+        pd.set_cmdline(&[&init_name[..init_name.len()-1]]);
 
-        debug_assert!(pd.cwd.is_none());
-        pd.cwd = Some(ICACHE.namei(&ROOTIPATH).expect("cannot find root inode by b'/'"));
+        debug_assert!(pd.fdt.is_none());
+        let mut fdt = FdTable::new();
+        fdt.cwd = Some(ICACHE.namei(&ROOTIPATH).expect("cannot find root inode by b'/'"));
+        pd.fdt = Some(Arc::try_new(SpinLock::new(fdt, "fdtable")).expect("cannot allocate fd table"));
+    }
+
+    /// # 功能说明
+    /// 关闭进程打开的所有文件，并释放当前工作目录的引用。
+    /// 该函数通常在进程退出时调用，用于清理进程的文件资源和目录引用。
+    ///
+    /// # 流程解释
+    /// 1. 在持有排它锁时取下文件表，这样 /proc 在排它锁下取得的文件表引用总是有效的。
+    /// 2. 释放排它锁之后放弃文件表的引用，最后一个引用被放弃时文件与当前工作目录被关闭（可能睡眠）。
+    /// 3. 放弃系统调用跟踪输出文件的引用。
+    ///
+    /// # 参数
+    /// - `&self`：当前进程，只能由进程自身在退出时调用。
+    ///
+    /// # 返回值
+    /// - 无返回值。
+    ///
+    /// # 可能的错误
+    /// - 若文件表已被关闭，`debug_assert!` 会在调试模式下触发断言失败。
+    /// - 释放文件句柄和目录引用过程中，若底层文件系统操作失败，可能影响资源释放完整性（依赖日志系统机制）。
+    ///
+    /// # 安全性
+    /// - 函数依赖外部日志系统 `LOG` 正确管理文件系统操作的事务一致性。
+    /// - 关闭文件和释放目录引用必须确保调用时无其他线程或代码持有相关资源，避免竞态条件。
+    /// - 进程私有数据通过 `UnsafeCell` 访问，只有进程自身会修改其文件表，其他进程只在持有排它锁时读取。
+    pub fn close_files(&self) {
+        let pdata = unsafe { self.data.get().as_mut().unwrap() };
+        let fdt = {
+            let _excl = self.excl.lock();
+            pdata.fdt.take()
+        };
+        debug_assert!(fdt.is_some());
+        drop(fdt);
+        drop(pdata.trace_file.take());
+    }
+
+    /// 线程退出时撤销陷阱帧映射并放弃对地址空间的引用，与 [`close_files`](Self::close_files)
+    /// 一样在持有排它锁时进行。
+    pub fn release_vm(&self) {
+        let pdata = unsafe { self.data.get().as_mut().unwrap() };
+        let _excl = self.excl.lock();
+        pdata.release_vm();
     }
 
     /// 如果 killed 标志为 true，则终止当前进程
@@ -458,6 +619,14 @@ impl Proc {
         }
     }
 
+    /// 如果有待处理的停止信号，则停止当前进程直到收到 `SIGCONT`，
+    /// 在返回用户空间之前调用。
+    pub fn check_stop(&mut self) {
+        if self.stop.load(Ordering::Relaxed) != 0 {
+            unsafe { PROC_MANAGER.stopping(self.index); }
+        }
+    }
+
     /// 通过以下方式终止当前进程：
     /// 1. 将其 killed 标志设置为 true
     /// 2. 然后退出
@@ -474,8 +643,9 @@ impl Proc {
     /// 1. 使能中断，允许系统中断处理。
     /// 2. 通过不安全代码获取当前进程的 TrapFrame 指针，读取系统调用号 `a7`。
     /// 3. 调用 `tf.admit_ecall()`，完成系统调用的相关状态处理（如跳过指令等）。
-    /// 4. 使用 `match` 匹配系统调用号，调用对应的系统调用实现函数。
-    /// 5. 若系统调用号非法，调用 `panic!` 抛出异常，终止内核执行。
+    /// 4. 在系统调用表（见 `systable`）中查找系统调用号，调用对应的系统调用实现函数，
+    ///    需要跟踪时在调用前后输出跟踪信息。
+    /// 5. 若系统调用号非法，输出警告（启用 `kernel_warning` 时）并使系统调用失败。
     /// 6. 将系统调用执行结果写入 TrapFrame 的返回寄存器 `a0`，
     ///    成功返回实际结果，失败返回 -1（以 `usize` 格式存储）。
     ///
@@ -486,7 +656,7 @@ impl Proc {
     /// - 无返回值，系统调用结果通过 TrapFrame 的 `a0` 寄存器返回给用户态。
     ///
     /// # 可能的错误
-    /// - 系统调用号非法时，会导致内核 panic，内核崩溃或重启。
+    /// - 系统调用号非法时返回 -1，不影响内核与其他进程。
     /// - 各个系统调用具体实现可能返回错误，统一映射为返回值 -1。
     ///
     /// # 安全性
@@ -499,32 +669,17 @@ impl Proc {
         let tf = unsafe { self.data.get_mut().tf.as_mut().unwrap() };
         let a7 = tf.a7;
         tf.admit_ecall();
-        let sys_result = match a7 {
-            1 => self.sys_fork(),
-            2 => self.sys_exit(),
-            3 => self.sys_wait(),
-            4 => self.sys_pipe(),
-            5 => self.sys_read(),
-            6 => self.sys_kill(),
-            7 => self.sys_exec(),
-            8 => self.sys_fstat(),
-            9 => self.sys_chdir(),
-            10 => self.sys_dup(),
-            11 => self.sys_getpid(),
-            12 => self.sys_sbrk(),
-            13 => self.sys_sleep(),
-            14 => self.sys_uptime(),
-            15 => self.sys_open(),
-            16 => self.sys_write(),
-            17 => self.sys_mknod(),
-            18 => self.sys_unlink(),
-            19 => self.sys_link(),
-            20 => self.sys_mkdir(),
-            21 => self.sys_close(),
-            _ => {
-                panic!("unknown syscall num: {}", a7);
-            }
+        let trace = self.trace_enter(a7);
+        let sys_result = match systable::lookup(a7) {
+            Some(desc) => (desc.handler)(self),
+            None => {
+                syscall_warning(format_args!("unknown syscall num: {}", a7));
+                Err(())
+            },
         };
+        if let Some(line) = trace {
+            self.trace_exit(a7, line, sys_result);
+        }
         tf.a0 = match sys_result {
             Ok(ret) => ret,
             Err(()) => -1isize as usize,
@@ -562,7 +717,7 @@ impl Proc {
     pub fn yielding(&mut self) {
         let mut guard = self.excl.lock();
         assert_eq!(guard.state, ProcState::RUNNING);
-        guard.state = ProcState::RUNNABLE;
+        unsafe { CPU_MANAGER.enqueue(self, &mut guard); }
         guard = unsafe { CPU_MANAGER.my_cpu_mut().sched(guard,
             self.data.get_mut().get_context()) };
         drop(guard);
@@ -600,13 +755,43 @@ impl Proc {
     /// - 保证在调用时持有适当的锁，避免竞态条件和死锁。
     /// - 进程状态和通道的修改均在锁保护下完成，保证线程安全。
     pub fn sleep<T>(&self, channel: usize, guard: SpinLockGuard<'_, T>) {
+        self.sleep_until(channel, guard, None);
+    }
+
+    /// 与 [`sleep`](Self::sleep) 相同，但 `deadline` 为 `Some` 时，
+    /// 时钟周期数到达 `deadline` 后即使没有被唤醒也会返回。
+    /// 返回后调用者需自行比较时钟判断是否超时。
+    pub fn sleep_until<T>(&self, channel: usize, guard: SpinLockGuard<'_, T>, deadline: Option<usize>) {
+        self.sleep_on(channel, guard, deadline, false);
+    }
+
+    /// 与 [`sleep_until`](Self::sleep_until) 相同，但睡眠是可中断的：
+    /// 睡眠期间收到停止信号时进程直接进入 `STOPPED` 状态，继续后与被唤醒一样返回。
+    /// 只能用于等待用户态事件（终端输入、管道、子进程等）且外层有重新检查循环的睡眠。
+    pub fn sleep_interruptible<T>(&self, channel: usize, guard: SpinLockGuard<'_, T>, deadline: Option<usize>) {
+        self.sleep_on(channel, guard, deadline, true);
+    }
+
+    fn sleep_on<T>(&self, channel: usize, guard: SpinLockGuard<'_, T>, deadline: Option<usize>, interruptible: bool) {
         // 必须先获取 p->lock 锁，才能修改 p->state，然后调用 sched。
         // 一旦我们持有 p->lock 锁，就可以确保不会错过任何唤醒操作（唤醒操作会锁定 p->lock），因此释放 lk 锁是安全的。
         let mut excl_guard = self.excl.lock();
         drop(guard);
+        // 此时只应持有进程自身的锁
+        #[cfg(feature = "lockdep")]
+        crate::lockdep::might_sleep(1);
+
+        // 已有待处理的停止信号时先停止，继续后作为一次虚假唤醒返回，由调用者重新检查
+        if interruptible && self.stop.load(Ordering::Relaxed) != 0 {
+            drop(excl_guard);
+            unsafe { PROC_MANAGER.stopping(self.index); }
+            return
+        }
 
         // 进入睡眠
         excl_guard.channel = channel;
+        excl_guard.deadline = deadline;
+        excl_guard.interruptible = interruptible;
         excl_guard.state = ProcState::SLEEPING;
 
         unsafe {
@@ -616,6 +801,8 @@ impl Proc {
         }
 
         excl_guard.channel = 0;
+        excl_guard.deadline = None;
+        excl_guard.interruptible = false;
         drop(excl_guard);
     }
 
@@ -629,14 +816,14 @@ impl Proc {
     /// 2. 通过 `PROC_MANAGER.alloc_proc()` 分配一个新的子进程，
     ///    若失败则返回错误 `Err(())`。
     /// 3. 获取子进程的排它锁 `cexcl` 和私有数据 `cdata`。
-    /// 4. 复制父进程的用户内存到子进程页表，调用 `uvm_copy`。
+    /// 4. 复制父进程的用户内存到子进程页表，调用 `uvm_copy`，并映射父进程的共享内存段。
     ///    若复制失败，清理子进程相关资源，返回错误。
     /// 5. 设置子进程的内存大小 `sz` 与父进程一致。
     /// 6. 复制 TrapFrame（用户寄存器状态），并将子进程的返回值寄存器 `a0` 设为 0。
-    /// 7. 克隆父进程的打开文件数组和当前工作目录。
+    /// 7. 复制父进程的打开文件表（含当前工作目录）。
     /// 8. 复制父进程名称到子进程。
-    /// 9. 记录子进程的进程 ID（pid）。
-    /// 10. 设置子进程的父进程为当前进程。
+    /// 9. 记录子进程的进程 ID（pid），继承进程组、会话与信号的处理方式。
+    /// 10. 设置子进程的父进程为当前线程组的主线程。
     /// 11. 将子进程状态置为 `RUNNABLE`，表示可调度。
     /// 12. 返回子进程的进程 ID。
     ///
@@ -658,22 +845,34 @@ impl Proc {
     /// - 调用者需保证进程状态和私有数据在调用时无并发冲突。
     /// - 子进程资源清理确保不产生内存泄漏和悬挂指针。
     fn fork(&mut self) -> Result<usize, ()> {
+        let leader = self.excl.lock().leader;
+        let (pgid, sid, sigignore) = unsafe { PROC_MANAGER.inherited(leader) };
         let pdata = self.data.get_mut();
-        let child = unsafe { PROC_MANAGER.alloc_proc().ok_or(())? };
+
+        // 复制打开文件表和当前工作目录，在获取子进程的锁之前完成，
+        // 因为失败时丢弃文件表可能需要睡眠
+        let fdt = pdata.fdtable().try_clone().ok_or(())?;
+        let fdt = Arc::try_new(SpinLock::new(fdt, "fdtable")).map_err(|_| ())?;
+
+        let child = unsafe { PROC_MANAGER.alloc_proc(None).ok_or(())? };
         let mut cexcl = child.excl.lock();
         let cdata = unsafe { child.data.get().as_mut().unwrap() };
 
         // 克隆内存
-        let cpgt = cdata.pagetable.as_mut().unwrap();
-        let size = pdata.sz;
-        if pdata.pagetable.as_mut().unwrap().uvm_copy(cpgt, size).is_err() {
+        let copied = {
+            let mut pvm = pdata.vm();
+            let mut cvm = cdata.vm();
+            let size = pvm.sz;
+            pvm.pagetable.uvm_copy(&mut cvm.pagetable, size).map(|()| cvm.sz = size)
+                .and_then(|()| pvm.shm_copy(&mut cvm))
+        };
+        if copied.is_err() {
             debug_assert_eq!(child.killed.load(Ordering::Relaxed), false);
             child.killed.store(false, Ordering::Relaxed);
             cdata.cleanup();
             cexcl.cleanup();
             return Err(())
         }
-        cdata.sz = size;
 
         // 克隆陷阱帧并在 a0 寄存器上返回 0
         unsafe {
@@ -681,25 +880,84 @@ impl Proc {
             cdata.tf.as_mut().unwrap().a0 = 0;
         }
 
-        // 克隆已打开的文件和当前工作目录
-        cdata.open_files.clone_from(&pdata.open_files);
-        cdata.cwd.clone_from(&pdata.cwd);
+        cdata.fdt = Some(fdt);
+
+        // 继承系统调用跟踪设置
+        cdata.trace_mask = pdata.trace_mask;
+        cdata.trace_file.clone_from(&pdata.trace_file);
         
-        // 复制进程名称
+        // 复制进程名称与命令行
         cdata.name.copy_from_slice(&pdata.name);
+        cdata.cmdline.copy_from_slice(&pdata.cmdline);
 
         let cpid = cexcl.pid;
 
+        // 继承进程组、会话与信号的处理方式
+        cexcl.pgid = pgid;
+        cexcl.sid = sid;
+        cexcl.sigignore = sigignore;
+        child.affinity.store(self.affinity.load(Ordering::Relaxed), Ordering::Relaxed);
+
         drop(cexcl);
 
-        unsafe { PROC_MANAGER.set_parent(child.index, self.index); }
+        // 线程创建的子进程同样属于整个线程组
+        unsafe { PROC_MANAGER.set_parent(child.index, leader); }
 
         let mut cexcl = child.excl.lock();
-        cexcl.state = ProcState::RUNNABLE;
+        unsafe { CPU_MANAGER.enqueue(child, &mut cexcl); }
         drop(cexcl);
 
         Ok(cpid)
     }
+
+    /// # 功能说明
+    /// 在当前线程组中创建一个新线程（clone）。
+    /// 新线程与创建者共享地址空间、打开文件表与当前工作目录，
+    /// 拥有自己的陷阱帧与内核栈，从用户函数 `func` 开始执行。
+    ///
+    /// # 流程解释
+    /// 1. 通过 `PROC_MANAGER.alloc_proc` 分配新线程，陷阱帧映射到共享地址空间。
+    /// 2. 复制创建者的陷阱帧，将 `epc` 设为 `func`、`a0` 设为 `arg`、`sp` 设为 `stack`，
+    ///    `ra` 清零，`func` 不能返回，应以 `exit_thread` 结束。
+    /// 3. 共享打开文件表，复制名称、命令行与跟踪设置。
+    /// 4. 记录线程组主线程，继承 CPU 亲和性，将新线程加入运行队列。
+    ///
+    /// # 参数
+    /// - `func`：新线程的入口地址。
+    /// - `arg`：传给入口函数的参数。
+    /// - `stack`：新线程的用户栈顶，由调用者分配。
+    ///
+    /// # 返回值
+    /// - `Ok(usize)`：新线程的线程号，线程号与进程号取自同一空间。
+    /// - `Err(())`：进程表已满、线程数已达上限或内存不足。
+    fn clone_thread(&mut self, func: usize, arg: usize, stack: usize) -> Result<usize, ()> {
+        let leader = self.excl.lock().leader;
+        let pdata = self.data.get_mut();
+
+        let child = unsafe { PROC_MANAGER.alloc_proc(pdata.vm.as_ref()).ok_or(())? };
+        let mut cexcl = child.excl.lock();
+        let cdata = unsafe { child.data.get().as_mut().unwrap() };
+
+        unsafe {
+            ptr::copy_nonoverlapping(pdata.tf, cdata.tf, 1);
+            let tf = cdata.tf.as_mut().unwrap();
+            tf.epc = func;
+            tf.a0 = arg;
+            tf.sp = stack;
+            tf.ra = 0;
+        }
+
+        cdata.fdt.clone_from(&pdata.fdt);
+        cdata.trace_mask = pdata.trace_mask;
+        cdata.trace_file.clone_from(&pdata.trace_file);
+        cdata.name.copy_from_slice(&pdata.name);
+        cdata.cmdline.copy_from_slice(&pdata.cmdline);
+
+        cexcl.leader = leader;
+        child.affinity.store(self.affinity.load(Ordering::Relaxed), Ordering::Relaxed);
+        unsafe { CPU_MANAGER.enqueue(child, &mut cexcl); }
+        Ok(cexcl.pid)
+    }
 }
 
 impl Proc {
@@ -752,33 +1010,33 @@ impl Proc {
     ///
     /// # 流程解释
     /// 1. 调用 `arg_raw` 获取第 `n` 个参数的原始值，视为文件描述符。
-    /// 2. 检查文件描述符是否超出最大允许值 `NFILE`。
+    /// 2. 检查文件描述符是否超出文件表的范围。
     /// 3. 检查该文件描述符对应的文件是否存在（是否为 `Some`）。
-    /// 4. 若检查通过，返回文件描述符；否则返回错误。
+    /// 4. 若检查通过，返回文件描述符及其文件的一个引用；否则返回错误。
+    ///
+    /// 文件表可能被同一线程组的其他线程修改，返回的引用保证文件在本次
+    /// 系统调用期间不会因其他线程关闭该描述符而被释放。
     ///
     /// # 参数
-    /// - `&mut self`：当前进程可变引用，用于访问打开的文件数组。
+    /// - `&mut self`：当前进程可变引用，用于访问打开的文件表。
     /// - `n`：参数索引，指明从第几个寄存器读取文件描述符。
     ///
     /// # 返回值
-    /// - `Ok(usize)`：合法且打开的文件描述符。
+    /// - `Ok((fd, file))`：合法且打开的文件描述符及其文件。
     /// - `Err(())`：无效或未打开的文件描述符。
     ///
     /// # 可能的错误
-    /// - 文件描述符超过允许的最大值 `NFILE`。
+    /// - 文件描述符超出文件表的范围。
     /// - 文件描述符对应的文件句柄为 `None`，表示文件未打开。
     ///
     /// # 安全性
     /// - 该函数内部调用 `arg_raw` 使用了 `unsafe`，需保证寄存器指针有效。
     /// - 读取和判断文件句柄时，确保没有并发修改导致状态不一致。
     #[inline]
-    fn arg_fd(&mut self, n: usize) -> Result<usize, ()> {
+    fn arg_fd(&mut self, n: usize) -> Result<(usize, Arc<File>), ()> {
         let fd = self.arg_raw(n);
-        if fd >= NFILE || self.data.get_mut().open_files[fd].is_none() {
-            Err(())
-        } else {
-            Ok(fd)
-        }
+        let file = self.data.get_mut().fdtable().get(fd).cloned().ok_or(())?;
+        Ok((fd, file))
     }
 
     /// # 功能说明
@@ -811,8 +1069,8 @@ impl Proc {
     /// - 需要保证缓冲区 `buf` 大小足够存放用户字符串。
     fn arg_str(&self, n: usize, buf: &mut [u8]) -> Result<(), &'static str> {
         let addr: usize = self.arg_raw(n);
-        let pagetable = unsafe { self.data.get().as_ref().unwrap().pagetable.as_ref().unwrap() };
-        pagetable.copy_in_str(addr, buf)?;
+        let pd = unsafe { self.data.get().as_ref().unwrap() };
+        pd.copy_in_str(addr, buf)?;
         Ok(())
     }
 
@@ -846,7 +1104,7 @@ impl Proc {
     /// - 调用者需保证地址合法且缓冲区足够存储数据。
     fn fetch_addr(&self, addr: usize) -> Result<usize, &'static str> {
         let pd = unsafe { self.data.get().as_ref().unwrap() };
-        if addr + mem::size_of::<usize>() > pd.sz {
+        if addr + mem::size_of::<usize>() > pd.size() {
             Err("input addr > proc's mem size")
         } else {
             let mut ret: usize = 0;
@@ -864,7 +1122,7 @@ impl Proc {
     ///从虚拟地址addr获取一个以空字符结尾的字符串到内核缓冲区中。
     fn fetch_str(&self, addr: usize, dst: &mut [u8]) -> Result<(), &'static str>{
         let pd = unsafe { self.data.get().as_ref().unwrap() };
-        pd.pagetable.as_ref().unwrap().copy_in_str(addr, dst)
+        pd.copy_in_str(addr, dst)
     }
 }
 
@@ -0,0 +1,159 @@
+//! 多路等待（poll）
+//!
+//! 进程可以同时等待多个文件描述符中的任意一个就绪。各种文件通过
+//! [`FileOps::poll`](crate::fs::vfs::FileOps::poll) 报告当前的就绪状态，
+//! 可能阻塞的对象（管道、终端等）各自内嵌一个 [`PollQueue`]：
+//! - 检查就绪状态时，对象在同一次持有自身锁期间把等待者 [`PollWaiter`] 登记到队列中；
+//! - 对象的状态可能改变时（管道读写与关闭、终端收到输入等），在持有自身锁期间调用
+//!   [`PollQueue::notify`]，只唤醒在该对象上登记过的等待者；
+//! - 每个进程有一个唤醒计数，等待者先记下计数再检查各个文件，若没有文件就绪，
+//!   在 [`POLL`] 锁内确认计数未变后才睡眠，因此检查与睡眠之间发生的状态改变不会丢失。
+//!
+//! 等待者被唤醒后从队列中移除，再次检查时重新登记；poll 返回时不注销，
+//! 残留的登记至多让同一进程表槽位上之后的 poll 多检查一次。
+//!
+//! 锁的顺序：先持有对象自身的锁，再获取 [`POLL`] 锁；检查文件时不持有 [`POLL`] 锁。
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem;
+use core::sync::atomic::Ordering;
+
+use crate::consts::NPROC;
+use crate::consts::fs::{NOFILE_MAX, POLLERR, POLLHUP, POLLNVAL};
+use crate::fs::File;
+use crate::process::PROC_MANAGER;
+use crate::spinlock::SpinLock;
+use crate::trap;
+
+use super::Proc;
+
+/// 每个进程表槽位的唤醒计数，[`PollQueue::notify`] 唤醒等待者时加一。
+static POLL: SpinLock<[usize; NPROC]> = SpinLock::new([0; NPROC], "poll");
+
+// 等待队列以 `u64` 位图记录进程表槽位
+const _: () = assert!(NPROC <= 64);
+
+/// 进程表下标为 `i` 的进程睡眠与被唤醒时使用的等待通道，各不相同且位于 [`POLL`] 之内。
+fn channel(i: usize) -> usize {
+    &POLL as *const SpinLock<[usize; NPROC]> as usize + i
+}
+
+/// 正在 poll 的进程，由 poll 交给各文件的 `poll` 方法登记到对象的 [`PollQueue`] 上。
+#[derive(Clone, Copy)]
+pub struct PollWaiter(usize);
+
+/// 一个可等待对象的 poll 等待队列，由对象自身的锁保护。
+/// 第 i 位表示进程表下标为 i 的进程在等待该对象。
+#[derive(Debug)]
+pub struct PollQueue(u64);
+
+impl PollQueue {
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// 登记等待者 `waiter`，须与检查对象的就绪状态在同一次持有对象锁期间进行。
+    pub fn register(&mut self, waiter: PollWaiter) {
+        self.0 |= 1 << waiter.0;
+    }
+
+    /// 对象的就绪状态可能已经改变，唤醒登记过的等待者并清空队列。须在持有对象锁期间调用。
+    pub fn notify(&mut self) {
+        let waiters = mem::take(&mut self.0);
+        if waiters == 0 {
+            return
+        }
+        let mut gens = POLL.lock();
+        for i in (0..NPROC).filter(|&i| waiters & (1 << i) != 0) {
+            gens[i] = gens[i].wrapping_add(1);
+            unsafe { PROC_MANAGER.wakeup(channel(i)); }
+        }
+    }
+}
+
+/// 与用户态的 `struct pollfd` 内存布局一致。
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PollFd {
+    fd: i32,
+    events: u16,
+    revents: u16,
+}
+
+impl Proc {
+    /// # 功能说明
+    /// 等待用户地址 `addr` 处的 `nfds` 个 `struct pollfd` 中的任意一个文件描述符就绪，
+    /// 并将各自的就绪状态写回其 `revents` 字段。
+    /// `POLLERR` 与 `POLLHUP` 总是被报告，无效的文件描述符报告 `POLLNVAL`，负的文件描述符被忽略。
+    ///
+    /// # 参数
+    /// - `addr`：用户空间中 `struct pollfd` 数组的地址。
+    /// - `nfds`：数组的长度，不超过 [`NOFILE_MAX`]。
+    /// - `timeout`：等待的最长时钟周期数，为负时不限时，为 0 时立即返回。
+    ///
+    /// # 返回值
+    /// - `Ok(n)`：`revents` 非 0 的项数，超时返回 0；
+    /// - `Err(())`：数组过长或地址无效，或内存不足，或进程被杀死。
+    pub(super) fn poll(&mut self, addr: usize, nfds: usize, timeout: i32) -> Result<usize, ()> {
+        if nfds > NOFILE_MAX {
+            return Err(())
+        }
+        let mut fds = Vec::new();
+        fds.try_reserve_exact(nfds).map_err(|_| ())?;
+        fds.resize(nfds, PollFd { fd: -1, events: 0, revents: 0 });
+        let size = nfds * mem::size_of::<PollFd>();
+        let pdata = self.data.get_mut();
+        pdata.copy_in(addr, fds.as_mut_ptr() as *mut u8, size)?;
+
+        // 等待期间文件描述符可能被关闭，因此持有文件的引用
+        let mut files: Vec<Option<Arc<File>>> = Vec::new();
+        files.try_reserve_exact(nfds).map_err(|_| ())?;
+        {
+            let fdt = pdata.fdtable();
+            files.extend(fds.iter().map(|pfd| match pfd.fd {
+                fd if fd >= 0 => fdt.get(fd as usize).cloned(),
+                _ => None,
+            }));
+        }
+
+        let deadline = match timeout {
+            t if t > 0 => Some(trap::clock_read().wrapping_add(t as usize)),
+            _ => None,
+        };
+        let waiter = PollWaiter(self.index);
+        let ready = loop {
+            let gen = POLL.lock()[self.index];
+            let mut ready = 0;
+            for (pfd, file) in fds.iter_mut().zip(files.iter()) {
+                pfd.revents = match file {
+                    _ if pfd.fd < 0 => 0,
+                    None => POLLNVAL,
+                    Some(f) => f.poll(waiter) & (pfd.events | POLLERR | POLLHUP),
+                };
+                if pfd.revents != 0 {
+                    ready += 1;
+                }
+            }
+            if ready > 0 || timeout == 0 {
+                break ready
+            }
+            if self.killed.load(Ordering::Relaxed) {
+                return Err(())
+            }
+            if let Some(d) = deadline {
+                if trap::clock_read().wrapping_sub(d) as isize >= 0 {
+                    break 0
+                }
+            }
+
+            let guard = POLL.lock();
+            if guard[self.index] == gen {
+                self.sleep_interruptible(channel(self.index), guard, deadline);
+            }
+        };
+
+        self.data.get_mut().copy_out(fds.as_ptr() as *const u8, addr, size)?;
+        Ok(ready)
+    }
+}
@@ -2,19 +2,28 @@
 
 use array_macro::array;
 
-use alloc::string::String;
 use alloc::boxed::Box;
 use alloc::sync::Arc;
 use core::convert::TryInto;
 use core::fmt::Display;
 use core::mem;
 
-use crate::consts::{MAXPATH, MAXARG, MAXARGLEN, fs::MAX_DIR_SIZE};
-use crate::process::PROC_MANAGER;
-use crate::fs::{ICACHE, Inode, InodeType, LOG, File, Pipe, FileStat};
+use crate::consts::{MAXPATH, MAXARG, MAXARGLEN, KLOG_BUF, IPC_RMID, RLIMIT_NOFILE, WNOHANG, WUNTRACED};
+use crate::consts::{CLOCK_MONOTONIC, CLOCK_REALTIME, ITIMER_REAL, MTIME_FREQ, TICK_INTERVAL};
+use crate::consts::{KPROF_START, KPROF_STOP};
+use crate::consts::signal::{SIG_DFL, SIG_IGN};
+use crate::consts::fs::{F_DUPFD, F_GETFD, F_SETFD, F_GETFL, F_SETFL, FD_CLOEXEC, O_CLOEXEC};
+use crate::consts::{SYSLOG_ACTION_READ_ALL, SYSLOG_ACTION_CLEAR, SYSLOG_ACTION_CONSOLE_LEVEL, SYSLOG_ACTION_SIZE_BUFFER};
+use crate::klog;
+use crate::profile;
+use crate::mm::{Address, SHM};
+use crate::process::{PROC_MANAGER, CpuManager, ALL_HARTS, push_off, pop_off};
+use crate::fs::{ICACHE, Inode, InodeType, LOG, File, Pipe, FileStat, vfs};
 use crate::trap;
+use crate::timer::{self, TimerKey, Timespec, Itimerval};
+use crate::driver::pty::Pty;
 
-use super::{Proc, elf};
+use super::{Proc, ProcData, elf};
 
 /// 系统调用结果类型
 pub type SysResult = Result<usize, ()>;
@@ -45,6 +54,38 @@ pub trait Syscall {
     fn sys_link(&mut self) -> SysResult;
     fn sys_mkdir(&mut self) -> SysResult;
     fn sys_close(&mut self) -> SysResult;
+    fn sys_ioctl(&mut self) -> SysResult;
+    fn sys_openpty(&mut self) -> SysResult;
+    fn sys_syslog(&mut self) -> SysResult;
+    fn sys_trace(&mut self) -> SysResult;
+    fn sys_clone(&mut self) -> SysResult;
+    fn sys_join(&mut self) -> SysResult;
+    fn sys_exit_thread(&mut self) -> SysResult;
+    fn sys_futex(&mut self) -> SysResult;
+    fn sys_shmget(&mut self) -> SysResult;
+    fn sys_shmat(&mut self) -> SysResult;
+    fn sys_shmdt(&mut self) -> SysResult;
+    fn sys_shmctl(&mut self) -> SysResult;
+    fn sys_poll(&mut self) -> SysResult;
+    fn sys_fcntl(&mut self) -> SysResult;
+    fn sys_dup2(&mut self) -> SysResult;
+    fn sys_dup3(&mut self) -> SysResult;
+    fn sys_getrlimit(&mut self) -> SysResult;
+    fn sys_setrlimit(&mut self) -> SysResult;
+    fn sys_mkfifo(&mut self) -> SysResult;
+    fn sys_setpgid(&mut self) -> SysResult;
+    fn sys_getpgid(&mut self) -> SysResult;
+    fn sys_setsid(&mut self) -> SysResult;
+    fn sys_waitpid(&mut self) -> SysResult;
+    fn sys_signal(&mut self) -> SysResult;
+    fn sys_alarm(&mut self) -> SysResult;
+    fn sys_setitimer(&mut self) -> SysResult;
+    fn sys_getitimer(&mut self) -> SysResult;
+    fn sys_clock_gettime(&mut self) -> SysResult;
+    fn sys_nanosleep(&mut self) -> SysResult;
+    fn sys_sched_setaffinity(&mut self) -> SysResult;
+    fn sys_sched_getaffinity(&mut self) -> SysResult;
+    fn sys_kprof(&mut self) -> SysResult;
 }
 
 /// 为进程实现系统调用接口
@@ -65,9 +106,6 @@ impl Syscall for Proc {
     fn sys_fork(&mut self) -> SysResult {
         let ret = self.fork();
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].fork() = {:?}(pid)", self.excl.lock().pid, ret);
-
         ret
     }
 
@@ -85,9 +123,6 @@ impl Syscall for Proc {
     fn sys_exit(&mut self) -> SysResult {
         let exit_status = self.arg_i32(0);
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].exit(status={})", self.excl.lock().pid, exit_status);
-
         unsafe { PROC_MANAGER.exiting(self.index, exit_status); }
         unreachable!("process exit");
     }
@@ -105,10 +140,7 @@ impl Syscall for Proc {
     /// - 错误：返回 Err(())
     fn sys_wait(&mut self) -> SysResult {
         let addr = self.arg_addr(0);
-        let ret =  unsafe { PROC_MANAGER.waiting(self.index, addr) };
-
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].wait(addr={:#x}) = {:?}(pid)", self.excl.lock().pid, addr, ret);
+        let ret =  unsafe { PROC_MANAGER.waiting(self.index, -1, addr, 0, false) };
 
         ret
     }
@@ -136,25 +168,22 @@ impl Syscall for Proc {
         let addr_fdread = pipefds_addr;
         let addr_fdwrite = pipefds_addr+mem::size_of::<u32>();
 
-        // 分配文件描述符
-        let pdata = self.data.get_mut();
-        let (fd_read, fd_write) = pdata.alloc_fd2().ok_or(())?;
-
         // 创建管道（返回读写文件对象）
         let (file_read, file_write) = Pipe::create().ok_or(())?;
 
+        // 分配文件描述符并绑定文件对象
+        let pdata = self.data.get_mut();
+        let (fd_read, fd_write) = install2(pdata, file_read, file_write)?;
+
         // 将描述符写入用户空间
         let fd_read_u32: u32 = fd_read.try_into().unwrap();
         let fd_write_u32: u32 = fd_write.try_into().unwrap();
-        pdata.copy_out(&fd_read_u32 as *const u32 as *const u8, addr_fdread, mem::size_of::<u32>())?;
-        pdata.copy_out(&fd_write_u32 as *const u32 as *const u8, addr_fdwrite, mem::size_of::<u32>())?;
-
-        // 绑定文件对象到描述符
-        pdata.open_files[fd_read].replace(file_read);
-        pdata.open_files[fd_write].replace(file_write);
-
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].pipe(addr={:#x}) = ok, fd=[{},{}]", self.excl.lock().pid, pipefds_addr, fd_read, fd_write);
+        if pdata.copy_out(&fd_read_u32 as *const u32 as *const u8, addr_fdread, mem::size_of::<u32>()).is_err() ||
+            pdata.copy_out(&fd_write_u32 as *const u32 as *const u8, addr_fdwrite, mem::size_of::<u32>()).is_err()
+        {
+            uninstall2(pdata, fd_read, fd_write);
+            return Err(())
+        }
 
         Ok(0)
     }
@@ -176,7 +205,7 @@ impl Syscall for Proc {
     /// # 安全
     /// 验证用户地址和计数有效性
     fn sys_read(&mut self) -> SysResult {
-        let fd = self.arg_fd(0)?;
+        let (_, file) = self.arg_fd(0)?;
         let user_addr = self.arg_addr(1);
         let count = self.arg_i32(2);
         if count <= 0 || self.data.get_mut().check_user_addr(user_addr).is_err() {
@@ -184,39 +213,46 @@ impl Syscall for Proc {
         }
         let count = count as u32;
         
-        let file = self.data.get_mut().open_files[fd].as_ref().unwrap();
         let ret = file.fread(user_addr, count);
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].read(fd={}, addr={:#x}, count={}) = {:?}", self.excl.lock().pid, fd, user_addr, count, ret);
-
         ret.map(|count| count as usize)
     }
 
     /// 终止指定进程
     ///
     /// # 功能说明
-    /// 向目标进程发送终止信号，使其退出执行。
+    /// 向目标进程或进程组发送信号，按信号的默认动作终止、停止或继续目标。
     ///
     /// # 参数
-    /// - `pid`: 目标进程ID
+    /// - `pid`: 大于 0 时为目标进程ID；为 0 时表示调用者所在的进程组；
+    ///   小于 -1 时表示进程组 `-pid`
+    /// - `sig`: 信号编号，为 0 时只检查目标是否存在
     ///
     /// # 返回值
     /// - 成功：返回 0
     /// - 错误：返回 Err(())
     ///
     /// # 注意
-    /// 目前仅支持终止信号，不支持其他信号类型
+    /// 目前仅支持 `SIGINT`、`SIGKILL`、`SIGTERM`、`SIGCONT`、`SIGSTOP` 与 `SIGTSTP`，
+    /// 不支持用户自定义的信号处理函数
     fn sys_kill(&mut self) -> SysResult {
         let pid = self.arg_i32(0);
-        if pid < 0 {
+        let sig = self.arg_i32(1);
+        if sig < 0 {
             return Err(())
         }
-        let pid = pid as usize;
-        let ret = unsafe { PROC_MANAGER.kill(pid) };
-
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].kill(pid={}) = {:?}", self.excl.lock().pid, pid, ret);
+        let sig = sig as usize;
+        let ret = unsafe {
+            match pid {
+                -1 => Err(()),
+                0 => {
+                    let (pgid, _) = PROC_MANAGER.group_ids(self.index, 0).ok_or(())?;
+                    PROC_MANAGER.signal_group(pgid, sig)
+                }
+                pid if pid < 0 => PROC_MANAGER.signal_group(pid.unsigned_abs() as usize, sig),
+                pid => PROC_MANAGER.signal(pid as usize, sig),
+            }
+        };
 
         ret.map(|()| 0)
     }
@@ -281,9 +317,6 @@ impl Syscall for Proc {
             }
         }
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].exec({}, {:#x}) = {:?}", self.excl.lock().pid, String::from_utf8_lossy(&path), uargv, result);
-
         if result.is_err() {
             syscall_warning(error);
         }
@@ -303,24 +336,20 @@ impl Syscall for Proc {
     /// - 成功：返回 0
     /// - 错误：返回 Err(())
     fn sys_fstat(&mut self) -> SysResult {
-        let fd = self.arg_fd(0)?;
+        let (_, file) = self.arg_fd(0)?;
         let addr = self.arg_addr(1);
         let mut stat = FileStat::uninit();
-        let file = self.data.get_mut().open_files[fd].as_ref().unwrap();
         let ret = if file.fstat(&mut stat).is_err() {
             Err(())
         } else {
-            let pgt = self.data.get_mut().pagetable.as_mut().unwrap();
-            if pgt.copy_out(&stat as *const FileStat as *const u8, addr, mem::size_of::<FileStat>()).is_err() {
+            let pdata = self.data.get_mut();
+            if pdata.copy_out(&stat as *const FileStat as *const u8, addr, mem::size_of::<FileStat>()).is_err() {
                 Err(())
             } else {
                 Ok(0)
             }
         };
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].fstat(fd={}, addr={:#x}) = {:?}", self.excl.lock().pid, fd, addr, stat);
-
         ret
     }
 
@@ -337,11 +366,15 @@ impl Syscall for Proc {
     /// - 错误：返回 Err(())
     ///
     /// # 流程
-    /// 1. 验证路径存在且是目录
-    /// 2. 更新进程的当前工作目录
+    /// 1. 验证路径位于根文件系统中（工作目录不能位于其他挂载的文件系统）
+    /// 2. 验证路径存在且是目录
+    /// 3. 更新进程的当前工作目录
     fn sys_chdir(&mut self) -> SysResult {
         let mut path: [u8; MAXPATH] = [0; MAXPATH];
         self.arg_str(0, &mut path).map_err(syscall_warning)?;
+        if !vfs::on_root_fs(&path) {
+            return Err(())
+        }
 
         LOG.begin_op();
         let inode: Inode;
@@ -357,7 +390,7 @@ impl Syscall for Proc {
             return Err(())
         }
         drop(idata);
-        let old_cwd = self.data.get_mut().cwd.replace(inode);
+        let old_cwd = self.data.get_mut().fdtable().cwd.replace(inode);
         debug_assert!(old_cwd.is_some());
         drop(old_cwd);
         LOG.end_op();
@@ -367,7 +400,8 @@ impl Syscall for Proc {
     /// 复制文件描述符
     ///
     /// # 功能说明
-    /// 创建指定文件描述符的副本，指向相同的文件对象。
+    /// 以最小的空闲文件描述符创建指定文件描述符的副本，指向相同的文件对象，
+    /// 新文件描述符的标志被清零。
     ///
     /// # 参数
     /// - `old_fd`: 原文件描述符
@@ -376,17 +410,8 @@ impl Syscall for Proc {
     /// - 成功：返回新文件描述符
     /// - 错误：返回 Err(())
     fn sys_dup(&mut self) -> SysResult {
-        let old_fd = self.arg_fd(0)?;
-        let pd = self.data.get_mut();
-        let new_fd = pd.alloc_fd().ok_or(())?;
-        
-        let old_file = pd.open_files[old_fd].as_ref().unwrap();
-        let new_file = Arc::clone(old_file);
-        let none_file = pd.open_files[new_fd].replace(new_file);
-        debug_assert!(none_file.is_none());
-
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].dup({}) = {}(fd)", self.excl.lock().pid, old_fd, new_fd);
+        let (_, file) = self.arg_fd(0)?;
+        let new_fd = self.data.get_mut().fdtable().install(Arc::clone(&file)).ok_or(())?;
 
         Ok(new_fd)
     }
@@ -401,9 +426,6 @@ impl Syscall for Proc {
     fn sys_getpid(&mut self) -> SysResult {
         let pid = self.excl.lock().pid;
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].getpid() = {}", pid, pid);
-
         Ok(pid)
     }
 
@@ -425,9 +447,6 @@ impl Syscall for Proc {
         let increment = self.arg_i32(0);
         let ret = self.data.get_mut().sbrk(increment);
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].sbrk({}) = {:?}", self.excl.lock().pid, increment, ret);
-
         ret
     }
 
@@ -453,9 +472,6 @@ impl Syscall for Proc {
         let count = count as usize;
         let ret = trap::clock_sleep(self, count);
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].sleep({}) = {:?}", self.excl.lock().pid, count, ret);
-
         ret.map(|()| 0)
     }
 
@@ -472,9 +488,6 @@ impl Syscall for Proc {
     fn sys_uptime(&mut self) -> SysResult {
         let ret = trap::clock_read();
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].uptime() = {}", self.excl.lock().pid, ret);
-
         Ok(ret)
     }
 
@@ -485,7 +498,7 @@ impl Syscall for Proc {
     ///
     /// # 参数
     /// - `path`: 文件路径
-    /// - `flags`: 打开标志（目前未完全实现）
+    /// - `flags`: 打开标志，`O_CLOEXEC` 为新文件描述符设置 `FD_CLOEXEC`
     ///
     /// # 返回值
     /// - 成功：返回文件描述符
@@ -501,15 +514,20 @@ impl Syscall for Proc {
             return Err(())
         }
 
-        let fd = self.data.get_mut().alloc_fd().ok_or(())?;
-        let file = File::open(&path, flags).ok_or(())?;
-        let none_file = self.data.get_mut().open_files[fd].replace(file);
-        debug_assert!(none_file.is_none());
-
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].open({}, {:#x}) = {}(fd)", self.excl.lock().pid, String::from_utf8_lossy(&path), flags, fd);
+        let file = File::open(&path, flags & !O_CLOEXEC).ok_or(())?;
+        let fd = {
+            let mut fdt = self.data.get_mut().fdtable();
+            fdt.install(Arc::clone(&file)).map(|fd| {
+                if flags & O_CLOEXEC != 0 {
+                    fdt.set_flags(fd, FD_CLOEXEC).unwrap();
+                }
+                fd
+            })
+        };
+        // 没有空闲的描述符时 file 在这里被关闭，此时不持有文件表的锁
+        drop(file);
 
-        Ok(fd)
+        fd.ok_or(())
     }
 
     /// 写入文件描述符
@@ -529,7 +547,7 @@ impl Syscall for Proc {
     /// # 安全
     /// 验证用户地址和计数有效性
     fn sys_write(&mut self) -> SysResult {
-        let fd = self.arg_fd(0)?;
+        let (_, file) = self.arg_fd(0)?;
         let user_addr = self.arg_addr(1);
         let count = self.arg_i32(2);
         if count <= 0 || self.data.get_mut().check_user_addr(user_addr).is_err() {
@@ -537,12 +555,8 @@ impl Syscall for Proc {
         }
         let count = count as u32;
 
-        let file = self.data.get_mut().open_files[fd].as_ref().unwrap();
         let ret = file.fwrite(user_addr, count);
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].write({}, {:#x}, {}) = {:?}", self.excl.lock().pid, fd, user_addr, count, ret);
-
         ret.map(|count| count as usize)
     }
 
@@ -570,16 +584,9 @@ impl Syscall for Proc {
 
         let major: u16 = major.try_into().map_err(|_| ())?;
         let minor: u16 = minor.try_into().map_err(|_| ())?;
-        LOG.begin_op();
-        let ret = ICACHE.create(&path, InodeType::Device, major, minor, true).ok_or(());
+        let ret = vfs::create(&path, InodeType::Device, major, minor, true).ok_or(());
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].mknod(path={}, major={}, minor={}) = {:?}",
-            self.excl.lock().pid, String::from_utf8_lossy(&path), major, minor, ret);
-
-        let ret = ret.map(|inode| {drop(inode);0});
-        LOG.end_op();
-        ret
+        ret.map(|inode| {drop(inode);0})
     }
 
     /// 删除文件链接
@@ -598,24 +605,7 @@ impl Syscall for Proc {
         let mut path: [u8; MAXPATH] = [0; MAXPATH];
         self.arg_str(0, &mut path).map_err(syscall_warning)?;
 
-        LOG.begin_op();
-        let mut name: [u8; MAX_DIR_SIZE] = [0; MAX_DIR_SIZE];
-        let dir_inode: Inode;
-        if let Some(inode) = ICACHE.namei_parent(&path, &mut name) {
-            dir_inode = inode;
-        } else {
-            LOG.end_op();
-            return Err(())
-        }
-
-        let mut dir_idata = dir_inode.lock();
-        let ret = dir_idata.dir_unlink(&name);
-        drop(dir_idata);
-        drop(dir_inode);
-        LOG.end_op();
-
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].unlink(path={}) = {:?}", self.excl.lock().pid, String::from_utf8_lossy(&path), ret);
+        let ret = vfs::unlink(&path);
 
         ret.map(|()| 0)
     }
@@ -643,56 +633,8 @@ impl Syscall for Proc {
         self.arg_str(0, &mut old_path).map_err(syscall_warning)?;
         self.arg_str(1, &mut new_path).map_err(syscall_warning)?;
 
-        LOG.begin_op();
-
-        // 查找原文件
-        let old_inode = ICACHE.namei(&old_path).ok_or_else(|| {LOG.end_op(); ()})?;
-        let mut old_idata = old_inode.lock();
-        let (old_dev, old_inum) = old_idata.get_dev_inum();
-        if old_idata.get_itype() == InodeType::Directory {
-            syscall_warning("trying to create new link to a directory");
-            LOG.end_op();
-            return Err(())
-        }
-        old_idata.link();
-        old_idata.update();
-        drop(old_idata);
-
-        // 如果无法创建新路径
-        let revert_link = move |inode: Inode| {
-            let mut idata = inode.lock();
-            idata.unlink();
-            idata.update();
-            drop(idata);
-            drop(inode);
-            LOG.end_op();
-        };
-
-        // 创建新路径
-        let mut name: [u8; MAX_DIR_SIZE] = [0; MAX_DIR_SIZE];
-        let new_inode: Inode;
-        match ICACHE.namei_parent(&new_path, &mut name) {
-            Some(inode) => new_inode = inode,
-            None => {
-                revert_link(old_inode);
-                return Err(())
-            }
-        }
-        let mut new_idata = new_inode.lock();
-        if new_idata.get_dev_inum().0 != old_dev || new_idata.dir_link(&name, old_inum).is_err() {
-            revert_link(old_inode);
-            return Err(())
-        }
-        drop(new_idata);
-        drop(new_inode);
-        drop(old_inode);
-
-        LOG.end_op();
+        vfs::link(&old_path, &new_path)?;
 
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].link(old_path={}, new_path={})", self.excl.lock().pid,
-            String::from_utf8_lossy(&old_path), String::from_utf8_lossy(&new_path));
-        
         Ok(0)
     }
 
@@ -714,21 +656,15 @@ impl Syscall for Proc {
         let mut path: [u8; MAXPATH] = [0; MAXPATH];
         self.arg_str(0, &mut path).map_err(syscall_warning)?;
 
-        LOG.begin_op();
-        let ret = ICACHE.create(&path, InodeType::Directory, 0, 0, false);
-
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].mkdir(path={}) = {:?}", self.excl.lock().pid, String::from_utf8_lossy(&path), ret);
+        let ret = vfs::create(&path, InodeType::Directory, 0, 0, false);
 
-        let ret = match ret {
+        match ret {
             Some(inode) => {
                 drop(inode);
                 Ok(0)
             },
             None => Err(()),
-        };
-        LOG.end_op();
-        ret
+        }
     }
 
     /// 关闭文件描述符
@@ -743,15 +679,897 @@ impl Syscall for Proc {
     /// - 成功：返回 0
     /// - 错误：返回 Err(())
     fn sys_close(&mut self) -> SysResult {
-        let fd = self.arg_fd(0)?;
-        let file = self.data.get_mut().open_files[fd].take();
-
-        #[cfg(feature = "trace_syscall")]
-        println!("[{}].close(fd={}), file={:?}", self.excl.lock().pid, fd, file);
+        let fd = self.arg_raw(0);
+        let file = self.data.get_mut().fdtable().take(fd).ok_or(())?;
 
+        // 释放文件表的锁之后再关闭文件
         drop(file);
         Ok(0)
     }
+
+    /// 设备控制
+    ///
+    /// # 功能说明
+    /// 对文件描述符指向的文件执行设备相关的控制命令，
+    /// 例如查询与设置终端属性、窗口大小与前台进程。
+    ///
+    /// # 参数
+    /// - `fd`: 文件描述符
+    /// - `request`: 控制命令
+    /// - `arg`: 命令参数，通常是用户空间中参数结构的地址
+    ///
+    /// # 返回值
+    /// - 成功：返回命令的结果
+    /// - 错误：返回 Err(())
+    fn sys_ioctl(&mut self) -> SysResult {
+        let (_, file) = self.arg_fd(0)?;
+        let request = self.arg_raw(1);
+        let arg = self.arg_addr(2);
+        file.ioctl(request, arg)
+    }
+
+    /// 创建伪终端
+    ///
+    /// # 功能说明
+    /// 创建一对伪终端文件，主端与从端的描述符依次写入用户提供的数组。
+    ///
+    /// # 参数
+    /// - `fds`: 用户空间中两个 int 的数组地址
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 错误：返回 Err(())
+    fn sys_openpty(&mut self) -> SysResult {
+        let fds_addr = self.arg_addr(0);
+
+        let (file_master, file_slave) = Pty::create().ok_or(())?;
+        let pdata = self.data.get_mut();
+        let (fd_master, fd_slave) = install2(pdata, file_master, file_slave)?;
+
+        let fd_master_u32: u32 = fd_master.try_into().unwrap();
+        let fd_slave_u32: u32 = fd_slave.try_into().unwrap();
+        if pdata.copy_out(&fd_master_u32 as *const u32 as *const u8, fds_addr, mem::size_of::<u32>()).is_err() ||
+            pdata.copy_out(&fd_slave_u32 as *const u32 as *const u8, fds_addr+mem::size_of::<u32>(), mem::size_of::<u32>()).is_err()
+        {
+            uninstall2(pdata, fd_master, fd_slave);
+            return Err(())
+        }
+
+        Ok(0)
+    }
+
+    /// 读取与控制内核日志
+    ///
+    /// # 功能说明
+    /// 按 `action` 执行内核日志操作：
+    /// - `SYSLOG_ACTION_READ_ALL`：将最新的至多 `len` 字节日志复制到 `buf`，返回复制的字节数；
+    /// - `SYSLOG_ACTION_CLEAR`：清空日志缓冲区；
+    /// - `SYSLOG_ACTION_CONSOLE_LEVEL`：将控制台级别设置为 `len`；
+    /// - `SYSLOG_ACTION_SIZE_BUFFER`：返回日志缓冲区的大小。
+    ///
+    /// # 参数
+    /// - `action`: 操作类型
+    /// - `buf`: 用户空间缓冲区地址
+    /// - `len`: 缓冲区长度或控制台级别
+    ///
+    /// # 返回值
+    /// - 成功：返回操作的结果
+    /// - 错误：返回 Err(())
+    fn sys_syslog(&mut self) -> SysResult {
+        let action = self.arg_i32(0);
+        let buf = self.arg_addr(1);
+        let len = self.arg_i32(2);
+        if len < 0 {
+            return Err(())
+        }
+        let len = len as usize;
+
+        match action {
+            SYSLOG_ACTION_READ_ALL => {
+                if self.data.get_mut().check_user_addr(buf).is_err() {
+                    return Err(())
+                }
+                klog::read(Address::Virtual(buf), len)
+            }
+            SYSLOG_ACTION_CLEAR => {
+                klog::clear();
+                Ok(0)
+            }
+            SYSLOG_ACTION_CONSOLE_LEVEL => klog::set_console_level(len).map(|()| 0),
+            SYSLOG_ACTION_SIZE_BUFFER => Ok(KLOG_BUF),
+            _ => Err(()),
+        }
+    }
+
+    /// 设置当前进程的系统调用跟踪
+    ///
+    /// # 功能说明
+    /// 此后当前进程及其 fork 出的子进程执行掩码中的系统调用时，
+    /// 都会输出一行包含参数与返回值的跟踪信息。掩码为 0 时关闭跟踪。
+    ///
+    /// # 参数
+    /// - `mask`: 跟踪掩码，第 n 位对应 n 号系统调用
+    /// - `fd`: 跟踪输出写入的文件描述符，为 -1 时写入内核日志
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 失败：文件描述符无效或不可写时返回 Err(())
+    fn sys_trace(&mut self) -> SysResult {
+        let mask = self.arg_raw(0) as u64;
+        let file = if self.arg_i32(1) == -1 {
+            None
+        } else {
+            let (_, file) = self.arg_fd(1)?;
+            if !file.writable() {
+                return Err(())
+            }
+            Some(file)
+        };
+
+        let pd = self.data.get_mut();
+        pd.trace_mask = mask;
+        pd.trace_file = if mask == 0 { None } else { file };
+        Ok(0)
+    }
+
+    /// 创建线程
+    ///
+    /// # 功能说明
+    /// 在当前线程组中创建新线程，新线程与调用者共享地址空间、
+    /// 打开的文件与当前工作目录，从 `func(arg)` 开始在 `stack` 上执行。
+    /// `func` 不能返回，应以 `exit_thread` 结束。
+    ///
+    /// # 参数
+    /// - `func`: 新线程的入口地址
+    /// - `arg`: 传给入口函数的参数
+    /// - `stack`: 新线程的用户栈顶，由调用者分配
+    ///
+    /// # 返回值
+    /// - 成功：返回新线程的线程号
+    /// - 失败：进程表已满、线程数已达上限或内存不足时返回 Err(())
+    ///
+    /// # 注意
+    /// 实际实现委托给 `Proc::clone_thread` 方法
+    fn sys_clone(&mut self) -> SysResult {
+        let func = self.arg_addr(0);
+        let arg = self.arg_addr(1);
+        let stack = self.arg_addr(2);
+        self.clone_thread(func, arg, stack)
+    }
+
+    /// 等待线程结束
+    ///
+    /// # 功能说明
+    /// 挂起当前线程，直到同一线程组中线程号为 `tid` 的线程结束，然后回收它。
+    ///
+    /// # 参数
+    /// - `tid`: 要等待的线程号，不能是主线程或调用者自身
+    /// - `status`: 用户空间地址，非零时写入线程的退出状态
+    ///
+    /// # 返回值
+    /// - 成功：返回被回收线程的线程号
+    /// - 失败：组内没有该线程、调用者被杀死或地址无效时返回 Err(())
+    fn sys_join(&mut self) -> SysResult {
+        let tid = self.arg_i32(0);
+        if tid < 0 {
+            return Err(())
+        }
+        let addr = self.arg_addr(1);
+        unsafe { PROC_MANAGER.joining(self.index, tid as usize, addr) }
+    }
+
+    /// 结束当前线程
+    ///
+    /// # 功能说明
+    /// 只结束调用线程，线程组中的其他线程继续运行。
+    /// 主线程调用时，等待其他线程全部结束后以 `status` 退出整个进程。
+    ///
+    /// # 注意
+    /// 调用后不会返回到用户空间
+    fn sys_exit_thread(&mut self) -> SysResult {
+        let exit_status = self.arg_i32(0);
+
+        unsafe { PROC_MANAGER.exit_thread(self.index, exit_status); }
+        unreachable!("thread exit");
+    }
+
+    /// 在用户内存中的字上等待或唤醒
+    ///
+    /// # 功能说明
+    /// 为用户空间的锁与条件变量提供阻塞原语：
+    /// `FUTEX_WAIT` 在字的值仍为 `val` 时睡眠，`FUTEX_WAKE` 唤醒最多 `val` 个等待者。
+    ///
+    /// # 参数
+    /// - `addr`: 字的用户空间地址，须 4 字节对齐
+    /// - `op`: 操作，`FUTEX_WAIT` 或 `FUTEX_WAKE`
+    /// - `val`: 期望的值或最多唤醒的数量
+    /// - `timeout`: 等待的最长时钟周期数，0 表示不限时
+    ///
+    /// # 返回值
+    /// - 成功：等待返回 0，等待超时返回 `FUTEX_TIMEDOUT`，唤醒返回唤醒的数量
+    /// - 失败：返回 Err(())
+    ///
+    /// # 注意
+    /// 实际实现委托给 `Proc::futex` 方法
+    fn sys_futex(&mut self) -> SysResult {
+        let addr = self.arg_addr(0);
+        let op = self.arg_i32(1);
+        let val = self.arg_i32(2);
+        let timeout = self.arg_i32(3);
+        if timeout < 0 {
+            return Err(())
+        }
+        self.futex(addr, op, val, timeout as usize)
+    }
+
+    /// 获取共享内存段
+    ///
+    /// # 功能说明
+    /// 查找键值为 `key` 的共享内存段，不存在且指定了 `IPC_CREAT` 时创建新段。
+    /// 键值为 `IPC_PRIVATE` 时总是创建新段。
+    ///
+    /// # 参数
+    /// - `key`: 段的键值
+    /// - `size`: 段的大小（字节数），创建时向上取整到页
+    /// - `flags`: `IPC_CREAT`、`IPC_EXCL` 的组合
+    ///
+    /// # 返回值
+    /// - 成功：返回段号
+    /// - 失败：返回 Err(())
+    fn sys_shmget(&mut self) -> SysResult {
+        let key = self.arg_raw(0);
+        let size = self.arg_i32(1);
+        if size < 0 {
+            return Err(())
+        }
+        let flags = self.arg_raw(2);
+        SHM.lock().get(key, size as usize, flags)
+    }
+
+    /// 映射共享内存段
+    ///
+    /// # 功能说明
+    /// 将段号为 `id` 的共享内存段映射到当前地址空间，映射在 fork 时被子进程继承。
+    ///
+    /// # 参数
+    /// - `id`: 段号
+    /// - `addr`: 映射的虚拟地址，须按页对齐并位于共享内存区域，为 0 时由内核选择
+    ///
+    /// # 返回值
+    /// - 成功：返回映射的虚拟地址
+    /// - 失败：返回 Err(())
+    fn sys_shmat(&mut self) -> SysResult {
+        let id = self.arg_raw(0);
+        let addr = self.arg_addr(1);
+        let seg = SHM.lock().lookup(id).ok_or(())?;
+        self.data.get_mut().vm().shm_attach(seg, addr)
+    }
+
+    /// 解除共享内存映射
+    ///
+    /// # 参数
+    /// - `addr`: `shmat` 返回的映射地址
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 失败：该地址上没有共享内存映射时返回 Err(())
+    fn sys_shmdt(&mut self) -> SysResult {
+        let addr = self.arg_addr(0);
+        let seg = self.data.get_mut().vm().shm_detach(addr).ok_or(())?;
+        drop(seg);
+        Ok(0)
+    }
+
+    /// 控制共享内存段
+    ///
+    /// # 功能说明
+    /// 目前只支持 `IPC_RMID`：删除段，此后无法再通过键值或段号获取它，
+    /// 段的内存在最后一个映射被解除后释放。
+    ///
+    /// # 参数
+    /// - `id`: 段号
+    /// - `cmd`: 命令
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 失败：段不存在或命令不受支持时返回 Err(())
+    fn sys_shmctl(&mut self) -> SysResult {
+        let id = self.arg_raw(0);
+        match self.arg_i32(1) {
+            IPC_RMID => SHM.lock().remove(id).map(|()| 0),
+            _ => Err(()),
+        }
+    }
+
+    /// 等待多个文件描述符就绪
+    ///
+    /// # 功能说明
+    /// 等待 `fds` 数组中任意一个文件描述符的 `events` 所关心的事件发生，
+    /// 或者超时，并在每一项的 `revents` 中写回发生的事件。
+    ///
+    /// # 参数
+    /// - `fds`: 用户空间中 `struct pollfd` 数组的地址
+    /// - `nfds`: 数组的长度
+    /// - `timeout`: 等待的最长时钟周期数，-1 表示不限时，0 表示不等待
+    ///
+    /// # 返回值
+    /// - 成功：返回有事件发生的项数，超时返回 0
+    /// - 失败：返回 Err(())
+    ///
+    /// # 注意
+    /// 实际实现委托给 `Proc::poll` 方法
+    fn sys_poll(&mut self) -> SysResult {
+        let fds = self.arg_addr(0);
+        let nfds = self.arg_i32(1);
+        if nfds < 0 {
+            return Err(())
+        }
+        let timeout = self.arg_i32(2);
+        self.poll(fds, nfds as usize, timeout)
+    }
+
+    /// 控制文件描述符
+    ///
+    /// # 功能说明
+    /// 按命令 `cmd` 操作文件描述符 `fd`：
+    /// - `F_DUPFD`：复制到不小于 `arg` 的最小空闲文件描述符；
+    /// - `F_GETFD` / `F_SETFD`：读取或设置文件描述符标志（`FD_CLOEXEC`），标志属于文件描述符本身；
+    /// - `F_GETFL` / `F_SETFL`：读取访问方式与状态标志，或设置状态标志（`O_NONBLOCK`），
+    ///   状态标志属于打开的文件，由 dup 或 fork 得到的文件描述符共用；
+    /// - 其余命令交给文件自身处理，例如管道的 `F_GETPIPE_SZ` / `F_SETPIPE_SZ`。
+    ///
+    /// # 参数
+    /// - `fd`: 文件描述符
+    /// - `cmd`: 命令
+    /// - `arg`: 命令的参数
+    ///
+    /// # 返回值
+    /// - 成功：`F_DUPFD` 返回新文件描述符，`F_GETFD`、`F_GETFL` 返回标志，
+    ///   `F_GETPIPE_SZ`、`F_SETPIPE_SZ` 返回管道容量，其余返回 0
+    /// - 失败：返回 Err(())
+    fn sys_fcntl(&mut self) -> SysResult {
+        let (fd, file) = self.arg_fd(0)?;
+        let cmd = self.arg_i32(1);
+        let arg = self.arg_i32(2);
+        let mut fdt = self.data.get_mut().fdtable();
+        match cmd {
+            F_DUPFD => {
+                if arg < 0 {
+                    return Err(())
+                }
+                fdt.install_from(Arc::clone(&file), arg as usize).ok_or(())
+            }
+            F_GETFD => fdt.flags(fd).map(|f| f as usize).ok_or(()),
+            F_SETFD => fdt.set_flags(fd, arg & FD_CLOEXEC).map(|()| 0),
+            F_GETFL => Ok(file.status_flags() as usize),
+            F_SETFL => {
+                file.set_status_flags(arg);
+                Ok(0)
+            }
+            _ => {
+                drop(fdt);
+                if arg < 0 {
+                    return Err(())
+                }
+                file.fcntl(cmd, arg as usize)
+            }
+        }
+    }
+
+    /// 复制文件描述符到指定位置
+    ///
+    /// # 功能说明
+    /// 使 `new_fd` 指向 `old_fd` 的文件，`new_fd` 原先打开的文件被关闭，
+    /// 新文件描述符的标志被清零。两者相同时什么也不做。
+    ///
+    /// # 参数
+    /// - `old_fd`: 原文件描述符
+    /// - `new_fd`: 目标文件描述符，须小于打开文件数的软限制
+    ///
+    /// # 返回值
+    /// - 成功：返回 `new_fd`
+    /// - 错误：返回 Err(())
+    fn sys_dup2(&mut self) -> SysResult {
+        let (old_fd, file) = self.arg_fd(0)?;
+        let new_fd = self.arg_raw(1);
+        if old_fd == new_fd {
+            return Ok(new_fd)
+        }
+        dup_to(self.data.get_mut(), file, new_fd, 0)
+    }
+
+    /// 复制文件描述符到指定位置，并设置标志
+    ///
+    /// # 功能说明
+    /// 与 `dup2` 相同，但 `flags` 含 `O_CLOEXEC` 时为 `new_fd` 设置 `FD_CLOEXEC`，
+    /// 且两个文件描述符相同时失败。
+    ///
+    /// # 参数
+    /// - `old_fd`: 原文件描述符
+    /// - `new_fd`: 目标文件描述符
+    /// - `flags`: 0 或 `O_CLOEXEC`
+    ///
+    /// # 返回值
+    /// - 成功：返回 `new_fd`
+    /// - 错误：返回 Err(())
+    fn sys_dup3(&mut self) -> SysResult {
+        let (old_fd, file) = self.arg_fd(0)?;
+        let new_fd = self.arg_raw(1);
+        let flags = self.arg_i32(2);
+        if old_fd == new_fd || flags & !O_CLOEXEC != 0 {
+            return Err(())
+        }
+        let fd_flags = if flags & O_CLOEXEC != 0 { FD_CLOEXEC } else { 0 };
+        dup_to(self.data.get_mut(), file, new_fd, fd_flags)
+    }
+
+    /// 获取资源限制
+    ///
+    /// # 功能说明
+    /// 将资源 `resource` 的软限制与硬限制写入用户空间的 `struct rlimit`，
+    /// 目前只支持 `RLIMIT_NOFILE`。
+    ///
+    /// # 参数
+    /// - `resource`: 资源
+    /// - `rlim`: 用户空间中 `struct rlimit` 的地址
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 错误：返回 Err(())
+    fn sys_getrlimit(&mut self) -> SysResult {
+        if self.arg_i32(0) != RLIMIT_NOFILE {
+            return Err(())
+        }
+        let addr = self.arg_addr(1);
+        let pdata = self.data.get_mut();
+        let (cur, max) = pdata.fdtable().limit();
+        let rlim = Rlimit { cur, max };
+        pdata.copy_out(&rlim as *const Rlimit as *const u8, addr, mem::size_of::<Rlimit>())?;
+        Ok(0)
+    }
+
+    /// 设置资源限制
+    ///
+    /// # 功能说明
+    /// 以用户空间的 `struct rlimit` 设置资源 `resource` 的软限制与硬限制，
+    /// 目前只支持 `RLIMIT_NOFILE`。软限制不能超过硬限制，硬限制只能降低。
+    /// 限制由线程组共享，并被 fork 出的子进程继承。
+    ///
+    /// # 参数
+    /// - `resource`: 资源
+    /// - `rlim`: 用户空间中 `struct rlimit` 的地址
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 错误：返回 Err(())
+    fn sys_setrlimit(&mut self) -> SysResult {
+        if self.arg_i32(0) != RLIMIT_NOFILE {
+            return Err(())
+        }
+        let addr = self.arg_addr(1);
+        let pdata = self.data.get_mut();
+        let mut rlim = Rlimit { cur: 0, max: 0 };
+        pdata.copy_in(addr, &mut rlim as *mut Rlimit as *mut u8, mem::size_of::<Rlimit>())?;
+        pdata.fdtable().set_limit(rlim.cur, rlim.max)?;
+        Ok(0)
+    }
+
+    /// 创建命名管道
+    ///
+    /// # 功能说明
+    /// 在指定路径创建一个 FIFO 节点。节点本身不存储数据，
+    /// 打开它的进程之间通过一个内存中的管道通信。
+    ///
+    /// # 参数
+    /// - `path`: FIFO 路径
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 错误：返回 Err(())
+    ///
+    /// # 注意
+    /// 路径已存在时失败
+    fn sys_mkfifo(&mut self) -> SysResult {
+        let mut path: [u8; MAXPATH] = [0; MAXPATH];
+        self.arg_str(0, &mut path).map_err(syscall_warning)?;
+
+        let inode = vfs::create(&path, InodeType::Fifo, 0, 0, false).ok_or(())?;
+        drop(inode);
+        Ok(0)
+    }
+
+    /// 设置进程组
+    ///
+    /// # 功能说明
+    /// 将调用者或其子进程移入同一会话中的一个进程组，或以其自身为首进程新建进程组。
+    ///
+    /// # 参数
+    /// - `pid`: 目标进程ID，为 0 时表示调用者
+    /// - `pgid`: 进程组号，为 0 时与目标进程ID相同
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 错误：返回 Err(())
+    ///
+    /// # 注意
+    /// 会话首进程不能改变进程组
+    fn sys_setpgid(&mut self) -> SysResult {
+        let pid = self.arg_i32(0);
+        let pgid = self.arg_i32(1);
+        if pid < 0 || pgid < 0 {
+            return Err(())
+        }
+        unsafe { PROC_MANAGER.set_pgid(self.index, pid as usize, pgid as usize)?; }
+        Ok(0)
+    }
+
+    /// 获取进程组
+    ///
+    /// # 功能说明
+    /// 查询目标进程所在的进程组。
+    ///
+    /// # 参数
+    /// - `pid`: 目标进程ID，为 0 时表示调用者
+    ///
+    /// # 返回值
+    /// - 成功：返回进程组号
+    /// - 错误：返回 Err(())
+    fn sys_getpgid(&mut self) -> SysResult {
+        let pid = self.arg_i32(0);
+        if pid < 0 {
+            return Err(())
+        }
+        let (pgid, _) = unsafe { PROC_MANAGER.group_ids(self.index, pid as usize).ok_or(())? };
+        Ok(pgid)
+    }
+
+    /// 创建会话
+    ///
+    /// # 功能说明
+    /// 创建一个新的会话，调用者成为新会话与新进程组的首进程。
+    ///
+    /// # 返回值
+    /// - 成功：返回新的会话号，即调用者的进程ID
+    /// - 错误：返回 Err(())
+    ///
+    /// # 注意
+    /// 进程组首进程不能创建会话，通常先 fork 再由子进程调用
+    fn sys_setsid(&mut self) -> SysResult {
+        unsafe { PROC_MANAGER.set_sid(self.index) }
+    }
+
+    /// 等待指定的子进程
+    ///
+    /// # 功能说明
+    /// 等待 `pid` 选中的子进程退出或被停止，状态按 `include/wait.h` 的约定编码。
+    ///
+    /// # 参数
+    /// - `pid`: 大于 0 时为子进程ID；为 -1 时表示任一子进程；
+    ///   为 0 时表示调用者所在进程组中的子进程；小于 -1 时表示进程组 `-pid` 中的子进程
+    /// - `status_addr`: 用户空间地址，用于存储编码后的状态，为 0 时不存储
+    /// - `options`: `WNOHANG` 与 `WUNTRACED` 的组合
+    ///
+    /// # 返回值
+    /// - 成功：返回状态改变的子进程ID；指定 `WNOHANG` 而没有子进程改变状态时返回 0
+    /// - 错误：返回 Err(())
+    fn sys_waitpid(&mut self) -> SysResult {
+        let pid = self.arg_i32(0) as isize;
+        let addr = self.arg_addr(1);
+        let options = self.arg_i32(2);
+        if options & !(WNOHANG | WUNTRACED) != 0 {
+            return Err(())
+        }
+        unsafe { PROC_MANAGER.waiting(self.index, pid, addr, options, true) }
+    }
+
+    /// 设置信号的处理方式
+    ///
+    /// # 功能说明
+    /// 设置整个线程组对信号的处理方式：采取默认动作或忽略。
+    /// 处理方式在 fork 时被继承，exec 后保持不变。
+    ///
+    /// # 参数
+    /// - `sig`: 信号编号
+    /// - `disp`: `SIG_DFL` 或 `SIG_IGN`
+    ///
+    /// # 返回值
+    /// - 成功：返回原来的处理方式
+    /// - 错误：返回 Err(())
+    ///
+    /// # 注意
+    /// `SIGKILL` 与 `SIGSTOP` 不能被忽略；忽略 `SIGCONT` 不影响其继续进程的作用
+    fn sys_signal(&mut self) -> SysResult {
+        let sig = self.arg_i32(0);
+        let ignore = match self.arg_raw(1) {
+            SIG_DFL => false,
+            SIG_IGN => true,
+            _ => return Err(()),
+        };
+        if sig < 0 {
+            return Err(())
+        }
+        let old = unsafe { PROC_MANAGER.set_disposition(self.index, sig as usize, ignore)? };
+        Ok(if old { SIG_IGN } else { SIG_DFL })
+    }
+
+    /// 设置闹钟
+    ///
+    /// # 功能说明
+    /// 在 `secs` 秒后向调用者所在的进程发送 `SIGALRM`，取代之前设置的闹钟或实时间隔定时器。
+    /// `secs` 为 0 时只取消原有的闹钟。
+    ///
+    /// # 参数
+    /// - `secs`: 秒数
+    ///
+    /// # 返回值
+    /// - 成功：返回原有闹钟的剩余秒数（向上取整），没有时返回 0
+    /// - 错误：返回 Err(())
+    ///
+    /// # 注意
+    /// `SIGALRM` 的默认动作是终止进程，可以用 `signal` 忽略
+    fn sys_alarm(&mut self) -> SysResult {
+        let secs = self.arg_raw(0) as u32 as usize;
+        let key = TimerKey::Real(unsafe { PROC_MANAGER.leader_pid(self.index) });
+        let ticks_per_sec = (MTIME_FREQ / TICK_INTERVAL) as usize;
+        let old = match secs {
+            0 => timer::cancel(key),
+            secs => timer::arm(key, secs * ticks_per_sec, 0)?,
+        };
+        Ok(old.map_or(0, |(ticks, _)| (ticks + ticks_per_sec - 1) / ticks_per_sec))
+    }
+
+    /// 设置间隔定时器
+    ///
+    /// # 功能说明
+    /// 设置进程的实时间隔定时器：经过 `it_value` 后发送 `SIGALRM`，
+    /// 之后若 `it_interval` 非 0 则每隔 `it_interval` 再发送一次。
+    /// `it_value` 为 0 时停止定时器。
+    ///
+    /// # 参数
+    /// - `which`: 定时器种类，只支持 `ITIMER_REAL`
+    /// - `new_addr`: 新设置的 `struct itimerval` 的用户空间地址
+    /// - `old_addr`: 用户空间地址，若非 0 则写入原有的设置
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 错误：返回 Err(())
+    ///
+    /// # 注意
+    /// 定时器的精度是一个时钟周期，时间向上取整到时钟周期
+    fn sys_setitimer(&mut self) -> SysResult {
+        if self.arg_i32(0) != ITIMER_REAL {
+            return Err(())
+        }
+        let new_addr = self.arg_addr(1);
+        let old_addr = self.arg_addr(2);
+        let mut new = Itimerval::default();
+        let pdata = self.data.get_mut();
+        pdata.copy_in(new_addr, &mut new as *mut Itimerval as *mut u8, mem::size_of::<Itimerval>())?;
+        let value = new.value.to_ticks().ok_or(())?;
+        let interval = new.interval.to_ticks().ok_or(())?;
+
+        let key = TimerKey::Real(unsafe { PROC_MANAGER.leader_pid(self.index) });
+        let old = match value {
+            0 => timer::cancel(key),
+            value => timer::arm(key, value, interval)?,
+        };
+        if old_addr != 0 {
+            let old = Itimerval::from_ticks(old);
+            self.data.get_mut().copy_out(&old as *const Itimerval as *const u8,
+                old_addr, mem::size_of::<Itimerval>())?;
+        }
+        Ok(0)
+    }
+
+    /// 查询间隔定时器
+    ///
+    /// # 功能说明
+    /// 查询进程的实时间隔定时器的剩余时间与间隔，定时器未设置时均为 0。
+    ///
+    /// # 参数
+    /// - `which`: 定时器种类，只支持 `ITIMER_REAL`
+    /// - `cur_addr`: 用户空间地址，写入当前的设置
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 错误：返回 Err(())
+    fn sys_getitimer(&mut self) -> SysResult {
+        if self.arg_i32(0) != ITIMER_REAL {
+            return Err(())
+        }
+        let addr = self.arg_addr(1);
+        let key = TimerKey::Real(unsafe { PROC_MANAGER.leader_pid(self.index) });
+        let cur = Itimerval::from_ticks(timer::query(key));
+        self.data.get_mut().copy_out(&cur as *const Itimerval as *const u8,
+            addr, mem::size_of::<Itimerval>())?;
+        Ok(0)
+    }
+
+    /// 读取时钟
+    ///
+    /// # 功能说明
+    /// 读取 CLINT 的 `mtime`，以 `struct timespec` 返回启动以来经过的时间，精度高于时钟周期。
+    ///
+    /// # 参数
+    /// - `clock`: `CLOCK_MONOTONIC` 或 `CLOCK_REALTIME`
+    /// - `tp_addr`: 用户空间地址，写入读到的时间
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 错误：返回 Err(())
+    ///
+    /// # 注意
+    /// 没有实时时钟，`CLOCK_REALTIME` 与 `CLOCK_MONOTONIC` 一样从启动时开始计时
+    fn sys_clock_gettime(&mut self) -> SysResult {
+        let clock = self.arg_i32(0);
+        if clock != CLOCK_REALTIME && clock != CLOCK_MONOTONIC {
+            return Err(())
+        }
+        let addr = self.arg_addr(1);
+        let tp = Timespec::from_mtime(timer::mtime());
+        self.data.get_mut().copy_out(&tp as *const Timespec as *const u8,
+            addr, mem::size_of::<Timespec>())?;
+        Ok(0)
+    }
+
+    /// 高精度睡眠
+    ///
+    /// # 功能说明
+    /// 使当前进程睡眠 `req` 指定的时间，以 `mtime` 判断是否到期。
+    ///
+    /// # 参数
+    /// - `req_addr`: 要睡眠的时间，`struct timespec` 的用户空间地址
+    /// - `rem_addr`: 用户空间地址，若非 0 则在睡眠被中断时写入剩余的时间
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 错误：返回 Err(())
+    ///
+    /// # 注意
+    /// 唤醒由定时器轮完成，实际睡眠的时间向上取整到时钟周期
+    fn sys_nanosleep(&mut self) -> SysResult {
+        let req_addr = self.arg_addr(0);
+        let rem_addr = self.arg_addr(1);
+        let mut req = Timespec::default();
+        let pdata = self.data.get_mut();
+        pdata.copy_in(req_addr, &mut req as *mut Timespec as *mut u8, mem::size_of::<Timespec>())?;
+        let deadline = req.to_mtime().and_then(|t| timer::mtime().checked_add(t)).ok_or(())?;
+
+        if timer::sleep_mtime(self, deadline).is_ok() {
+            return Ok(0)
+        }
+        if rem_addr != 0 {
+            let rem = Timespec::from_mtime(deadline.saturating_sub(timer::mtime()));
+            self.data.get_mut().copy_out(&rem as *const Timespec as *const u8,
+                rem_addr, mem::size_of::<Timespec>())?;
+        }
+        Err(())
+    }
+
+    /// 设置 CPU 亲和性
+    ///
+    /// # 功能说明
+    /// 限制线程只能在位图 `mask` 中的 CPU 上运行，第 n 位对应 n 号 CPU，不存在的 CPU 被忽略。
+    ///
+    /// # 参数
+    /// - `pid`: 目标线程的线程号，0 表示调用者自身
+    /// - `mask`: 允许运行的 CPU 的位图
+    ///
+    /// # 返回值
+    /// - 成功：返回 0
+    /// - 错误：返回 Err(())
+    ///
+    /// # 注意
+    /// 调用者不再被允许在当前 CPU 上运行时立即让出 CPU，其他线程在下一次调度时迁移
+    fn sys_sched_setaffinity(&mut self) -> SysResult {
+        let pid = self.arg_i32(0);
+        let mask = self.arg_raw(1) & ALL_HARTS;
+        if pid < 0 || mask == 0 {
+            return Err(())
+        }
+        unsafe { PROC_MANAGER.affinity(self.index, pid as usize, Some(mask))?; }
+
+        push_off();
+        let id = unsafe { CpuManager::cpu_id() };
+        pop_off();
+        if mask & (1 << id) == 0 && (pid == 0 || self.excl.lock().pid == pid as usize) {
+            self.yielding();
+        }
+        Ok(0)
+    }
+
+    /// 查询 CPU 亲和性
+    ///
+    /// # 功能说明
+    /// 返回线程允许运行的 CPU 的位图。
+    ///
+    /// # 参数
+    /// - `pid`: 目标线程的线程号，0 表示调用者自身
+    ///
+    /// # 返回值
+    /// - 成功：返回位图，第 n 位对应 n 号 CPU
+    /// - 错误：返回 Err(())
+    fn sys_sched_getaffinity(&mut self) -> SysResult {
+        let pid = self.arg_i32(0);
+        if pid < 0 {
+            return Err(())
+        }
+        unsafe { PROC_MANAGER.affinity(self.index, pid as usize, None) }
+    }
+
+    /// 控制内核分析器
+    ///
+    /// # 功能说明
+    /// 按 `cmd` 开启或关闭内核采样分析器：
+    /// - `KPROF_START`：清空之前的采样与自旋锁统计，开始采样，返回 0；
+    /// - `KPROF_STOP`：停止采样，返回开启以来的采样次数。
+    ///
+    /// # 参数
+    /// - `cmd`: 操作类型
+    ///
+    /// # 返回值
+    /// - 成功：返回操作的结果
+    /// - 错误：返回 Err(())
+    ///
+    /// # 注意
+    /// 采样结果从 `/proc/profile` 读取，自旋锁统计从 `/proc/lockstat` 读取
+    fn sys_kprof(&mut self) -> SysResult {
+        match self.arg_i32(0) {
+            KPROF_START => {
+                profile::start();
+                Ok(0)
+            }
+            KPROF_STOP => Ok(profile::stop()),
+            _ => Err(()),
+        }
+    }
+}
+
+/// 为一对文件分配两个文件描述符，例如管道的读端与写端。
+///
+/// 两个描述符在同一次持有文件表锁时分配，失败时两个文件都在释放锁之后关闭。
+fn install2(pdata: &ProcData, file1: Arc<File>, file2: Arc<File>) -> Result<(usize, usize), ()> {
+    let mut fdt = pdata.fdtable();
+    let fd1 = fdt.install(Arc::clone(&file1)).ok_or(())?;
+    match fdt.install(Arc::clone(&file2)) {
+        Some(fd2) => Ok((fd1, fd2)),
+        None => {
+            let file1 = fdt.take(fd1);
+            drop(fdt);
+            drop(file1);
+            Err(())
+        }
+    }
+}
+
+/// 使文件描述符 `fd` 指向 `file`，标志设为 `flags`，原先打开的文件在释放文件表的锁之后关闭。
+fn dup_to(pdata: &ProcData, file: Arc<File>, fd: usize, flags: i32) -> SysResult {
+    let ret = pdata.fdtable().install_at(file, fd, flags);
+    match ret {
+        Ok(old) => {
+            drop(old);
+            Ok(fd)
+        }
+        Err(file) => {
+            drop(file);
+            Err(())
+        }
+    }
+}
+
+/// 与用户态的 `struct rlimit` 内存布局一致。
+#[repr(C)]
+struct Rlimit {
+    cur: usize,
+    max: usize,
+}
+
+/// 撤销 [`install2`] 分配的一对文件描述符。
+fn uninstall2(pdata: &ProcData, fd1: usize, fd2: usize) {
+    let mut fdt = pdata.fdtable();
+    let files = (fdt.take(fd1), fdt.take(fd2));
+    drop(fdt);
+    drop(files);
 }
 
 /// 系统调用警告函数
@@ -765,7 +1583,7 @@ impl Syscall for Proc {
 /// # 注意
 /// 仅在启用 `kernel_warning` 特性时实际输出
 #[inline]
-fn syscall_warning<T: Display>(s: T) {
+pub(super) fn syscall_warning<T: Display>(s: T) {
     #[cfg(feature = "kernel_warning")]
-    println!("syscall waring: {}", s);
+    kwarn!("syscall waring: {}", s);
 }
@@ -0,0 +1,188 @@
+//! 系统调用表
+//!
+//! 本文件中的 [`syscall_table!`] 调用是系统调用的唯一定义来源，每一项给出
+//! 系统调用号、名称、处理函数、参数类型与返回值类型，文档注释说明返回 -1 的情形：
+//! - 内核：宏展开为以系统调用号为下标的 [`SYSCALLS`] 表，供分发与跟踪使用；
+//! - 用户库：`user/usys.pl` 解析本文件，生成 `user/usys.S` 中的系统调用桩，
+//!   以及 `include/syscall.h` 中的系统调用号；
+//! - 文档：`user/usys.pl -d` 生成 `docs/syscalls.md`。
+//!
+//! 新增系统调用时，在 [`Syscall`] 中实现处理函数并在表中加入一项即可。
+//! `usys.pl` 按行解析表项，每一项必须写在一行内。
+
+use super::Proc;
+use super::syscall::{Syscall, SysResult};
+
+/// 系统调用参数的解码方式，用于跟踪输出。
+#[derive(Clone, Copy)]
+pub enum Arg {
+    /// 有符号整数
+    Int,
+    /// 十六进制数，用于地址与标志位
+    Hex,
+    /// 用户空间中以 0 结尾的字符串，例如路径
+    Str,
+}
+
+/// 系统调用返回值的解码方式。
+#[derive(Clone, Copy)]
+pub enum Ret {
+    /// 有符号整数
+    Int,
+    /// 十六进制数，用于地址
+    Hex,
+    /// 成功时不返回
+    Never,
+}
+
+/// 一个系统调用的描述。
+pub struct SyscallDesc {
+    pub name: &'static str,
+    pub handler: fn(&mut Proc) -> SysResult,
+    pub args: &'static [Arg],
+    pub ret: Ret,
+}
+
+/// 查找 `num` 号系统调用的描述，系统调用号无效时返回 `None`。
+pub fn lookup(num: usize) -> Option<&'static SyscallDesc> {
+    SYSCALLS.get(num)?.as_ref()
+}
+
+macro_rules! syscall_table {
+    ($(
+        $(#[doc = $doc:literal])*
+        $num:literal $name:ident => $handler:ident($($arg:ident: $kind:ident),*) -> $ret:ident;
+    )*) => {
+        /// 系统调用号的上界（不含）。
+        pub const NSYSCALL: usize = {
+            let mut n = 0;
+            $(if $num >= n { n = $num + 1; })*
+            n
+        };
+
+        /// 系统调用描述表，以系统调用号为下标，未使用的编号为 `None`。
+        pub static SYSCALLS: [Option<SyscallDesc>; NSYSCALL] = {
+            const NONE: Option<SyscallDesc> = None;
+            let mut table = [NONE; NSYSCALL];
+            $(
+                assert!(table[$num].is_none(), "duplicate syscall number");
+                table[$num] = Some(SyscallDesc {
+                    name: stringify!($name),
+                    handler: <Proc as Syscall>::$handler,
+                    args: &[$(Arg::$kind),*],
+                    ret: Ret::$ret,
+                });
+            )*
+            table
+        };
+    };
+}
+
+syscall_table! {
+    /// 进程表已满或内存不足。
+    1 fork => sys_fork() -> Int;
+    /// 不会返回。
+    2 exit => sys_exit(status: Int) -> Never;
+    /// 没有子进程，或进程被终止，或 `status` 地址无效。
+    3 wait => sys_wait(status: Hex) -> Int;
+    /// 文件描述符或内存不足，或 `fds` 地址无效。
+    4 pipe => sys_pipe(fds: Hex) -> Int;
+    /// 文件描述符无效或不可读，或 `buf` 地址无效，或读取出错，或设置了 `O_NONBLOCK` 而没有可读的数据。
+    5 read => sys_read(fd: Int, buf: Hex, n: Int) -> Int;
+    /// 进程或进程组不存在，或 `pid` 为 -1，或信号不受支持。
+    6 kill => sys_kill(pid: Int, sig: Int) -> Int;
+    /// 路径或参数无效，或文件不是合法的 ELF 可执行文件，或内存不足，或进程正在退出、其他线程正在 exec。
+    7 exec => sys_exec(path: Str, argv: Hex) -> Int;
+    /// 文件描述符无效，或文件不支持状态查询，或 `st` 地址无效。
+    8 fstat => sys_fstat(fd: Int, st: Hex) -> Int;
+    /// 路径不存在或不是目录。
+    9 chdir => sys_chdir(path: Str) -> Int;
+    /// 文件描述符无效，或达到打开文件数的限制。
+    10 dup => sys_dup(fd: Int) -> Int;
+    /// 不会失败。
+    11 getpid => sys_getpid() -> Int;
+    /// 内存不足或地址空间越界。
+    12 sbrk => sys_sbrk(n: Int) -> Hex;
+    /// 进程被终止。
+    13 sleep => sys_sleep(ticks: Int) -> Int;
+    /// 不会失败。
+    14 uptime => sys_uptime() -> Int;
+    /// 路径不存在（未指定 `O_CREATE`），或以写方式打开目录，或达到打开文件数的限制。
+    15 open => sys_open(path: Str, flags: Hex) -> Int;
+    /// 文件描述符无效或不可写，或 `buf` 地址无效，或写入出错（例如管道读端已关闭），或设置了 `O_NONBLOCK` 而无法立即写入。
+    16 write => sys_write(fd: Int, buf: Hex, n: Int) -> Int;
+    /// 路径已存在或父目录不存在。
+    17 mknod => sys_mknod(path: Str, major: Int, minor: Int) -> Int;
+    /// 路径不存在，或是 `.`、`..`，或是非空目录。
+    18 unlink => sys_unlink(path: Str) -> Int;
+    /// 源路径不存在或是目录，或新路径已存在，或两者不在同一设备上。
+    19 link => sys_link(old: Str, new: Str) -> Int;
+    /// 路径已存在或父目录不存在。
+    20 mkdir => sys_mkdir(path: Str) -> Int;
+    /// 文件描述符无效。
+    21 close => sys_close(fd: Int) -> Int;
+    /// 文件描述符无效，或文件不支持该命令，或参数非法。
+    22 ioctl => sys_ioctl(fd: Int, request: Hex, arg: Hex) -> Int;
+    /// 文件描述符或内存不足，或 `fds` 地址无效。
+    23 openpty => sys_openpty(fds: Hex) -> Int;
+    /// 操作或参数无效，或 `buf` 地址无效。
+    24 syslog => sys_syslog(action: Int, buf: Hex, len: Int) -> Int;
+    /// 文件描述符无效或不可写。
+    25 trace => sys_trace(mask: Hex, fd: Int) -> Int;
+    /// 进程表已满、线程数已达上限或内存不足。
+    26 clone => sys_clone(func: Hex, arg: Hex, stack: Hex) -> Int;
+    /// 线程组中没有该线程（或是主线程、调用者自身），或调用者被终止，或 `status` 地址无效。
+    27 join => sys_join(tid: Int, status: Hex) -> Int;
+    /// 不会返回。
+    28 exit_thread => sys_exit_thread(status: Int) -> Never;
+    /// 地址无效或未对齐，或操作未知，或字的值不等于 `val`，或进程被终止。等待超时不返回 -1，而是返回 -2（`FUTEX_TIMEDOUT`）。
+    29 futex => sys_futex(addr: Hex, op: Int, val: Int, timeout: Int) -> Int;
+    /// 段不存在且未指定 `IPC_CREAT`，或指定了 `IPC_EXCL` 而段已存在，或大小非法，或段表已满、内存不足。
+    30 shmget => sys_shmget(key: Int, size: Int, flags: Hex) -> Int;
+    /// 段不存在，或地址未对齐、不在共享内存区域内、与已有映射重叠，或内存不足。
+    31 shmat => sys_shmat(id: Int, addr: Hex) -> Hex;
+    /// 该地址上没有共享内存映射。
+    32 shmdt => sys_shmdt(addr: Hex) -> Int;
+    /// 段不存在或命令不受支持。
+    33 shmctl => sys_shmctl(id: Int, cmd: Int) -> Int;
+    /// 数组过长或地址无效，或进程被终止。
+    34 poll => sys_poll(fds: Hex, nfds: Int, timeout: Int) -> Int;
+    /// 文件描述符无效，或命令不受支持，或没有不小于 `arg` 的空闲文件描述符。
+    35 fcntl => sys_fcntl(fd: Int, cmd: Int, arg: Hex) -> Int;
+    /// 文件描述符无效，或 `new` 超出打开文件数的限制。
+    36 dup2 => sys_dup2(old: Int, new: Int) -> Int;
+    /// 文件描述符无效或相同，或 `new` 超出打开文件数的限制，或标志非法。
+    37 dup3 => sys_dup3(old: Int, new: Int, flags: Hex) -> Int;
+    /// 资源不受支持，或 `rlim` 地址无效。
+    38 getrlimit => sys_getrlimit(resource: Int, rlim: Hex) -> Int;
+    /// 资源不受支持，或 `rlim` 地址无效，或软限制超过硬限制，或试图提高硬限制。
+    39 setrlimit => sys_setrlimit(resource: Int, rlim: Hex) -> Int;
+    /// 路径已存在或父目录不存在，或目标文件系统不支持创建。
+    40 mkfifo => sys_mkfifo(path: Str) -> Int;
+    /// 目标不是调用者或其子进程，或不在调用者的会话中，或是会话首进程，或进程组不在调用者的会话中。
+    41 setpgid => sys_setpgid(pid: Int, pgid: Int) -> Int;
+    /// 进程不存在。
+    42 getpgid => sys_getpgid(pid: Int) -> Int;
+    /// 调用者已是进程组首进程。
+    43 setsid => sys_setsid() -> Int;
+    /// 没有选中的子进程，或进程被终止，或 `status` 地址无效，或选项非法。
+    44 waitpid => sys_waitpid(pid: Int, status: Hex, options: Hex) -> Int;
+    /// 信号不受支持或不能忽略，或处理方式非法。
+    45 signal => sys_signal(sig: Int, disp: Int) -> Int;
+    /// 内存不足。
+    46 alarm => sys_alarm(secs: Int) -> Int;
+    /// 定时器不受支持，或时间非法，或地址无效，或内存不足。
+    47 setitimer => sys_setitimer(which: Int, new: Hex, old: Hex) -> Int;
+    /// 定时器不受支持，或地址无效。
+    48 getitimer => sys_getitimer(which: Int, cur: Hex) -> Int;
+    /// 时钟不受支持，或地址无效。
+    49 clock_gettime => sys_clock_gettime(clock: Int, tp: Hex) -> Int;
+    /// 时间非法，或地址无效，或进程被终止，或内存不足。
+    50 nanosleep => sys_nanosleep(req: Hex, rem: Hex) -> Int;
+    /// 线程不存在，或位图中没有存在的 CPU。
+    51 sched_setaffinity => sys_sched_setaffinity(pid: Int, mask: Hex) -> Int;
+    /// 线程不存在。
+    52 sched_getaffinity => sys_sched_getaffinity(pid: Int) -> Hex;
+    /// 操作类型非法。
+    53 kprof => sys_kprof(cmd: Int) -> Int;
+}
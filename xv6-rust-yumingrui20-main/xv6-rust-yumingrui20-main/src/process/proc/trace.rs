@@ -0,0 +1,87 @@
+//! 系统调用跟踪
+//!
+//! 进程通过 `trace(mask, fd)` 系统调用设置跟踪掩码，掩码中第 n 位为 1 表示跟踪 n 号系统调用。
+//! 被跟踪的系统调用返回时，根据系统调用表中的参数类型解码参数与返回值，
+//! 以 `[pid] name(args) = ret` 的形式写入指定的跟踪文件（例如 strace 持有读端的管道），
+//! 未指定文件时写入内核日志。跟踪设置在 fork 时被子进程继承，exec 后保持不变。
+//!
+//! 启用 `trace_syscall` 特性时，所有进程的所有系统调用都会以跟踪级别写入内核日志。
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::consts::MAXPATH;
+
+use super::Proc;
+use super::systable::{self, Arg, Ret};
+
+impl Proc {
+    /// 系统调用进入时调用：若 `num` 号系统调用需要跟踪，解码其参数并返回尚未完成的跟踪行。
+    ///
+    /// 参数必须在系统调用执行之前解码，因为 a0 寄存器会被返回值覆盖。
+    /// 不会返回的系统调用（如 exit）在这里直接输出。
+    pub(super) fn trace_enter(&mut self, num: usize) -> Option<String> {
+        let desc = systable::lookup(num)?;
+        if self.data.get_mut().trace_mask & (1 << num) == 0 && !cfg!(feature = "trace_syscall") {
+            return None
+        }
+
+        let mut line = String::new();
+        let pid = self.excl.lock().pid;
+        let _ = write!(line, "[{}] {}(", pid, desc.name);
+        for (i, arg) in desc.args.iter().enumerate() {
+            if i > 0 {
+                line.push_str(", ");
+            }
+            let raw = self.arg_raw(i);
+            let _ = match arg {
+                Arg::Int => write!(line, "{}", raw as isize),
+                Arg::Hex => write!(line, "{:#x}", raw),
+                Arg::Str => {
+                    let mut buf = [0u8; MAXPATH];
+                    match self.arg_str(i, &mut buf) {
+                        Ok(()) => {
+                            let len = buf.iter().position(|&c| c == 0).unwrap_or(MAXPATH);
+                            write!(line, "{:?}", String::from_utf8_lossy(&buf[..len]))
+                        }
+                        Err(_) => write!(line, "{:#x}", raw),
+                    }
+                }
+            };
+        }
+        line.push(')');
+
+        if let Ret::Never = desc.ret {
+            line.push_str(" = ?");
+            self.trace_emit(line);
+            return None
+        }
+        Some(line)
+    }
+
+    /// 系统调用返回时调用：补上返回值并输出跟踪行。
+    pub(super) fn trace_exit(&mut self, num: usize, mut line: String, ret: Result<usize, ()>) {
+        let _ = match (ret, systable::lookup(num).unwrap().ret) {
+            (Ok(v), Ret::Hex) => write!(line, " = {:#x}", v),
+            (Ok(v), _) => write!(line, " = {}", v as isize),
+            (Err(()), _) => write!(line, " = -1"),
+        };
+        self.trace_emit(line);
+    }
+
+    /// 将一行跟踪输出写入跟踪文件，没有跟踪文件时写入内核日志。
+    fn trace_emit(&mut self, mut line: String) {
+        let pdata = self.data.get_mut();
+        if pdata.trace_mask == 0 {
+            ktrace!("{}", line);
+            return
+        }
+        match pdata.trace_file.as_ref() {
+            Some(file) => {
+                line.push('\n');
+                let _ = file.fwrite_kernel(line.as_bytes());
+            }
+            None => kinfo!("{}", line),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! 用户地址空间
+//!
+//! 同一线程组的所有线程共享一个 [`AddrSpace`]：页表与用户内存大小都是共享的，
+//! 某个线程 sbrk 之后其他线程立即可见。
+//!
+//! 每个线程有自己的陷阱帧页，主线程的陷阱帧映射在 `TRAPFRAME`，
+//! 其他线程依次映射在其下方的页中，陷阱处理通过 `sscratch` 找到各自的陷阱帧。
+//!
+//! 共享内存段映射在 `SHMBASE` 与最低的陷阱帧之间，堆不能增长到 `SHMBASE` 之上。
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::consts::{PGSIZE, SHMBASE, TRAMPOLINE, TRAPFRAME, NTHREAD};
+use crate::mm::{PageTable, PhysAddr, PteFlag, ShmSegment, VirtAddr, pg_round_up};
+
+/// 地址空间中的一个共享内存映射。
+pub struct ShmMapping {
+    /// 映射的起始虚拟地址
+    pub va: usize,
+    /// 被映射的段
+    pub seg: Arc<ShmSegment>,
+}
+
+impl ShmMapping {
+    /// 映射的结束虚拟地址（不含）
+    pub fn end(&self) -> usize {
+        self.va + self.seg.size()
+    }
+}
+
+/// 线程组共享的用户地址空间，由 `SpinLock` 保护。
+pub struct AddrSpace {
+    /// 用户页表
+    pub pagetable: Box<PageTable>,
+    /// 用户地址空间的大小（字节数）
+    pub sz: usize,
+    /// 陷阱帧槽位的占用情况，第 i 位对应映射在 `TRAPFRAME - i*PGSIZE` 的陷阱帧
+    tf_slots: usize,
+    /// 共享内存映射，按起始地址排序
+    shm: Vec<ShmMapping>,
+}
+
+impl AddrSpace {
+    /// 创建只包含跳板页与陷阱帧 `tf` 的地址空间，陷阱帧映射在 `TRAPFRAME`。
+    pub fn new(tf: usize) -> Option<Self> {
+        let pagetable = PageTable::alloc_proc_pagetable(tf)?;
+        Some(Self::from_pagetable(pagetable, 0))
+    }
+
+    /// 由 [`PageTable::alloc_proc_pagetable`] 创建的页表构造地址空间，
+    /// 页表中的陷阱帧占用槽位 0。
+    pub fn from_pagetable(pagetable: Box<PageTable>, sz: usize) -> Self {
+        Self {
+            pagetable,
+            sz,
+            tf_slots: 1,
+            shm: Vec::new(),
+        }
+    }
+
+    /// 为新线程映射陷阱帧 `tf`
+    ///
+    /// # 返回值
+    /// - `Some(va)`：陷阱帧在用户页表中的虚拟地址；
+    /// - `None`：线程数已达上限 [`NTHREAD`] 或内存不足。
+    pub fn map_trapframe(&mut self, tf: usize) -> Option<usize> {
+        let slot = (0..NTHREAD).find(|&i| self.tf_slots & (1 << i) == 0)?;
+        let va = usize::from(TRAPFRAME) - slot * PGSIZE;
+        self.pagetable
+            .map_pages(
+                VirtAddr::try_from(va).unwrap(),
+                PGSIZE,
+                PhysAddr::try_from(tf).unwrap(),
+                PteFlag::R | PteFlag::W,
+            )
+            .ok()?;
+        self.tf_slots |= 1 << slot;
+        Some(va)
+    }
+
+    /// 撤销虚拟地址 `va` 处的陷阱帧映射，不释放陷阱帧本身。
+    pub fn unmap_trapframe(&mut self, va: usize) {
+        let slot = (usize::from(TRAPFRAME) - va) / PGSIZE;
+        debug_assert!(self.tf_slots & (1 << slot) != 0);
+        self.pagetable.uvm_unmap(va, 1, false);
+        self.tf_slots &= !(1 << slot);
+    }
+
+    /// 堆可以增长到的最大大小，共享内存区域从此处开始。
+    pub fn max_size() -> usize {
+        usize::from(SHMBASE)
+    }
+
+    /// # 功能说明
+    /// 将共享内存段 `seg` 映射到虚拟地址 `addr`，`addr` 为 0 时由内核在共享内存区域中选择地址。
+    ///
+    /// # 返回值
+    /// - `Ok(va)`：映射的起始虚拟地址；
+    /// - `Err(())`：地址未按页对齐、不在共享内存区域内或与已有映射重叠，或区域已满、内存不足。
+    pub fn shm_attach(&mut self, seg: Arc<ShmSegment>, addr: usize) -> Result<usize, ()> {
+        let size = seg.size();
+        let base = pg_round_up(self.sz).max(Self::max_size());
+        let top = usize::from(TRAPFRAME) - (NTHREAD-1)*PGSIZE;
+        let va = if addr == 0 {
+            // 第一个足够大的空隙
+            let mut va = base;
+            for m in self.shm.iter() {
+                if va + size <= m.va {
+                    break;
+                }
+                va = va.max(m.end());
+            }
+            va
+        } else {
+            if addr % PGSIZE != 0 || addr < base {
+                return Err(())
+            }
+            if self.shm.iter().any(|m| addr < m.end() && m.va < addr + size) {
+                return Err(())
+            }
+            addr
+        };
+        if va + size > top {
+            return Err(())
+        }
+
+        self.shm.try_reserve(1).map_err(|_| ())?;
+        seg.map(&mut self.pagetable, va)?;
+        let pos = self.shm.iter().position(|m| m.va > va).unwrap_or(self.shm.len());
+        self.shm.insert(pos, ShmMapping { va, seg });
+        Ok(va)
+    }
+
+    /// 解除起始地址为 `addr` 的共享内存映射，返回被映射的段。
+    /// 段可能因此被释放，丢弃返回值不会睡眠。
+    pub fn shm_detach(&mut self, addr: usize) -> Option<Arc<ShmSegment>> {
+        let pos = self.shm.iter().position(|m| m.va == addr)?;
+        let m = self.shm.remove(pos);
+        self.pagetable.uvm_unmap(m.va, m.seg.size() / PGSIZE, false);
+        Some(m.seg)
+    }
+
+    /// `[addr, addr+len)` 是否整个位于堆 `[0, sz)` 或某一个共享内存映射之内。
+    pub fn in_user_range(&self, addr: usize, len: usize) -> bool {
+        let end = match addr.checked_add(len) {
+            Some(end) => end,
+            None => return false,
+        };
+        end <= self.sz || self.shm.iter().any(|m| m.va <= addr && end <= m.end())
+    }
+
+    /// 所有共享内存映射，按起始地址排序。
+    pub fn shm_mappings(&self) -> &[ShmMapping] {
+        &self.shm
+    }
+
+    /// fork 时在子进程的地址空间 `child` 中建立相同的共享内存映射。
+    pub fn shm_copy(&self, child: &mut AddrSpace) -> Result<(), ()> {
+        child.shm.try_reserve_exact(self.shm.len()).map_err(|_| ())?;
+        for m in self.shm.iter() {
+            m.seg.map(&mut child.pagetable, m.va)?;
+            child.shm.push(ShmMapping { va: m.va, seg: Arc::clone(&m.seg) });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AddrSpace {
+    /// 最后一个线程释放地址空间时，撤销剩余的陷阱帧映射、共享内存映射与跳板页映射，并释放用户内存。
+    fn drop(&mut self) {
+        while let Some(m) = self.shm.pop() {
+            self.pagetable.uvm_unmap(m.va, m.seg.size() / PGSIZE, false);
+        }
+        for slot in 0..NTHREAD {
+            if self.tf_slots & (1 << slot) != 0 {
+                self.unmap_trapframe(usize::from(TRAPFRAME) - slot * PGSIZE);
+            }
+        }
+        self.pagetable.uvm_unmap(TRAMPOLINE.into(), 1, false);
+        if self.sz > 0 {
+            self.pagetable.uvm_unmap(0, pg_round_up(self.sz)/PGSIZE, true);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! 内核采样分析器
+//!
+//! 分析器开启后，运行进程的 CPU 每隔 [`PROFILE_INTERVAL`] 产生一次时钟中断（见 `trap::clock_arm`），
+//! 陷阱处理程序记录被中断的指令地址 `sepc`，并区分中断发生在用户态还是内核态。
+//! 空闲的 CPU 不产生采样。采样保存在固定大小的缓冲区中，写满后丢弃之后的采样。
+//!
+//! 采样由 `/proc/profile` 按地址汇总输出，在主机上用 `tools/kprof.py` 对照内核 ELF 文件换算成函数名。
+
+use array_macro::array;
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::consts::PROFILE_INTERVAL;
+use crate::spinlock;
+
+/// 采样缓冲区的容量
+const NSAMPLE: usize = 8192;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// 开启以来的采样次数，可能超过缓冲区容量
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+/// 采样的指令地址，最低位为 1 表示用户态（指令地址至少两字节对齐）
+static SAMPLES: [AtomicUsize; NSAMPLE] = array![_ => AtomicUsize::new(0); NSAMPLE];
+
+/// 分析器是否开启。
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// 清空之前的采样与锁统计，开启分析器。
+pub fn start() {
+    ENABLED.store(false, Ordering::SeqCst);
+    COUNT.store(0, Ordering::SeqCst);
+    spinlock::reset_lock_stats();
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// 关闭分析器，返回开启以来的采样次数。
+pub fn stop() -> usize {
+    ENABLED.store(false, Ordering::SeqCst);
+    COUNT.load(Ordering::SeqCst)
+}
+
+/// 由时钟中断处理程序调用，记录被中断的指令地址 `pc`。
+///
+/// # 参数
+/// - `pc`：中断时的 `sepc`；
+/// - `user`：中断是否发生在用户态。
+pub fn sample(pc: usize, user: bool) {
+    if !enabled() {
+        return
+    }
+    let i = COUNT.fetch_add(1, Ordering::Relaxed);
+    if i < NSAMPLE {
+        SAMPLES[i].store(pc | user as usize, Ordering::Relaxed);
+    }
+}
+
+/// 生成 `/proc/profile` 的内容：首行为汇总信息，之后每行为 `次数 k|u 地址`，按次数从多到少排列。
+pub fn dump(buf: &mut String) -> fmt::Result {
+    let count = COUNT.load(Ordering::SeqCst);
+    let recorded = count.min(NSAMPLE);
+    writeln!(buf, "# samples {} dropped {} interval {}{}", recorded, count - recorded,
+        PROFILE_INTERVAL, if enabled() { " running" } else { "" })?;
+
+    let mut hist = BTreeMap::new();
+    for sample in SAMPLES[..recorded].iter() {
+        *hist.entry(sample.load(Ordering::Relaxed)).or_insert(0usize) += 1;
+    }
+    let mut hist: Vec<(usize, usize)> = hist.into_iter().collect();
+    hist.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+    for (sample, n) in hist {
+        let mode = if sample & 1 != 0 { 'u' } else { 'k' };
+        writeln!(buf, "{} {} {:#x}", n, mode, sample & !1)?;
+    }
+    Ok(())
+}
@@ -6,16 +6,17 @@
 //! # 关键寄存器
 //! - `mtime`: 64位全局计时器（所有核心共享）
 //! - `mtimecmp`: 每个核心独立的64位计时器比较寄存器
+//! - `msip`: 每个核心独立的32位软件中断寄存器，写 1 触发该核心的机器模式软件中断
 //!
 //! # 工作原理
 //! 当 `mtime` 的值大于或等于某个核心的 `mtimecmp` 时，
-//! 会触发该核心的定时器中断。通过定期更新 `mtimecmp`，
-//! 可以实现周期性定时器中断。
+//! 会触发该核心的定时器中断。每次中断后按需更新 `mtimecmp`，
+//! 即可决定下一次中断的时间。
 
 use core::ptr;
 use core::convert::Into;
 
-use crate::consts::{CLINT_MTIME, CLINT_MTIMECMP};
+use crate::consts::{CLINT_MSIP, CLINT_MTIME, CLINT_MTIMECMP};
 
 /// 读取全局计时器值 (mtime)
 ///
@@ -29,7 +30,7 @@ use crate::consts::{CLINT_MTIME, CLINT_MTIMECMP};
 /// - 直接访问内存映射寄存器
 /// - 使用 volatile 读取确保不被编译器优化
 #[inline]
-unsafe fn read_mtime() -> u64 {
+pub unsafe fn read_mtime() -> u64 {
     ptr::read_volatile(Into::<usize>::into(CLINT_MTIME) as *const u64)
 }
 
@@ -53,6 +54,7 @@ unsafe fn write_mtimecmp(mhartid: usize, value: u64) {
 /// # 功能说明
 /// 基于当前时间设置未来的定时器中断：
 ///   新比较值 = 当前时间 + 间隔
+/// 结果溢出时取最大值，即不再触发中断。
 ///
 /// # 参数
 /// - `mhartid`: 目标核心ID
@@ -69,7 +71,23 @@ unsafe fn write_mtimecmp(mhartid: usize, value: u64) {
 /// ```
 pub unsafe fn add_mtimecmp(mhartid: usize, interval: u64) {
     let value = read_mtime();
-    write_mtimecmp(mhartid, value + interval);
+    write_mtimecmp(mhartid, value.saturating_add(interval));
+}
+
+/// 触发核心的机器模式软件中断
+///
+/// # 功能说明
+/// 向目标核心的 `msip` 寄存器写 1，目标核心的 `timervec` 清除它并转为监督者模式软件中断。
+///
+/// # 参数
+/// - `mhartid`: 目标核心ID
+///
+/// # 安全性
+/// - 直接访问硬件寄存器
+/// - 需确保核心ID有效
+pub unsafe fn set_msip(mhartid: usize) {
+    let offset = Into::<usize>::into(CLINT_MSIP) + 4 * mhartid;
+    ptr::write_volatile(offset as *mut u32, 1);
 }
 
 /// 读取核心的计时器比较寄存器值
@@ -50,3 +50,14 @@ pub unsafe fn set_mtie() {
     mie.set_bit(7, true);
     write(mie);
 }
+
+/// 启用机器模式软件中断（MSIE），用于接收处理器间中断
+///
+/// # 安全性
+/// - 修改特权寄存器状态
+/// - 应在中断系统初始化后调用
+pub unsafe fn set_msie() {
+    let mut mie = read();
+    mie.set_bit(3, true);
+    write(mie);
+}
@@ -32,6 +32,42 @@ pub mod medeleg {
     }
 }
 
+/// 机器计数器使能寄存器 (mcounteren) 操作
+///
+/// # 功能说明
+/// 控制监督模式能否读取 `cycle`、`time`、`instret` 计数器
+pub mod mcounteren {
+    /// 设置 mcounteren 寄存器值
+    ///
+    /// # 参数
+    /// - `mcounteren`: 位 0、1、2 分别对应 `cycle`、`time`、`instret`
+    pub unsafe fn write(mcounteren: usize) {
+        core::arch::asm!("csrw mcounteren, {}", in(reg)mcounteren);
+    }
+}
+
+/// 性能计数器 (cycle, time) 读取
+///
+/// # 功能说明
+/// 在监督模式下读取计数器，需要机器模式先在 `mcounteren` 中允许访问
+pub mod counter {
+    /// 读取时钟周期计数器 (cycle)
+    #[inline]
+    pub unsafe fn cycle() -> u64 {
+        let ret: u64;
+        core::arch::asm!("csrr {}, cycle", out(reg)ret);
+        ret
+    }
+
+    /// 读取实时计数器 (time)，与 CLINT 的 `mtime` 同源
+    #[inline]
+    pub unsafe fn time() -> u64 {
+        let ret: u64;
+        core::arch::asm!("csrr {}, time", out(reg)ret);
+        ret
+    }
+}
+
 /// 机器异常程序计数器 (mepc) 操作
 ///
 /// # 功能说明
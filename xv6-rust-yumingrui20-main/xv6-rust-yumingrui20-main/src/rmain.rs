@@ -2,7 +2,7 @@
 
 use core::sync::atomic::{AtomicBool, Ordering};
 
-use crate::driver::{virtio_disk::DISK, console};
+use crate::driver::{virtio_disk::DISK, console, mem, random};
 use crate::register::tp;
 use crate::fs::BCACHE;
 use crate::mm::kalloc::KERNEL_HEAP;
@@ -10,6 +10,7 @@ use crate::mm::{kvm_init, kvm_init_hart};
 use crate::plic;
 use crate::process::{PROC_MANAGER, CPU_MANAGER};
 use crate::trap::trap_init_hart;
+use crate::ipi;
 
 /// 用于多核启动同步的全局原子布尔变量。
 ///
@@ -58,8 +59,11 @@ pub unsafe fn rust_main() -> ! {
         PROC_MANAGER.proc_init(); // 进程表
         kvm_init_hart(); // 开启分页
         trap_init_hart(); // 安装内核陷阱向量
+        ipi::set_online(); // 可以接收处理器间中断
         plic::init();
         plic::init_hart(cpuid);
+        mem::init();                // 内存类字符设备
+        random::init();             // 随机数设备
         BCACHE.binit();             // 缓冲区缓存
         DISK.lock().init();         // 仿真硬盘
         PROC_MANAGER.user_init();   //  第一个用户进程
@@ -71,6 +75,7 @@ pub unsafe fn rust_main() -> ! {
         println!("hart {} starting", cpuid);
         kvm_init_hart(); // 开启分页
         trap_init_hart(); // 安装内核陷阱向量
+        ipi::set_online(); // 可以接收处理器间中断
         plic::init_hart(cpuid); // 向 PLIC 请求设备中断
     }
 
@@ -8,6 +8,8 @@ use core::cell::{Cell, UnsafeCell};
 
 use crate::process::{CPU_MANAGER, PROC_MANAGER};
 use crate::spinlock::SpinLock;
+#[cfg(feature = "lockdep")]
+use crate::lockdep::{self, LockKind};
 
 /// 睡眠锁结构，提供阻塞式同步机制
 ///
@@ -74,6 +76,9 @@ impl<T: ?Sized> SleepLock<T> {
     /// # 安全性
     /// - 使用`UnsafeCell`获取数据指针，但通过守卫模式保证安全访问
     pub fn lock(&self) -> SleepLockGuard<'_, T> {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.name, self as *const Self as *const u8 as usize, LockKind::Sleep);
+
         // 获取内部自旋锁（保护locked状态）
         let mut guard = self.lock.lock();
 
@@ -108,6 +113,9 @@ impl<T: ?Sized> SleepLock<T> {
     /// 3. 唤醒等待该锁的进程
     /// 4. 释放内部自旋锁
     fn unlock(&self) {
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self as *const Self as *const u8 as usize, LockKind::Sleep);
+
         let guard = self.lock.lock();
         self.locked.set(false);
         self.wakeup();
@@ -1,11 +1,19 @@
 //! 自旋锁模块
 //! 自旋锁将数据包裹在自身内部以保护这些数据。
 
+use array_macro::array;
+
+use alloc::vec::Vec;
 use core::cell::{Cell, UnsafeCell};
+use core::hint::spin_loop;
 use core::ops::{Deref, DerefMut, Drop};
-use core::sync::atomic::{fence, AtomicBool, Ordering};
+use core::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
 use core::ptr::addr_of_mut;
 
+use crate::consts::NCPU;
+use crate::ipi;
+#[cfg(feature = "lockdep")]
+use crate::lockdep::{self, LockKind};
 use crate::process::{CpuManager, pop_off, push_off};
 
 /// 表示一个自旋锁结构，用于在多核环境下保护共享数据。
@@ -21,12 +29,14 @@ use crate::process::{CpuManager, pop_off, push_off};
 /// - `lock`: 原子布尔值，表示锁的状态（`false`=未锁定，`true`=已锁定）；
 /// - `name`: 锁的名称，用于调试和标识；
 /// - `cpuid`: 当前持有锁的CPU ID（-1表示无CPU持有）；
+/// - `class`: 锁类在统计表中的序号加一，第一次获取时查找，0 表示尚未查找；
 /// - `data`: 被保护的数据，通过`UnsafeCell`实现内部可变性。
 #[derive(Debug)]
 pub struct SpinLock<T: ?Sized> {
     lock: AtomicBool,
     name: &'static str,
     cpuid: Cell<isize>,
+    class: AtomicUsize,
     data: UnsafeCell<T>,
 }
 
@@ -52,6 +62,7 @@ impl<T> SpinLock<T> {
             lock: AtomicBool::new(false),
             name,
             cpuid: Cell::new(-1),
+            class: AtomicUsize::new(0),
             data: UnsafeCell::new(data),
         }
     }
@@ -137,10 +148,10 @@ impl<T: ?Sized> SpinLock<T> {
     ///
     /// # 流程解释
     /// 1. 调用`push_off()`禁用中断；
-    /// 2. 检查是否已持有锁（防止死锁）；
-    /// 3. 使用原子比较交换（CAS）忙等待获取锁；
+    /// 2. 检查是否已持有锁（防止死锁），启用 `lockdep` 特性时检查获取顺序；
+    /// 3. 使用原子比较交换（CAS）忙等待获取锁，等待期间处理发给本 CPU 的跨核调用；
     /// 4. 获取成功后设置内存屏障；
-    /// 5. 记录当前CPU ID。
+    /// 5. 记录当前CPU ID，并把获取次数与忙等待次数计入锁类的统计。
     ///
     /// # 注意
     /// 此方法不返回守卫对象，仅供内部使用。
@@ -149,10 +160,31 @@ impl<T: ?Sized> SpinLock<T> {
         if unsafe { self.holding() } {
             panic!("spinlock {} acquire", self.name);
         }
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self.name, self as *const Self as *const u8 as usize, LockKind::Spin);
+        let mut spins = 0;
         while self.lock.compare_exchange(false, true,
-            Ordering::Acquire, Ordering::Acquire).is_err() {}
+            Ordering::Acquire, Ordering::Acquire).is_err() {
+            // 持有锁的 CPU 可能正在等待本 CPU 响应跨核调用
+            ipi::handle();
+            spins += 1;
+        }
         fence(Ordering::SeqCst);
-        unsafe { self.cpuid.set(CpuManager::cpu_id() as isize) };
+        let id = unsafe { CpuManager::cpu_id() };
+        self.cpuid.set(id as isize);
+        LOCK_STAT[id][self.class()].record(spins);
+    }
+
+    /// 返回锁类在统计表中的序号，第一次调用时按名称查找并缓存在锁中。
+    fn class(&self) -> usize {
+        match self.class.load(Ordering::Relaxed) {
+            0 => {
+                let class = lock_class(self.name);
+                self.class.store(class + 1, Ordering::Relaxed);
+                class
+            }
+            class => class - 1,
+        }
     }
 
     /// 释放锁的核心实现（内部方法）。
@@ -170,6 +202,8 @@ impl<T: ?Sized> SpinLock<T> {
         if unsafe { !self.holding() } {
             panic!("spinlock {} release", self.name);
         }
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self as *const Self as *const u8 as usize, LockKind::Spin);
         self.cpuid.set(-1);
         fence(Ordering::SeqCst);
         self.lock.store(false, Ordering::Release);
@@ -256,6 +290,110 @@ impl<'a, T> SpinLockGuard<'a, T> {
     }
 }
 
+/// 统计表中锁类的数量上限，超出的锁类都计入最后一项
+const NLOCKCLASS: usize = 64;
+
+/// 一个 CPU 上一个锁类的统计，只由该 CPU 在持有锁时更新，不需要原子的读-改-写。
+struct ClassStat {
+    acquired: AtomicUsize,
+    contended: AtomicUsize,
+    spins: AtomicUsize,
+}
+
+impl ClassStat {
+    const fn new() -> Self {
+        Self {
+            acquired: AtomicUsize::new(0),
+            contended: AtomicUsize::new(0),
+            spins: AtomicUsize::new(0),
+        }
+    }
+
+    fn record(&self, spins: usize) {
+        let add = |counter: &AtomicUsize, n: usize|
+            counter.store(counter.load(Ordering::Relaxed) + n, Ordering::Relaxed);
+        add(&self.acquired, 1);
+        if spins > 0 {
+            add(&self.contended, 1);
+            add(&self.spins, spins);
+        }
+    }
+}
+
+/// 锁类的名称，名称相同的自旋锁属于同一个锁类
+static mut LOCK_CLASSES: [&str; NLOCKCLASS] = [""; NLOCKCLASS];
+static NLOCKCLASSES: AtomicUsize = AtomicUsize::new(0);
+/// 保护 [`LOCK_CLASSES`] 的标志，本身不能用自旋锁实现
+static CLASS_LOCK: AtomicBool = AtomicBool::new(false);
+/// `LOCK_STAT[cpu][class]`：每个 CPU 上每个锁类的统计
+static LOCK_STAT: [[ClassStat; NLOCKCLASS]; NCPU] =
+    array![_ => array![_ => ClassStat::new(); NLOCKCLASS]; NCPU];
+
+/// 查找名称为 `name` 的锁类，第一次出现时新建。
+fn lock_class(name: &'static str) -> usize {
+    while CLASS_LOCK.compare_exchange(false, true,
+        Ordering::Acquire, Ordering::Relaxed).is_err() {
+        spin_loop();
+    }
+    let n = NLOCKCLASSES.load(Ordering::Relaxed);
+    let classes = unsafe { &mut *addr_of_mut!(LOCK_CLASSES) };
+    let class = match classes[..n].iter().position(|&c| c == name) {
+        Some(class) => class,
+        None if n < NLOCKCLASS - 1 => {
+            classes[n] = name;
+            NLOCKCLASSES.store(n + 1, Ordering::Release);
+            n
+        }
+        None => {
+            classes[NLOCKCLASS - 1] = "(other)";
+            NLOCKCLASSES.store(NLOCKCLASS, Ordering::Release);
+            NLOCKCLASS - 1
+        }
+    };
+    CLASS_LOCK.store(false, Ordering::Release);
+    class
+}
+
+/// 一个锁类在所有 CPU 上的统计之和，参见 [`lock_stats`]。
+pub struct LockStat {
+    pub name: &'static str,
+    /// 获取次数
+    pub acquired: usize,
+    /// 需要忙等待的获取次数
+    pub contended: usize,
+    /// 忙等待的总循环次数
+    pub spins: usize,
+}
+
+/// 返回每个被获取过的锁类的统计，按忙等待的总循环次数从多到少排列，用于 /proc/lockstat。
+/// 其他 CPU 上的统计随时可能改变，结果只是一个近似的快照。
+pub fn lock_stats() -> Vec<LockStat> {
+    let n = NLOCKCLASSES.load(Ordering::Acquire);
+    let classes = unsafe { &*core::ptr::addr_of!(LOCK_CLASSES) };
+    let mut stats: Vec<LockStat> = (0..n).map(|class| {
+        let sum = |f: fn(&ClassStat) -> &AtomicUsize| LOCK_STAT.iter()
+            .map(|cpu| f(&cpu[class]).load(Ordering::Relaxed))
+            .sum();
+        LockStat {
+            name: classes[class],
+            acquired: sum(|s| &s.acquired),
+            contended: sum(|s| &s.contended),
+            spins: sum(|s| &s.spins),
+        }
+    }).filter(|stat| stat.acquired > 0).collect();
+    stats.sort_unstable_by(|a, b| b.spins.cmp(&a.spins));
+    stats
+}
+
+/// 清零所有锁类的统计。其他 CPU 此时可能正在更新，个别计数可能没有被清零。
+pub fn reset_lock_stats() {
+    for stat in LOCK_STAT.iter().flatten() {
+        stat.acquired.store(0, Ordering::Relaxed);
+        stat.contended.store(0, Ordering::Relaxed);
+        stat.spins.store(0, Ordering::Relaxed);
+    }
+}
+
 /// 从spin crate借鉴 (https://crates.io/crates/spin)
 #[cfg(feature = "unit_test")]
 pub mod tests {
@@ -3,9 +3,9 @@
 
 use core::{arch::asm, convert::Into};
 
-use crate::{consts::{CLINT_MTIMECMP, NCPU}, register::sie};
+use crate::{consts::{CLINT_MSIP, CLINT_MTIMECMP, NCPU, TICK_INTERVAL}, register::sie};
 use crate::register::{
-    clint, medeleg, mepc, mhartid, mideleg, mie, mscratch, mstatus, mtvec, satp, tp,
+    clint, mcounteren, medeleg, mepc, mhartid, mideleg, mie, mscratch, mstatus, mtvec, satp, tp,
 };
 use crate::rmain::rust_main;
 
@@ -14,7 +14,7 @@ use crate::rmain::rust_main;
 /// 该数组为每个CPU核心提供32个usize大小的存储空间，但仅使用前6个元素：
 /// - [0..3]：为timervec保存寄存器的空间
 /// - [4]：CLINT MTIMECMP寄存器的地址
-/// - [5]：定时器中断的期望间隔（周期数）
+/// - [5]：CLINT MSIP寄存器的地址
 /// 
 /// # 安全性
 /// - 使用`static mut`声明，访问需在`unsafe`块中
@@ -61,6 +61,9 @@ pub unsafe fn start() -> ! {
     mideleg::write(0xffff);
     sie::intr_on();
 
+    // 允许监督模式读取 cycle、time 与 instret 计数器
+    mcounteren::write(0b111);
+
     asm!("
         li t0, -1
         csrw pmpaddr0, t0
@@ -85,9 +88,11 @@ pub unsafe fn start() -> ! {
 /// 初始化机器模式定时器中断
 ///
 /// # 功能说明
-/// 配置每个CPU核心的定时器中断，使其定期触发。
+/// 配置每个CPU核心的定时器中断，在一个时钟周期后触发第一次中断。
 /// 中断将由`timervec`处理（在汇编中定义），
-/// 该处理程序将机器模式中断转换为监督者模式的软件中断。
+/// 该处理程序将机器模式中断转换为监督者模式的软件中断，
+/// 之后的中断时间由监督者模式按需设置（见`trap::clock_arm`）。
+/// 处理器间中断（机器模式软件中断）同样由`timervec`转发。
 ///
 /// # 流程解释
 /// 1. 获取当前核心ID(hartid)
@@ -95,7 +100,7 @@ pub unsafe fn start() -> ! {
 /// 3. 在MSCRATCH0中准备定时器中断处理所需信息
 /// 4. 设置mscratch寄存器指向当前核心的上下文存储区
 /// 5. 设置机器模式陷阱处理程序为timervec
-/// 6. 启用机器模式中断(MIE)和定时器中断(MTIE)
+/// 6. 启用机器模式中断(MIE)、定时器中断(MTIE)和软件中断(MSIE)
 ///
 /// # 参数
 /// 无
@@ -111,16 +116,15 @@ unsafe fn timerinit() {
     let id = mhartid::read();
 
     // 向 CLINT 请求一个定时器中断。
-    let interval: u64 = 1000000; // 时钟周期；在 qemu 中大约0.1秒。
-    clint::add_mtimecmp(id, interval);
+    clint::add_mtimecmp(id, TICK_INTERVAL); // 时钟周期；在 qemu 中大约0.1秒。
 
     // 为 timervec 在 scratch [] 中准备信息。
     // scratch [0..3]：供 timervec 保存寄存器的空间。
     // scratch [4]：CLINT 的 MTIMECMP 寄存器的地址。
-    // scratch [5]：定时器中断之间的期望间隔（以时钟周期为单位）。
+    // scratch [5]：CLINT 的 MSIP 寄存器的地址。
     let offset = 32 * id;
     MSCRATCH0[offset + 4] = 8 * id + Into::<usize>::into(CLINT_MTIMECMP);
-    MSCRATCH0[offset + 5] = interval as usize;
+    MSCRATCH0[offset + 5] = 4 * id + Into::<usize>::into(CLINT_MSIP);
     mscratch::write((MSCRATCH0.as_ptr() as usize) + offset * core::mem::size_of::<usize>());
 
     // 设置机器模式的陷阱处理程序。
@@ -132,6 +136,7 @@ unsafe fn timerinit() {
     // 启用机器模式中断。
     mstatus::set_mie();
 
-    // 启用机器模式定时器中断。
+    // 启用机器模式定时器中断与软件中断。
     mie::set_mtie();
+    mie::set_msie();
 }
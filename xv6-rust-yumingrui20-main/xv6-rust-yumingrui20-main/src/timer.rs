@@ -0,0 +1,322 @@
+//! 内核定时器
+//!
+//! 待触发的定时器保存在定时器轮 [`TIMERS`] 中：轮有 [`WHEEL_SIZE`] 个槽，
+//! 在第 `t` 个时钟周期到期的定时器放在第 `t % WHEEL_SIZE` 个槽里，
+//! 每个时钟周期只需检查刚刚经过的槽，到期时间更远的定时器留在槽中等待下一圈。
+//!
+//! 定时器由 [`TimerKey`] 标识，同一标识至多有一个待触发的定时器，触发的动作也由标识决定：
+//! - [`TimerKey::Real`]：向进程发送 `SIGALRM`，用于 `alarm` 与 `setitimer`；
+//! - [`TimerKey::Sleep`]：唤醒在等待通道上睡眠的进程，用于 `sleep` 与 `nanosleep`。
+//!
+//! 一个时钟周期是 [`TICK_INTERVAL`] 个 `mtime` 周期，时钟周期数由 `mtime` 换算（见 [`ticks`]），
+//! 空闲时跳过的时钟周期不会丢失；定时器的精度是一个时钟周期，
+//! `clock_gettime` 则直接读取 CLINT 的 `mtime`，精度高于时钟周期。
+//!
+//! 锁的顺序：触发定时器之前先释放 [`TIMERS`]，因此持有它时不会获取进程的锁；
+//! 睡眠者在 [`TIMERS`] 锁内设置定时器并睡眠，不会错过唤醒。
+
+use array_macro::array;
+
+use alloc::vec::Vec;
+use core::cmp::max;
+use core::sync::atomic::Ordering;
+
+use crate::consts::{MTIME_FREQ, TICK_INTERVAL};
+use crate::consts::signal::SIGALRM;
+use crate::process::{Proc, PROC_MANAGER};
+use crate::register::clint;
+use crate::spinlock::SpinLock;
+
+/// 定时器轮的槽数。
+pub const WHEEL_SIZE: usize = 64;
+
+const NSEC_PER_SEC: u64 = 1_000_000_000;
+const USEC_PER_SEC: u64 = 1_000_000;
+/// 一个时钟周期的微秒数
+const USEC_PER_TICK: u64 = TICK_INTERVAL * USEC_PER_SEC / MTIME_FREQ;
+/// 定时器时长的上限（时钟周期数），保证到期时间的回绕比较不出错
+const MAX_TICKS: usize = usize::MAX >> 2;
+
+/// 所有待触发的定时器。
+static TIMERS: SpinLock<TimerWheel> = SpinLock::new(TimerWheel::new(), "timers");
+
+/// 定时器的标识，同时决定定时器触发时的动作。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerKey {
+    /// PID 为给定值的进程的实时间隔定时器（`ITIMER_REAL`），触发时发送 `SIGALRM`。
+    Real(usize),
+    /// 在给定的等待通道上限时睡眠的进程，触发时唤醒该通道。
+    Sleep(usize),
+}
+
+struct Timer {
+    key: TimerKey,
+    /// 到期的时钟周期
+    expires: usize,
+    /// 周期性定时器的间隔，0 表示只触发一次
+    interval: usize,
+}
+
+struct TimerWheel {
+    slots: [Vec<Timer>; WHEEL_SIZE],
+    /// 已经处理过的最后一个时钟周期
+    last: usize,
+}
+
+impl TimerWheel {
+    const fn new() -> Self {
+        Self {
+            slots: array![_ => Vec::new(); WHEEL_SIZE],
+            last: 0,
+        }
+    }
+
+    fn remove(&mut self, key: TimerKey) -> Option<Timer> {
+        for slot in self.slots.iter_mut() {
+            if let Some(i) = slot.iter().position(|t| t.key == key) {
+                return Some(slot.swap_remove(i))
+            }
+        }
+        None
+    }
+
+    fn insert(&mut self, timer: Timer) -> Result<(), ()> {
+        let slot = &mut self.slots[timer.expires % WHEEL_SIZE];
+        slot.try_reserve(1).map_err(|_| ())?;
+        slot.push(timer);
+        Ok(())
+    }
+
+    /// 定时器 `timer` 的剩余时钟周期数与间隔。
+    fn remaining(&self, timer: &Timer) -> (usize, usize) {
+        (max(timer.expires.wrapping_sub(ticks()) as isize, 1) as usize, timer.interval)
+    }
+
+    /// 取出一个在时钟周期 `now` 或之前到期的定时器，只检查 `(last, now]` 对应的槽。
+    fn pop_expired(&mut self, now: usize) -> Option<Timer> {
+        let span = now.wrapping_sub(self.last);
+        for n in 1..=span.min(WHEEL_SIZE) {
+            let slot = &mut self.slots[self.last.wrapping_add(n) % WHEEL_SIZE];
+            if let Some(i) = slot.iter().position(|t| now.wrapping_sub(t.expires) as isize >= 0) {
+                return Some(slot.swap_remove(i))
+            }
+        }
+        None
+    }
+}
+
+/// 时钟中断时调用：触发在时钟周期 `now` 或之前到期的定时器，周期性定时器重新加入轮中。
+/// 调用时不得持有任何进程的锁。
+pub fn expire(now: usize) {
+    loop {
+        let mut wheel = TIMERS.lock();
+        let timer = match wheel.pop_expired(now) {
+            Some(timer) => timer,
+            None => {
+                wheel.last = now;
+                break
+            }
+        };
+        if timer.interval > 0 {
+            // 内存不足时周期性定时器停止
+            let _ = wheel.insert(Timer {
+                key: timer.key,
+                expires: now.wrapping_add(timer.interval),
+                interval: timer.interval,
+            });
+        }
+        drop(wheel);
+        fire(timer.key);
+    }
+}
+
+/// 最早到期的定时器的到期时钟周期，没有定时器时返回 `None`。
+/// 到期时间以时钟周期 `base` 为起点比较先后。
+pub fn next_expiry(base: usize) -> Option<usize> {
+    let wheel = TIMERS.lock();
+    wheel.slots.iter().flat_map(|slot| slot.iter())
+        .map(|t| t.expires)
+        .min_by_key(|expires| expires.wrapping_sub(base) as isize)
+}
+
+fn fire(key: TimerKey) {
+    unsafe {
+        match key {
+            TimerKey::Real(pid) => { let _ = PROC_MANAGER.signal(pid, SIGALRM); },
+            TimerKey::Sleep(channel) => PROC_MANAGER.wakeup(channel),
+        }
+    }
+}
+
+/// # 功能说明
+/// 设置标识为 `key` 的定时器在 `ticks` 个时钟周期后触发，之后每 `interval` 个时钟周期触发一次，
+/// `interval` 为 0 时只触发一次。同一标识原有的定时器被替换。
+///
+/// # 返回值
+/// - `Ok(old)`：原有定时器的剩余时钟周期数与间隔，没有时为 `None`；
+/// - `Err(())`：内存不足，原有的定时器保持不变。
+pub fn arm(key: TimerKey, ticks: usize, interval: usize) -> Result<Option<(usize, usize)>, ()> {
+    let mut wheel = TIMERS.lock();
+    arm_locked(&mut wheel, key, ticks, interval)
+}
+
+fn arm_locked(wheel: &mut TimerWheel, key: TimerKey, ticks: usize, interval: usize)
+    -> Result<Option<(usize, usize)>, ()>
+{
+    // 以当前的时钟周期为起点：空闲时 `last` 可能落后于当前时间
+    let expires = self::ticks().wrapping_add(max(ticks, 1));
+    let old = wheel.remove(key);
+    let ret = old.as_ref().map(|t| wheel.remaining(t));
+    match wheel.insert(Timer { key, expires, interval }) {
+        Ok(()) => Ok(ret),
+        Err(()) => {
+            if let Some(old) = old {
+                let _ = wheel.insert(old);
+            }
+            Err(())
+        }
+    }
+}
+
+/// 取消标识为 `key` 的定时器，返回其剩余时钟周期数与间隔。
+pub fn cancel(key: TimerKey) -> Option<(usize, usize)> {
+    let mut wheel = TIMERS.lock();
+    let old = wheel.remove(key)?;
+    Some(wheel.remaining(&old))
+}
+
+/// 查询标识为 `key` 的定时器的剩余时钟周期数与间隔。
+pub fn query(key: TimerKey) -> Option<(usize, usize)> {
+    let wheel = TIMERS.lock();
+    wheel.slots.iter().flat_map(|slot| slot.iter())
+        .find(|t| t.key == key)
+        .map(|t| wheel.remaining(t))
+}
+
+/// 在等待通道 `channel` 上可中断地睡眠，至多 `ticks` 个时钟周期。
+/// 返回后定时器可能仍在轮中，调用者结束等待时须以 [`cancel`] 取消。
+/// 内存不足时返回 `Err(())`，不睡眠。
+pub fn sleep(p: &Proc, channel: usize, ticks: usize) -> Result<(), ()> {
+    let mut wheel = TIMERS.lock();
+    arm_locked(&mut wheel, TimerKey::Sleep(channel), ticks, 0)?;
+    p.sleep_interruptible(channel, wheel, None);
+    Ok(())
+}
+
+/// 睡眠直到 `mtime` 不小于 `deadline`。
+///
+/// # 返回值
+/// - `Ok(())`：已到达截止时间；
+/// - `Err(())`：进程被杀死或内存不足。
+pub fn sleep_mtime(p: &Proc, deadline: u64) -> Result<(), ()> {
+    // 以栈上变量的地址作为等待通道，每个睡眠者各不相同
+    let channel = &deadline as *const u64 as usize;
+    let ret = loop {
+        let now = mtime();
+        if now >= deadline {
+            break Ok(())
+        }
+        if p.killed.load(Ordering::Relaxed) {
+            break Err(())
+        }
+        let ticks = (deadline - now + TICK_INTERVAL - 1) / TICK_INTERVAL;
+        if let Err(()) = sleep(p, channel, ticks as usize) {
+            break Err(())
+        }
+    };
+    cancel(TimerKey::Sleep(channel));
+    ret
+}
+
+/// 启动以来经过的时钟周期数。
+pub fn ticks() -> usize {
+    (mtime() / TICK_INTERVAL) as usize
+}
+
+/// 读取 CLINT 的 `mtime`，即启动以来经过的 `mtime` 周期数。
+pub fn mtime() -> u64 {
+    unsafe { clint::read_mtime() }
+}
+
+/// 与用户态的 `struct timespec` 内存布局一致。
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Timespec {
+    pub sec: i64,
+    pub nsec: i64,
+}
+
+impl Timespec {
+    /// 由 `mtime` 周期数换算。
+    pub fn from_mtime(t: u64) -> Self {
+        Self {
+            sec: (t / MTIME_FREQ) as i64,
+            nsec: (t % MTIME_FREQ * NSEC_PER_SEC / MTIME_FREQ) as i64,
+        }
+    }
+
+    /// 换算为 `mtime` 周期数，不足一个周期的部分向上取整；字段非法或溢出时返回 `None`。
+    pub fn to_mtime(&self) -> Option<u64> {
+        if self.sec < 0 || self.nsec < 0 || self.nsec as u64 >= NSEC_PER_SEC {
+            return None
+        }
+        let frac = (self.nsec as u64 * MTIME_FREQ + NSEC_PER_SEC - 1) / NSEC_PER_SEC;
+        (self.sec as u64).checked_mul(MTIME_FREQ)?.checked_add(frac)
+    }
+}
+
+/// 与用户态的 `struct timeval` 内存布局一致。
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Timeval {
+    pub sec: i64,
+    pub usec: i64,
+}
+
+impl Timeval {
+    /// 由时钟周期数换算。
+    pub fn from_ticks(ticks: usize) -> Self {
+        let usec = ticks as u128 * USEC_PER_TICK as u128;
+        let usec_per_sec = USEC_PER_SEC as u128;
+        Self {
+            sec: (usec / usec_per_sec) as i64,
+            usec: (usec % usec_per_sec) as i64,
+        }
+    }
+
+    /// 换算为时钟周期数，不足一个时钟周期的部分向上取整；字段非法或溢出时返回 `None`。
+    pub fn to_ticks(&self) -> Option<usize> {
+        if self.sec < 0 || self.usec < 0 || self.usec as u64 >= USEC_PER_SEC {
+            return None
+        }
+        let usec = (self.sec as u64).checked_mul(USEC_PER_SEC)?.checked_add(self.usec as u64)?;
+        let ticks = (usec / USEC_PER_TICK + (usec % USEC_PER_TICK != 0) as u64) as usize;
+        if ticks > MAX_TICKS {
+            return None
+        }
+        Some(ticks)
+    }
+}
+
+/// 与用户态的 `struct itimerval` 内存布局一致。
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Itimerval {
+    /// 周期性定时器的间隔，为 0 时只触发一次
+    pub interval: Timeval,
+    /// 距下一次触发的时间，为 0 时定时器停止
+    pub value: Timeval,
+}
+
+impl Itimerval {
+    /// 由 [`arm`]、[`cancel`] 或 [`query`] 返回的剩余时钟周期数与间隔换算，`None` 表示定时器未设置。
+    pub fn from_ticks(t: Option<(usize, usize)>) -> Self {
+        match t {
+            Some((value, interval)) => Self {
+                interval: Timeval::from_ticks(interval),
+                value: Timeval::from_ticks(value),
+            },
+            None => Self::default(),
+        }
+    }
+}
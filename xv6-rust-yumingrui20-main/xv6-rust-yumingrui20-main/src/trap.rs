@@ -1,16 +1,23 @@
 //! 中断处理模块，用户或内核模式下发生中断或异常时进行处理
 
-use core::num::Wrapping;
-use core::sync::atomic::Ordering;
+use array_macro::array;
 
-use crate::{consts::{TRAMPOLINE, TRAPFRAME, UART0_IRQ, VIRTIO0_IRQ}, process::{PROC_MANAGER, Proc}};
-use crate::register::{stvec, sstatus, sepc, stval, sip,
+use core::cmp::{max, min};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{consts::{NCPU, PROFILE_INTERVAL, TICK_INTERVAL, TRAMPOLINE, UART0_IRQ, VIRTIO0_IRQ}, process::{PROC_MANAGER, Proc}};
+use crate::register::{clint, stvec, sstatus, sepc, stval, sip,
     scause::{self, ScauseType}};
 use crate::process::{CPU_MANAGER, CpuManager};
 use crate::spinlock::SpinLock;
 use crate::plic;
 use crate::driver::virtio_disk::DISK;
 use crate::driver::uart::UART;
+use crate::driver::random;
+use crate::timer;
+use crate::ipi;
+use crate::mm::tlb;
+use crate::profile;
 
 /// 初始化当前CPU核心的中断处理
 ///
@@ -43,7 +50,7 @@ pub unsafe fn trap_init_hart() {
 ///   - 软件中断：处理时钟中断
 ///   - 系统调用：执行系统调用处理
 ///   - 其他异常：终止进程
-/// 4. 处理完成后返回用户空间
+/// 4. 处理待处理的停止信号，然后返回用户空间
 ///
 /// # 安全性
 /// - 必须由trampoline.S在正确上下文中调用
@@ -59,6 +66,9 @@ pub unsafe extern fn user_trap() {
     extern "C" {fn kernelvec();}
     stvec::write(kernelvec as usize);
 
+    // 跳板代码已切换到内核页表并刷新了 TLB
+    tlb::leave_user();
+
     // 获取当前进程
     let p = CPU_MANAGER.my_proc();
 
@@ -70,6 +80,9 @@ pub unsafe extern fn user_trap() {
             // 从PLIC中断控制器获取中断号
             let irq = plic::claim();
 
+            // 中断到达的时刻作为随机数设备的熵来源
+            random::add_entropy(irq as u64);
+
             // 处理UART串口中断
             if irq as usize == UART0_IRQ {
                 UART.intr();
@@ -91,20 +104,29 @@ pub unsafe extern fn user_trap() {
             p.check_abondon(-1);
         }
         ScauseType::IntSSoft => {
-            // 监督者模式软件中断
-
-            // 仅在CPU 0上更新时钟计数
-            if CpuManager::cpu_id() == 0 {
-                clock_intr();
-            }
+            // 监督者模式软件中断，由时钟中断或处理器间中断转发而来
 
             // 清除软件中断标志
             sip::clear_ssip();
 
-            // 检查进程终止标志
-            p.check_abondon(-1);
-            // 主动让出CPU
-            p.yielding();
+            // 执行其他CPU发来的跨核调用
+            ipi::handle();
+
+            // 时钟中断只发给设置了它的CPU，每个CPU都要处理
+            if clock_fired() {
+                profile::sample(sepc::read(), true);
+                clock_intr();
+
+                if clock_slice_over() {
+                    // 检查进程终止标志
+                    p.check_abondon(-1);
+                    // 主动让出CPU
+                    p.yielding();
+                } else {
+                    // 分析器的采样中断，时间片尚未用完
+                    clock_arm(false);
+                }
+            }
         }
         ScauseType::ExcUEcall => {
             // 用户模式系统调用
@@ -127,6 +149,10 @@ pub unsafe extern fn user_trap() {
         }
     }
 
+    // 处理待处理的停止信号，被继续或杀死后返回
+    p.check_stop();
+    p.check_abondon(-1);
+
     // 返回用户空间
     user_trap_ret();
 }
@@ -158,12 +184,15 @@ pub unsafe fn user_trap_ret() -> ! {
     // 设置陷阱向量为用户空间处理程序（trampoline.S）
     stvec::write(TRAMPOLINE.into());
 
-    // 获取当前进程的用户页表
-    let satp = {
+    // 获取当前进程的用户页表与陷阱帧在其中的地址
+    let (satp, trapframe) = {
         let pd = CPU_MANAGER.my_proc().data.get_mut();
-        pd.user_ret_prepare()
+        (pd.user_ret_prepare(), pd.trapframe_va())
     };
 
+    // 此后修改该页表的CPU会通过跨核调用刷新本CPU的TLB
+    tlb::enter_user(satp);
+
     // 计算userret在跳板页中的虚拟地址
     extern "C" {
         fn trampoline();    // 跳板页起始地址
@@ -173,8 +202,9 @@ pub unsafe fn user_trap_ret() -> ! {
     let userret_virt: extern "C" fn(usize, usize) -> ! =
         core::mem::transmute(Into::<usize>::into(TRAMPOLINE) + distance);
 
-    // 调用userret(TRAPFRAME, satp)返回用户空间
-    userret_virt(TRAPFRAME.into(), satp);
+    // 调用userret(TRAPFRAME, satp)返回用户空间，
+    // 同一地址空间中的其他线程的陷阱帧位于 TRAPFRAME 下方
+    userret_virt(trapframe, satp);
 }
 
 /// 内核模式陷阱处理（由kernelvec调用）
@@ -219,6 +249,7 @@ pub unsafe fn kerneltrap() {
 
             // 处理PLIC中断（同用户模式）
             let irq = plic::claim();
+            random::add_entropy(irq as u64);
             if irq as usize == UART0_IRQ {
                 UART.intr();
             } else if irq as usize == VIRTIO0_IRQ {
@@ -231,18 +262,27 @@ pub unsafe fn kerneltrap() {
             }
         }
         ScauseType::IntSSoft => {
-            // 监督者模式软件中断
-
-            // 仅在CPU 0上更新时钟计数
-            if CpuManager::cpu_id() == 0 {
-                clock_intr();
-            }
+            // 监督者模式软件中断，由时钟中断或处理器间中断转发而来
 
             // 清除软件中断标志
             sip::clear_ssip();
 
-            // 尝试让出CPU（调度其他进程）
-            CPU_MANAGER.my_cpu_mut().try_yield_proc();
+            // 执行其他CPU发来的跨核调用
+            ipi::handle();
+
+            // 时钟中断只发给设置了它的CPU，每个CPU都要处理
+            if clock_fired() {
+                profile::sample(local_sepc, false);
+                clock_intr();
+
+                if clock_slice_over() {
+                    // 尝试让出CPU（调度其他进程）
+                    CPU_MANAGER.my_cpu_mut().try_yield_proc();
+                } else {
+                    // 分析器的采样中断，时间片尚未用完
+                    clock_arm(false);
+                }
+            }
         }
         ScauseType::ExcUEcall => {  // 用户模式系统调用（内核不应触发）
             panic!("ecall from supervisor mode");
@@ -259,25 +299,87 @@ pub unsafe fn kerneltrap() {
     sstatus::write(local_sstatus);
 }
 
-/// 全局时钟计数器（自旋锁保护）
-static TICKS: SpinLock<Wrapping<usize>> = SpinLock::new(Wrapping(0), "time");
+/// 最后处理过的时钟周期（自旋锁保护）
+static TICKS: SpinLock<usize> = SpinLock::new(0, "time");
 
-/// 处理时钟中断（更新全局计数器）
+/// 每个CPU当前时间片结束的时钟周期，由 [`clock_arm`] 设置
+static SLICE_END: [AtomicUsize; NCPU] = array![_ => AtomicUsize::new(0); NCPU];
+
+/// 处理时钟中断
 ///
 /// # 功能说明
-/// 增加全局时钟计数并唤醒等待时钟的进程。
-/// 由时钟中断处理程序调用。
+/// 由 `mtime` 得到当前时钟周期，唤醒睡眠截止时间已到的进程，然后触发到期的定时器。
+/// 由时钟中断处理程序调用；同一时钟周期已被其他CPU处理过时直接返回。
 fn clock_intr() {
     let mut guard = TICKS.lock();
-    *guard += Wrapping(1);
-    unsafe { PROC_MANAGER.wakeup(&TICKS as *const _ as usize); }
+    let now = clock_read();
+    if *guard == now {
+        return
+    }
+    *guard = now;
+    unsafe { PROC_MANAGER.wakeup_clock(now); }
     drop(guard);
+    timer::expire(now);
+}
+
+/// 当前CPU的时钟中断是否已经到来
+///
+/// # 功能说明
+/// `timervec` 处理时钟中断时把 `mtimecmp` 设为最大值，据此区分时钟中断与处理器间中断。
+/// 空闲且没有任何截止时间的CPU的 `mtimecmp` 也是最大值，此时误判只会多检查一次时钟周期、
+/// 可能多记录一个分析器采样，没有影响。
+fn clock_fired() -> bool {
+    unsafe { clint::read_mtimecmp(CpuManager::cpu_id()) == u64::MAX }
+}
+
+/// 当前CPU的时间片是否已经用完。分析器开启时时钟中断更频繁，只有到达时钟周期的边界才让出CPU。
+fn clock_slice_over() -> bool {
+    let id = unsafe { CpuManager::cpu_id() };
+    clock_read() >= SLICE_END[id].load(Ordering::Relaxed)
+}
+
+/// 设置当前CPU的下一次时钟中断
+///
+/// # 功能说明
+/// 运行进程时在下一个时钟周期开始时中断，作为时间片的结束，
+/// 分析器开启时还每隔 `PROFILE_INTERVAL` 中断一次用于采样；
+/// 空闲时推迟到最早的定时器或睡眠截止时间，都没有时不再产生时钟中断。
+/// 由调度器在切换到进程或进入空闲前调用。
+///
+/// # 参数
+/// - `idle`: 当前CPU是否将要空闲
+pub fn clock_arm(idle: bool) {
+    let last = *TICKS.lock();
+    let next = if idle {
+        let expiry = timer::next_expiry(last);
+        let deadline = unsafe { PROC_MANAGER.next_deadline(last) };
+        // 不早于下一个未处理的时钟周期，已处理过的时钟周期不会再次处理
+        expiry.into_iter().chain(deadline)
+            .map(|t| max(t.wrapping_sub(last) as isize, 1) as usize)
+            .min()
+            .map(|delta| last.wrapping_add(delta))
+    } else {
+        Some(clock_read().wrapping_add(1))
+    };
+    let mut interval = match next {
+        Some(tick) => (tick as u64).saturating_mul(TICK_INTERVAL).saturating_sub(timer::mtime()),
+        None => u64::MAX,
+    };
+    let id = unsafe { CpuManager::cpu_id() };
+    if !idle {
+        SLICE_END[id].store(clock_read().wrapping_add(1), Ordering::Relaxed);
+        if profile::enabled() {
+            interval = min(interval, PROFILE_INTERVAL);
+        }
+    }
+    unsafe { clint::add_mtimecmp(id, interval); }
 }
 
 /// 使进程休眠指定时钟周期
 ///
 /// # 功能说明
 /// 将当前进程置于休眠状态，直到经过指定数量的时钟周期。
+/// 睡眠由定时器轮中的定时器唤醒，而不是每个时钟周期都被唤醒一次。
 ///
 /// # 参数
 /// - `p`: 当前进程引用
@@ -287,28 +389,32 @@ fn clock_intr() {
 /// - `Ok(())`: 成功休眠指定周期
 /// - `Err(())`: 休眠期间进程被终止
 pub fn clock_sleep(p: &Proc, count: usize) -> Result<(), ()> {
-    let mut guard = TICKS.lock();
-    let old_ticks = *guard; // 记录起始时钟
+    let old_ticks = clock_read(); // 记录起始时钟
+    // 以栈上变量的地址作为等待通道
+    let channel = &old_ticks as *const usize as usize;
 
     // 等待指定周期
-    while (*guard - old_ticks) < Wrapping(count) {
+    let ret = loop {
+        let elapsed = clock_read().wrapping_sub(old_ticks);
+        if elapsed >= count {
+            break Ok(())
+        }
         // 检查进程终止标志
         if p.killed.load(Ordering::Relaxed) {
-            return Err(())
+            break Err(())
         }
-
-        // 在TICKS地址上休眠
-        p.sleep(&TICKS as *const _ as usize, guard);
-        // 被唤醒后重新获取锁
-        guard = TICKS.lock();
-    }
-    Ok(())
+        if let Err(()) = timer::sleep(p, channel, count - elapsed) {
+            break Err(())
+        }
+    };
+    timer::cancel(timer::TimerKey::Sleep(channel));
+    ret
 }
 
 /// 读取当前时钟计数值
 ///
 /// # 返回值
-/// 系统启动以来的时钟周期数
+/// 系统启动以来的时钟周期数，由 `mtime` 换算，空闲时不产生时钟中断也保持准确
 pub fn clock_read() -> usize {
-    TICKS.lock().0
+    timer::ticks()
 }
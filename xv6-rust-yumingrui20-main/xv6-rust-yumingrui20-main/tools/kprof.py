@@ -0,0 +1,75 @@
+#!/usr/bin/env python3
+"""Symbolize kernel profiler samples.
+
+Run `kprof cmd` (or `kprof start` ... `kprof stop` and `cat /proc/profile`)
+inside xv6, save the console output, then on the host:
+
+    python3 tools/kprof.py [-n top] [--nm nm] kernel-elf console-output
+
+Sample lines from /proc/profile look like `count k|u address`. Kernel
+addresses are mapped to the enclosing function with nm; user samples are
+counted together since they belong to different programs.
+"""
+
+import argparse
+import bisect
+import re
+import subprocess
+import sys
+from collections import Counter
+
+SAMPLE = re.compile(r"^\s*(\d+) ([ku]) (0x[0-9a-f]+)\s*$")
+
+
+def load_symbols(nm, kernel):
+    out = subprocess.run([nm, "-n", "-C", "--defined-only", kernel],
+                         check=True, capture_output=True, text=True).stdout
+    addrs, names = [], []
+    for line in out.splitlines():
+        parts = line.split(None, 2)
+        # skip local labels and mapping symbols
+        if len(parts) == 3 and parts[1] in "tTwW" and not parts[2].startswith((".", "$")):
+            addrs.append(int(parts[0], 16))
+            names.append(parts[2])
+    return addrs, names
+
+
+def symbolize(addrs, names, pc):
+    i = bisect.bisect_right(addrs, pc) - 1
+    if i < 0:
+        return "[unknown]"
+    # drop the hash rustc appends to legacy mangled names
+    return re.sub(r"::h[0-9a-f]{16}$", "", names[i])
+
+
+def main():
+    ap = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    ap.add_argument("kernel", help="kernel ELF with symbols")
+    ap.add_argument("output", nargs="?", default="-",
+                    help="saved console output, default stdin")
+    ap.add_argument("-n", type=int, default=30, help="functions to print")
+    ap.add_argument("--nm", default="riscv64-unknown-elf-nm")
+    args = ap.parse_args()
+
+    addrs, names = load_symbols(args.nm, args.kernel)
+    text = sys.stdin if args.output == "-" else open(args.output)
+
+    funcs = Counter()
+    total = 0
+    for line in text:
+        m = SAMPLE.match(line)
+        if not m:
+            continue
+        count, mode, pc = int(m.group(1)), m.group(2), int(m.group(3), 16)
+        funcs["[user]" if mode == "u" else symbolize(addrs, names, pc)] += count
+        total += count
+    if total == 0:
+        sys.exit("kprof.py: no samples found")
+
+    print(f"{total} samples")
+    for name, count in funcs.most_common(args.n):
+        print(f"{count:8} {100.0 * count / total:6.2f}%  {name}")
+
+
+if __name__ == "__main__":
+    main()
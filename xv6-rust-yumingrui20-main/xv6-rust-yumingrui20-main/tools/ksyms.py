@@ -0,0 +1,96 @@
+#!/usr/bin/env python3
+"""Embed the kernel symbol table used for backtraces.
+
+    python3 tools/ksyms.py [--nm nm] kernel-elf
+
+The kernel reserves a zero-filled `.ksyms` section (see src/backtrace.rs).
+After linking, this script collects the function symbols with nm and
+writes the table into that section in place, so addresses do not move.
+Table layout, little endian:
+
+    u64 magic "KSYMTAB\\0", u64 count
+    count * (u64 addr, u32 name offset, u32 name length), sorted by addr
+    names, offsets counted from the start of the table
+"""
+
+import argparse
+import re
+import struct
+import subprocess
+import sys
+
+MAGIC = b"KSYMTAB\0"
+# longer names (deeply nested generics) are cut to keep the table small
+MAX_NAME = 128
+
+
+def find_section(elf, name):
+    """Return (file offset, size) of the named section of an ELF64 file."""
+    if elf[:4] != b"\x7fELF" or elf[4] != 2 or elf[5] != 1:
+        sys.exit("ksyms.py: not a little-endian ELF64 file")
+    shoff, = struct.unpack_from("<Q", elf, 0x28)
+    shentsize, shnum, shstrndx = struct.unpack_from("<HHH", elf, 0x3a)
+
+    def header(i):
+        # sh_name, sh_type, sh_flags, sh_addr, sh_offset, sh_size
+        return struct.unpack_from("<IIQQQQ", elf, shoff + i * shentsize)
+
+    strtab_off = header(shstrndx)[4]
+    for i in range(shnum):
+        sh_name, _, _, _, offset, size = header(i)
+        end = elf.index(b"\0", strtab_off + sh_name)
+        if elf[strtab_off + sh_name:end].decode() == name:
+            return offset, size
+    sys.exit(f"ksyms.py: no {name} section")
+
+
+def load_symbols(nm, kernel):
+    out = subprocess.run([nm, "-n", "-C", "--defined-only", kernel],
+                         check=True, capture_output=True, text=True).stdout
+    syms = {}
+    for line in out.splitlines():
+        parts = line.split(None, 2)
+        if len(parts) != 3 or parts[1] not in "tTwW":
+            continue
+        addr, name = int(parts[0], 16), parts[2]
+        # skip local labels and mapping symbols
+        if name.startswith((".", "$")):
+            continue
+        name = re.sub(r"::h[0-9a-f]{16}$", "", name)
+        syms.setdefault(addr, name.encode()[:MAX_NAME])
+    return sorted(syms.items())
+
+
+def build_table(syms):
+    names, offsets = bytearray(), {}
+    base = 16 + 16 * len(syms)
+    entries = bytearray()
+    for addr, name in syms:
+        if name not in offsets:
+            offsets[name] = base + len(names)
+            names += name
+        entries += struct.pack("<QII", addr, offsets[name], len(name))
+    return MAGIC + struct.pack("<Q", len(syms)) + entries + names
+
+
+def main():
+    ap = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    ap.add_argument("kernel")
+    ap.add_argument("--nm", default="riscv64-unknown-elf-nm")
+    args = ap.parse_args()
+
+    with open(args.kernel, "rb") as f:
+        elf = bytearray(f.read())
+    offset, size = find_section(elf, ".ksyms")
+    table = build_table(load_symbols(args.nm, args.kernel))
+    if len(table) > size:
+        sys.exit(f"ksyms.py: symbol table is {len(table)} bytes, "
+                 f".ksyms has {size}; enlarge KSYMS_SIZE in src/backtrace.rs")
+    elf[offset:offset + size] = table + bytes(size - len(table))
+    with open(args.kernel, "wb") as f:
+        f.write(elf)
+    print(f"ksyms.py: {len(table)} bytes of symbols written to {args.kernel}")
+
+
+if __name__ == "__main__":
+    main()
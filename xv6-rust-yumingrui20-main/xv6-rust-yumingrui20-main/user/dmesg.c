@@ -0,0 +1,45 @@
+#include "include/types.h"
+#include "include/stat.h"
+#include "include/syslog.h"
+#include "user/user.h"
+
+// Print the kernel log.
+//   dmesg           print the log
+//   dmesg -c        print the log, then clear it
+//   dmesg -n level  set the console level (0 error .. 4 trace)
+
+int
+main(int argc, char **argv)
+{
+  int size, n, clear = 0;
+  char *buf;
+
+  if(argc == 3 && strcmp(argv[1], "-n") == 0){
+    if(syslog(SYSLOG_ACTION_CONSOLE_LEVEL, 0, atoi(argv[2])) < 0){
+      fprintf(2, "dmesg: bad level %s\n", argv[2]);
+      exit(1);
+    }
+    exit(0);
+  }
+  if(argc == 2 && strcmp(argv[1], "-c") == 0){
+    clear = 1;
+  } else if(argc != 1){
+    fprintf(2, "usage: dmesg [-c] [-n level]\n");
+    exit(1);
+  }
+
+  size = syslog(SYSLOG_ACTION_SIZE_BUFFER, 0, 0);
+  if((buf = malloc(size)) == 0){
+    fprintf(2, "dmesg: out of memory\n");
+    exit(1);
+  }
+  n = syslog(SYSLOG_ACTION_READ_ALL, buf, size);
+  if(n < 0){
+    fprintf(2, "dmesg: read failed\n");
+    exit(1);
+  }
+  write(1, buf, n);
+  if(clear)
+    syslog(SYSLOG_ACTION_CLEAR, 0, 0);
+  exit(0);
+}
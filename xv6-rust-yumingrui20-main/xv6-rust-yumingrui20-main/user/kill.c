@@ -1,17 +1,29 @@
 #include "include/types.h"
 #include "include/stat.h"
+#include "include/signal.h"
 #include "user/user.h"
 
 int
 main(int argc, char **argv)
 {
-  int i;
+  int i, sig;
 
   if(argc < 2){
-    fprintf(2, "usage: kill pid...\n");
+    fprintf(2, "usage: kill [-sig] pid...\n");
     exit(1);
   }
-  for(i=1; i<argc; i++)
-    kill(atoi(argv[i]));
+  sig = SIGKILL;
+  i = 1;
+  if(argv[1][0] == '-'){
+    sig = atoi(argv[1] + 1);
+    i = 2;
+  }
+  for(; i<argc; i++){
+    // a negative pid signals a process group
+    if(argv[i][0] == '-')
+      kill(-atoi(argv[i] + 1), sig);
+    else
+      kill(atoi(argv[i]), sig);
+  }
   exit(0);
 }
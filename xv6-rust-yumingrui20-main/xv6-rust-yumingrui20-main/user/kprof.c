@@ -0,0 +1,64 @@
+#include "include/types.h"
+#include "include/stat.h"
+#include "include/fcntl.h"
+#include "include/kprof.h"
+#include "user/user.h"
+
+// Profile the kernel.
+//   kprof start          clear samples and lock statistics, start sampling
+//   kprof stop           stop sampling
+//   kprof cmd [args...]  sample while cmd runs, then print /proc/profile
+//                        and /proc/lockstat
+// Save the console output and run tools/kprof.py on the host to map the
+// sampled addresses to kernel functions.
+
+static void
+cat(char *path)
+{
+  int fd, n;
+  char buf[512];
+
+  if((fd = open(path, O_RDONLY)) < 0){
+    fprintf(2, "kprof: cannot open %s\n", path);
+    return;
+  }
+  while((n = read(fd, buf, sizeof(buf))) > 0)
+    write(1, buf, n);
+  close(fd);
+}
+
+int
+main(int argc, char **argv)
+{
+  int pid;
+
+  if(argc < 2){
+    fprintf(2, "usage: kprof start | stop | cmd [args...]\n");
+    exit(1);
+  }
+  if(strcmp(argv[1], "start") == 0){
+    kprof(KPROF_START);
+    exit(0);
+  }
+  if(strcmp(argv[1], "stop") == 0){
+    printf("kprof: %d samples\n", kprof(KPROF_STOP));
+    exit(0);
+  }
+
+  kprof(KPROF_START);
+  pid = fork();
+  if(pid < 0){
+    fprintf(2, "kprof: fork failed\n");
+    exit(1);
+  }
+  if(pid == 0){
+    exec(argv[1], argv + 1);
+    fprintf(2, "kprof: exec %s failed\n", argv[1]);
+    exit(1);
+  }
+  wait(0);
+  kprof(KPROF_STOP);
+  cat("/proc/profile");
+  cat("/proc/lockstat");
+  exit(0);
+}
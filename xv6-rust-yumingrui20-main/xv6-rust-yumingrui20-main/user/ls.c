@@ -2,6 +2,7 @@
 #include "include/stat.h"
 #include "user/user.h"
 #include "include/fs.h"
+#include "include/fcntl.h"
 
 char*
 fmtname(char *path)
@@ -30,7 +31,7 @@ ls(char *path)
   struct dirent de;
   struct stat st;
 
-  if((fd = open(path, 0)) < 0){
+  if((fd = open(path, O_RDONLY|O_NONBLOCK)) < 0){
     fprintf(2, "ls: cannot open %s\n", path);
     return;
   }
@@ -43,6 +44,7 @@ ls(char *path)
 
   switch(st.type){
   case T_FILE:
+  case T_FIFO:
     printf("%s %d %d %l\n", fmtname(path), st.type, st.ino, st.size);
     break;
 
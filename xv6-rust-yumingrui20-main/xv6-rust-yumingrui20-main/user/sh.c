@@ -3,6 +3,9 @@
 #include "include/types.h"
 #include "user/user.h"
 #include "include/fcntl.h"
+#include "include/termios.h"
+#include "include/signal.h"
+#include "include/wait.h"
 
 // Parsed command representation
 #define EXEC  1
@@ -49,6 +52,21 @@ struct backcmd {
   struct cmd *cmd;
 };
 
+// Job table for job control
+#define NJOB 8
+
+#define JOB_FREE     0
+#define JOB_RUNNING  1
+#define JOB_STOPPED  2
+
+struct job {
+  int pgid;       // process group, also the pid of the job's first process
+  int state;
+  char cmd[32];   // command line, for messages
+} jobs[NJOB];
+
+int shpgid;
+
 int fork1(void);  // Fork but panics on failure.
 void panic(char*);
 struct cmd *parsecmd(char*);
@@ -63,6 +81,7 @@ runcmd(struct cmd *cmd)
   struct listcmd *lcmd;
   struct pipecmd *pcmd;
   struct redircmd *rcmd;
+  int fd;
 
   if(cmd == 0)
     exit(1);
@@ -81,11 +100,14 @@ runcmd(struct cmd *cmd)
 
   case REDIR:
     rcmd = (struct redircmd*)cmd;
-    close(rcmd->fd);
-    if(open(rcmd->file, rcmd->mode) < 0){
+    if((fd = open(rcmd->file, rcmd->mode)) < 0){
       fprintf(2, "open %s failed\n", rcmd->file);
       exit(1);
     }
+    if(fd != rcmd->fd){
+      dup2(fd, rcmd->fd);
+      close(fd);
+    }
     runcmd(rcmd->cmd);
     break;
 
@@ -102,15 +124,13 @@ runcmd(struct cmd *cmd)
     if(pipe(p) < 0)
       panic("pipe");
     if(fork1() == 0){
-      close(1);
-      dup(p[1]);
+      dup2(p[1], 1);
       close(p[0]);
       close(p[1]);
       runcmd(pcmd->left);
     }
     if(fork1() == 0){
-      close(0);
-      dup(p[0]);
+      dup2(p[0], 0);
       close(p[0]);
       close(p[1]);
       runcmd(pcmd->right);
@@ -141,11 +161,115 @@ getcmd(char *buf, int nbuf)
   return 0;
 }
 
+// Strip a trailing '&' from the command line.
+// Return 1 if there was one, that is, the command runs in the background.
+int
+background(char *buf)
+{
+  int i;
+
+  for(i = strlen(buf) - 1; i >= 0 && strchr(" \t\r\n\v", buf[i]); i--)
+    ;
+  if(i < 0 || buf[i] != '&')
+    return 0;
+  buf[i] = 0;
+  return 1;
+}
+
+struct job*
+freejob(void)
+{
+  struct job *j;
+
+  for(j = jobs; j < &jobs[NJOB]; j++)
+    if(j->state == JOB_FREE)
+      return j;
+  return 0;
+}
+
+struct job*
+findjob(int pgid)
+{
+  struct job *j;
+
+  for(j = jobs; j < &jobs[NJOB]; j++)
+    if(j->state != JOB_FREE && j->pgid == pgid)
+      return j;
+  return 0;
+}
+
+// The job named by the argument of fg and bg: "n" or "%n",
+// or the highest-numbered job if there is no argument.
+struct job*
+jobarg(char *s)
+{
+  struct job *j;
+  int n;
+
+  while(*s == ' ')
+    s++;
+  if(*s == '%')
+    s++;
+  if(*s == 0){
+    for(j = &jobs[NJOB-1]; j >= jobs; j--)
+      if(j->state != JOB_FREE)
+        return j;
+    return 0;
+  }
+  n = atoi(s);
+  if(n < 1 || n > NJOB || jobs[n-1].state == JOB_FREE)
+    return 0;
+  return &jobs[n-1];
+}
+
+void
+printjob(struct job *j, char *what)
+{
+  printf("[%d] %s  %s\n", (int)(j - jobs) + 1, what, j->cmd);
+}
+
+// Give the terminal to job j and wait until it exits or stops.
+void
+waitfg(struct job *j)
+{
+  int st;
+
+  ioctl(0, TIOCSPGRP, &j->pgid);
+  if(waitpid(j->pgid, &st, WUNTRACED) == j->pgid && WIFSTOPPED(st)){
+    j->state = JOB_STOPPED;
+    printjob(j, "stopped");
+  } else {
+    j->state = JOB_FREE;
+  }
+  ioctl(0, TIOCSPGRP, &shpgid);
+}
+
+// Collect background jobs that have exited or stopped, without waiting.
+void
+reapjobs(void)
+{
+  struct job *j;
+  int pid, st;
+
+  while((pid = waitpid(-1, &st, WNOHANG|WUNTRACED)) > 0){
+    if((j = findjob(pid)) == 0)
+      continue;
+    if(WIFSTOPPED(st)){
+      j->state = JOB_STOPPED;
+      printjob(j, "stopped");
+    } else {
+      printjob(j, "done");
+      j->state = JOB_FREE;
+    }
+  }
+}
+
 int
 main(void)
 {
   static char buf[100];
-  int fd;
+  struct job *j;
+  int fd, pid, bg;
 
   // Ensure that three file descriptors are open.
   while((fd = open("console", O_RDWR)) >= 0){
@@ -155,8 +279,18 @@ main(void)
     }
   }
 
+  // Run in our own process group and leave ^C and ^Z to the jobs.
+  setpgid(0, 0);
+  shpgid = getpid();
+  signal(SIGINT, SIG_IGN);
+  signal(SIGTSTP, SIG_IGN);
+  ioctl(0, TIOCSPGRP, &shpgid);
+
   // Read and run input commands.
-  while(getcmd(buf, sizeof(buf)) >= 0){
+  for(;;){
+    reapjobs();
+    if(getcmd(buf, sizeof(buf)) < 0)
+      break;
     if(buf[0] == 'c' && buf[1] == 'd' && buf[2] == ' '){
       // Chdir must be called by the parent, not the child.
       buf[strlen(buf)-1] = 0;  // chop \n
@@ -164,9 +298,52 @@ main(void)
         fprintf(2, "cannot cd %s\n", buf+3);
       continue;
     }
-    if(fork1() == 0)
+    if(strcmp(buf, "jobs\n") == 0){
+      for(j = jobs; j < &jobs[NJOB]; j++)
+        if(j->state != JOB_FREE)
+          printjob(j, j->state == JOB_STOPPED ? "stopped" : "running");
+      continue;
+    }
+    if((buf[0] == 'f' || buf[0] == 'b') && buf[1] == 'g' &&
+       (buf[2] == '\n' || buf[2] == ' ')){
+      buf[strlen(buf)-1] = 0;  // chop \n
+      if((j = jobarg(buf+2)) == 0){
+        fprintf(2, "%cg: no such job\n", buf[0]);
+        continue;
+      }
+      kill(-j->pgid, SIGCONT);
+      j->state = JOB_RUNNING;
+      if(buf[0] == 'f')
+        waitfg(j);
+      else
+        printjob(j, "running");
+      continue;
+    }
+
+    if((j = freejob()) == 0){
+      fprintf(2, "sh: too many jobs\n");
+      continue;
+    }
+    bg = background(buf);
+    memset(j->cmd, 0, sizeof(j->cmd));
+    memmove(j->cmd, buf, sizeof(j->cmd) - 1);
+    if(strchr(j->cmd, '\n'))
+      *strchr(j->cmd, '\n') = 0;
+
+    if((pid = fork1()) == 0){
+      setpgid(0, 0);
+      signal(SIGINT, SIG_DFL);
+      signal(SIGTSTP, SIG_DFL);
       runcmd(parsecmd(buf));
-    wait(0);
+    }
+    // Also in the parent, so the group exists before the terminal is handed over.
+    setpgid(pid, pid);
+    j->pgid = pid;
+    j->state = JOB_RUNNING;
+    if(bg)
+      printf("[%d] %d\n", (int)(j - jobs) + 1, pid);
+    else
+      waitfg(j);
   }
   exit(0);
 }
@@ -0,0 +1,66 @@
+#include "include/types.h"
+#include "include/stat.h"
+#include "include/fcntl.h"
+#include "user/user.h"
+
+// Run a command and print the system calls it makes.
+//   strace [-m mask] [-o file] cmd [args...]
+// mask selects system calls by number (bit n traces call n, default all);
+// the trace goes to stderr, or to file with -o.
+
+int
+main(int argc, char **argv)
+{
+  int i, n, pid, out = 2, mask = -1;
+  int p[2];
+  char buf[128];
+
+  for(i = 1; i + 1 < argc && argv[i][0] == '-'; i += 2){
+    if(strcmp(argv[i], "-m") == 0){
+      mask = atoi(argv[i+1]);
+    } else if(strcmp(argv[i], "-o") == 0){
+      if((out = open(argv[i+1], O_CREATE|O_WRONLY)) < 0){
+        fprintf(2, "strace: cannot open %s\n", argv[i+1]);
+        exit(1);
+      }
+    } else {
+      break;
+    }
+  }
+  if(i >= argc){
+    fprintf(2, "usage: strace [-m mask] [-o file] cmd [args...]\n");
+    exit(1);
+  }
+
+  if(pipe(p) < 0){
+    fprintf(2, "strace: pipe failed\n");
+    exit(1);
+  }
+  pid = fork();
+  if(pid < 0){
+    fprintf(2, "strace: fork failed\n");
+    exit(1);
+  }
+  if(pid == 0){
+    close(p[0]);
+    if(out != 2)
+      close(out);
+    if(trace(mask, p[1]) < 0){
+      fprintf(2, "strace: trace failed\n");
+      exit(1);
+    }
+    close(p[1]);
+    exec(argv[i], argv + i);
+    fprintf(2, "strace: exec %s failed\n", argv[i]);
+    exit(1);
+  }
+
+  // The pipe reaches end of file once the command and all of its
+  // children have exited and dropped their trace file.
+  close(p[1]);
+  while((n = read(p[0], buf, sizeof(buf))) > 0)
+    write(out, buf, n);
+  close(p[0]);
+  wait(0);
+  exit(0);
+}
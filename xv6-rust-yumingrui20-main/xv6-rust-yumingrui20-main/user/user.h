@@ -1,5 +1,9 @@
 struct stat;
 struct rtcdate;
+struct pollfd;
+struct rlimit;
+struct itimerval;
+struct timespec;
 
 // system calls
 int fork(void);
@@ -9,7 +13,7 @@ int pipe(int*);
 int write(int, const void*, int);
 int read(int, void*, int);
 int close(int);
-int kill(int);
+int kill(int, int);
 int exec(char*, char**);
 int open(const char*, int);
 int mknod(const char*, short, short);
@@ -23,6 +27,38 @@ int getpid(void);
 char* sbrk(int);
 int sleep(int);
 int uptime(void);
+int ioctl(int, int, void*);
+int openpty(int*);
+int syslog(int, char*, int);
+int trace(int, int);
+int clone(void(*)(void*), void*, void*);
+int join(int, int*);
+int exit_thread(int) __attribute__((noreturn));
+int futex(int*, int, int, int);
+int shmget(int, int, int);
+void* shmat(int, void*);
+int shmdt(void*);
+int shmctl(int, int);
+int poll(struct pollfd*, int, int);
+int fcntl(int, int, int);
+int dup2(int, int);
+int dup3(int, int, int);
+int getrlimit(int, struct rlimit*);
+int setrlimit(int, const struct rlimit*);
+int mkfifo(const char*);
+int setpgid(int, int);
+int getpgid(int);
+int setsid(void);
+int waitpid(int, int*, int);
+int signal(int, int);
+int alarm(int);
+int setitimer(int, const struct itimerval*, struct itimerval*);
+int getitimer(int, struct itimerval*);
+int clock_gettime(int, struct timespec*);
+int nanosleep(const struct timespec*, struct timespec*);
+int sched_setaffinity(int, uint64);
+uint64 sched_getaffinity(int);
+int kprof(int);
 
 // ulib.c
 int stat(const char*, struct stat*);
@@ -40,3 +76,13 @@ void free(void*);
 int atoi(const char*);
 int memcmp(const void *, const void *, uint);
 void *memcpy(void *, const void *, uint);
+
+// uthread.c
+struct mutex {
+  int state;  // 0: unlocked, 1: locked, 2: locked with waiters
+};
+int thread_create(int (*)(void*), void*);
+int thread_join(int, int*);
+void thread_exit(int) __attribute__((noreturn));
+void mutex_lock(struct mutex*);
+void mutex_unlock(struct mutex*);
@@ -7,6 +7,16 @@
 #include "include/syscall.h"
 #include "include/memlayout.h"
 #include "include/riscv.h"
+#include "include/termios.h"
+#include "include/futex.h"
+#include "include/shm.h"
+#include "include/signal.h"
+#include "include/wait.h"
+#include "include/poll.h"
+#include "include/resource.h"
+#include "include/time.h"
+#include "include/kprof.h"
+#include "include/syslog.h"
 
 //
 // Tests xv6 system calls.  usertests without arguments runs them all
@@ -780,6 +790,66 @@ pipe1(char *s)
 }
 
 
+// input written to a pty master goes through the line discipline
+// to the slave, and the echo comes back to the master.
+void
+ptytest(char *s)
+{
+  int fds[2], n;
+  char b[16];
+  struct termios t;
+
+  if(openpty(fds) != 0){
+    printf("%s: openpty() failed\n", s);
+    exit(1);
+  }
+
+  // canonical mode: erase and echo
+  if(write(fds[0], "hx\x7fi\r", 5) != 5){
+    printf("%s: write master failed\n", s);
+    exit(1);
+  }
+  n = read(fds[1], b, sizeof(b));
+  if(n != 3 || memcmp(b, "hi\n", 3) != 0){
+    printf("%s: slave read %d bytes\n", s, n);
+    exit(1);
+  }
+  n = read(fds[0], b, sizeof(b));
+  if(n != 7 || memcmp(b, "hx\b \bi\n", 7) != 0){
+    printf("%s: master read %d bytes of echo\n", s, n);
+    exit(1);
+  }
+
+  // raw mode without echo
+  if(ioctl(fds[1], TCGETS, &t) != 0){
+    printf("%s: TCGETS failed\n", s);
+    exit(1);
+  }
+  t.lflag &= ~(ICANON|ECHO);
+  if(ioctl(fds[1], TCSETS, &t) != 0){
+    printf("%s: TCSETS failed\n", s);
+    exit(1);
+  }
+  write(fds[0], "\x7f", 1);
+  if(read(fds[1], b, sizeof(b)) != 1 || b[0] != 0x7f){
+    printf("%s: raw read failed\n", s);
+    exit(1);
+  }
+
+  // slave output and hangup
+  write(fds[1], "ok", 2);
+  if(read(fds[0], b, sizeof(b)) != 2 || memcmp(b, "ok", 2) != 0){
+    printf("%s: master read of slave output failed\n", s);
+    exit(1);
+  }
+  close(fds[0]);
+  if(read(fds[1], b, sizeof(b)) != 0){
+    printf("%s: slave read after hangup\n", s);
+    exit(1);
+  }
+  close(fds[1]);
+}
+
 // test if child is killed (status = -1)
 void
 killstatus(char *s)
@@ -799,7 +869,7 @@ killstatus(char *s)
       exit(0);
     }
     sleep(1);
-    kill(pid1);
+    kill(pid1, SIGKILL);
     wait(&xst);
     if(xst != -1) {
        printf("%s: status should be -1\n", s);
@@ -856,9 +926,9 @@ preempt(char *s)
   }
   close(pfds[0]);
   printf("kill... ");
-  kill(pid1);
-  kill(pid2);
-  kill(pid3);
+  kill(pid1, SIGKILL);
+  kill(pid2, SIGKILL);
+  kill(pid3, SIGKILL);
   printf("wait... ");
   wait(0);
   wait(0);
@@ -893,6 +963,1142 @@ exitwait(char *s)
   }
 }
 
+// threads share memory and are joined with their exit status
+static int threadcount[4];
+
+static int
+threadworker(void *arg)
+{
+  int n = (int)(uint64)arg;
+
+  for(int i = 0; i < 1000; i++)
+    threadcount[n]++;
+  return n + 10;
+}
+
+void
+threadtest(char *s)
+{
+  int tids[4], xst;
+
+  for(int i = 0; i < 4; i++){
+    tids[i] = thread_create(threadworker, (void*)(uint64)i);
+    if(tids[i] < 0){
+      printf("%s: thread_create failed\n", s);
+      exit(1);
+    }
+  }
+  for(int i = 0; i < 4; i++){
+    if(thread_join(tids[i], &xst) != tids[i] || xst != i + 10){
+      printf("%s: thread_join wrong tid or status\n", s);
+      exit(1);
+    }
+    if(threadcount[i] != 1000){
+      printf("%s: thread %d did not share memory\n", s, i);
+      exit(1);
+    }
+  }
+  if(join(tids[0], 0) >= 0){
+    printf("%s: joined a thread twice\n", s);
+    exit(1);
+  }
+}
+
+static int
+threadexiter(void *arg)
+{
+  exit(7);
+}
+
+// exit from any thread ends the whole process
+void
+threadexit(char *s)
+{
+  int pid, xst;
+
+  pid = fork();
+  if(pid < 0){
+    printf("%s: fork failed\n", s);
+    exit(1);
+  }
+  if(pid == 0){
+    if(thread_create(threadexiter, 0) < 0)
+      exit(1);
+    for(;;)
+      getpid();
+  }
+  wait(&xst);
+  if(xst != 7){
+    printf("%s: status should be 7, got %d\n", s, xst);
+    exit(1);
+  }
+}
+
+static int
+threadspinner(void *arg)
+{
+  for(;;)
+    getpid();
+  return 0;
+}
+
+static int
+threadexecer(void *arg)
+{
+  char *args[] = { "echo", "threadexec", 0 };
+
+  exec("echo", args);
+  exit(1);
+}
+
+// exec from any thread ends the other threads and keeps the pid
+void
+threadexec(char *s)
+{
+  int pid, xst;
+  char *args[] = { "echo", "threadexec", 0 };
+
+  for(int leader = 1; leader >= 0; leader--){
+    pid = fork();
+    if(pid < 0){
+      printf("%s: fork failed\n", s);
+      exit(1);
+    }
+    if(pid == 0){
+      close(1);
+      if(open("/dev/null", O_WRONLY) != 1)
+        exit(1);
+      if(leader){
+        if(thread_create(threadspinner, 0) < 0)
+          exit(1);
+        exec("echo", args);
+        exit(1);
+      }
+      if(thread_create(threadexecer, 0) < 0)
+        exit(1);
+      threadspinner(0);
+    }
+    if(wait(&xst) != pid || xst != 0){
+      printf("%s: exec from %s thread failed\n", s, leader ? "main" : "other");
+      exit(1);
+    }
+  }
+}
+
+// threads serialize on a futex-based mutex
+static struct mutex futexlock;
+static int futexcount;
+
+static int
+futexworker(void *arg)
+{
+  for(int i = 0; i < 1000; i++){
+    mutex_lock(&futexlock);
+    int n = futexcount;
+    getpid();
+    futexcount = n + 1;
+    mutex_unlock(&futexlock);
+  }
+  return 0;
+}
+
+void
+futextest(char *s)
+{
+  int tids[4], word = 1;
+
+  if(futex(&word, FUTEX_WAIT, 0, 0) != -1){
+    printf("%s: futex wait on changed word did not fail\n", s);
+    exit(1);
+  }
+  if(futex(&word, FUTEX_WAIT, 1, 2) != FUTEX_TIMEDOUT){
+    printf("%s: futex wait did not time out\n", s);
+    exit(1);
+  }
+  if(futex(&word, FUTEX_WAKE, 1, 0) != 0){
+    printf("%s: futex woke a nonexistent waiter\n", s);
+    exit(1);
+  }
+
+  for(int i = 0; i < 4; i++){
+    tids[i] = thread_create(futexworker, 0);
+    if(tids[i] < 0){
+      printf("%s: thread_create failed\n", s);
+      exit(1);
+    }
+  }
+  for(int i = 0; i < 4; i++)
+    thread_join(tids[i], 0);
+  if(futexcount != 4000){
+    printf("%s: count %d, expected 4000\n", s, futexcount);
+    exit(1);
+  }
+}
+
+// the memory devices and /dev/random behave as documented.
+void
+devtest(char *s)
+{
+  char buf[300], buf2[300];
+  int fd, i;
+
+  if((fd = open("/dev/null", O_RDWR)) < 0){
+    printf("%s: cannot open /dev/null\n", s);
+    exit(1);
+  }
+  if(read(fd, buf, sizeof(buf)) != 0 || write(fd, "x", 1) != 1){
+    printf("%s: /dev/null misbehaves\n", s);
+    exit(1);
+  }
+  close(fd);
+
+  if((fd = open("/dev/zero", O_RDONLY)) < 0){
+    printf("%s: cannot open /dev/zero\n", s);
+    exit(1);
+  }
+  memset(buf, 'a', sizeof(buf));
+  if(read(fd, buf, sizeof(buf)) != sizeof(buf)){
+    printf("%s: short read from /dev/zero\n", s);
+    exit(1);
+  }
+  for(i = 0; i < sizeof(buf); i++){
+    if(buf[i] != 0){
+      printf("%s: /dev/zero byte %d is %d\n", s, i, buf[i]);
+      exit(1);
+    }
+  }
+  close(fd);
+
+  if((fd = open("/dev/full", O_RDWR)) < 0){
+    printf("%s: cannot open /dev/full\n", s);
+    exit(1);
+  }
+  if(write(fd, "x", 1) >= 0){
+    printf("%s: write to /dev/full succeeded\n", s);
+    exit(1);
+  }
+  memset(buf, 'a', sizeof(buf));
+  if(read(fd, buf, 1) != 1 || buf[0] != 0){
+    printf("%s: read from /dev/full failed\n", s);
+    exit(1);
+  }
+  close(fd);
+
+  if((fd = open("/dev/random", O_RDONLY)) < 0){
+    printf("%s: cannot open /dev/random\n", s);
+    exit(1);
+  }
+  if(read(fd, buf, sizeof(buf)) != sizeof(buf) || read(fd, buf2, sizeof(buf2)) != sizeof(buf2)){
+    printf("%s: short read from /dev/random\n", s);
+    exit(1);
+  }
+  if(memcmp(buf, buf2, sizeof(buf)) == 0){
+    printf("%s: /dev/random repeated itself\n", s);
+    exit(1);
+  }
+  close(fd);
+}
+
+// shared memory is visible across fork and survives the
+// exit of the process that created it
+void
+shmtest(char *s)
+{
+  int id, pid, xst;
+  char *p;
+
+  id = shmget(IPC_PRIVATE, 2*PGSIZE, IPC_CREAT);
+  if(id < 0){
+    printf("%s: shmget failed\n", s);
+    exit(1);
+  }
+  p = shmat(id, 0);
+  if(p == (char*)-1){
+    printf("%s: shmat failed\n", s);
+    exit(1);
+  }
+  if(shmat(id, p) != (char*)-1){
+    printf("%s: overlapping shmat succeeded\n", s);
+    exit(1);
+  }
+
+  pid = fork();
+  if(pid < 0){
+    printf("%s: fork failed\n", s);
+    exit(1);
+  }
+  if(pid == 0){
+    p[0] = 'x';
+    p[PGSIZE] = 'y';
+    exit(0);
+  }
+  wait(&xst);
+  if(xst != 0 || p[0] != 'x' || p[PGSIZE] != 'y'){
+    printf("%s: child write not visible\n", s);
+    exit(1);
+  }
+
+  // a futex word in the segment is shared with a forked child
+  int *w = (int*)(p + PGSIZE);
+  *w = 0;
+  pid = fork();
+  if(pid < 0){
+    printf("%s: fork failed\n", s);
+    exit(1);
+  }
+  if(pid == 0){
+    for(int i = 0; i < 100; i++){
+      if(futex(w, FUTEX_WAKE, 1, 0) == 1)
+        exit(0);
+      sleep(1);
+    }
+    exit(1);
+  }
+  if(futex(w, FUTEX_WAIT, 0, 100) != 0){
+    printf("%s: futex wait on shared memory failed\n", s);
+    exit(1);
+  }
+  wait(&xst);
+  if(xst != 0){
+    printf("%s: child futex wake failed\n", s);
+    exit(1);
+  }
+
+  // system calls accept buffers inside the segment
+  int fds[2];
+  if(pipe(fds) < 0){
+    printf("%s: pipe failed\n", s);
+    exit(1);
+  }
+  if(write(fds[1], "hello", 5) != 5 || read(fds[0], p + 100, 5) != 5 ||
+     memcmp(p + 100, "hello", 5) != 0){
+    printf("%s: pipe read into shared memory failed\n", s);
+    exit(1);
+  }
+  close(fds[0]);
+  close(fds[1]);
+
+  if(shmctl(id, IPC_RMID) < 0){
+    printf("%s: shmctl failed\n", s);
+    exit(1);
+  }
+  if(shmat(id, 0) != (char*)-1){
+    printf("%s: shmat of removed segment succeeded\n", s);
+    exit(1);
+  }
+  // still mapped until detached
+  p[1] = 'z';
+  if(shmdt(p) < 0 || shmdt(p) >= 0){
+    printf("%s: shmdt failed\n", s);
+    exit(1);
+  }
+}
+
+// poll reports pipe readiness, wakes up on a write from
+// another process, and times out when nothing happens
+void
+polltest(char *s)
+{
+  int fds[2], pid, xst;
+  struct pollfd pfd[2];
+  char c;
+
+  if(pipe(fds) < 0){
+    printf("%s: pipe failed\n", s);
+    exit(1);
+  }
+  pfd[0].fd = fds[0];
+  pfd[0].events = POLLIN;
+  pfd[1].fd = fds[1];
+  pfd[1].events = POLLOUT;
+  if(poll(pfd, 2, 0) != 1 || pfd[0].revents != 0 || pfd[1].revents != POLLOUT){
+    printf("%s: poll of empty pipe wrong\n", s);
+    exit(1);
+  }
+  if(poll(pfd, 1, 2) != 0){
+    printf("%s: poll did not time out\n", s);
+    exit(1);
+  }
+
+  pid = fork();
+  if(pid < 0){
+    printf("%s: fork failed\n", s);
+    exit(1);
+  }
+  if(pid == 0){
+    sleep(2);
+    write(fds[1], "x", 1);
+    exit(0);
+  }
+  if(poll(pfd, 1, -1) != 1 || pfd[0].revents != POLLIN){
+    printf("%s: poll did not see data\n", s);
+    exit(1);
+  }
+  wait(&xst);
+  read(fds[0], &c, 1);
+
+  close(fds[1]);
+  if(poll(pfd, 1, -1) != 1 || pfd[0].revents != POLLHUP){
+    printf("%s: poll did not see hangup\n", s);
+    exit(1);
+  }
+  pfd[1].fd = fds[1];
+  if(poll(&pfd[1], 1, 0) != 1 || pfd[1].revents != POLLNVAL){
+    printf("%s: poll of closed fd did not report POLLNVAL\n", s);
+    exit(1);
+  }
+  close(fds[0]);
+}
+
+// O_NONBLOCK pipe ends fail instead of sleeping, and
+// fcntl descriptor flags belong to the descriptor only
+void
+nonblocktest(char *s)
+{
+  int fds[2], fd, n;
+  char buf[64];
+
+  if(pipe(fds) < 0){
+    printf("%s: pipe failed\n", s);
+    exit(1);
+  }
+  if(fcntl(fds[0], F_SETFL, O_NONBLOCK) < 0 ||
+     fcntl(fds[0], F_GETFL, 0) != (O_RDONLY|O_NONBLOCK) ||
+     fcntl(fds[1], F_SETFL, O_NONBLOCK) < 0){
+    printf("%s: F_SETFL failed\n", s);
+    exit(1);
+  }
+  if(read(fds[0], buf, 1) >= 0){
+    printf("%s: read of empty pipe did not fail\n", s);
+    exit(1);
+  }
+  for(;;){
+    n = write(fds[1], buf, sizeof(buf));
+    if(n < 0)
+      break;
+    if(n == 0){
+      printf("%s: write returned 0\n", s);
+      exit(1);
+    }
+  }
+  if(read(fds[0], buf, sizeof(buf)) != sizeof(buf)){
+    printf("%s: read of full pipe failed\n", s);
+    exit(1);
+  }
+
+  fd = fcntl(fds[0], F_DUPFD, 10);
+  if(fd < 10){
+    printf("%s: F_DUPFD returned %d\n", s, fd);
+    exit(1);
+  }
+  if(fcntl(fd, F_SETFD, FD_CLOEXEC) < 0 || fcntl(fd, F_GETFD, 0) != FD_CLOEXEC ||
+     fcntl(fds[0], F_GETFD, 0) != 0 || (fcntl(fd, F_GETFL, 0) & O_NONBLOCK) == 0){
+    printf("%s: descriptor flags wrong\n", s);
+    exit(1);
+  }
+  close(fd);
+  close(fds[0]);
+  close(fds[1]);
+}
+
+// F_SETPIPE_SZ rounds the capacity up to a power-of-two number
+// of pages and refuses to drop buffered data; small writes are
+// atomic, so a nonblocking one fails instead of writing part
+void
+pipesizetest(char *s)
+{
+  int fds[2], size, n, total;
+
+  if(pipe(fds) < 0){
+    printf("%s: pipe failed\n", s);
+    exit(1);
+  }
+  fcntl(fds[1], F_SETFL, O_NONBLOCK);
+  size = fcntl(fds[0], F_GETPIPE_SZ, 0);
+  if(size <= 0 || write(fds[1], buf, size - 10) != size - 10 || write(fds[1], buf, 100) >= 0){
+    printf("%s: small write not atomic\n", s);
+    exit(1);
+  }
+  if(fcntl(fds[0], F_SETPIPE_SZ, size + 1) != 2 * size ||
+     fcntl(fds[1], F_GETPIPE_SZ, 0) != 2 * size){
+    printf("%s: F_SETPIPE_SZ did not round up\n", s);
+    exit(1);
+  }
+  total = size - 10;
+  while((n = write(fds[1], buf, sizeof(buf))) > 0)
+    total += n;
+  if(total != 2 * size){
+    printf("%s: resized pipe holds %d bytes\n", s, total);
+    exit(1);
+  }
+  if(fcntl(fds[0], F_SETPIPE_SZ, size) >= 0){
+    printf("%s: shrinking below the buffered data succeeded\n", s);
+    exit(1);
+  }
+  while(total > 0 && (n = read(fds[0], buf, sizeof(buf))) > 0)
+    total -= n;
+  if(total != 0 || fcntl(fds[0], F_SETPIPE_SZ, size) != size){
+    printf("%s: shrinking an empty pipe failed\n", s);
+    exit(1);
+  }
+  close(fds[0]);
+  close(fds[1]);
+}
+
+// dup2 replaces the target descriptor, and the per-process
+// descriptor limit can be raised up to the hard limit
+void
+dup2test(char *s)
+{
+  struct rlimit rl, old;
+  int fds[2];
+
+  if(pipe(fds) < 0){
+    printf("%s: pipe failed\n", s);
+    exit(1);
+  }
+  if(dup2(fds[0], fds[1]) != fds[1] || write(fds[1], "x", 1) >= 0){
+    printf("%s: dup2 did not replace the write end\n", s);
+    exit(1);
+  }
+  if(dup2(fds[0], fds[0]) != fds[0] || dup3(fds[0], fds[0], 0) >= 0){
+    printf("%s: dup2 to itself wrong\n", s);
+    exit(1);
+  }
+
+  if(getrlimit(RLIMIT_NOFILE, &old) < 0){
+    printf("%s: getrlimit failed\n", s);
+    exit(1);
+  }
+  if(dup2(fds[0], old.rlim_cur) >= 0){
+    printf("%s: dup2 beyond the limit succeeded\n", s);
+    exit(1);
+  }
+  rl.rlim_cur = old.rlim_cur + 8;
+  rl.rlim_max = old.rlim_max;
+  if(setrlimit(RLIMIT_NOFILE, &rl) < 0 || dup3(fds[0], old.rlim_cur, O_CLOEXEC) != old.rlim_cur){
+    printf("%s: dup3 after raising the limit failed\n", s);
+    exit(1);
+  }
+  if(fcntl(old.rlim_cur, F_GETFD, 0) != FD_CLOEXEC){
+    printf("%s: dup3 did not set FD_CLOEXEC\n", s);
+    exit(1);
+  }
+  rl.rlim_max = old.rlim_max + 1;
+  if(setrlimit(RLIMIT_NOFILE, &rl) >= 0){
+    printf("%s: raising the hard limit succeeded\n", s);
+    exit(1);
+  }
+  close(old.rlim_cur);
+  close(fds[0]);
+  close(fds[1]);
+}
+
+// exec closes descriptors marked FD_CLOEXEC: the parent sees the
+// pipe hang up while the child is still running cat
+void
+cloexectest(char *s)
+{
+  int in[2], p[2], pid, xst;
+  char *argv[] = { "cat", 0 };
+  struct pollfd pfd;
+
+  if(pipe(in) < 0 || pipe(p) < 0){
+    printf("%s: pipe failed\n", s);
+    exit(1);
+  }
+  pid = fork();
+  if(pid < 0){
+    printf("%s: fork failed\n", s);
+    exit(1);
+  }
+  if(pid == 0){
+    dup2(in[0], 0);
+    close(in[0]);
+    close(in[1]);
+    close(p[0]);
+    fcntl(p[1], F_SETFD, FD_CLOEXEC);
+    exec("cat", argv);
+    printf("%s: exec cat failed\n", s);
+    exit(1);
+  }
+  close(in[0]);
+  close(p[1]);
+
+  pfd.fd = p[0];
+  pfd.events = POLLIN;
+  if(poll(&pfd, 1, 100) != 1 || pfd.revents != POLLHUP){
+    printf("%s: FD_CLOEXEC descriptor survived exec\n", s);
+    exit(1);
+  }
+  close(in[1]);
+  wait(&xst);
+  close(p[0]);
+  if(xst != 0){
+    printf("%s: cat failed\n", s);
+    exit(1);
+  }
+
+  // the extra flags do not count as a write access mode
+  int fd1 = open(".", O_RDONLY|O_CLOEXEC|O_NONBLOCK);
+  int fd2 = open("/proc/uptime", O_RDONLY|O_CLOEXEC);
+  if(fd1 < 0 || fd2 < 0){
+    printf("%s: read-only open with O_CLOEXEC failed\n", s);
+    exit(1);
+  }
+  close(fd1);
+  close(fd2);
+}
+
+// a FIFO connects a writer and a reader that open it by name;
+// each open waits for the other end, except O_NONBLOCK ones
+void
+fifotest(char *s)
+{
+  char *name = "fifo0";
+  char buf[16];
+  struct stat st;
+  int fd, pid, xst;
+
+  unlink(name);
+  if(mkfifo(name) < 0){
+    printf("%s: mkfifo failed\n", s);
+    exit(1);
+  }
+  if(mkfifo(name) >= 0){
+    printf("%s: mkfifo of an existing path succeeded\n", s);
+    exit(1);
+  }
+  if(open(name, O_WRONLY|O_NONBLOCK) >= 0){
+    printf("%s: O_NONBLOCK open for writing without readers succeeded\n", s);
+    exit(1);
+  }
+
+  pid = fork();
+  if(pid < 0){
+    printf("%s: fork failed\n", s);
+    exit(1);
+  }
+  if(pid == 0){
+    fd = open(name, O_WRONLY);
+    if(fd < 0 || write(fd, "hello", 5) != 5)
+      exit(1);
+    close(fd);
+    exit(0);
+  }
+
+  fd = open(name, O_RDONLY);
+  if(fd < 0){
+    printf("%s: open for reading failed\n", s);
+    exit(1);
+  }
+  if(fstat(fd, &st) < 0 || st.type != T_FIFO){
+    printf("%s: fstat type wrong\n", s);
+    exit(1);
+  }
+  if(read(fd, buf, sizeof(buf)) != 5 || memcmp(buf, "hello", 5) != 0){
+    printf("%s: read wrong data\n", s);
+    exit(1);
+  }
+  wait(&xst);
+  if(xst != 0){
+    printf("%s: writer failed\n", s);
+    exit(1);
+  }
+  if(read(fd, buf, sizeof(buf)) != 0){
+    printf("%s: no end of file after the writer closed\n", s);
+    exit(1);
+  }
+  close(fd);
+  unlink(name);
+}
+
+// SIGSTOP stops a spinning child and SIGTSTP one blocked in read;
+// waitpid(WUNTRACED) reports each stop once, SIGCONT resumes, and
+// signals to a process group reach every member
+void
+jobcontroltest(char *s)
+{
+  int fds[2], pid, pid2, st;
+  char c;
+
+  pid = fork();
+  if(pid < 0){
+    printf("%s: fork failed\n", s);
+    exit(1);
+  }
+  if(pid == 0)
+    for(;;)
+      ;
+  if(waitpid(pid, &st, WNOHANG) != 0){
+    printf("%s: WNOHANG did not return 0\n", s);
+    exit(1);
+  }
+  kill(pid, SIGSTOP);
+  if(waitpid(pid, &st, WUNTRACED) != pid || !WIFSTOPPED(st) || WSTOPSIG(st) != SIGSTOP){
+    printf("%s: stop of a running child not reported\n", s);
+    exit(1);
+  }
+  if(waitpid(pid, &st, WUNTRACED|WNOHANG) != 0){
+    printf("%s: stop reported twice\n", s);
+    exit(1);
+  }
+  kill(pid, SIGCONT);
+  kill(pid, SIGKILL);
+  if(waitpid(pid, &st, 0) != pid || WIFSTOPPED(st)){
+    printf("%s: killed child not reaped\n", s);
+    exit(1);
+  }
+
+  if(pipe(fds) < 0){
+    printf("%s: pipe failed\n", s);
+    exit(1);
+  }
+  pid = fork();
+  if(pid < 0){
+    printf("%s: fork failed\n", s);
+    exit(1);
+  }
+  if(pid == 0){
+    signal(SIGINT, SIG_IGN);
+    if(read(fds[0], &c, 1) != 1)
+      exit(1);
+    exit(7);
+  }
+  sleep(1);
+  kill(pid, SIGINT);
+  kill(pid, SIGTSTP);
+  if(waitpid(pid, &st, WUNTRACED) != pid || !WIFSTOPPED(st) || WSTOPSIG(st) != SIGTSTP){
+    printf("%s: stop of a sleeping child not reported\n", s);
+    exit(1);
+  }
+  write(fds[1], "x", 1);
+  kill(pid, SIGCONT);
+  if(waitpid(pid, &st, 0) != pid || !WIFEXITED(st) || WEXITSTATUS(st) != 7){
+    printf("%s: continued child did not exit normally\n", s);
+    exit(1);
+  }
+  close(fds[0]);
+  close(fds[1]);
+
+  // two children in a group led by the first
+  pid = fork();
+  if(pid == 0){
+    setpgid(0, 0);
+    for(;;)
+      ;
+  }
+  setpgid(pid, pid);
+  pid2 = fork();
+  if(pid2 == 0){
+    setpgid(0, pid);
+    for(;;)
+      ;
+  }
+  if(pid < 0 || pid2 < 0){
+    printf("%s: fork failed\n", s);
+    exit(1);
+  }
+  setpgid(pid2, pid);
+  if(getpgid(pid) != pid || getpgid(pid2) != pid || getpgid(0) == pid){
+    printf("%s: setpgid did not take effect\n", s);
+    exit(1);
+  }
+  kill(-pid, SIGKILL);
+  if(wait(0) < 0 || wait(0) < 0){
+    printf("%s: group kill missed a member\n", s);
+    exit(1);
+  }
+}
+
+// alarm() kills a child that does not ignore SIGALRM, the interval
+// timer reports its remaining time, and nanosleep() sleeps at least
+// as long as asked according to clock_gettime()
+void
+timertest(char *s)
+{
+  struct itimerval it, old;
+  struct timespec t0, t1, req;
+  int pid, st;
+  long ns;
+
+  pid = fork();
+  if(pid < 0){
+    printf("%s: fork failed\n", s);
+    exit(1);
+  }
+  if(pid == 0){
+    alarm(1);
+    for(;;)
+      ;
+  }
+  if(wait(&st) != pid || st != -1){
+    printf("%s: alarm did not kill the child\n", s);
+    exit(1);
+  }
+
+  if(alarm(5) != 0 || alarm(0) != 5){
+    printf("%s: alarm did not return the remaining time\n", s);
+    exit(1);
+  }
+  memset(&it, 0, sizeof(it));
+  it.it_value.tv_sec = 3;
+  it.it_interval.tv_usec = 500000;
+  if(setitimer(ITIMER_REAL, &it, 0) < 0 || getitimer(ITIMER_REAL, &old) < 0){
+    printf("%s: setitimer failed\n", s);
+    exit(1);
+  }
+  if(old.it_value.tv_sec > 3 || old.it_value.tv_sec < 2 || old.it_interval.tv_usec != 500000){
+    printf("%s: getitimer returned the wrong setting\n", s);
+    exit(1);
+  }
+  memset(&it, 0, sizeof(it));
+  if(setitimer(ITIMER_REAL, &it, &old) < 0 || (old.it_value.tv_sec == 0 && old.it_value.tv_usec == 0)){
+    printf("%s: setitimer did not return the old setting\n", s);
+    exit(1);
+  }
+  if(getitimer(ITIMER_REAL, &old) < 0 || old.it_value.tv_sec != 0 || old.it_value.tv_usec != 0){
+    printf("%s: timer not stopped\n", s);
+    exit(1);
+  }
+
+  req.tv_sec = 0;
+  req.tv_nsec = 150000000;
+  if(clock_gettime(CLOCK_MONOTONIC, &t0) < 0 || nanosleep(&req, 0) < 0
+     || clock_gettime(CLOCK_MONOTONIC, &t1) < 0){
+    printf("%s: nanosleep failed\n", s);
+    exit(1);
+  }
+  if(t1.tv_nsec < 0 || t1.tv_nsec >= 1000000000){
+    printf("%s: bad tv_nsec %d\n", s, (int)t1.tv_nsec);
+    exit(1);
+  }
+  ns = (t1.tv_sec - t0.tv_sec) * 1000000000 + t1.tv_nsec - t0.tv_nsec;
+  if(ns < 150000000){
+    printf("%s: nanosleep returned early\n", s);
+    exit(1);
+  }
+  req.tv_nsec = 1000000000;
+  if(nanosleep(&req, 0) >= 0 || clock_gettime(42, &t0) >= 0){
+    printf("%s: invalid arguments accepted\n", s);
+    exit(1);
+  }
+}
+
+// a pinned process keeps its mask across fork and still gets to run;
+// an empty mask and unknown pids are rejected
+void
+affinitytest(char *s)
+{
+  uint64 all, m;
+  int pid, st, i;
+
+  all = sched_getaffinity(0);
+  if(all == 0 || (all & 1) == 0){
+    printf("%s: bad default mask %p\n", s, all);
+    exit(1);
+  }
+  if(sched_setaffinity(0, 0) >= 0 || sched_setaffinity(0, ~all) >= 0){
+    printf("%s: empty mask accepted\n", s);
+    exit(1);
+  }
+  if(sched_setaffinity(1000000, 1) >= 0 || sched_getaffinity(1000000) != (uint64)-1){
+    printf("%s: unknown pid accepted\n", s);
+    exit(1);
+  }
+
+  for(m = 1; m & all; m <<= 1){
+    pid = fork();
+    if(pid < 0){
+      printf("%s: fork failed\n", s);
+      exit(1);
+    }
+    if(pid == 0){
+      if(sched_setaffinity(0, m) < 0)
+        exit(1);
+      // the child of a pinned process inherits the mask
+      pid = fork();
+      if(pid == 0){
+        for(i = 0; i < 1000000; i++)
+          ;
+        exit(sched_getaffinity(0) == m ? 0 : 2);
+      }
+      if(pid < 0 || wait(&st) != pid)
+        exit(1);
+      exit(st);
+    }
+    if(wait(&st) != pid || st != 0){
+      printf("%s: pinned to %p, status %d\n", s, m, st);
+      exit(1);
+    }
+  }
+  if(sched_getaffinity(0) != all){
+    printf("%s: mask of the parent changed\n", s);
+    exit(1);
+  }
+}
+
+// read a whole /proc file into buf, terminated with 0.
+static int
+readproc(char *s, char *path, char *buf, int size)
+{
+  int fd, n, tot;
+
+  if((fd = open(path, O_RDONLY)) < 0){
+    printf("%s: cannot open %s\n", s, path);
+    exit(1);
+  }
+  tot = 0;
+  while(tot < size - 1 && (n = read(fd, buf + tot, size - 1 - tot)) > 0)
+    tot += n;
+  close(fd);
+  buf[tot] = 0;
+  return tot;
+}
+
+// does the n-byte buf contain the string pat?
+static int
+hasstr(char *buf, int n, char *pat)
+{
+  int len = strlen(pat);
+  for(int i = 0; i + len <= n; i++)
+    if(memcmp(buf + i, pat, len) == 0)
+      return 1;
+  return 0;
+}
+
+// /proc/<pid> describes the caller and is listed in /proc,
+// and the global files have the expected content.
+void
+proctest(char *s)
+{
+  char pidstr[16], path[32], pat[32], buf[512];
+  struct dirent de;
+  int fd, n, len, found;
+
+  len = 0;
+  for(n = getpid(); n > 0; n /= 10)
+    len++;
+  pidstr[len] = 0;
+  for(n = getpid(); n > 0; n /= 10)
+    pidstr[--len] = '0' + n % 10;
+
+  strcpy(path, "/proc/");
+  strcpy(path + strlen(path), pidstr);
+  strcpy(path + strlen(path), "/status");
+  n = readproc(s, path, buf, sizeof(buf));
+  strcpy(pat, "\npid: ");
+  strcpy(pat + strlen(pat), pidstr);
+  strcpy(pat + strlen(pat), "\n");
+  if(!hasstr(buf, n, pat) || !hasstr(buf, n, "state: RUNNING\n")){
+    printf("%s: bad %s: %s\n", s, path, buf);
+    exit(1);
+  }
+
+  strcpy(path + strlen(path) - strlen("status"), "cmdline");
+  n = readproc(s, path, buf, sizeof(buf));
+  if(n < 9 || memcmp(buf, "usertests", 9) != 0 || buf[n-1] != '\n'){
+    printf("%s: bad %s: %s\n", s, path, buf);
+    exit(1);
+  }
+
+  n = readproc(s, "/proc/meminfo", buf, sizeof(buf));
+  if(memcmp(buf, "heap: [", 7) != 0 || !hasstr(buf, n, "\nfree: ")){
+    printf("%s: bad /proc/meminfo: %s\n", s, buf);
+    exit(1);
+  }
+
+  if((fd = open("/proc", O_RDONLY)) < 0){
+    printf("%s: cannot open /proc\n", s);
+    exit(1);
+  }
+  found = 0;
+  while(read(fd, &de, sizeof(de)) == sizeof(de)){
+    if(de.inum != 0 && strcmp(de.name, pidstr) == 0)
+      found = 1;
+  }
+  close(fd);
+  if(!found){
+    printf("%s: /proc/%s not listed\n", s, pidstr);
+    exit(1);
+  }
+}
+
+// the profiler samples a busy process, and /proc/profile and
+// /proc/lockstat can be read.
+void
+kproftest(char *s)
+{
+  char buf[64];
+  int fd, n, t0;
+
+  if(kprof(-1) >= 0){
+    printf("%s: bad command accepted\n", s);
+    exit(1);
+  }
+  if(kprof(KPROF_START) != 0){
+    printf("%s: start failed\n", s);
+    exit(1);
+  }
+  t0 = uptime();
+  while(uptime() < t0 + 3)
+    ;
+  if((n = kprof(KPROF_STOP)) <= 0){
+    printf("%s: %d samples\n", s, n);
+    exit(1);
+  }
+
+  if((fd = open("/proc/profile", O_RDONLY)) < 0){
+    printf("%s: cannot open /proc/profile\n", s);
+    exit(1);
+  }
+  n = read(fd, buf, sizeof(buf) - 1);
+  close(fd);
+  if(n < 10 || memcmp(buf, "# samples ", 10) != 0){
+    printf("%s: bad /proc/profile\n", s);
+    exit(1);
+  }
+  if((fd = open("/proc/lockstat", O_RDONLY)) < 0){
+    printf("%s: cannot open /proc/lockstat\n", s);
+    exit(1);
+  }
+  n = read(fd, buf, sizeof(buf) - 1);
+  close(fd);
+  if(n < 4 || memcmp(buf, "name", 4) != 0){
+    printf("%s: bad /proc/lockstat\n", s);
+    exit(1);
+  }
+}
+
+// append the decimal form of n >= 0 to the string buf.
+static void
+strcatint(char *buf, int n)
+{
+  char digits[16];
+  int i = 0;
+
+  do {
+    digits[i++] = '0' + n % 10;
+    n /= 10;
+  } while(n > 0);
+  buf += strlen(buf);
+  while(i > 0)
+    *buf++ = digits[--i];
+  *buf = 0;
+}
+
+// trace writes one decoded line per traced system call to the
+// given fd, and refuses a read-only fd.
+void
+tracetest(char *s)
+{
+  char buf[64], expect[64];
+  int p[2], fd, n;
+
+  if(pipe(p) < 0){
+    printf("%s: pipe failed\n", s);
+    exit(1);
+  }
+  if(trace(1 << SYS_dup, p[0]) >= 0){
+    printf("%s: trace to a read-only fd succeeded\n", s);
+    exit(1);
+  }
+  if(trace(1 << SYS_dup, p[1]) < 0){
+    printf("%s: trace failed\n", s);
+    exit(1);
+  }
+  fd = dup(p[0]);
+  getpid();
+  trace(0, -1);
+  close(fd);
+  close(p[1]);
+
+  strcpy(expect, "[");
+  strcatint(expect, getpid());
+  strcpy(expect + strlen(expect), "] dup(");
+  strcatint(expect, p[0]);
+  strcpy(expect + strlen(expect), ") = ");
+  strcatint(expect, fd);
+  strcpy(expect + strlen(expect), "\n");
+
+  n = read(p[0], buf, sizeof(buf) - 1);
+  close(p[0]);
+  if(n < 0)
+    n = 0;
+  buf[n] = 0;
+  if(fd < 0 || strcmp(buf, expect) != 0){
+    printf("%s: trace output '%s', expected '%s'\n", s, buf, expect);
+    exit(1);
+  }
+}
+
+// an unknown system call number fails instead of panicking the kernel.
+void
+badsyscall(char *s)
+{
+  register uint64 a0 asm("a0");
+  register uint64 a7 asm("a7") = 1000;
+
+  asm volatile("ecall" : "=r" (a0) : "r" (a7) : "memory");
+  if((int)a0 != -1){
+    printf("%s: unknown system call returned %d\n", s, (int)a0);
+    exit(1);
+  }
+}
+
+// reading the kernel log while another process overwrites the
+// whole ring several times returns a sane count and does not
+// crash the kernel.
+void
+klogtest(char *s)
+{
+  int pid, n, size, xst, reads;
+  char *buf;
+
+  size = syslog(SYSLOG_ACTION_SIZE_BUFFER, 0, 0);
+  if(size <= 0 || (buf = malloc(size)) == 0){
+    printf("%s: bad log size %d\n", s, size);
+    exit(1);
+  }
+
+  pid = fork();
+  if(pid < 0){
+    printf("%s: fork failed\n", s);
+    exit(1);
+  }
+  if(pid == 0){
+    // each traced getpid logs one line; fill the ring about eight times
+    syslog(SYSLOG_ACTION_CONSOLE_LEVEL, 0, KLOG_WARN);
+    if(trace(1 << SYS_getpid, -1) < 0)
+      exit(1);
+    for(int i = 0; i < 4000; i++)
+      getpid();
+    trace(0, -1);
+    exit(0);
+  }
+
+  reads = 0;
+  while(waitpid(pid, &xst, WNOHANG) == 0){
+    n = syslog(SYSLOG_ACTION_READ_ALL, buf, size);
+    if(n < 0 || n > size){
+      printf("%s: read returned %d\n", s, n);
+      exit(1);
+    }
+    reads++;
+  }
+  syslog(SYSLOG_ACTION_CONSOLE_LEVEL, 0, KLOG_INFO);
+  if(xst != 0 || reads == 0){
+    printf("%s: writer failed\n", s);
+    exit(1);
+  }
+  n = syslog(SYSLOG_ACTION_READ_ALL, buf, size);
+  if(n <= 0 || buf[n-1] != '\n'){
+    printf("%s: final read returned %d\n", s, n);
+    exit(1);
+  }
+  free(buf);
+}
+
 // try to find races in the reparenting
 // code that handles a parent exiting
 // when it still has live children.
@@ -914,7 +2120,7 @@ reparent(char *s)
     } else {
       int pid2 = fork();
       if(pid2 < 0){
-        kill(master_pid);
+        kill(master_pid, SIGKILL);
         exit(1);
       }
       exit(0);
@@ -2263,7 +3469,7 @@ sbrkfail(char *s)
   for(i = 0; i < sizeof(pids)/sizeof(pids[0]); i++){
     if(pids[i] == -1)
       continue;
-    kill(pids[i]);
+    kill(pids[i], SIGKILL);
     wait(0);
   }
   if(c == (char*)0xffffffffffffffffL){
@@ -2816,9 +4022,30 @@ main(int argc, char *argv[])
     {iputtest, "iput"},
     {mem, "mem"},
     {pipe1, "pipe1"},
+    {ptytest, "ptytest"},
     {killstatus, "killstatus"},
     {preempt, "preempt"},
     {exitwait, "exitwait"},
+    {threadtest, "threadtest"},
+    {threadexit, "threadexit"},
+    {threadexec, "threadexec"},
+    {futextest, "futextest"},
+    {devtest, "devtest"},
+    {shmtest, "shmtest"},
+    {polltest, "polltest"},
+    {nonblocktest, "nonblocktest"},
+    {pipesizetest, "pipesizetest"},
+    {dup2test, "dup2test"},
+    {cloexectest, "cloexectest"},
+    {fifotest, "fifotest"},
+    {jobcontroltest, "jobcontroltest"},
+    {timertest, "timertest"},
+    {affinitytest, "affinitytest"},
+    {proctest, "proctest"},
+    {kproftest, "kproftest"},
+    {klogtest, "klogtest"},
+    {tracetest, "tracetest"},
+    {badsyscall, "badsyscall"},
     {rmdot, "rmdot"},
     {fourteen, "fourteen"},
     {bigfile, "bigfile"},
@@ -1,38 +1,65 @@
 #!/usr/bin/perl -w
 
-# Generate usys.S, the stubs for syscalls.
+# Generate the user side of the system call interface from the
+# kernel's system call table (src/process/proc/systable.rs):
+#
+#   usys.pl table      usys.S, the stubs for syscalls
+#   usys.pl -h table   include/syscall.h, the system call numbers
+#   usys.pl -d table   docs/syscalls.md, the system call reference
 
-print "# generated by usys.pl - do not edit\n";
+my $mode = "";
+if(@ARGV && $ARGV[0] =~ /^-[hd]$/){
+    $mode = shift;
+}
+@ARGV == 1 or die "usage: usys.pl [-h|-d] systable.rs\n";
 
-print "#include \"include/syscall.h\"\n";
+# Each entry is one line: "num name => handler(arg: Kind, ...) -> Ret;",
+# preceded by doc comments describing when it fails.
+my @calls;
+my @doc;
+my $intable = 0;
+open(my $fh, "<", $ARGV[0]) or die "usys.pl: cannot open $ARGV[0]\n";
+while(<$fh>){
+    if(/^syscall_table!\s*\{/){
+        $intable = 1;
+    } elsif($intable && /^\}/){
+        $intable = 0;
+    } elsif($intable && m{^\s*///\s?(.*)$}){
+        push(@doc, $1);
+    } elsif($intable && /^\s*(\d+)\s+(\w+)\s*=>\s*\w+\((.*)\)\s*->\s*(\w+);/){
+        push(@calls, { num => $1, name => $2, args => $3, ret => $4, doc => join(" ", @doc) });
+        @doc = ();
+    }
+}
+close($fh);
+@calls or die "usys.pl: no system calls found in $ARGV[0]\n";
 
-sub entry {
-    my $name = shift;
-    print ".global $name\n";
-    print "${name}:\n";
-    print " li a7, SYS_${name}\n";
-    print " ecall\n";
-    print " ret\n";
+if($mode eq "-h"){
+    print "// generated by usys.pl - do not edit\n";
+    print "// System call numbers\n";
+    foreach my $c (@calls){
+        printf("#define %-12s %2d\n", "SYS_$c->{name}", $c->{num});
+    }
+} elsif($mode eq "-d"){
+    print "<!-- generated by usys.pl - do not edit -->\n";
+    print "# 系统调用\n\n";
+    print "所有系统调用失败时返回 -1，下表列出各系统调用返回 -1 的情形。";
+    print "使用表中没有的系统调用号时同样返回 -1。\n\n";
+    print "| 编号 | 系统调用 | 返回值 | 失败情形 |\n";
+    print "| ---- | -------- | ------ | -------- |\n";
+    foreach my $c (@calls){
+        my $args = join(", ", map { (split(/\s*:\s*/))[0] } split(/\s*,\s*/, $c->{args}));
+        print "| $c->{num} | `$c->{name}($args)` | $c->{ret} | $c->{doc} |\n";
+    }
+} else {
+    print "# generated by usys.pl - do not edit\n";
+    print "#include \"include/syscall.h\"\n";
+    foreach my $c (@calls){
+        my $name = $c->{name};
+        print ".global $name\n";
+        print "${name}:\n";
+        print " li a7, SYS_${name}\n";
+        print " ecall\n";
+        print " ret\n";
+    }
 }
-	
-entry("fork");
-entry("exit");
-entry("wait");
-entry("pipe");
-entry("read");
-entry("write");
-entry("close");
-entry("kill");
-entry("exec");
-entry("open");
-entry("mknod");
-entry("unlink");
-entry("fstat");
-entry("link");
-entry("mkdir");
-entry("chdir");
-entry("dup");
-entry("getpid");
-entry("sbrk");
-entry("sleep");
-entry("uptime");
@@ -0,0 +1,108 @@
+#include "include/types.h"
+#include "include/futex.h"
+#include "user/user.h"
+
+// Threads on top of clone/join.
+// Each thread runs on a stack from malloc; the start routine and its
+// argument sit at the top of that stack. thread_join frees the stack,
+// so a thread's stack must not be freed while it is running.
+// The thread table below is not locked: create and join threads from
+// one thread only.
+
+#define STACKSIZE (2*4096)
+#define MAXTHREAD 16
+
+struct tstart {
+  int (*fn)(void*);
+  void *arg;
+};
+
+static struct {
+  int tid;
+  char *stack;
+} threads[MAXTHREAD];
+
+static void
+thread_start(void *p)
+{
+  struct tstart *s = p;
+
+  exit_thread(s->fn(s->arg));
+}
+
+int
+thread_create(int (*fn)(void*), void *arg)
+{
+  struct tstart *s;
+  char *stack;
+  int i, tid;
+
+  for(i = 0; i < MAXTHREAD; i++)
+    if(threads[i].stack == 0)
+      break;
+  if(i == MAXTHREAD)
+    return -1;
+
+  stack = malloc(STACKSIZE);
+  if(stack == 0)
+    return -1;
+  s = (struct tstart*)(((uint64)(stack + STACKSIZE) - sizeof(*s)) & ~15);
+  s->fn = fn;
+  s->arg = arg;
+
+  tid = clone(thread_start, s, s);
+  if(tid < 0){
+    free(stack);
+    return -1;
+  }
+  threads[i].tid = tid;
+  threads[i].stack = stack;
+  return tid;
+}
+
+int
+thread_join(int tid, int *status)
+{
+  int i;
+
+  if(join(tid, status) < 0)
+    return -1;
+  for(i = 0; i < MAXTHREAD; i++){
+    if(threads[i].stack && threads[i].tid == tid){
+      free(threads[i].stack);
+      threads[i].stack = 0;
+      break;
+    }
+  }
+  return tid;
+}
+
+void
+thread_exit(int status)
+{
+  exit_thread(status);
+}
+
+// Mutexes stay in user space when uncontended and otherwise
+// sleep in the kernel with futex().
+void
+mutex_lock(struct mutex *m)
+{
+  int c = 0;
+
+  if(__atomic_compare_exchange_n(&m->state, &c, 1, 0, __ATOMIC_ACQUIRE, __ATOMIC_RELAXED))
+    return;
+  if(c != 2)
+    c = __atomic_exchange_n(&m->state, 2, __ATOMIC_ACQUIRE);
+  while(c != 0){
+    futex(&m->state, FUTEX_WAIT, 2, 0);
+    c = __atomic_exchange_n(&m->state, 2, __ATOMIC_ACQUIRE);
+  }
+}
+
+void
+mutex_unlock(struct mutex *m)
+{
+  if(__atomic_exchange_n(&m->state, 0, __ATOMIC_RELEASE) == 2)
+    futex(&m->state, FUTEX_WAKE, 1, 0);
+}